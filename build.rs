@@ -0,0 +1,40 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    generate_grpc_service();
+}
+
+/// Generates the server of the `prefixload.v1.Daemon` gRPC service described
+/// in `proto/prefixload.proto`. The messages are defined by hand in
+/// `src/cli/commands/daemon/grpc.rs`, so the build does not need `protoc`.
+#[cfg(feature = "grpc")]
+fn generate_grpc_service() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(format!("super::{}", input))
+            .output_type(format!("super::{}", output))
+            .codec_path("tonic_prost::ProstCodec")
+            .build()
+    };
+
+    let service = Service::builder()
+        .name("Daemon")
+        .package("prefixload.v1")
+        .method(method("status", "Status", "StatusRequest", "StatusReply"))
+        .method(method(
+            "trigger",
+            "Trigger",
+            "TriggerRequest",
+            "ControlReply",
+        ))
+        .method(method("pause", "Pause", "PauseRequest", "ControlReply"))
+        .method(method("resume", "Resume", "ResumeRequest", "ControlReply"))
+        .build();
+
+    Builder::new().build_client(false).compile(&[service]);
+}
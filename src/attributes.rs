@@ -0,0 +1,245 @@
+use crate::error::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+/// Object metadata keys (`x-amz-meta-prefixload-*`) holding the file attributes.
+pub const MODE_METADATA_KEY: &str = "prefixload-mode";
+pub const UID_METADATA_KEY: &str = "prefixload-uid";
+pub const GID_METADATA_KEY: &str = "prefixload-gid";
+pub const OWNER_METADATA_KEY: &str = "prefixload-owner";
+pub const GROUP_METADATA_KEY: &str = "prefixload-group";
+pub const MTIME_METADATA_KEY: &str = "prefixload-mtime";
+
+#[cfg(unix)]
+const PASSWD_PATH: &str = "/etc/passwd";
+#[cfg(unix)]
+const GROUP_PATH: &str = "/etc/group";
+
+/// POSIX attributes of a local file, stored as object metadata on upload so
+/// that a restored file gets its permissions, owner and mtime back.
+/// Only the mtime is recorded on Windows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileAttributes {
+    /// Permission bits, including setuid/setgid/sticky.
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// User name of `uid` according to /etc/passwd.
+    pub owner: Option<String>,
+    /// Group name of `gid` according to /etc/group.
+    pub group: Option<String>,
+    pub mtime: Option<DateTime<Utc>>,
+}
+
+impl FileAttributes {
+    /// Reads the attributes from a file's metadata.
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        let mtime = metadata.modified().ok().map(DateTime::<Utc>::from);
+
+        #[cfg(unix)]
+        {
+            Self {
+                mode: Some(metadata.mode() & 0o7777),
+                uid: Some(metadata.uid()),
+                gid: Some(metadata.gid()),
+                owner: read_db(PASSWD_PATH).and_then(|db| name_of(&db, metadata.uid())),
+                group: read_db(GROUP_PATH).and_then(|db| name_of(&db, metadata.gid())),
+                mtime,
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            Self {
+                mtime,
+                ..Default::default()
+            }
+        }
+    }
+
+    /// Returns the attributes as S3 user metadata.
+    pub fn to_object_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        if let Some(mode) = self.mode {
+            metadata.insert(MODE_METADATA_KEY.to_string(), format!("{:04o}", mode));
+        }
+        if let Some(uid) = self.uid {
+            metadata.insert(UID_METADATA_KEY.to_string(), uid.to_string());
+        }
+        if let Some(gid) = self.gid {
+            metadata.insert(GID_METADATA_KEY.to_string(), gid.to_string());
+        }
+        if let Some(owner) = &self.owner {
+            metadata.insert(OWNER_METADATA_KEY.to_string(), owner.clone());
+        }
+        if let Some(group) = &self.group {
+            metadata.insert(GROUP_METADATA_KEY.to_string(), group.clone());
+        }
+        if let Some(mtime) = self.mtime {
+            metadata.insert(
+                MTIME_METADATA_KEY.to_string(),
+                mtime.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            );
+        }
+        metadata
+    }
+
+    /// Reads the attributes back from S3 user metadata; unparsable values are skipped.
+    pub fn from_object_metadata(metadata: &HashMap<String, String>) -> Self {
+        Self {
+            mode: metadata
+                .get(MODE_METADATA_KEY)
+                .and_then(|mode| u32::from_str_radix(mode, 8).ok()),
+            uid: metadata
+                .get(UID_METADATA_KEY)
+                .and_then(|id| id.parse().ok()),
+            gid: metadata
+                .get(GID_METADATA_KEY)
+                .and_then(|id| id.parse().ok()),
+            owner: metadata.get(OWNER_METADATA_KEY).cloned(),
+            group: metadata.get(GROUP_METADATA_KEY).cloned(),
+            mtime: metadata
+                .get(MTIME_METADATA_KEY)
+                .and_then(|mtime| DateTime::parse_from_rfc3339(mtime).ok())
+                .map(|mtime| mtime.with_timezone(&Utc)),
+        }
+    }
+
+    /// Applies the attributes to a restored file.
+    ///
+    /// The owner is looked up by name first, so a restore on another machine
+    /// maps it to the local account, and falls back to the numeric IDs.
+    /// Changing the owner needs root; without it the owner is left as is.
+    pub fn apply(&self, path: &Path) -> Result<()> {
+        // The mtime goes first: the file must still be writable to set it.
+        if let Some(mtime) = self.mtime {
+            fs::File::options()
+                .write(true)
+                .open(path)?
+                .set_modified(mtime.into())?;
+        }
+
+        #[cfg(unix)]
+        {
+            let uid = self
+                .owner
+                .as_deref()
+                .and_then(|name| read_db(PASSWD_PATH).and_then(|db| id_of(&db, name)))
+                .or(self.uid);
+            let gid = self
+                .group
+                .as_deref()
+                .and_then(|name| read_db(GROUP_PATH).and_then(|db| id_of(&db, name)))
+                .or(self.gid);
+
+            if uid.is_some() || gid.is_some() {
+                match std::os::unix::fs::chown(path, uid, gid) {
+                    Err(e) if e.kind() != std::io::ErrorKind::PermissionDenied => {
+                        return Err(e.into());
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(mode) = self.mode {
+                fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn read_db(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok()
+}
+
+/// Returns the name of `id` in an /etc/passwd or /etc/group formatted database.
+#[cfg(unix)]
+fn name_of(db: &str, id: u32) -> Option<String> {
+    db.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        let entry_id = fields.nth(1)?;
+        (entry_id.parse() == Ok(id)).then(|| name.to_string())
+    })
+}
+
+/// Returns the ID of `name` in an /etc/passwd or /etc/group formatted database.
+#[cfg(unix)]
+fn id_of(db: &str, name: &str) -> Option<u32> {
+    db.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != name {
+            return None;
+        }
+        fields.nth(1)?.parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn object_metadata_roundtrip() {
+        let attributes = FileAttributes {
+            mode: Some(0o4750),
+            uid: Some(1000),
+            gid: Some(100),
+            owner: Some("alice".to_string()),
+            group: Some("users".to_string()),
+            mtime: Some(
+                DateTime::parse_from_rfc3339("2025-09-21T03:00:00.5Z")
+                    .unwrap()
+                    .into(),
+            ),
+        };
+
+        let metadata = attributes.to_object_metadata();
+        assert_eq!(metadata[MODE_METADATA_KEY], "4750");
+        assert_eq!(
+            metadata[MTIME_METADATA_KEY],
+            "2025-09-21T03:00:00.500000000Z"
+        );
+        assert_eq!(FileAttributes::from_object_metadata(&metadata), attributes);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn passwd_lookups_map_names_and_ids() {
+        let db = "root:x:0:0:root:/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/bash\n";
+
+        assert_eq!(name_of(db, 1000), Some("alice".to_string()));
+        assert_eq!(name_of(db, 42), None);
+        assert_eq!(id_of(db, "root"), Some(0));
+        assert_eq!(id_of(db, "bob"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn apply_restores_mode_and_mtime() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "data").unwrap();
+
+        let mut attributes = FileAttributes::from_metadata(&fs::metadata(&path).unwrap());
+        attributes.mode = Some(0o640);
+        attributes.mtime = Some(
+            DateTime::parse_from_rfc3339("2020-01-02T03:04:05Z")
+                .unwrap()
+                .into(),
+        );
+        attributes.apply(&path).unwrap();
+
+        let restored = FileAttributes::from_metadata(&fs::metadata(&path).unwrap());
+        assert_eq!(restored, attributes);
+    }
+}
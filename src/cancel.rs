@@ -0,0 +1,99 @@
+use crate::error::PrefixloadError;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+/// Stops a run or an ETag calculation midway, e.g. on Ctrl-C or from
+/// `daemon cancel`. Clones share the same state, so the token can be
+/// cancelled from another task or thread than the one it stops.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<CancelState>);
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the work using this token stop at its next check, and wakes
+    /// up everything waiting in `cancelled`.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Waits until the token is cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            // Registered before the check, so a `cancel` in between is not missed.
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Runs `future` to completion unless the token is cancelled first, in
+    /// which case `future` is dropped and `PrefixloadError::Cancelled` is
+    /// returned with `what` was stopped.
+    pub async fn run<F: Future>(
+        &self,
+        what: &str,
+        future: F,
+    ) -> Result<F::Output, PrefixloadError> {
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => Err(self.error(what)),
+            output = future => Ok(output),
+        }
+    }
+
+    /// Returns `PrefixloadError::Cancelled` if the token is cancelled.
+    pub fn check(&self, what: &str) -> Result<(), PrefixloadError> {
+        if self.is_cancelled() {
+            return Err(self.error(what));
+        }
+        Ok(())
+    }
+
+    fn error(&self, what: &str) -> PrefixloadError {
+        PrefixloadError::Cancelled(format!("Cancelled {}.", what))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn run_stops_pending_futures_once_cancelled() {
+        let token = CancelToken::new();
+        assert_eq!(token.run("the test", async { 1 }).await.unwrap(), 1);
+        assert!(token.check("the test").is_ok());
+
+        let waiting = tokio::spawn({
+            let token = token.clone();
+            async move { token.run("the upload", std::future::pending::<()>()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        token.cancel();
+
+        let err = waiting.await.unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "Error [Cancelled]: Cancelled the upload.");
+        assert!(token.check("the test").is_err());
+        // Cancelled tokens stop new work right away.
+        assert!(token.run("the test", async {}).await.is_err());
+    }
+}
@@ -0,0 +1,450 @@
+// This module handles the `audit` command, which checks that the objects the
+// latest runs left in the bucket are still there and unchanged.
+
+use crate::cancel::CancelToken;
+use crate::cli::AuditArgs;
+use crate::cli::commands::restore::{load_report, report_keys};
+use crate::cli::commands::run::{bucket_client, retry_throttled};
+use crate::cli::output::Status;
+use crate::clients::concurrency::AdaptiveConcurrency;
+use crate::clients::s3::S3Client;
+use crate::config::{self, Config};
+use crate::dedup;
+use crate::error::{PrefixloadError, Result};
+use crate::report::RunReport;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+/// An object a run left in the bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AuditItem {
+    key: String,
+    /// ETag the object had after the run, if the report records it.
+    etag: Option<String>,
+    run_id: String,
+}
+
+/// What is wrong with an audited object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    Missing,
+    /// The object has another ETag than after the run.
+    Changed {
+        etag: Option<String>,
+    },
+}
+
+/// State shared by the concurrent checks of an audit.
+struct AuditContext {
+    client: S3Client,
+    bucket: String,
+    limiter: AdaptiveConcurrency,
+}
+
+/// Returns the report of the latest run of every job (or only of `job`),
+/// newest first, among the reports uploaded with `upload_run_log`. Runs
+/// without a job count as a job of their own.
+async fn latest_reports(
+    client: &S3Client,
+    bucket: &str,
+    job: Option<&str>,
+) -> Result<Vec<RunReport>> {
+    let mut jobs = HashSet::new();
+    let mut reports = Vec::new();
+    for (_, key) in report_keys(client, bucket).await? {
+        let report = load_report(client, bucket, &key).await?;
+        if job.is_some() && report.job.as_deref() != job {
+            continue;
+        }
+        if jobs.insert(report.job.clone()) {
+            reports.push(report);
+            if job.is_some() {
+                break;
+            }
+        }
+    }
+    Ok(reports)
+}
+
+/// Returns the objects `reports` left in the bucket, once per key: the
+/// uploaded and unchanged files, or their indexes in `dedup` mode. Newer
+/// reports come first and win for keys in several of them.
+fn audit_items(reports: &[RunReport], dedup: bool) -> Vec<AuditItem> {
+    let mut keys = HashSet::new();
+    let mut items = Vec::new();
+    for report in reports {
+        for file in &report.files {
+            if !matches!(file.status, Status::Uploaded | Status::Skipped) {
+                continue;
+            }
+            // Duplicates that were not copied have no object of their own.
+            let key = file.duplicate_of.as_ref().unwrap_or(&file.remote_key);
+            let (key, etag) = if dedup {
+                (dedup::index_key(key), None)
+            } else {
+                (key.clone(), file.etag.clone())
+            };
+            if keys.insert(key.clone()) {
+                items.push(AuditItem {
+                    key,
+                    etag,
+                    run_id: report.run_id.clone(),
+                });
+            }
+        }
+    }
+    items
+}
+
+/// Reads the ETag of an object and compares it with the one of the run.
+async fn check(client: &S3Client, bucket: &str, item: &AuditItem) -> Result<Option<Problem>> {
+    let Some(head) = client.head_object(bucket, &item.key).await? else {
+        return Ok(Some(Problem::Missing));
+    };
+    Ok(match &item.etag {
+        Some(etag) if head.etag.as_ref() != Some(etag) => {
+            Some(Problem::Changed { etag: head.etag })
+        }
+        _ => None,
+    })
+}
+
+/// Checks an object while holding a concurrency slot. Throttled requests
+/// lower the concurrency limit and are retried, as they are for uploads.
+async fn check_limited(
+    ctx: Arc<AuditContext>,
+    item: AuditItem,
+) -> (AuditItem, Result<Option<Problem>>) {
+    let permit = ctx.limiter.acquire().await;
+    let result = retry_throttled(
+        &ctx.limiter,
+        &CancelToken::default(),
+        permit,
+        |_, _| {},
+        || check(&ctx.client, &ctx.bucket, &item),
+    )
+    .await;
+    (item, result)
+}
+
+/// Checks the objects `reports` left in the bucket, as many at a time as
+/// `limiter` allows.
+///
+/// # Returns
+///
+/// The lines to print, or an error holding them if an object is missing,
+/// changed or could not be checked.
+async fn audit(
+    client: &S3Client,
+    bucket: &str,
+    reports: &[RunReport],
+    dedup: bool,
+    limiter: AdaptiveConcurrency,
+) -> Result<Vec<String>> {
+    let mut lines = vec![format!("Auditing the objects of {} run(s):", reports.len())];
+    for report in reports {
+        lines.push(format!(
+            "  {} (job {}, finished {})",
+            report.run_id,
+            report.job.as_deref().unwrap_or("-"),
+            report.finished_at
+        ));
+    }
+
+    let items = audit_items(reports, dedup);
+    let count = items.len();
+    let ctx = Arc::new(AuditContext {
+        client: client.clone(),
+        bucket: bucket.to_string(),
+        limiter,
+    });
+    let mut tasks = JoinSet::new();
+    for item in items {
+        tasks.spawn(check_limited(Arc::clone(&ctx), item));
+    }
+
+    // Problems with the keys they are sorted by.
+    let mut problems = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (item, result) = joined
+            .map_err(|e| PrefixloadError::Custom(format!("Object check task failed: {}", e)))?;
+        let line = match result {
+            Ok(None) => continue,
+            Ok(Some(Problem::Missing)) => {
+                format!("MISSING  {} (run {})", item.key, item.run_id)
+            }
+            Ok(Some(Problem::Changed { etag })) => format!(
+                "CHANGED  {} has ETag {}, expected {} (run {})",
+                item.key,
+                etag.as_deref().unwrap_or("none"),
+                item.etag.as_deref().unwrap_or_default(),
+                item.run_id
+            ),
+            Err(err) => format!("FAILED   {} could not be checked: {}", item.key, err),
+        };
+        problems.push((item.key, line));
+    }
+    problems.sort_unstable();
+
+    if !problems.is_empty() {
+        let found = problems.len();
+        lines.extend(problems.into_iter().map(|(_, line)| line));
+        lines.push(format!(
+            "{} of {} objects are missing, changed or could not be checked.",
+            found, count
+        ));
+        return Err(PrefixloadError::Custom(lines.join("\n")));
+    }
+
+    lines.push(format!("All {} objects are present and unchanged.", count));
+    Ok(lines)
+}
+
+/// The main entry point for the `audit` command.
+///
+/// Reads the report of the latest run of every job (of `--job`, if given)
+/// among the reports runs upload with `upload_run_log`, and checks with a
+/// HEAD request that every object those runs uploaded or found unchanged is
+/// still in the bucket with the same ETag. Reports of versions that did not
+/// record ETags are only checked for missing objects.
+///
+/// # Returns
+///
+/// A `Result` containing the report, or a `PrefixloadError` holding it if an
+/// object is missing or changed, so scheduled audits fail visibly.
+pub async fn run(args: &AuditArgs) -> Result<String> {
+    let container = config::container_mode();
+    let config = if container {
+        Config::from_env()?
+    } else {
+        Config::load()?
+    };
+    let job = args
+        .job
+        .as_deref()
+        .map(|name| config.job(name))
+        .transpose()?;
+
+    let mut lines = Vec::new();
+    let client = bucket_client(&config, job, container, &mut |line| {
+        lines.push(line.to_string())
+    })
+    .await?;
+
+    let reports = latest_reports(&client, &config.bucket, args.job.as_deref()).await?;
+    if reports.is_empty() {
+        return Err(PrefixloadError::Custom(format!(
+            "No run{} has a report in the bucket. Runs upload their reports with \
+             `upload_run_log: true`.",
+            args.job
+                .as_deref()
+                .map(|job| format!(" of job '{}'", job))
+                .unwrap_or_default()
+        )));
+    }
+
+    let limiter = AdaptiveConcurrency::new(config.min_concurrency, config.max_concurrency);
+    lines.extend(audit(&client, &config.bucket, &reports, config.dedup, limiter).await?);
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::commands::test_support::client;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn report(run_id: &str, job: Option<&str>, files: serde_json::Value) -> RunReport {
+        serde_json::from_value(serde_json::json!({
+            "run_id": run_id,
+            "hostname": "db-01",
+            "job": job,
+            "started_at": "2024-06-01T03:00:00+00:00",
+            "finished_at": "2024-06-01T03:10:00+00:00",
+            "duration_secs": 600.0,
+            "success": true,
+            "matched": 0,
+            "uploaded": 0,
+            "skipped": 0,
+            "failed": 0,
+            "too_large": 0,
+            "max_file_size": 0,
+            "uploaded_bytes": 0,
+            "files": files
+        }))
+        .unwrap()
+    }
+
+    fn head(etag: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).insert_header("ETag", format!("\"{}\"", etag))
+    }
+
+    #[tokio::test]
+    async fn latest_reports_keeps_the_last_run_of_every_job() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("prefix", "_logs/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>_logs/20240531T030000Z-a.json</Key></Contents>\
+                 <Contents><Key>_logs/20240601T030000Z-b.json</Key></Contents>\
+                 <Contents><Key>_logs/20240602T030000Z-c.json</Key></Contents>\
+                 </ListBucketResult>",
+            ))
+            .mount(&server)
+            .await;
+        for (key, run_id, job) in [
+            ("20240531T030000Z-a", "a", Some("db")),
+            ("20240601T030000Z-b", "b", None),
+            ("20240602T030000Z-c", "c", Some("db")),
+        ] {
+            let body = serde_json::to_string(&report(run_id, job, serde_json::json!([]))).unwrap();
+            Mock::given(method("GET"))
+                .and(path(format!("/bucket/_logs/{}.json", key)))
+                .respond_with(ResponseTemplate::new(200).set_body_string(body))
+                .mount(&server)
+                .await;
+        }
+        let client = client(&server).await;
+
+        let run_ids = |reports: Vec<RunReport>| -> Vec<String> {
+            reports.into_iter().map(|report| report.run_id).collect()
+        };
+        let reports = latest_reports(&client, "bucket", None).await.unwrap();
+        assert_eq!(run_ids(reports), ["c", "b"]);
+        let reports = latest_reports(&client, "bucket", Some("db")).await.unwrap();
+        assert_eq!(run_ids(reports), ["c"]);
+        let reports = latest_reports(&client, "bucket", Some("weekly"))
+            .await
+            .unwrap();
+        assert!(reports.is_empty());
+    }
+
+    #[test]
+    fn audit_items_lists_each_stored_object_once() {
+        let newer = report(
+            "new",
+            None,
+            serde_json::json!([
+                {"local_path": "/data/a", "remote_key": "db/a", "size": 1, "status": "uploaded", "etag": "a2"},
+                {"local_path": "/data/b", "remote_key": "db/b", "size": 1, "status": "skipped", "etag": "a2", "duplicate_of": "db/a"},
+                {"local_path": "/data/c", "remote_key": "db/c", "size": 1, "status": "failed", "error": "boom"}
+            ]),
+        );
+        let older = report(
+            "old",
+            Some("db"),
+            serde_json::json!([
+                {"local_path": "/data/a", "remote_key": "db/a", "size": 1, "status": "uploaded", "etag": "a1"},
+                {"local_path": "/data/d", "remote_key": "db/d", "size": 1, "status": "skipped"}
+            ]),
+        );
+        let reports = [newer, older];
+
+        let item = |key: &str, etag: Option<&str>, run_id: &str| AuditItem {
+            key: key.to_string(),
+            etag: etag.map(str::to_string),
+            run_id: run_id.to_string(),
+        };
+        assert_eq!(
+            audit_items(&reports, false),
+            [item("db/a", Some("a2"), "new"), item("db/d", None, "old")]
+        );
+        assert_eq!(
+            audit_items(&reports, true),
+            [
+                item("db/a.index.json", None, "new"),
+                item("db/d.index.json", None, "old")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn audit_reports_missing_and_changed_objects() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/bucket/db/a"))
+            .respond_with(head("a1"))
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/bucket/db/b"))
+            .respond_with(head("b2"))
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/bucket/db/c"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/bucket/db/d"))
+            .respond_with(head("whatever"))
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+        let reports = [report(
+            "r1",
+            Some("db"),
+            serde_json::json!([
+                {"local_path": "/data/a", "remote_key": "db/a", "size": 1, "status": "uploaded", "etag": "a1"},
+                {"local_path": "/data/b", "remote_key": "db/b", "size": 1, "status": "skipped", "etag": "b1"},
+                {"local_path": "/data/c", "remote_key": "db/c", "size": 1, "status": "uploaded", "etag": "c1"},
+                {"local_path": "/data/d", "remote_key": "db/d", "size": 1, "status": "uploaded"}
+            ]),
+        )];
+
+        let err = audit(
+            &client,
+            "bucket",
+            &reports,
+            false,
+            AdaptiveConcurrency::new(1, 4),
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.contains("  r1 (job db, finished 2024-06-01T03:10:00+00:00)"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains(
+                "CHANGED  db/b has ETag b2, expected b1 (run r1)\n\
+                 MISSING  db/c (run r1)\n\
+                 2 of 4 objects are missing, changed or could not be checked."
+            ),
+            "{}",
+            err
+        );
+
+        let reports = [report(
+            "r2",
+            None,
+            serde_json::json!([
+                {"local_path": "/data/a", "remote_key": "db/a", "size": 1, "status": "uploaded", "etag": "a1"},
+                {"local_path": "/data/d", "remote_key": "db/d", "size": 1, "status": "skipped"}
+            ]),
+        )];
+        let lines = audit(
+            &client,
+            "bucket",
+            &reports,
+            false,
+            AdaptiveConcurrency::new(1, 4),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            lines,
+            [
+                "Auditing the objects of 1 run(s):",
+                "  r2 (job -, finished 2024-06-01T03:10:00+00:00)",
+                "All 2 objects are present and unchanged."
+            ]
+        );
+    }
+}
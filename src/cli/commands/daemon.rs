@@ -0,0 +1,1272 @@
+use crate::cancel::CancelToken;
+use crate::cli::commands::run::{RunControl, RunOutcome, execute, failure_of, summary};
+use crate::cli::commands::schedule::{TIME_FORMAT, next_runs, parse_schedule};
+use crate::cli::output::Styler;
+use crate::cli::{DaemonCommand, RunArgs};
+use crate::clients::sts::SessionCredentials;
+use crate::config::{self, Config};
+use crate::control;
+use crate::error::{PrefixloadError, Result};
+use crate::redact;
+use crate::report::{FailureKind, RunReport};
+use chrono::{DateTime, Local};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+
+/// File name of the control socket inside the local data directory.
+#[cfg(unix)]
+const SOCKET_NAME: &str = "daemon.sock";
+
+/// How often the daemon checks `config.yml` for edits.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Returns the path of the daemon's control socket, next to `state.json`.
+/// - Linux: ~/.local/share/prefixload/daemon.sock
+/// - macOS: ~/Library/Application Support/prefixload/daemon.sock
+#[cfg(unix)]
+fn socket_path() -> Result<PathBuf> {
+    let mut dir = dirs_next::data_local_dir().ok_or_else(|| {
+        PrefixloadError::Custom("Could not find local data directory.".to_string())
+    })?;
+
+    dir.push("prefixload");
+    fs::create_dir_all(&dir)?;
+
+    dir.push(SOCKET_NAME);
+
+    Ok(dir)
+}
+
+/// Human-readable name of what a run uploads.
+fn run_label(job: Option<&str>) -> String {
+    match job {
+        Some(job) => format!("job '{}'", job),
+        None => "all rules".to_string(),
+    }
+}
+
+/// Prints a timestamped line to stdout, the daemon's console log.
+fn say(message: &str) {
+    println!(
+        "[{}] {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        redact::redact_text(message)
+    );
+}
+
+/// What the daemon is doing, as reported by `daemon status`.
+#[derive(Debug, Default)]
+struct DaemonStatus {
+    started_at: Option<DateTime<Local>>,
+    /// Label and start time of the run in progress.
+    running: Option<(String, DateTime<Local>)>,
+    /// Cancels the run in progress.
+    cancel: Option<CancelToken>,
+    /// Job and time of the next scheduled run.
+    next: Option<(String, DateTime<Local>)>,
+    /// One-line outcome of the last run.
+    last: Option<String>,
+    /// Run counters for `/metrics`.
+    metrics: RunMetrics,
+}
+
+/// Counters of the daemon's runs, served as Prometheus metrics.
+#[derive(Debug, Default)]
+struct RunMetrics {
+    succeeded: u64,
+    failed: u64,
+    uploaded_bytes: u64,
+    last_finished_at: Option<DateTime<Local>>,
+    last_success_at: Option<DateTime<Local>>,
+    /// Report of the last run that got to sync files.
+    last_report: Option<RunReport>,
+}
+
+impl RunMetrics {
+    /// Counts a finished run; `report` is `None` for runs that failed
+    /// before they synced any files.
+    fn record(&mut self, report: Option<RunReport>, success: bool) {
+        let now = Local::now();
+        self.last_finished_at = Some(now);
+        if success {
+            self.succeeded += 1;
+            self.last_success_at = Some(now);
+        } else {
+            self.failed += 1;
+        }
+        if let Some(report) = report {
+            self.uploaded_bytes += report.uploaded_bytes;
+            self.last_report = Some(report);
+        }
+    }
+}
+
+/// State shared between the scheduler loop and the control socket.
+struct Shared {
+    status: Mutex<DaemonStatus>,
+    /// Runs requested with `daemon trigger`; `None` runs all rules.
+    triggers: mpsc::UnboundedSender<Option<String>>,
+}
+
+impl Shared {
+    fn status(&self) -> std::sync::MutexGuard<'_, DaemonStatus> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns the scheduled job that runs next after `after`, if any.
+fn next_scheduled(
+    config: &Config,
+    after: DateTime<Local>,
+) -> Result<Option<(String, DateTime<Local>)>> {
+    let mut next: Option<(String, DateTime<Local>)> = None;
+
+    for job in &config.jobs {
+        let Some(schedule) = &job.schedule else {
+            continue;
+        };
+        let cron = parse_schedule(schedule)?;
+        if let Some(time) = next_runs(&cron, after, 1).into_iter().next()
+            && next.as_ref().is_none_or(|(_, best)| time < *best)
+        {
+            next = Some((job.name.clone(), time));
+        }
+    }
+
+    Ok(next)
+}
+
+/// Renders a config value for the reload log.
+fn describe_value(value: Option<&serde_yaml::Value>) -> String {
+    match value {
+        None | Some(serde_yaml::Value::Null) => "unset".to_string(),
+        Some(serde_yaml::Value::String(s)) => s.clone(),
+        Some(value) => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+/// Lists the keys whose values differ between two mappings as
+/// `key: old -> new`, skipping the keys in `skip`. The values of keys named
+/// like secrets are masked.
+fn mapping_changes(old: &serde_yaml::Value, new: &serde_yaml::Value, skip: &[&str]) -> Vec<String> {
+    let empty = serde_yaml::Mapping::new();
+    let old = old.as_mapping().unwrap_or(&empty);
+    let new = new.as_mapping().unwrap_or(&empty);
+
+    let mut keys: Vec<&str> = old
+        .keys()
+        .chain(new.keys())
+        .filter_map(|k| k.as_str())
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter(|key| !skip.contains(key) && old.get(key) != new.get(key))
+        .map(|key| {
+            let describe = |value: Option<&serde_yaml::Value>| match value {
+                Some(value) if !value.is_null() && redact::is_secret_name(key) => {
+                    redact::MASK.to_string()
+                }
+                value => describe_value(value),
+            };
+            format!(
+                "{}: {} -> {}",
+                key,
+                describe(old.get(key)),
+                describe(new.get(key))
+            )
+        })
+        .collect()
+}
+
+/// Describes what changed between two configs, one line per change:
+/// top-level settings, rules (by `local_name_prefix`) and jobs (by name).
+fn config_changes(old: &Config, new: &Config) -> Result<Vec<String>> {
+    let mut changes = mapping_changes(
+        &serde_yaml::to_value(old)?,
+        &serde_yaml::to_value(new)?,
+        // Only the `hosts` entry of this host matters, and it is applied.
+        &["directory_struct", "jobs", "hosts"],
+    );
+
+    for rule in &old.directory_struct {
+        match new
+            .directory_struct
+            .iter()
+            .find(|r| r.local_name_prefix == rule.local_name_prefix)
+        {
+            None => changes.push(format!("rule '{}' removed", rule.local_name_prefix)),
+            Some(updated) if updated.remote_path != rule.remote_path => changes.push(format!(
+                "rule '{}': remote_path {} -> {}",
+                rule.local_name_prefix, rule.remote_path, updated.remote_path
+            )),
+            Some(_) => {}
+        }
+    }
+    for rule in &new.directory_struct {
+        if !old
+            .directory_struct
+            .iter()
+            .any(|r| r.local_name_prefix == rule.local_name_prefix)
+        {
+            changes.push(format!(
+                "rule '{}' added -> {}",
+                rule.local_name_prefix, rule.remote_path
+            ));
+        }
+    }
+
+    for job in &old.jobs {
+        match new.jobs.iter().find(|j| j.name == job.name) {
+            None => changes.push(format!("job '{}' removed", job.name)),
+            Some(updated) => {
+                for change in mapping_changes(
+                    &serde_yaml::to_value(job)?,
+                    &serde_yaml::to_value(updated)?,
+                    &[],
+                ) {
+                    changes.push(format!("job '{}': {}", job.name, change));
+                }
+            }
+        }
+    }
+    for job in &new.jobs {
+        if !old.jobs.iter().any(|j| j.name == job.name) {
+            changes.push(format!("job '{}' added", job.name));
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Notices edits of `config.yml` and of the rule files it includes by
+/// polling their modification times and sizes, which works the same on
+/// every platform and file system.
+struct ConfigFile {
+    path: PathBuf,
+    /// Each watched file with its modification time and size when last
+    /// polled; `None` if the file could not be read.
+    seen: Vec<(PathBuf, Option<(SystemTime, u64)>)>,
+}
+
+impl ConfigFile {
+    fn new(path: PathBuf, include: &[String]) -> Self {
+        let mut file = Self {
+            path,
+            seen: Vec::new(),
+        };
+        file.poll(include);
+        file
+    }
+
+    fn fingerprint(path: &Path) -> Option<(SystemTime, u64)> {
+        let metadata = fs::metadata(path).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    }
+
+    /// Returns `true` if a watched file changed since the last poll, or a
+    /// file was added to or removed from the `include` patterns.
+    fn poll(&mut self, include: &[String]) -> bool {
+        let base = self.path.parent().unwrap_or(Path::new("."));
+        // A missing included file fails the reload, which reports it.
+        let mut files = vec![self.path.clone()];
+        files.extend(config::include_files(include, base).unwrap_or_default());
+
+        let seen: Vec<_> = files
+            .into_iter()
+            .map(|file| {
+                let fingerprint = Self::fingerprint(&file);
+                (file, fingerprint)
+            })
+            .collect();
+        let changed = seen != self.seen;
+        self.seen = seen;
+        changed
+    }
+
+    /// Waits until a watched file changes.
+    async fn changed(&mut self, include: &[String]) {
+        loop {
+            tokio::time::sleep(CONFIG_POLL_INTERVAL).await;
+            if self.poll(include) {
+                return;
+            }
+        }
+    }
+}
+
+/// Re-reads `config.yml` and logs what changed, prefixed with `cause`
+/// (SIGHUP or an edit of the file). An invalid file is reported and the
+/// previous config stays in effect, for the schedules and the runs alike.
+fn reload_config(config: &mut Config, cause: &str) {
+    let reloaded = Config::load().and_then(|new| {
+        // Reject schedules the scheduler could not follow.
+        next_scheduled(&new, Local::now())?;
+        Ok(new)
+    });
+
+    match reloaded {
+        Ok(new) => {
+            match config_changes(config, &new) {
+                Ok(changes) if changes.is_empty() => {
+                    say(&format!("{}: config reloaded, nothing changed.", cause));
+                }
+                Ok(changes) => {
+                    say(&format!("{}: config reloaded. Changes:", cause));
+                    for change in changes {
+                        say(&format!("  {}", change));
+                    }
+                }
+                Err(err) => say(&format!("{}: config reloaded ({}).", cause, err)),
+            }
+            *config = new;
+        }
+        Err(err) => say(&format!(
+            "{}: keeping the previous config, the new one is invalid: {}",
+            cause, err
+        )),
+    }
+}
+
+/// Answers a single control request (`status`, `trigger [job]`, `pause`,
+/// `resume`, `cancel`).
+fn handle_request(request: &str, shared: &Shared) -> String {
+    let mut words = request.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let argument = words.next();
+
+    match command {
+        "status" => {
+            let status = shared.status();
+            let mut lines = Vec::new();
+
+            if let Some(started_at) = status.started_at {
+                lines.push(format!(
+                    "Daemon running since {} (pid {}).",
+                    started_at.format(TIME_FORMAT),
+                    std::process::id()
+                ));
+            }
+            lines.push(match &status.running {
+                Some((label, since)) => {
+                    format!("Running {} since {}.", label, since.format(TIME_FORMAT))
+                }
+                None => "Idle.".to_string(),
+            });
+            if control::is_paused() {
+                lines.push("Uploads are paused.".to_string());
+            }
+            if let Ok(Some(session)) = SessionCredentials::load() {
+                lines.push(session_status(&session));
+            }
+            lines.push(match &status.next {
+                Some((job, at)) => {
+                    format!("Next run: job '{}' at {}.", job, at.format(TIME_FORMAT))
+                }
+                None => "Next run: none scheduled.".to_string(),
+            });
+            if let Some(last) = &status.last {
+                lines.push(format!("Last run: {}", last));
+            }
+
+            lines.join("\n")
+        }
+        "trigger" => trigger(argument, shared).unwrap_or_else(|err| err.to_string()),
+        "pause" => pause().unwrap_or_else(|err| err.to_string()),
+        "resume" => resume().unwrap_or_else(|err| err.to_string()),
+        "cancel" => {
+            let status = shared.status();
+            match (&status.running, &status.cancel) {
+                (Some((label, _)), Some(cancel)) => {
+                    cancel.cancel();
+                    format!("Cancelling the run of {}.", label)
+                }
+                _ => "No run in progress.".to_string(),
+            }
+        }
+        _ => format!(
+            "Unknown command '{}'. Use status, trigger [job], pause, resume or cancel.",
+            request
+        ),
+    }
+}
+
+/// Describes when the cached session credentials of the runs expire.
+fn session_status(session: &SessionCredentials) -> String {
+    let now = chrono::Utc::now();
+    if let Some(error) = session.expiry_error(now) {
+        error
+    } else if let Some(warning) = session.expiry_warning(now) {
+        warning
+    } else {
+        format!(
+            "Runs use the {} until {}.",
+            session.source.describe(),
+            session.expires_at_local()
+        )
+    }
+}
+
+/// Queues a run of `job`, or of all rules, for the scheduler loop.
+fn trigger(job: Option<&str>, shared: &Shared) -> Result<String> {
+    if let Some(job) = job {
+        Config::load()?.job(job)?;
+    }
+    shared
+        .triggers
+        .send(job.map(str::to_string))
+        .map_err(|_| PrefixloadError::Custom("The daemon is shutting down.".to_string()))?;
+
+    Ok(format!("Triggered a run of {}.", run_label(job)))
+}
+
+/// Pauses the daemon's uploads.
+fn pause() -> Result<String> {
+    Ok(if control::pause()? {
+        "Paused."
+    } else {
+        "Already paused."
+    }
+    .to_string())
+}
+
+/// Resumes the daemon's uploads.
+fn resume() -> Result<String> {
+    Ok(if control::resume()? {
+        "Resumed."
+    } else {
+        "Not paused."
+    }
+    .to_string())
+}
+
+/// Renders the daemon's status and run counters in the Prometheus text
+/// exposition format.
+fn render_metrics(status: &DaemonStatus) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, f64)]| {
+        let _ = writeln!(out, "# HELP prefixload_{} {}", name, help);
+        let _ = writeln!(out, "# TYPE prefixload_{} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "prefixload_{}{} {}", name, labels, value);
+        }
+    };
+    let timestamp = |time: Option<DateTime<Local>>| time.map_or(0.0, |t| t.timestamp() as f64);
+    let flag = |on: bool| if on { 1.0 } else { 0.0 };
+    let metrics = &status.metrics;
+
+    metric(
+        "daemon_start_time_seconds",
+        "gauge",
+        "Unix time the daemon started.",
+        &[("", timestamp(status.started_at))],
+    );
+    metric(
+        "running",
+        "gauge",
+        "Whether a run is in progress.",
+        &[("", flag(status.running.is_some()))],
+    );
+    metric(
+        "paused",
+        "gauge",
+        "Whether uploads are paused.",
+        &[("", flag(control::is_paused()))],
+    );
+    metric(
+        "next_run_timestamp_seconds",
+        "gauge",
+        "Unix time of the next scheduled run, 0 if none.",
+        &[("", timestamp(status.next.as_ref().map(|(_, at)| *at)))],
+    );
+    metric(
+        "runs_total",
+        "counter",
+        "Runs finished since the daemon started, by result.",
+        &[
+            ("{result=\"success\"}", metrics.succeeded as f64),
+            ("{result=\"failure\"}", metrics.failed as f64),
+        ],
+    );
+    metric(
+        "uploaded_bytes_total",
+        "counter",
+        "Bytes uploaded since the daemon started.",
+        &[("", metrics.uploaded_bytes as f64)],
+    );
+    metric(
+        "last_run_timestamp_seconds",
+        "gauge",
+        "Unix time the last run finished, 0 if none.",
+        &[("", timestamp(metrics.last_finished_at))],
+    );
+    metric(
+        "last_success_timestamp_seconds",
+        "gauge",
+        "Unix time the last successful run finished, 0 if none.",
+        &[("", timestamp(metrics.last_success_at))],
+    );
+    metric(
+        "last_run_success",
+        "gauge",
+        "Whether the last run succeeded.",
+        &[(
+            "",
+            flag(
+                metrics.last_finished_at.is_some()
+                    && metrics.last_finished_at == metrics.last_success_at,
+            ),
+        )],
+    );
+    if let Some(report) = &metrics.last_report {
+        metric(
+            "last_run_duration_seconds",
+            "gauge",
+            "Duration of the last run that synced files.",
+            &[("", report.duration_secs)],
+        );
+        metric(
+            "last_run_files",
+            "gauge",
+            "Files of the last run that synced files, by status.",
+            &[
+                ("{status=\"uploaded\"}", report.uploaded as f64),
+                ("{status=\"skipped\"}", report.skipped as f64),
+                ("{status=\"failed\"}", report.failed as f64),
+                ("{status=\"too_large\"}", report.too_large as f64),
+            ],
+        );
+        metric(
+            "last_run_uploaded_bytes",
+            "gauge",
+            "Bytes uploaded by the last run that synced files.",
+            &[("", report.uploaded_bytes as f64)],
+        );
+    }
+
+    out
+}
+
+/// Answers an HTTP request for `/metrics` or `/healthz` with its status
+/// line, content type and body.
+fn http_response(request_line: &str, shared: &Shared) -> (&'static str, &'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    match (method, path) {
+        ("GET", "/metrics") => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            render_metrics(&shared.status()),
+        ),
+        ("GET", "/healthz") => ("200 OK", "text/plain", "ok\n".to_string()),
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+    }
+}
+
+/// Runs one backup in summary-only mode and records its outcome.
+/// `read_only` is the global `--read-only` flag of `daemon start`. The run
+/// uses `config`, the last valid version of `config.yml`.
+async fn run_once(job: Option<String>, shared: &Shared, read_only: bool, config: Config) {
+    let label = run_label(job.as_deref());
+    say(&format!("Starting a run of {}.", label));
+    let keep_going = config.defaults.keep_going;
+    let control = RunControl {
+        config: Some(config),
+        ..RunControl::default()
+    };
+    {
+        let mut status = shared.status();
+        status.running = Some((label.clone(), Local::now()));
+        status.cancel = Some(control.cancel.clone());
+    }
+
+    let args = RunArgs {
+        summary_only: true,
+        keep_going,
+        job,
+        read_only,
+        ..Default::default()
+    };
+    let outcome = match execute(&args, Styler::plain(), control).await {
+        Ok(RunOutcome::NotStarted(note)) => note,
+        Ok(RunOutcome::Finished { report, error }) => {
+            let outcome = match &error {
+                Some(err) => format!(
+                    "failed ({}): {} {}",
+                    failure_of(&report, err),
+                    err,
+                    summary(&report)
+                ),
+                None => summary(&report),
+            };
+            shared
+                .status()
+                .metrics
+                .record(Some(*report), error.is_none());
+            outcome
+        }
+        Err(err) => {
+            shared.status().metrics.record(None, false);
+            format!("failed ({}): {}", FailureKind::of(&err), err)
+        }
+    };
+
+    say(&format!("Run of {} finished: {}", label, outcome));
+    let mut status = shared.status();
+    status.running = None;
+    status.cancel = None;
+    status.last = Some(format!(
+        "{} at {}: {}",
+        label,
+        Local::now().format(TIME_FORMAT),
+        outcome
+    ));
+}
+
+#[cfg(feature = "grpc")]
+mod grpc;
+
+#[cfg(unix)]
+mod socket {
+    use super::*;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+
+    /// Binds the control socket, replacing a stale one left by a crashed daemon.
+    pub(super) async fn bind(path: &Path) -> Result<UnixListener> {
+        if path.exists() {
+            if UnixStream::connect(path).await.is_ok() {
+                return Err(PrefixloadError::Custom(format!(
+                    "A daemon is already running (control socket {}).",
+                    path.display()
+                )));
+            }
+            fs::remove_file(path)?;
+        }
+
+        Ok(UnixListener::bind(path)?)
+    }
+
+    /// Accepts control connections until the task is aborted. Each
+    /// connection sends one request line and receives one reply.
+    pub(super) async fn serve(listener: UnixListener, shared: Arc<Shared>) {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let shared = Arc::clone(&shared);
+
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut request = String::new();
+                if BufReader::new(reader).read_line(&mut request).await.is_ok() {
+                    let reply = handle_request(request.trim(), &shared);
+                    let _ = writer.write_all(reply.as_bytes()).await;
+                }
+            });
+        }
+    }
+
+    /// Sends one request to a running daemon and returns its reply.
+    pub(super) async fn send(path: &Path, request: &str) -> Result<String> {
+        let mut stream = UnixStream::connect(path).await.map_err(|e| {
+            PrefixloadError::Custom(format!(
+                "Could not reach the daemon at {}: {}. Is `prefixload daemon start` running?",
+                path.display(),
+                e
+            ))
+        })?;
+
+        stream
+            .write_all(format!("{}\n", request).as_bytes())
+            .await?;
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).await?;
+
+        Ok(reply)
+    }
+}
+
+mod http {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Binds the metrics endpoint to `port` on the loopback interface.
+    pub(super) async fn bind(port: u16) -> Result<TcpListener> {
+        TcpListener::bind(("127.0.0.1", port)).await.map_err(|e| {
+            PrefixloadError::Custom(format!(
+                "Failed to listen for metrics on 127.0.0.1:{}: {}",
+                port, e
+            ))
+        })
+    }
+
+    /// Answers HTTP requests for `/metrics` and `/healthz` until the task
+    /// is aborted, one request per connection.
+    pub(super) async fn serve(listener: TcpListener, shared: Arc<Shared>) {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let shared = Arc::clone(&shared);
+
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut reader = BufReader::new(reader);
+                let mut request_line = String::new();
+                if reader.read_line(&mut request_line).await.is_err() {
+                    return;
+                }
+                // The headers are read but not needed.
+                let mut header = String::new();
+                while matches!(reader.read_line(&mut header).await, Ok(n) if n > 0)
+                    && !header.trim().is_empty()
+                {
+                    header.clear();
+                }
+
+                let (status, content_type, body) = http_response(&request_line, &shared);
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    content_type,
+                    body.len(),
+                    body
+                );
+                let _ = writer.write_all(response.as_bytes()).await;
+            });
+        }
+    }
+}
+
+/// Serves the gRPC control service on `addr` for fleet controllers.
+#[cfg(feature = "grpc")]
+async fn start_grpc(
+    config: &Config,
+    addr: std::net::SocketAddr,
+    shared: std::sync::Arc<Shared>,
+) -> Result<Option<tokio::task::JoinHandle<()>>> {
+    let server = grpc::spawn(config, addr, shared).await?;
+    say(&format!("Serving gRPC control (mutual TLS) on {}", addr));
+    Ok(Some(server))
+}
+
+#[cfg(not(feature = "grpc"))]
+async fn start_grpc(
+    _config: &Config,
+    addr: std::net::SocketAddr,
+    _shared: std::sync::Arc<Shared>,
+) -> Result<Option<tokio::task::JoinHandle<()>>> {
+    say(&format!(
+        "Warning: not serving gRPC control on {}: this build has no gRPC support \
+         (build with `--features grpc`).",
+        addr
+    ));
+    Ok(None)
+}
+
+/// SIGHUP on Unix, which asks the daemon to re-read `config.yml`. Other
+/// platforms have no such signal, so it never arrives there.
+struct Hangup {
+    #[cfg(unix)]
+    signal: tokio::signal::unix::Signal,
+}
+
+impl Hangup {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?,
+        })
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        self.signal.recv().await;
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Runs the scheduler in the foreground until Ctrl-C: see `serve`.
+async fn start(read_only: bool) -> Result<String> {
+    let stop = CancelToken::new();
+    let ctrl_c = {
+        let stop = stop.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                stop.cancel();
+            }
+        })
+    };
+    let result = serve(read_only, &stop).await;
+    ctrl_c.abort();
+    result
+}
+
+/// Runs the scheduler: executes jobs on their schedule and runs triggered
+/// from the control socket, one at a time, until `stop` is cancelled,
+/// which cancels the run in progress.
+///
+/// SIGHUP re-reads `config.yml`, and so do edits of the file or of the rule
+/// files it includes, noticed within `CONFIG_POLL_INTERVAL`; a SIGHUP or edit
+/// during a run is applied once the run finishes. Runs use the last valid
+/// version of the file.
+///
+/// The control socket and SIGHUP are Unix-only: elsewhere, e.g. in the
+/// Windows service, the daemon only follows the schedules and edits.
+pub(crate) async fn serve(read_only: bool, stop: &CancelToken) -> Result<String> {
+    use std::sync::Arc;
+
+    #[cfg(unix)]
+    let path = socket_path()?;
+    #[cfg(unix)]
+    let listener = socket::bind(&path).await?;
+    let mut config = Config::load()?;
+    let mut config_file = ConfigFile::new(Config::config_path()?, &config.include);
+    let mut hangup = Hangup::new()?;
+
+    let (triggers, mut trigger_rx) = mpsc::unbounded_channel();
+    let shared = Arc::new(Shared {
+        status: Mutex::new(DaemonStatus {
+            started_at: Some(Local::now()),
+            ..Default::default()
+        }),
+        triggers,
+    });
+    #[cfg(unix)]
+    let server = tokio::spawn(socket::serve(listener, Arc::clone(&shared)));
+    let metrics_server = match config.metrics_port {
+        Some(port) => {
+            let listener = http::bind(port).await?;
+            say(&format!(
+                "Serving metrics on http://127.0.0.1:{}/metrics",
+                port
+            ));
+            Some(tokio::spawn(http::serve(listener, Arc::clone(&shared))))
+        }
+        None => None,
+    };
+    let grpc_server = match config.grpc_listen {
+        Some(addr) => start_grpc(&config, addr, Arc::clone(&shared)).await?,
+        None => None,
+    };
+
+    #[cfg(unix)]
+    say(&format!(
+        "Daemon started. Control socket: {}",
+        path.display()
+    ));
+    #[cfg(not(unix))]
+    say("Daemon started. This platform has no control socket.");
+
+    loop {
+        let next = next_scheduled(&config, Local::now())?;
+        match &next {
+            Some((job, at)) => say(&format!(
+                "Next run: job '{}' at {}.",
+                job,
+                at.format(TIME_FORMAT)
+            )),
+            None => say("No scheduled jobs. Waiting for `prefixload daemon trigger`."),
+        }
+        shared.status().next = next.clone();
+
+        let until_next = next
+            .as_ref()
+            .map(|(_, at)| (*at - Local::now()).to_std().unwrap_or_default());
+
+        let include = config.include.clone();
+        let job = tokio::select! {
+            _ = async {
+                match until_next {
+                    Some(wait) => tokio::time::sleep(wait).await,
+                    None => std::future::pending().await,
+                }
+            } => next.map(|(job, _)| job),
+            Some(job) = trigger_rx.recv() => job,
+            _ = hangup.recv() => {
+                // The edit SIGHUP announces is not reloaded a second time.
+                config_file.poll(&include);
+                reload_config(&mut config, "Received SIGHUP");
+                continue;
+            }
+            _ = config_file.changed(&include) => {
+                reload_config(&mut config, "config.yml changed");
+                continue;
+            }
+            _ = stop.cancelled() => break,
+        };
+
+        let run = run_once(job, &shared, read_only, config.clone());
+        tokio::pin!(run);
+        tokio::select! {
+            _ = &mut run => {}
+            _ = stop.cancelled() => {
+                // The run aborts its multipart uploads before the daemon stops.
+                let cancel = shared.status().cancel.clone();
+                if let Some(cancel) = cancel {
+                    cancel.cancel();
+                }
+                run.await;
+                break;
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        server.abort();
+        let _ = fs::remove_file(&path);
+    }
+    if let Some(metrics_server) = metrics_server {
+        metrics_server.abort();
+    }
+    if let Some(grpc_server) = grpc_server {
+        grpc_server.abort();
+    }
+
+    Ok("Daemon stopped.\n".to_string())
+}
+
+#[cfg(not(unix))]
+fn unsupported() -> PrefixloadError {
+    PrefixloadError::Custom(
+        "Controlling the daemon needs its control socket, which is only available on \
+         Unix-like systems."
+            .to_string(),
+    )
+}
+
+/// Sends a control request to the running daemon.
+async fn request(request: &str) -> Result<String> {
+    #[cfg(unix)]
+    {
+        let reply = socket::send(&socket_path()?, request).await?;
+        Ok(format!("{}\n", reply))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = request;
+        Err(unsupported())
+    }
+}
+
+/// Dispatches `daemon` subcommands. `read_only` applies to the runs of
+/// `daemon start`.
+pub async fn run(command: DaemonCommand, read_only: bool) -> Result<String> {
+    match command {
+        DaemonCommand::Start => start(read_only).await,
+        DaemonCommand::Status => request("status").await,
+        DaemonCommand::Trigger { job } => match job {
+            Some(job) => request(&format!("trigger {}", job)).await,
+            None => request("trigger").await,
+        },
+        DaemonCommand::Pause => request("pause").await,
+        DaemonCommand::Resume => request("resume").await,
+        DaemonCommand::Cancel => request("cancel").await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serial_test::serial;
+    use std::env;
+    use tempfile::TempDir;
+
+    #[cfg(not(windows))]
+    const DATA_LOCAL_ENV: &str = "XDG_DATA_HOME";
+    #[cfg(windows)]
+    const DATA_LOCAL_ENV: &str = "LOCALAPPDATA";
+
+    fn temp_data_dir() -> TempDir {
+        let tmp = TempDir::new().expect("temp dir");
+        unsafe { env::set_var(DATA_LOCAL_ENV, tmp.path()) };
+        tmp
+    }
+
+    fn shared() -> (Shared, mpsc::UnboundedReceiver<Option<String>>) {
+        let (triggers, rx) = mpsc::unbounded_channel();
+        let shared = Shared {
+            status: Mutex::new(DaemonStatus::default()),
+            triggers,
+        };
+        (shared, rx)
+    }
+
+    /// Parses a minimal config followed by `rest`.
+    fn config(rest: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            "endpoint: e\nbucket: b\nregion: r\nforce_path_style: false\npart_size: 5MiB\n\
+             local_directory_path: /tmp\n{}",
+            rest
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn next_scheduled_picks_earliest_job() {
+        let config = config(
+            "directory_struct: []\njobs:\n\
+             - name: nightly\n  schedule: daily at 03:00\n\
+             - name: hourly\n  schedule: hourly\n\
+             - name: manual\n",
+        );
+        let after = Local.with_ymd_and_hms(2025, 9, 21, 12, 30, 0).unwrap();
+
+        let (job, at) = next_scheduled(&config, after).unwrap().unwrap();
+        assert_eq!(job, "hourly");
+        assert_eq!(at, Local.with_ymd_and_hms(2025, 9, 21, 13, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn config_file_poll_notices_edits() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.yml");
+        fs::write(&path, "bucket: b\n").unwrap();
+
+        let mut file = ConfigFile::new(path.clone(), &[]);
+        assert!(!file.poll(&[]));
+        fs::write(&path, "bucket: backups\n").unwrap();
+        assert!(file.poll(&[]));
+        assert!(!file.poll(&[]));
+        fs::remove_file(&path).unwrap();
+        assert!(file.poll(&[]));
+    }
+
+    #[test]
+    fn config_file_poll_notices_included_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.yml");
+        fs::write(&path, "bucket: b\n").unwrap();
+        fs::create_dir(dir.path().join("rules.d")).unwrap();
+        let include = vec!["rules.d/*.yml".to_string()];
+
+        let mut file = ConfigFile::new(path, &include);
+        let rules = dir.path().join("rules.d").join("db.yml");
+        fs::write(&rules, "directory_struct: []\n").unwrap();
+        assert!(file.poll(&include));
+        assert!(!file.poll(&include));
+        fs::write(
+            &rules,
+            "directory_struct:\n- {local_name_prefix: db_, remote_path: db/}\n",
+        )
+        .unwrap();
+        assert!(file.poll(&include));
+        fs::remove_file(&rules).unwrap();
+        assert!(file.poll(&include));
+    }
+
+    #[test]
+    fn config_changes_lists_settings_rules_and_jobs() {
+        let old = config(
+            "directory_struct:\n\
+             - {local_name_prefix: db_, remote_path: db/}\n\
+             - {local_name_prefix: logs_, remote_path: logs/}\n\
+             jobs:\n\
+             - {name: nightly, schedule: daily at 03:00}\n\
+             - {name: weekly, schedule: weekly}\n",
+        );
+        let new = config(
+            "max_duration: 60\ndirectory_struct:\n\
+             - {local_name_prefix: db_, remote_path: database/}\n\
+             - {local_name_prefix: media_, remote_path: media/}\n\
+             jobs:\n\
+             - {name: nightly, schedule: daily at 04:00, rules: [db_]}\n\
+             - {name: hourly, schedule: hourly}\n",
+        );
+
+        assert_eq!(
+            config_changes(&old, &new).unwrap(),
+            vec![
+                "max_duration: unset -> 60",
+                "rule 'db_': remote_path db/ -> database/",
+                "rule 'logs_' removed",
+                "rule 'media_' added -> media/",
+                "job 'nightly': rules: unset -> [\"db_\"]",
+                "job 'nightly': schedule: daily at 03:00 -> daily at 04:00",
+                "job 'weekly' removed",
+                "job 'hourly' added",
+            ]
+        );
+        assert!(config_changes(&old, &old).unwrap().is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn handle_request_triggers_and_reports_status() {
+        let _guard = temp_data_dir();
+        let (shared, mut rx) = shared();
+
+        assert_eq!(
+            handle_request("trigger", &shared),
+            "Triggered a run of all rules."
+        );
+        assert_eq!(rx.try_recv().unwrap(), None);
+
+        shared.status().running = Some(("job 'x'".to_string(), Local::now()));
+        assert_eq!(handle_request("pause", &shared), "Paused.");
+
+        let status = handle_request("status", &shared);
+        assert!(status.contains("Running job 'x' since"));
+        assert!(status.contains("Uploads are paused."));
+        assert!(status.contains("Next run: none scheduled."));
+        assert!(!status.contains("Credentials"));
+
+        SessionCredentials {
+            access_key: "ASIAKEY".to_string(),
+            secret_key: "SECRET".to_string(),
+            session_token: "TOKEN".to_string(),
+            expires_at: chrono::Utc::now() - chrono::Duration::hours(1),
+            source: crate::clients::sts::SessionSource::Env,
+        }
+        .save()
+        .unwrap();
+        let status = handle_request("status", &shared);
+        assert!(
+            status.contains("Credentials expired: the temporary credentials"),
+            "{}",
+            status
+        );
+        assert!(status.contains("prefixload login --from-env"));
+
+        assert_eq!(handle_request("resume", &shared), "Resumed.");
+        assert!(handle_request("reboot", &shared).starts_with("Unknown command"));
+
+        let cancel = CancelToken::new();
+        shared.status().cancel = Some(cancel.clone());
+        assert_eq!(
+            handle_request("cancel", &shared),
+            "Cancelling the run of job 'x'."
+        );
+        assert!(cancel.is_cancelled());
+        shared.status().running = None;
+        assert_eq!(handle_request("cancel", &shared), "No run in progress.");
+    }
+
+    fn report(uploaded: usize, uploaded_bytes: u64) -> RunReport {
+        RunReport {
+            run_id: "run-1".to_string(),
+            hostname: String::new(),
+            job: None,
+            started_at: String::new(),
+            finished_at: String::new(),
+            duration_secs: 12.5,
+            success: true,
+            matched: uploaded + 1,
+            uploaded,
+            skipped: 1,
+            failed: 0,
+            too_large: 0,
+            max_file_size: 0,
+            uploaded_bytes,
+            error: None,
+            rules: Vec::new(),
+            requests: None,
+            interrupted: Vec::new(),
+            failure: None,
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn render_metrics_exposes_run_counters() {
+        let _guard = temp_data_dir();
+        let mut status = DaemonStatus::default();
+
+        let metrics = render_metrics(&status);
+        assert!(metrics.contains("# TYPE prefixload_runs_total counter\n"));
+        assert!(metrics.contains("prefixload_runs_total{result=\"success\"} 0\n"));
+        assert!(metrics.contains("prefixload_last_run_success 0\n"));
+        assert!(!metrics.contains("prefixload_last_run_files"));
+
+        status.metrics.record(Some(report(2, 2048)), true);
+        status.metrics.record(None, false);
+        status.metrics.record(Some(report(3, 1024)), true);
+
+        let metrics = render_metrics(&status);
+        assert!(metrics.contains("prefixload_runs_total{result=\"success\"} 2\n"));
+        assert!(metrics.contains("prefixload_runs_total{result=\"failure\"} 1\n"));
+        assert!(metrics.contains("prefixload_uploaded_bytes_total 3072\n"));
+        assert!(metrics.contains("prefixload_last_run_success 1\n"));
+        assert!(metrics.contains("prefixload_last_run_files{status=\"uploaded\"} 3\n"));
+        assert!(metrics.contains("prefixload_last_run_duration_seconds 12.5\n"));
+        assert!(metrics.contains("prefixload_running 0\n"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    #[serial]
+    async fn metrics_endpoint_serves_metrics_and_health() {
+        use std::sync::Arc;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpStream;
+
+        let _guard = temp_data_dir();
+        let listener = http::bind(0).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shared, _rx) = shared();
+        let server = tokio::spawn(http::serve(listener, Arc::new(shared)));
+
+        let get = |path: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let response = get("/healthz").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\nok\n"));
+
+        let response = get("/metrics").await;
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+        assert!(response.contains("prefixload_runs_total{result=\"failure\"} 0\n"));
+
+        assert!(get("/").await.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        server.abort();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    #[serial]
+    async fn control_socket_roundtrip() {
+        use std::sync::Arc;
+
+        let guard = temp_data_dir();
+        let path = guard.path().join(SOCKET_NAME);
+        let listener = socket::bind(&path).await.unwrap();
+        let (shared, _rx) = shared();
+        let server = tokio::spawn(socket::serve(listener, Arc::new(shared)));
+
+        let reply = socket::send(&path, "status").await.unwrap();
+        assert!(reply.contains("Idle."));
+
+        let err = socket::bind(&path).await.unwrap_err();
+        assert!(err.to_string().contains("already running"));
+
+        server.abort();
+    }
+}
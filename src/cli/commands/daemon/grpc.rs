@@ -0,0 +1,277 @@
+use super::{Shared, pause, resume, say, trigger};
+use crate::config::Config;
+use crate::control;
+use crate::error::{PrefixloadError, Result};
+use chrono::{DateTime, Local};
+use proto::daemon_server::{Daemon, DaemonServer};
+use proto::{
+    ControlReply, PauseRequest, ResumeRequest, StatusReply, StatusRequest, TriggerRequest,
+};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tonic::{Request, Response, Status};
+
+/// Messages and server of `prefixload.v1.Daemon`, as described in
+/// `proto/prefixload.proto`.
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StatusRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StatusReply {
+        #[prost(uint32, tag = "1")]
+        pub pid: u32,
+        #[prost(int64, tag = "2")]
+        pub started_at: i64,
+        #[prost(string, tag = "3")]
+        pub running: String,
+        #[prost(int64, tag = "4")]
+        pub running_since: i64,
+        #[prost(bool, tag = "5")]
+        pub paused: bool,
+        #[prost(string, tag = "6")]
+        pub next_job: String,
+        #[prost(int64, tag = "7")]
+        pub next_run_at: i64,
+        #[prost(string, tag = "8")]
+        pub last_run: String,
+        #[prost(int64, tag = "9")]
+        pub last_run_at: i64,
+        #[prost(int64, tag = "10")]
+        pub last_success_at: i64,
+        #[prost(uint64, tag = "11")]
+        pub runs_succeeded: u64,
+        #[prost(uint64, tag = "12")]
+        pub runs_failed: u64,
+        #[prost(uint64, tag = "13")]
+        pub uploaded_bytes: u64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TriggerRequest {
+        #[prost(string, tag = "1")]
+        pub job: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PauseRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ResumeRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ControlReply {
+        #[prost(string, tag = "1")]
+        pub message: String,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/prefixload.v1.Daemon.rs"));
+}
+
+/// The daemon's controls, the same as on the control socket.
+struct DaemonService {
+    shared: Arc<Shared>,
+}
+
+#[tonic::async_trait]
+impl Daemon for DaemonService {
+    async fn status(
+        &self,
+        _request: Request<StatusRequest>,
+    ) -> std::result::Result<Response<StatusReply>, Status> {
+        Ok(Response::new(status_reply(&self.shared)))
+    }
+
+    async fn trigger(
+        &self,
+        request: Request<TriggerRequest>,
+    ) -> std::result::Result<Response<ControlReply>, Status> {
+        let job = request.into_inner().job;
+        let job = Some(job.as_str()).filter(|job| !job.is_empty());
+        reply(trigger(job, &self.shared))
+    }
+
+    async fn pause(
+        &self,
+        _request: Request<PauseRequest>,
+    ) -> std::result::Result<Response<ControlReply>, Status> {
+        reply(pause())
+    }
+
+    async fn resume(
+        &self,
+        _request: Request<ResumeRequest>,
+    ) -> std::result::Result<Response<ControlReply>, Status> {
+        reply(resume())
+    }
+}
+
+/// Wraps the reply of a control operation; errors are reported as
+/// `FAILED_PRECONDITION` with their message.
+fn reply(result: Result<String>) -> std::result::Result<Response<ControlReply>, Status> {
+    result
+        .map(|message| Response::new(ControlReply { message }))
+        .map_err(|err| Status::failed_precondition(err.to_string()))
+}
+
+/// Snapshots the daemon's status and run counters.
+fn status_reply(shared: &Shared) -> StatusReply {
+    let status = shared.status();
+    let timestamp = |time: Option<DateTime<Local>>| time.map_or(0, |t| t.timestamp());
+    let (running, running_since) = match &status.running {
+        Some((label, since)) => (label.clone(), since.timestamp()),
+        None => (String::new(), 0),
+    };
+    let (next_job, next_run_at) = match &status.next {
+        Some((job, at)) => (job.clone(), at.timestamp()),
+        None => (String::new(), 0),
+    };
+
+    StatusReply {
+        pid: std::process::id(),
+        started_at: timestamp(status.started_at),
+        running,
+        running_since,
+        paused: control::is_paused(),
+        next_job,
+        next_run_at,
+        last_run: status.last.clone().unwrap_or_default(),
+        last_run_at: timestamp(status.metrics.last_finished_at),
+        last_success_at: timestamp(status.metrics.last_success_at),
+        runs_succeeded: status.metrics.succeeded,
+        runs_failed: status.metrics.failed,
+        uploaded_bytes: status.metrics.uploaded_bytes,
+    }
+}
+
+/// Reads a PEM file of the mutual TLS setup.
+fn read_pem(path: Option<&Path>, option: &str) -> Result<Vec<u8>> {
+    let path = path.ok_or_else(|| {
+        PrefixloadError::Custom(format!("grpc_listen needs {} for mutual TLS.", option))
+    })?;
+
+    fs::read(path).map_err(|err| {
+        PrefixloadError::Custom(format!(
+            "Failed to read {} {}: {}",
+            option,
+            path.display(),
+            err
+        ))
+    })
+}
+
+/// Loads the server certificate and the CA of the clients it accepts.
+/// Clients without a certificate signed by that CA are refused.
+fn tls_config(config: &Config) -> Result<ServerTlsConfig> {
+    let cert = read_pem(config.grpc_cert_file.as_deref(), "grpc_cert_file")?;
+    let key = read_pem(config.grpc_key_file.as_deref(), "grpc_key_file")?;
+    let client_ca = read_pem(config.grpc_client_ca_file.as_deref(), "grpc_client_ca_file")?;
+
+    Ok(ServerTlsConfig::new()
+        .identity(Identity::from_pem(cert, key))
+        .client_ca_root(Certificate::from_pem(client_ca)))
+}
+
+/// Binds the gRPC control service to `addr` and serves it until the
+/// returned task is aborted.
+pub(super) async fn spawn(
+    config: &Config,
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+) -> Result<JoinHandle<()>> {
+    let mut server = Server::builder()
+        .tls_config(tls_config(config)?)
+        .map_err(|e| {
+            PrefixloadError::Custom(format!("Invalid TLS setup of the gRPC service: {}", e))
+        })?;
+    let listener = TcpListener::bind(addr).await.map_err(|e| {
+        PrefixloadError::Custom(format!("Failed to listen for gRPC on {}: {}", addr, e))
+    })?;
+    let router = server.add_service(DaemonServer::new(DaemonService { shared }));
+
+    Ok(tokio::spawn(async move {
+        if let Err(err) = router
+            .serve_with_incoming(TcpIncoming::from(listener))
+            .await
+        {
+            say(&format!("gRPC control service stopped: {}", err));
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::commands::daemon::DaemonStatus;
+    use serial_test::serial;
+    use std::env;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+    use tokio::sync::mpsc;
+
+    fn shared() -> (Arc<Shared>, mpsc::UnboundedReceiver<Option<String>>) {
+        let (triggers, rx) = mpsc::unbounded_channel();
+        let shared = Shared {
+            status: Mutex::new(DaemonStatus::default()),
+            triggers,
+        };
+        (Arc::new(shared), rx)
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn service_triggers_pauses_and_reports_status() {
+        let tmp = TempDir::new().unwrap();
+        unsafe { env::set_var("XDG_DATA_HOME", tmp.path()) };
+        let (shared, mut rx) = shared();
+        shared.status().metrics.record(None, false);
+        let service = DaemonService { shared };
+
+        let reply = service
+            .trigger(Request::new(TriggerRequest::default()))
+            .await
+            .unwrap();
+        assert_eq!(reply.get_ref().message, "Triggered a run of all rules.");
+        assert_eq!(rx.try_recv().unwrap(), None);
+
+        let reply = service.pause(Request::new(PauseRequest {})).await.unwrap();
+        assert_eq!(reply.get_ref().message, "Paused.");
+
+        let status = service
+            .status(Request::new(StatusRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(status.paused);
+        assert_eq!(status.running, "");
+        assert_eq!(status.runs_failed, 1);
+        assert!(status.last_run_at > 0);
+        assert_eq!(status.last_success_at, 0);
+
+        let reply = service
+            .resume(Request::new(ResumeRequest {}))
+            .await
+            .unwrap();
+        assert_eq!(reply.get_ref().message, "Resumed.");
+    }
+
+    #[test]
+    fn tls_config_reports_unreadable_files() {
+        let tmp = TempDir::new().unwrap();
+        let mut config: Config = serde_yaml::from_str(
+            "endpoint: e\nbucket: b\nregion: r\nforce_path_style: false\npart_size: 5MiB\n\
+             local_directory_path: /tmp\ndirectory_struct: []\n",
+        )
+        .unwrap();
+        config.grpc_cert_file = Some(tmp.path().join("server.pem"));
+
+        let err = tls_config(&config).unwrap_err();
+        assert!(err.to_string().contains("Failed to read grpc_cert_file"));
+    }
+}
@@ -0,0 +1,322 @@
+// This module handles the `doctor` command, which probes the S3 operations a
+// run needs with the configured credentials and explains missing permissions.
+
+use crate::cli::DoctorArgs;
+use crate::cli::commands::run::bucket_client;
+use crate::clients::s3::{Access, S3Client};
+use crate::config::{self, Config};
+use crate::error::{PrefixloadError, Result};
+use uuid::Uuid;
+
+/// Name of the empty object written (and deleted again) to probe uploads.
+const PROBE_OBJECT_PREFIX: &str = ".prefixload-doctor-";
+
+/// Outcome of probing one permission on one resource.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Check {
+    permission: &'static str,
+    resource: String,
+    access: Access,
+    /// What to grant when the check fails, or why it does not matter.
+    hint: String,
+    /// `false` for permissions a run does not need.
+    required: bool,
+}
+
+impl Check {
+    fn line(&self) -> String {
+        let status = match (&self.access, self.required) {
+            (Access::Allowed, _) => "ok",
+            (Access::Denied(_), true) => "MISSING",
+            (Access::Denied(_), false) => "note",
+        };
+        let mut line = format!("{:<8} {:<15} {}", status, self.permission, self.resource);
+        if let Access::Denied(code) = &self.access {
+            line.push_str(&format!("\n         {} ({})", self.hint, code));
+        }
+        line
+    }
+}
+
+/// Probes the operations a run performs under `prefix` (a rule's
+/// `remote_path`): listing the prefix, reading an object's ETag and
+/// uploading an object. The probe object is deleted again; runs never
+/// delete objects, so a denied delete is only noted. A read-only client
+/// only probes listing and reading.
+async fn check_prefix(client: &S3Client, bucket: &str, prefix: &str) -> Result<Vec<Check>> {
+    let prefix = key_prefix(prefix);
+    let key = format!("{}{}{}", prefix, PROBE_OBJECT_PREFIX, Uuid::new_v4());
+    let bucket_arn = format!("arn:aws:s3:::{}", bucket);
+    let objects_arn = format!("{}/{}*", bucket_arn, prefix);
+
+    let mut checks = vec![
+        Check {
+            permission: "s3:ListBucket",
+            resource: format!("{} (prefix {:?})", bucket_arn, prefix),
+            access: client.probe_list(bucket, &prefix).await?,
+            hint: format!(
+                "Grant s3:ListBucket on {}. Without it, S3 answers 403 instead of 404 \
+                 for files that were never uploaded.",
+                bucket_arn
+            ),
+            required: true,
+        },
+        Check {
+            permission: "s3:GetObject",
+            resource: objects_arn.clone(),
+            access: client.probe_head(bucket, &key).await?,
+            hint: format!(
+                "Grant s3:GetObject on {} (and s3:ListBucket on {}). Runs read the \
+                 ETags of uploaded objects to skip unchanged files.",
+                objects_arn, bucket_arn
+            ),
+            required: true,
+        },
+    ];
+    if client.is_read_only() {
+        return Ok(checks);
+    }
+
+    let put = client.probe_put(bucket, &key).await?;
+    let uploaded = put == Access::Allowed;
+    checks.push(Check {
+        permission: "s3:PutObject",
+        resource: objects_arn.clone(),
+        access: put,
+        hint: format!(
+            "Grant s3:PutObject on {}. It covers multipart uploads and copies as well.",
+            objects_arn
+        ),
+        required: true,
+    });
+
+    if uploaded {
+        checks.push(Check {
+            permission: "s3:DeleteObject",
+            resource: objects_arn,
+            access: client.probe_delete(bucket, &key).await?,
+            hint: format!(
+                "Runs do not need s3:DeleteObject, but the empty probe object {} \
+                 was left behind; delete it by hand.",
+                key
+            ),
+            required: false,
+        });
+    }
+
+    Ok(checks)
+}
+
+/// Returns the key prefix of the objects under `remote_path`.
+fn key_prefix(remote_path: &str) -> String {
+    let prefix = remote_path.trim_matches('/');
+    if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", prefix)
+    }
+}
+
+/// The main entry point for the `doctor` command.
+///
+/// Probes the S3 permissions of every distinct `remote_path` of the rules
+/// (of `--job`, if given) with the credentials a run would use, and fails
+/// with the list of missing permissions and what to grant. In read-only
+/// mode, uploads are not probed.
+///
+/// # Returns
+///
+/// A `Result` containing the report, or a `PrefixloadError` holding it if a
+/// required permission is missing.
+pub async fn run(args: &DoctorArgs, read_only: bool) -> Result<String> {
+    let container = config::container_mode();
+    let mut config = if container {
+        Config::from_env()?
+    } else {
+        Config::load()?
+    };
+    config.read_only |= read_only;
+    let job = args
+        .job
+        .as_deref()
+        .map(|name| config.job(name))
+        .transpose()?;
+    let rules = match job {
+        Some(job) => config.rules_for_job(job)?,
+        None => config.directory_struct.clone(),
+    };
+
+    let mut lines = Vec::new();
+    let client = bucket_client(&config, job, container, &mut |line| {
+        lines.push(line.to_string())
+    })
+    .await?;
+
+    lines.push(format!(
+        "Checking bucket {} at {} with access key {}:",
+        config.bucket,
+        config.endpoint,
+        client.credentials().access_key_id()
+    ));
+    if config.read_only {
+        lines.push("Read-only mode: s3:PutObject and s3:DeleteObject are not checked.".to_string());
+    }
+
+    let mut prefixes: Vec<&str> = rules.iter().map(|rule| rule.remote_path.as_str()).collect();
+    prefixes.sort_unstable();
+    prefixes.dedup();
+
+    let mut missing = 0;
+    for prefix in prefixes {
+        for check in check_prefix(&client, &config.bucket, prefix).await? {
+            if check.required && check.access != Access::Allowed {
+                missing += 1;
+            }
+            lines.push(check.line());
+        }
+    }
+
+    if missing > 0 {
+        lines.push(format!(
+            "{} required permission(s) missing; runs will fail with AccessDenied.",
+            missing
+        ));
+        return Err(PrefixloadError::Custom(lines.join("\n")));
+    }
+
+    lines.push("All permissions a run needs are granted.".to_string());
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::commands::test_support::client;
+    use crate::clients::s3::S3ClientOptions;
+    use wiremock::matchers::{method, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn list_response() -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_string(
+            "<ListBucketResult><Name>bucket</Name><KeyCount>0</KeyCount></ListBucketResult>",
+        )
+    }
+
+    #[test]
+    fn key_prefix_ends_with_a_slash() {
+        assert_eq!(key_prefix("backups"), "backups/");
+        assert_eq!(key_prefix("/db/daily/"), "db/daily/");
+        assert_eq!(key_prefix(""), "");
+    }
+
+    #[tokio::test]
+    async fn check_prefix_passes_with_all_permissions() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("prefix", "backups/"))
+            .respond_with(list_response())
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let checks = check_prefix(&client(&server).await, "bucket", "backups")
+            .await
+            .unwrap();
+        let permissions: Vec<_> = checks.iter().map(|check| check.permission).collect();
+        assert_eq!(
+            permissions,
+            [
+                "s3:ListBucket",
+                "s3:GetObject",
+                "s3:PutObject",
+                "s3:DeleteObject"
+            ]
+        );
+        assert!(checks.iter().all(|check| check.access == Access::Allowed));
+        assert_eq!(
+            checks[2].line(),
+            "ok       s3:PutObject    arn:aws:s3:::bucket/backups/*"
+        );
+    }
+
+    #[tokio::test]
+    async fn check_prefix_explains_denied_operations() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(list_response())
+            .mount(&server)
+            .await;
+        // Without s3:ListBucket, S3 denies HEAD requests of missing objects.
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(403).set_body_string(
+                "<Error><Code>AccessDenied</Code><Message>Access Denied</Message></Error>",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let checks = check_prefix(&client(&server).await, "bucket", "backups")
+            .await
+            .unwrap();
+        assert_eq!(checks.len(), 3);
+        assert_eq!(checks[0].access, Access::Allowed);
+        assert!(matches!(checks[1].access, Access::Denied(_)));
+        assert_eq!(checks[2].access, Access::Denied("AccessDenied".to_string()));
+
+        let line = checks[2].line();
+        assert!(line.starts_with("MISSING  s3:PutObject"), "{}", line);
+        assert!(line.contains("Grant s3:PutObject on arn:aws:s3:::bucket/backups/*"));
+    }
+
+    #[tokio::test]
+    async fn check_prefix_does_not_write_when_read_only() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(list_response())
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let options = S3ClientOptions::default()
+            .with_access_key("AKIDTEST")
+            .with_secret_key("SECRET")
+            .with_endpoint(server.uri())
+            .with_region("us-east-1")
+            .with_force_path_style(true)
+            .with_read_only(true);
+        let client = S3Client::new(options).await.unwrap();
+
+        let checks = check_prefix(&client, "bucket", "backups").await.unwrap();
+        let permissions: Vec<_> = checks.iter().map(|check| check.permission).collect();
+        assert_eq!(permissions, ["s3:ListBucket", "s3:GetObject"]);
+    }
+}
@@ -0,0 +1,134 @@
+// This module handles the `etag` command, which prints the S3 ETag of a local
+// file so it can be compared with the one the S3 console shows.
+
+use crate::cancel::CancelToken;
+use crate::cli::EtagArgs;
+use crate::cli::format::format_bytes;
+use crate::config::{self, Config};
+use crate::crypto::checksum::sha256_hex;
+use crate::crypto::etag::calculate_s3_etag_with_progress;
+use crate::error::{PrefixloadError, Result};
+use crate::uring::ReadMode;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Calculates the ETag (and with `sha256`, the SHA-256 digest) of `path`,
+/// showing the share hashed so far on stderr when it is a terminal.
+///
+/// # Returns
+///
+/// The lines to print.
+fn describe(
+    path: &Path,
+    part_size: u64,
+    sha256: bool,
+    cancel: &CancelToken,
+) -> Result<Vec<String>> {
+    let size = std::fs::metadata(path)?.len();
+    let show_progress = std::io::stderr().is_terminal();
+    let shown_percent = AtomicU64::new(0);
+    let progress = |hashed: u64| {
+        let percent = hashed * 100 / size.max(1);
+        if show_progress && shown_percent.fetch_max(percent, Ordering::Relaxed) < percent {
+            eprint!("\rHashing {}: {}%", path.display(), percent);
+            let _ = std::io::stderr().flush();
+        }
+    };
+
+    let etag =
+        calculate_s3_etag_with_progress(path, part_size, ReadMode::Standard, progress, cancel);
+    if show_progress && shown_percent.into_inner() > 0 {
+        eprintln!();
+    }
+    let etag = etag?;
+
+    let mut lines = vec![
+        format!("File:      {} ({})", path.display(), format_bytes(size)),
+        format!("Part size: {}", format_bytes(part_size)),
+        format!("ETag:      {}", etag),
+    ];
+    if size > part_size {
+        lines.push(format!(
+            "           (multipart: {} parts; the ETag only matches objects uploaded with the same part size)",
+            size.div_ceil(part_size)
+        ));
+    }
+    if sha256 {
+        lines.push(format!("SHA-256:   {}", sha256_hex(path)?));
+    }
+    Ok(lines)
+}
+
+/// The main entry point for the `etag` command.
+///
+/// Prints the ETag S3 gives the file when prefixload uploads it with
+/// `--part-size` (or the `part_size` of the config): the MD5 digest of the
+/// file, or for files larger than a part, the MD5 of the part digests
+/// followed by the number of parts. Ctrl-C stops hashing large files.
+///
+/// # Returns
+///
+/// A `Result` containing the ETag and size of the file, or a `PrefixloadError`.
+pub async fn run(args: &EtagArgs) -> Result<String> {
+    let part_size = match args.part_size {
+        Some(part_size) => part_size,
+        None if config::container_mode() => Config::from_env()?.part_size,
+        None => Config::load()?.part_size,
+    }
+    .as_u64();
+    if part_size == 0 {
+        return Err(PrefixloadError::Custom(
+            "--part-size must be greater than 0.".to_string(),
+        ));
+    }
+
+    let cancel = CancelToken::new();
+    let mut task = tokio::task::spawn_blocking({
+        let (path, sha256, cancel) = (args.file.clone(), args.sha256, cancel.clone());
+        move || describe(&path, part_size, sha256, &cancel)
+    });
+    let lines = tokio::select! {
+        result = &mut task => result,
+        _ = tokio::signal::ctrl_c() => {
+            cancel.cancel();
+            task.await
+        }
+    }
+    .map_err(|e| PrefixloadError::Custom(format!("ETag calculation task failed: {}", e)))??;
+
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const MIB: u64 = 1024 * 1024;
+
+    #[test]
+    fn describe_prints_the_multipart_etag_and_sha256() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("dump.sql");
+        fs::write(&path, vec![b'a'; (5 * MIB / 2) as usize]).unwrap();
+
+        let lines = describe(&path, MIB, true, &CancelToken::new()).unwrap();
+        let etag = crate::crypto::etag::calculate_s3_etag(&path, MIB, ReadMode::Standard).unwrap();
+        assert_eq!(lines[1], "Part size: 1.0 MiB");
+        assert_eq!(lines[2], format!("ETag:      {}", etag));
+        assert!(lines[3].contains("multipart: 3 parts"), "{}", lines[3]);
+        assert_eq!(
+            lines[4],
+            format!("SHA-256:   {}", sha256_hex(&path).unwrap())
+        );
+    }
+
+    #[test]
+    fn describe_fails_for_a_missing_file() {
+        let dir = tempdir().unwrap();
+        let err = describe(&dir.path().join("missing"), MIB, false, &CancelToken::new());
+        assert!(err.is_err());
+    }
+}
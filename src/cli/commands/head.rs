@@ -0,0 +1,182 @@
+// This module handles the `head` command, which prints what S3 reports about an
+// object, to debug files that do not sync as expected.
+
+use crate::cli::HeadArgs;
+use crate::cli::commands::run::bucket_client;
+use crate::cli::format::format_bytes;
+use crate::cli::output::OutputFormat;
+use crate::clients::s3::ObjectHead;
+use crate::config::{self, Config};
+use crate::error::{PrefixloadError, Result};
+use chrono::{DateTime, Local, Utc};
+
+/// Formats an optional timestamp in local time.
+fn local_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|time| {
+        time.with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S %:z")
+            .to_string()
+    })
+    .unwrap_or_else(|| "-".to_string())
+}
+
+/// Renders the properties of the object `key` as aligned lines.
+fn text(key: &str, head: &ObjectHead) -> Vec<String> {
+    let encryption = match (&head.server_side_encryption, &head.sse_kms_key_id) {
+        (Some(sse), Some(key_id)) => format!("{} (key {})", sse, key_id),
+        (Some(sse), None) => sse.clone(),
+        (None, _) => "none".to_string(),
+    };
+    let object_lock = match &head.object_lock_mode {
+        Some(mode) => format!(
+            "{} until {}",
+            mode,
+            local_time(head.object_lock_retain_until)
+        ),
+        None => "none".to_string(),
+    };
+
+    let mut lines = vec![
+        format!("Key:           {}", key),
+        format!(
+            "Size:          {} ({} bytes)",
+            format_bytes(head.size),
+            head.size
+        ),
+        format!("ETag:          {}", head.etag.as_deref().unwrap_or("-")),
+        format!("Last modified: {}", local_time(head.last_modified)),
+        format!(
+            "Storage class: {}",
+            head.storage_class.as_deref().unwrap_or("STANDARD")
+        ),
+        format!("Encryption:    {}", encryption),
+        format!(
+            "Content type:  {}",
+            head.content_type.as_deref().unwrap_or("-")
+        ),
+        format!(
+            "Version:       {}",
+            head.version_id.as_deref().unwrap_or("-")
+        ),
+        format!("Object Lock:   {}", object_lock),
+        format!(
+            "Legal hold:    {}",
+            if head.legal_hold { "on" } else { "off" }
+        ),
+    ];
+    if head.metadata.is_empty() {
+        lines.push("Metadata:      none".to_string());
+    } else {
+        lines.push("Metadata:".to_string());
+        lines.extend(
+            head.metadata
+                .iter()
+                .map(|(name, value)| format!("  {}: {}", name, value)),
+        );
+    }
+    lines
+}
+
+/// Renders the properties of the object `key` as a JSON object.
+fn json(key: &str, head: &ObjectHead) -> Result<String> {
+    let mut value = serde_json::to_value(head).map_err(|e| {
+        PrefixloadError::Custom(format!("Failed to serialize the object properties: {}", e))
+    })?;
+    value["key"] = key.into();
+    Ok(value.to_string())
+}
+
+/// The main entry point for the `head` command.
+///
+/// Sends a `HEAD` request for `key` in the configured bucket and prints the
+/// size, ETag, last modification, storage class, encryption, Object Lock
+/// state and user metadata of the object, or with `--output json`, the same
+/// as one JSON object.
+///
+/// # Returns
+///
+/// A `Result` containing the properties, or a `PrefixloadError` if the
+/// object does not exist.
+pub async fn run(args: &HeadArgs, output: OutputFormat) -> Result<String> {
+    let container = config::container_mode();
+    let config = if container {
+        Config::from_env()?
+    } else {
+        Config::load()?
+    };
+
+    let mut lines = Vec::new();
+    let client = bucket_client(&config, None, container, &mut |line| {
+        lines.push(line.to_string())
+    })
+    .await?;
+
+    let Some(head) = client.head_object(&config.bucket, &args.key).await? else {
+        return Err(PrefixloadError::Custom(format!(
+            "No object {} in bucket {}.",
+            args.key, config.bucket
+        )));
+    };
+
+    match output {
+        OutputFormat::Json | OutputFormat::Ndjson => json(&args.key, &head),
+        OutputFormat::Text => {
+            lines.extend(text(&args.key, &head));
+            Ok(lines.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::s3::{S3Client, S3ClientOptions};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn head_object_reads_the_reported_properties() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/bucket/db/a.sql"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"abc-2\"")
+                    .insert_header("Content-Length", "2048")
+                    .insert_header("Last-Modified", "Sat, 01 Jun 2024 03:00:00 GMT")
+                    .insert_header("x-amz-storage-class", "STANDARD_IA")
+                    .insert_header("x-amz-server-side-encryption", "aws:kms")
+                    .insert_header("x-amz-server-side-encryption-aws-kms-key-id", "key-1")
+                    .insert_header("x-amz-object-lock-legal-hold", "ON")
+                    .insert_header("x-amz-meta-source-host", "db-01"),
+            )
+            .mount(&server)
+            .await;
+        let options = S3ClientOptions::default()
+            .with_access_key("AKIDTEST")
+            .with_secret_key("SECRET")
+            .with_endpoint(server.uri())
+            .with_region("us-east-1")
+            .with_force_path_style(true);
+        let client = S3Client::new(options).await.unwrap();
+
+        let head = client
+            .head_object("bucket", "db/a.sql")
+            .await
+            .unwrap()
+            .unwrap();
+        let lines = text("db/a.sql", &head);
+        assert_eq!(lines[1], "Size:          2.0 KiB (2048 bytes)");
+        assert_eq!(lines[2], "ETag:          abc-2");
+        assert_eq!(lines[4], "Storage class: STANDARD_IA");
+        assert_eq!(lines[5], "Encryption:    aws:kms (key key-1)");
+        assert_eq!(lines[9], "Legal hold:    on");
+        assert_eq!(lines[11], "  source-host: db-01");
+
+        let value: serde_json::Value =
+            serde_json::from_str(&json("db/a.sql", &head).unwrap()).unwrap();
+        assert_eq!(value["key"], "db/a.sql");
+        assert_eq!(value["last_modified"], "2024-06-01T03:00:00Z");
+        assert_eq!(value["metadata"]["source-host"], "db-01");
+    }
+}
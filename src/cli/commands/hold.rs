@@ -0,0 +1,206 @@
+// This module handles the `hold` command, which places or removes Object Lock
+// legal holds on the objects under a key prefix.
+
+use crate::cli::commands::run::bucket_client;
+use crate::cli::confirm::confirm;
+use crate::cli::{HoldArgs, HoldCommand};
+use crate::clients::s3::S3Client;
+use crate::config::{self, Config};
+use crate::error::{PrefixloadError, Result};
+
+/// Places (`on`) or removes the legal hold of every object under `prefix`.
+/// With `dry_run`, only lists the objects.
+///
+/// # Returns
+///
+/// The lines to print, or an error naming the object the first failure
+/// happened on and how many objects were changed before it.
+async fn apply(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+    on: bool,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let keys = client.list_keys(bucket, prefix).await?;
+    if keys.is_empty() {
+        return Ok(vec![format!("No objects under '{}'.", prefix)]);
+    }
+
+    let action = if on { "Set" } else { "Cleared" };
+    if dry_run {
+        let mut lines = vec![format!(
+            "Would {} the legal hold of {} objects under '{}':",
+            if on { "set" } else { "clear" },
+            keys.len(),
+            prefix
+        )];
+        lines.extend(keys.iter().map(|key| format!("  {}", key)));
+        return Ok(lines);
+    }
+
+    for (done, key) in keys.iter().enumerate() {
+        client
+            .set_legal_hold(bucket, key, on)
+            .await
+            .map_err(|err| {
+                PrefixloadError::Custom(format!(
+                    "{} the legal hold of {} of {} objects under '{}', then failed on {}: {}",
+                    action,
+                    done,
+                    keys.len(),
+                    prefix,
+                    key,
+                    err
+                ))
+            })?;
+    }
+
+    Ok(vec![format!(
+        "{} the legal hold of {} objects under '{}'.",
+        action,
+        keys.len(),
+        prefix
+    )])
+}
+
+/// The main entry point for the `hold` command.
+///
+/// Legal holds keep object versions from being deleted or overwritten,
+/// independently of `object_lock_mode` retention and without an end date,
+/// until they are cleared. The bucket needs Object Lock enabled. In
+/// read-only mode, only `--dry-run` succeeds. Clearing holds asks for
+/// confirmation first unless `yes` is set.
+///
+/// # Returns
+///
+/// A `Result` containing a summary, or a `PrefixloadError`.
+pub async fn run(command: HoldCommand, read_only: bool, yes: bool) -> Result<String> {
+    let (args, on): (&HoldArgs, bool) = match &command {
+        HoldCommand::Set(args) => (args, true),
+        HoldCommand::Clear(args) => (args, false),
+    };
+
+    let container = config::container_mode();
+    let mut config = if container {
+        Config::from_env()?
+    } else {
+        Config::load()?
+    };
+    config.read_only |= read_only;
+
+    // In read-only mode the clear is refused anyway, so there is nothing to confirm.
+    if !on && !args.dry_run && !config.read_only {
+        let question = format!(
+            "Clear the legal hold of every object under '{}'?",
+            args.prefix
+        );
+        if !confirm(&question, yes)? {
+            return Ok("No legal holds were cleared.".to_string());
+        }
+    }
+
+    let mut lines = Vec::new();
+    let client = bucket_client(&config, None, container, &mut |line| {
+        lines.push(line.to_string())
+    })
+    .await?;
+
+    lines.extend(apply(&client, &config.bucket, &args.prefix, on, args.dry_run).await?);
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::commands::test_support::client;
+    use wiremock::matchers::{body_string_contains, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mount_listing(server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(query_param("prefix", "db/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>db/1.sql</Key></Contents>\
+                 <Contents><Key>db/2.sql</Key></Contents></ListBucketResult>",
+            ))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn apply_sets_and_clears_holds_under_the_prefix() {
+        let server = MockServer::start().await;
+        mount_listing(&server).await;
+        Mock::given(method("PUT"))
+            .and(query_param("legal-hold", ""))
+            .and(body_string_contains("<Status>ON</Status>"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/bucket/db/2.sql"))
+            .and(body_string_contains("<Status>OFF</Status>"))
+            .respond_with(ResponseTemplate::new(403).set_body_string(
+                "<Error><Code>AccessDenied</Code><Message>Access Denied</Message></Error>",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(body_string_contains("<Status>OFF</Status>"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+
+        let lines = apply(&client, "bucket", "db/", true, false).await.unwrap();
+        assert_eq!(lines, ["Set the legal hold of 2 objects under 'db/'."]);
+
+        let err = apply(&client, "bucket", "db/", false, false)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "Cleared the legal hold of 1 of 2 objects under 'db/', then failed on db/2.sql"
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_lists_objects_in_a_dry_run() {
+        let server = MockServer::start().await;
+        mount_listing(&server).await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(query_param("prefix", "logs/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<ListBucketResult><IsTruncated>false</IsTruncated></ListBucketResult>",
+            ))
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+
+        let lines = apply(&client, "bucket", "db/", true, true).await.unwrap();
+        assert_eq!(
+            lines,
+            [
+                "Would set the legal hold of 2 objects under 'db/':",
+                "  db/1.sql",
+                "  db/2.sql"
+            ]
+        );
+
+        let lines = apply(&client, "bucket", "logs/", true, false)
+            .await
+            .unwrap();
+        assert_eq!(lines, ["No objects under 'logs/'."]);
+    }
+}
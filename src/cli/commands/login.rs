@@ -1,8 +1,12 @@
 // This module handles the logic for the `login` command, which allows users to
 // authenticate with their AWS credentials and save them for future use.
 
+use crate::cli::LoginArgs;
 use crate::clients::s3::{S3Client, S3ClientOptions};
-use crate::config::Config;
+use crate::clients::sts::{
+    DEFAULT_SESSION_DURATION_SECS, SessionCredentials, SessionSource, StsClient,
+};
+use crate::config::{self, Config};
 use crate::error::{PrefixloadError, Result};
 use configparser::ini::Ini;
 use requestty::Question;
@@ -41,24 +45,134 @@ fn input_credentials() -> Result<(String, String)> {
     Ok((access_key, secret_key))
 }
 
+/// Returns the keys given with `--access-key` and `--secret-key` or, with
+/// `--from-env`, read from `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+///
+/// # Returns
+///
+/// `None` if neither was requested and the keys are prompted for.
+fn credentials_from_args(args: &LoginArgs) -> Result<Option<(String, String)>> {
+    if let (Some(access_key), Some(secret_key)) = (&args.access_key, &args.secret_key) {
+        return Ok(Some((access_key.clone(), secret_key.clone())));
+    }
+    if !args.from_env {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        env_var("AWS_ACCESS_KEY_ID")?,
+        env_var("AWS_SECRET_ACCESS_KEY")?,
+    )))
+}
+
+/// Returns the trimmed value of the environment variable `name` for `--from-env`.
+fn env_var(name: &str) -> Result<String> {
+    std::env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| PrefixloadError::Custom(format!("--from-env needs {} to be set.", name)))
+}
+
+/// Returns the temporary credentials of the environment for `--from-env`:
+/// `AWS_SESSION_TOKEN` with the keys and `AWS_CREDENTIAL_EXPIRATION`
+/// (RFC 3339, as set by `aws configure export-credentials --format env`).
+///
+/// # Returns
+///
+/// `None` if `AWS_SESSION_TOKEN` is not set, i.e. the keys are long-term.
+fn session_from_env() -> Result<Option<SessionCredentials>> {
+    let Ok(session_token) = env_var("AWS_SESSION_TOKEN") else {
+        return Ok(None);
+    };
+
+    let expiration = env_var("AWS_CREDENTIAL_EXPIRATION").map_err(|_| {
+        PrefixloadError::Custom(
+            "--from-env needs AWS_CREDENTIAL_EXPIRATION with AWS_SESSION_TOKEN, \
+             so runs can tell when the temporary credentials expire."
+                .to_string(),
+        )
+    })?;
+    let expires_at = chrono::DateTime::parse_from_rfc3339(&expiration)
+        .map_err(|e| {
+            PrefixloadError::Custom(format!(
+                "Invalid AWS_CREDENTIAL_EXPIRATION {}: {}",
+                expiration, e
+            ))
+        })?
+        .to_utc();
+
+    let session = SessionCredentials {
+        access_key: env_var("AWS_ACCESS_KEY_ID")?,
+        secret_key: env_var("AWS_SECRET_ACCESS_KEY")?,
+        session_token,
+        expires_at,
+        source: SessionSource::Env,
+    };
+    if session.is_expired(chrono::Utc::now()) {
+        return Err(PrefixloadError::Custom(format!(
+            "The temporary credentials in the environment expire at {}. Export fresh ones first.",
+            session.expires_at_local()
+        )));
+    }
+
+    Ok(Some(session))
+}
+
+/// Prompts for the MFA device ARN (unless given) and the current TOTP code.
+///
+/// # Returns
+///
+/// A `Result` containing a tuple with the `(serial_number, token_code)`.
+fn input_mfa(serial_number: Option<&str>) -> Result<(String, String)> {
+    let serial_number = match serial_number {
+        Some(serial_number) => serial_number.to_string(),
+        None => {
+            let question = Question::input("mfa_serial")
+                .message("Enter MFA device ARN (arn:aws:iam::<account>:mfa/<name>):")
+                .build();
+            requestty::prompt_one(question)?
+                .as_string()
+                .map(String::from)
+                .ok_or_else(|| {
+                    PrefixloadError::Custom("Failed to parse MFA device ARN.".to_string())
+                })?
+        }
+    };
+
+    let code_question = Question::input("mfa_code")
+        .message("Enter MFA code:")
+        .validate(|code, _| {
+            if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+                Ok(())
+            } else {
+                Err("The MFA code has 6 digits.".to_string())
+            }
+        })
+        .build();
+
+    let token_code = requestty::prompt_one(code_question)?
+        .as_string()
+        .map(String::from)
+        .ok_or_else(|| PrefixloadError::Custom("Failed to parse MFA code.".to_string()))?;
+
+    Ok((serial_number, token_code))
+}
+
 /// Validates the provided AWS credentials by attempting to access the configured S3 bucket.
 ///
 /// # Arguments
 ///
-/// * `access_key` - The AWS Access Key ID.
-/// * `secret_key` - The AWS Secret Access Key.
+/// * `credentials` - Options holding the keys (and session token) to check.
 ///
 /// # Returns
 ///
 /// An empty `Result` (`Ok(())`) if the credentials are valid and the bucket is accessible,
 /// otherwise a `PrefixloadError`.
-async fn credentials_valid(access_key: &str, secret_key: &str) -> Result<()> {
+async fn credentials_valid(credentials: S3ClientOptions) -> Result<()> {
     let config: Config = Config::load()?;
 
-    let s3_options = S3ClientOptions::default()
-        .with_access_key(access_key.to_string())
-        .with_secret_key(secret_key.to_string())
-        .with_endpoint(config.endpoint.clone());
+    let s3_options = credentials.with_endpoint(config.endpoint.clone());
 
     let s3_client = S3Client::new(s3_options).await?;
 
@@ -118,22 +232,94 @@ fn save_credentials_to_file(access_key: &str, secret_key: &str) -> Result<()> {
     Ok(())
 }
 
+/// Exchanges the saved long-term keys and an MFA code for temporary
+/// credentials via STS `GetSessionToken` and caches them for `run`.
+async fn login_mfa(args: &LoginArgs) -> Result<String> {
+    let long_term = S3ClientOptions::from_aws_config().await.map_err(|err| {
+        PrefixloadError::Custom(format!(
+            "{}. Run `prefixload login` first to save your long-term keys.",
+            err
+        ))
+    })?;
+
+    let (serial_number, token_code) = input_mfa(args.mfa_serial.as_deref())?;
+
+    let session = StsClient::new(&long_term.access_key, &long_term.secret_key, None)
+        .get_session_token(
+            &serial_number,
+            &token_code,
+            args.duration.unwrap_or(DEFAULT_SESSION_DURATION_SECS),
+        )
+        .await?;
+
+    save_session(&session).await
+}
+
+/// Validates the temporary credentials of `session` and caches them for `run`,
+/// which uses them until they expire.
+async fn save_session(session: &SessionCredentials) -> Result<String> {
+    credentials_valid(session.to_s3_options())
+        .await
+        .map_err(|err| {
+            PrefixloadError::Custom(format!("Session credentials not valid: {}", err))
+        })?;
+    session.save()?;
+
+    Ok(format!(
+        "Saved the {}. Runs use them until {}; then run `{}` again.",
+        session.source.describe(),
+        session.expires_at_local(),
+        session.source.login_command()
+    ))
+}
+
 /// The main entry point for the `login` command.
 ///
 /// It orchestrates the process of getting, validating, and saving credentials.
+/// With `--access-key`/`--secret-key` or `--from-env` it runs without prompts,
+/// e.g. in provisioning scripts; `--from-env` caches temporary credentials
+/// (`AWS_SESSION_TOKEN`) with their expiry instead of saving them as keys.
+/// With `--mfa`, it starts an MFA session instead (see [`login_mfa`]).
 ///
 /// # Returns
 ///
 /// A `Result` containing a success message string or a `PrefixloadError`.
-pub async fn run() -> Result<String> {
-    // Get credentials from user input.
-    let (access_key, secret_key) = input_credentials()?;
+pub async fn run(args: &LoginArgs) -> Result<String> {
+    // Prompts are disabled in container mode, credentials come from the environment.
+    if config::container_mode() {
+        return Err(PrefixloadError::Custom(format!(
+            "`prefixload login` is disabled in container mode ({} is set). \
+             Set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY instead.",
+            config::CONTAINER_ENV
+        )));
+    }
+
+    if args.mfa {
+        return login_mfa(args).await;
+    }
+    // Temporary credentials are cached with their expiry, like an MFA session.
+    if args.from_env
+        && let Some(session) = session_from_env()?
+    {
+        return save_session(&session).await;
+    }
+
+    // Take the credentials from the flags or the environment, or ask for them.
+    let (access_key, secret_key) = match credentials_from_args(args)? {
+        Some(keys) => keys,
+        None => input_credentials()?,
+    };
 
     // Validate the credentials.
-    match credentials_valid(&access_key, &secret_key).await {
+    let credentials = S3ClientOptions::default()
+        .with_access_key(access_key.clone())
+        .with_secret_key(secret_key.clone());
+    match credentials_valid(credentials).await {
         Ok(()) => {
-            // If valid, save them to the file.
+            // If valid, save them to the file. A cached MFA session of the
+            // previous keys would otherwise take precedence over them.
             save_credentials_to_file(&access_key, &secret_key)?;
+            SessionCredentials::clear()?;
             Ok("Credentials have been saved successfully!".to_string())
         }
         Err(err) => Err(PrefixloadError::Custom(format!(
@@ -150,6 +336,85 @@ mod tests {
     use std::fs;
     use tempfile::tempdir;
 
+    #[test]
+    #[serial]
+    fn test_credentials_from_args() {
+        unsafe {
+            std::env::remove_var("AWS_ACCESS_KEY_ID");
+            std::env::remove_var("AWS_SECRET_ACCESS_KEY");
+        }
+        assert_eq!(credentials_from_args(&LoginArgs::default()).unwrap(), None);
+
+        let args = LoginArgs {
+            access_key: Some("AKIDFLAG".to_string()),
+            secret_key: Some("flag-secret".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            credentials_from_args(&args).unwrap(),
+            Some(("AKIDFLAG".to_string(), "flag-secret".to_string()))
+        );
+
+        let args = LoginArgs {
+            from_env: true,
+            ..Default::default()
+        };
+        let err = credentials_from_args(&args).unwrap_err();
+        assert!(err.to_string().contains("AWS_ACCESS_KEY_ID"));
+
+        unsafe {
+            std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDENV");
+            std::env::set_var("AWS_SECRET_ACCESS_KEY", "env-secret\n");
+        }
+        assert_eq!(
+            credentials_from_args(&args).unwrap(),
+            Some(("AKIDENV".to_string(), "env-secret".to_string()))
+        );
+        unsafe {
+            std::env::remove_var("AWS_ACCESS_KEY_ID");
+            std::env::remove_var("AWS_SECRET_ACCESS_KEY");
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_session_from_env() {
+        let vars = [
+            "AWS_ACCESS_KEY_ID",
+            "AWS_SECRET_ACCESS_KEY",
+            "AWS_SESSION_TOKEN",
+            "AWS_CREDENTIAL_EXPIRATION",
+        ];
+        unsafe {
+            for var in vars {
+                std::env::remove_var(var);
+            }
+            std::env::set_var("AWS_ACCESS_KEY_ID", "ASIAENV");
+            std::env::set_var("AWS_SECRET_ACCESS_KEY", "env-secret");
+        }
+        assert_eq!(session_from_env().unwrap(), None);
+
+        unsafe { std::env::set_var("AWS_SESSION_TOKEN", "env-token") };
+        let err = session_from_env().unwrap_err();
+        assert!(err.to_string().contains("AWS_CREDENTIAL_EXPIRATION"));
+
+        unsafe { std::env::set_var("AWS_CREDENTIAL_EXPIRATION", "2020-01-01T00:00:00Z") };
+        let err = session_from_env().unwrap_err();
+        assert!(err.to_string().contains("Export fresh ones"));
+
+        unsafe { std::env::set_var("AWS_CREDENTIAL_EXPIRATION", "2099-01-01T00:00:00+01:00") };
+        let session = session_from_env().unwrap().unwrap();
+        assert_eq!(session.session_token, "env-token");
+        assert_eq!(session.source, SessionSource::Env);
+        assert_eq!(session.expires_at.to_rfc3339(), "2098-12-31T23:00:00+00:00");
+
+        unsafe {
+            for var in vars {
+                std::env::remove_var(var);
+            }
+        }
+    }
+
     #[test]
     #[serial]
     fn test_save_credentials_to_file() {
@@ -1,4 +1,18 @@
+pub mod audit;
 pub mod config;
+pub mod daemon;
+pub mod doctor;
+pub mod etag;
+pub mod head;
+pub mod hold;
 pub mod login;
+pub mod orphans;
+pub mod pause;
+pub mod restore;
 pub mod run;
 pub mod schedule;
+pub mod service;
+pub mod state;
+pub mod stats;
+#[cfg(test)]
+mod test_support;
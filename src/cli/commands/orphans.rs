@@ -0,0 +1,170 @@
+// This module handles the `orphans` command, which lists the objects under the
+// configured remote paths that no local file maps to anymore, and the multipart
+// uploads that were never completed.
+
+use crate::cli::OrphansArgs;
+use crate::cli::commands::run::{RUN_LOG_PREFIX, bucket_client, mapped_keys, remote_prefixes};
+use crate::clients::s3::{IncompleteUpload, S3Client};
+use crate::config::{self, Config};
+use crate::dedup::CHUNK_PREFIX;
+use crate::error::Result;
+use chrono::Local;
+use std::collections::{BTreeSet, HashSet};
+
+/// Returns `true` for the run logs and dedup chunks prefixload keeps next
+/// to the backups, which no local file maps to.
+fn is_internal(key: &str) -> bool {
+    [RUN_LOG_PREFIX, CHUNK_PREFIX].iter().any(|prefix| {
+        key.strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+/// Lists the objects under `prefixes` that are not in `mapped`, and the
+/// incomplete multipart uploads under them.
+///
+/// # Returns
+///
+/// The lines to print.
+async fn orphans(
+    client: &S3Client,
+    bucket: &str,
+    prefixes: &[String],
+    mapped: &HashSet<String>,
+) -> Result<Vec<String>> {
+    let mut orphaned = BTreeSet::new();
+    let mut uploads: Vec<IncompleteUpload> = Vec::new();
+    for prefix in prefixes {
+        orphaned.extend(
+            client
+                .list_keys(bucket, prefix)
+                .await?
+                .into_iter()
+                .filter(|key| !mapped.contains(key) && !is_internal(key)),
+        );
+        uploads.extend(client.list_multipart_uploads(bucket, prefix).await?);
+    }
+    uploads.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let mut lines: Vec<String> = orphaned
+        .iter()
+        .map(|key| format!("ORPHANED    {}", key))
+        .collect();
+    lines.extend(uploads.iter().map(|upload| {
+        format!(
+            "INCOMPLETE  {} (started {}, upload ID {})",
+            upload.key,
+            upload
+                .initiated
+                .map(|time| {
+                    time.with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string()
+                })
+                .unwrap_or_else(|| "-".to_string()),
+            upload.upload_id
+        )
+    }));
+    lines.push(format!(
+        "{} object(s) no local file maps to, {} incomplete multipart upload(s).",
+        orphaned.len(),
+        uploads.len()
+    ));
+    Ok(lines)
+}
+
+/// The main entry point for the `orphans` command.
+///
+/// Scans the local directories like a run does and lists the objects under
+/// the `remote_path` of every rule (of `--job`, if given) that no current
+/// local file maps to, such as backups whose local file was deleted, and the
+/// multipart uploads that were started but never completed or aborted, such
+/// as those of interrupted runs. Nothing is changed; the listing is meant
+/// to be reviewed before deleting anything.
+///
+/// # Returns
+///
+/// A `Result` containing the listing, or a `PrefixloadError`.
+pub async fn run(args: &OrphansArgs) -> Result<String> {
+    let container = config::container_mode();
+    let config = if container {
+        Config::from_env()?
+    } else {
+        Config::load()?
+    };
+    let job = args
+        .job
+        .as_deref()
+        .map(|name| config.job(name))
+        .transpose()?;
+    let rules = match job {
+        Some(job) => config.rules_for_job(job)?,
+        None => config.directory_struct.clone(),
+    };
+
+    let mut lines = Vec::new();
+    let client = bucket_client(&config, job, container, &mut |line| {
+        lines.push(line.to_string())
+    })
+    .await?;
+
+    let mapped = mapped_keys(&config, &rules, &|_| {})?;
+    lines.extend(orphans(&client, &config.bucket, &remote_prefixes(&rules), &mapped).await?);
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::s3::S3ClientOptions;
+    use wiremock::matchers::{method, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn orphans_lists_unmapped_objects_and_incomplete_uploads() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("list-type", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>db/a.sql</Key></Contents>\
+                 <Contents><Key>db/b.sql</Key></Contents>\
+                 <Contents><Key>_logs/run.json</Key></Contents></ListBucketResult>",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(query_param("uploads", ""))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<ListMultipartUploadsResult><Bucket>bucket</Bucket>\
+                 <IsTruncated>false</IsTruncated><Upload><Key>db/c.sql</Key>\
+                 <UploadId>upload-1</UploadId><Initiated>2024-06-01T03:00:00.000Z</Initiated>\
+                 </Upload></ListMultipartUploadsResult>",
+            ))
+            .mount(&server)
+            .await;
+        let options = S3ClientOptions::default()
+            .with_access_key("AKIDTEST")
+            .with_secret_key("SECRET")
+            .with_endpoint(server.uri())
+            .with_region("us-east-1")
+            .with_force_path_style(true);
+        let client = S3Client::new(options).await.unwrap();
+
+        let mapped = HashSet::from(["db/a.sql".to_string()]);
+        let lines = orphans(&client, "bucket", &[String::new()], &mapped)
+            .await
+            .unwrap();
+        assert_eq!(lines[0], "ORPHANED    db/b.sql");
+        assert!(
+            lines[1].starts_with("INCOMPLETE  db/c.sql (started "),
+            "{}",
+            lines[1]
+        );
+        assert!(lines[1].ends_with(", upload ID upload-1)"));
+        assert_eq!(
+            lines[2],
+            "1 object(s) no local file maps to, 1 incomplete multipart upload(s)."
+        );
+    }
+}
@@ -0,0 +1,20 @@
+use crate::control;
+use crate::error::Result;
+
+/// Pauses in-progress and future runs until `prefixload resume`.
+pub async fn pause() -> Result<String> {
+    Ok(if control::pause()? {
+        "Paused. Running uploads finish, no new ones start until `prefixload resume`.".to_string()
+    } else {
+        "Already paused.".to_string()
+    })
+}
+
+/// Resumes paused runs.
+pub async fn resume() -> Result<String> {
+    Ok(if control::resume()? {
+        "Resumed.".to_string()
+    } else {
+        "Not paused.".to_string()
+    })
+}
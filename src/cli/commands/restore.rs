@@ -0,0 +1,772 @@
+// This module handles the `restore` command, which downloads the objects of a
+// past run, in the versions that run left in the bucket, to a local directory.
+
+use crate::attributes::FileAttributes;
+use crate::cancel::CancelToken;
+use crate::cli::RestoreArgs;
+use crate::cli::commands::run::{RUN_LOG_PREFIX, bucket_client, retry_throttled};
+use crate::cli::format::{format_bytes, format_transfer};
+use crate::cli::output::Status;
+use crate::cli::progress::TransferStats;
+use crate::clients::concurrency::AdaptiveConcurrency;
+use crate::clients::s3::{ObjectVersion, S3Client};
+use crate::config::{self, Config};
+use crate::error::{PrefixloadError, Result};
+use crate::glob::glob_match;
+use crate::report::RunReport;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+/// Formats of `--at` without a time zone, which are read as local time.
+const LOCAL_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Format of the start time at the beginning of uploaded run report names.
+const REPORT_NAME_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// An object of a run to download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RestoreItem {
+    /// Key of the file, which is also its path under the target directory.
+    key: String,
+    /// Key the content is read from: the key of the file this one is a
+    /// duplicate of, if any.
+    source: String,
+    size: u64,
+}
+
+/// The `--include` and `--exclude` patterns of a restore. `*` matches any
+/// run of characters and `?` a single one. Patterns with a `/` are matched
+/// against the whole key, the others against the file name.
+struct KeyFilter<'a> {
+    include: &'a [String],
+    exclude: &'a [String],
+}
+
+impl KeyFilter<'_> {
+    /// Returns `true` if `key` matches an include pattern (or there are
+    /// none) and no exclude pattern.
+    fn selects(&self, key: &str) -> bool {
+        let matches = |pattern: &String| {
+            let name = if pattern.contains('/') {
+                key
+            } else {
+                key.rsplit('/').next().unwrap_or(key)
+            };
+            glob_match(pattern, name)
+        };
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// State shared by the concurrent downloads of a restore.
+struct RestoreContext {
+    client: S3Client,
+    bucket: String,
+    dir: PathBuf,
+    /// End of the restored run; objects are restored in the version that
+    /// was current then.
+    at: DateTime<Utc>,
+    limiter: AdaptiveConcurrency,
+    stats: TransferStats,
+}
+
+/// Parses `--at`: an RFC 3339 timestamp or a local date and time.
+fn parse_at(value: &str) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(at.to_utc());
+    }
+
+    LOCAL_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|at| Local.from_local_datetime(&at).earliest())
+        .map(|at| at.to_utc())
+        .ok_or_else(|| {
+            PrefixloadError::Custom(format!(
+                "Invalid point in time '{}'. Examples: \"2024-06-01T03:00\", \
+                 \"2024-06-01T03:00:00Z\".",
+                value
+            ))
+        })
+}
+
+/// Returns the start time in the key of an uploaded run report,
+/// `_logs/<start time>-<run ID>.json`.
+fn report_started_at(key: &str) -> Option<DateTime<Utc>> {
+    let name = key
+        .strip_prefix(RUN_LOG_PREFIX)?
+        .strip_prefix('/')?
+        .strip_suffix(".json")?;
+    let (started_at, _run_id) = name.split_once('-')?;
+    NaiveDateTime::parse_from_str(started_at, REPORT_NAME_TIME_FORMAT)
+        .ok()
+        .map(|started_at| started_at.and_utc())
+}
+
+/// Returns the keys of the run reports uploaded with `upload_run_log`, with
+/// the start times of their runs, newest first.
+pub async fn report_keys(client: &S3Client, bucket: &str) -> Result<Vec<(DateTime<Utc>, String)>> {
+    let mut keys: Vec<(DateTime<Utc>, String)> = client
+        .list_keys(bucket, &format!("{}/", RUN_LOG_PREFIX))
+        .await?
+        .into_iter()
+        .filter_map(|key| Some((report_started_at(&key)?, key)))
+        .collect();
+    keys.sort_unstable_by_key(|(started_at, _)| std::cmp::Reverse(*started_at));
+    Ok(keys)
+}
+
+/// Downloads and parses the uploaded run report at `key`.
+pub async fn load_report(client: &S3Client, bucket: &str, key: &str) -> Result<RunReport> {
+    let data = client.get_bytes(bucket, key).await?;
+    serde_json::from_slice(&data).map_err(|e| {
+        PrefixloadError::Custom(format!("Failed to parse the run report <{}>: {}", key, e))
+    })
+}
+
+/// Returns the report of the last run (of `job`, if given) that finished at
+/// or before `at`, looking through the reports uploaded with
+/// `upload_run_log`, newest first.
+async fn find_report(
+    client: &S3Client,
+    bucket: &str,
+    at: DateTime<Utc>,
+    job: Option<&str>,
+) -> Result<RunReport> {
+    let keys = report_keys(client, bucket).await?;
+    for (_, key) in keys.into_iter().filter(|(started_at, _)| *started_at <= at) {
+        let report = load_report(client, bucket, &key).await?;
+        let finished_at = DateTime::parse_from_rfc3339(&report.finished_at).map_err(|e| {
+            PrefixloadError::Custom(format!(
+                "Invalid finished_at in the run report <{}>: {}",
+                key, e
+            ))
+        })?;
+
+        if finished_at.to_utc() <= at && (job.is_none() || report.job.as_deref() == job) {
+            return Ok(report);
+        }
+    }
+
+    Err(PrefixloadError::Custom(format!(
+        "No run{} finished before {} has a report under <{}/>. Runs upload their \
+         reports with `upload_run_log: true`.",
+        job.map(|job| format!(" of job '{}'", job))
+            .unwrap_or_default(),
+        at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        RUN_LOG_PREFIX
+    )))
+}
+
+/// Returns the objects `report` left in the bucket that `filter` selects:
+/// its uploaded files and the unchanged ones it skipped.
+fn restore_items(report: &RunReport, filter: &KeyFilter<'_>) -> Vec<RestoreItem> {
+    report
+        .files
+        .iter()
+        .filter(|file| matches!(file.status, Status::Uploaded | Status::Skipped))
+        .filter(|file| filter.selects(&file.remote_key))
+        .map(|file| RestoreItem {
+            key: file.remote_key.clone(),
+            source: file
+                .duplicate_of
+                .clone()
+                .unwrap_or_else(|| file.remote_key.clone()),
+            size: file.size,
+        })
+        .collect()
+}
+
+/// Returns the version of an object that was current at `at`, given its
+/// versions newest first, or `None` if the object did not exist then.
+fn version_at(versions: &[ObjectVersion], at: DateTime<Utc>) -> Option<&ObjectVersion> {
+    versions
+        .iter()
+        .find(|version| version.last_modified <= at)
+        .filter(|version| !version.delete_marker)
+}
+
+/// Returns the path of `key` under `dir`, refusing keys that would leave it.
+fn target_path(dir: &Path, key: &str) -> Result<PathBuf> {
+    let relative = Path::new(key);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(PrefixloadError::Custom(format!(
+            "Refusing to restore <{}> outside of {}.",
+            key,
+            dir.display()
+        )));
+    }
+    Ok(dir.join(relative))
+}
+
+/// Downloads one object of the run in the version that was current at the
+/// end of the run and applies the file attributes stored with it.
+///
+/// # Returns
+///
+/// A warning if the attributes could not be applied.
+async fn restore_item(ctx: &RestoreContext, item: &RestoreItem) -> Result<Option<String>> {
+    let path = target_path(&ctx.dir, &item.key)?;
+    let versions = ctx.client.list_versions(&ctx.bucket, &item.source).await?;
+    let version = version_at(&versions, ctx.at).ok_or_else(|| {
+        PrefixloadError::Custom(format!(
+            "<{}> has no version from before the run ended; without bucket \
+             versioning, objects overwritten since cannot be restored.",
+            item.source
+        ))
+    })?;
+    // The current version is downloaded without its ID, which needs
+    // s3:GetObject only rather than s3:GetObjectVersion as well.
+    let version_id = (Some(version) != versions.first()).then_some(version.version_id.as_str());
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let metadata = ctx
+        .client
+        .download_file(&ctx.bucket, &item.source, version_id, &path)
+        .await?;
+
+    let warning = FileAttributes::from_object_metadata(&metadata)
+        .apply(&path)
+        .err()
+        .map(|err| {
+            format!(
+                "  - Warning: failed to restore the attributes of {}: {}",
+                path.display(),
+                err
+            )
+        });
+    Ok(warning)
+}
+
+/// Restores an object while holding a concurrency slot. Throttled requests
+/// lower the concurrency limit and are retried, as they are for uploads.
+async fn download(
+    ctx: Arc<RestoreContext>,
+    item: RestoreItem,
+) -> (RestoreItem, Result<Option<String>>) {
+    let permit = ctx.limiter.acquire().await;
+    let result = retry_throttled(
+        &ctx.limiter,
+        &CancelToken::default(),
+        permit,
+        |_, _| {},
+        || restore_item(&ctx, &item),
+    )
+    .await;
+    (item, result)
+}
+
+/// Downloads the objects of `report` that `filter` selects to `dir`, as many
+/// at a time as `limiter` allows. Every restored object is logged with a
+/// progress line, as uploads are.
+///
+/// # Returns
+///
+/// A summary, or an error holding it if an object could not be restored.
+async fn restore(
+    client: &S3Client,
+    bucket: &str,
+    report: &RunReport,
+    filter: &KeyFilter<'_>,
+    dir: &Path,
+    limiter: AdaptiveConcurrency,
+    log: &mut dyn FnMut(&str),
+) -> Result<String> {
+    let at = DateTime::parse_from_rfc3339(&report.finished_at)
+        .map_err(|e| PrefixloadError::Custom(format!("Invalid finished_at: {}", e)))?
+        .to_utc();
+    let items = restore_items(report, filter);
+    let count = items.len();
+
+    log(&format!(
+        "Restoring {} objects of run {} (finished {}) to {}:",
+        count,
+        report.run_id,
+        at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        dir.display()
+    ));
+    let ctx = Arc::new(RestoreContext {
+        client: client.clone(),
+        bucket: bucket.to_string(),
+        dir: dir.to_path_buf(),
+        at,
+        limiter,
+        stats: TransferStats::new(items.iter().map(|item| item.size).sum()),
+    });
+
+    let mut tasks = JoinSet::new();
+    for item in items {
+        tasks.spawn(download(Arc::clone(&ctx), item));
+    }
+
+    let mut failed = 0;
+    while let Some(joined) = tasks.join_next().await {
+        let (item, result) = joined
+            .map_err(|e| PrefixloadError::Custom(format!("File download task failed: {}", e)))?;
+        match &result {
+            Ok(warning) => {
+                log(&format!(
+                    "Restored {} ({})",
+                    item.key,
+                    format_bytes(item.size)
+                ));
+                if let Some(warning) = warning {
+                    log(warning);
+                }
+            }
+            Err(err) => {
+                failed += 1;
+                log(&format!("Failed to restore {}: {}", item.key, err));
+            }
+        }
+        log(&format!(
+            "  - {}",
+            ctx.stats.record(item.size, result.is_ok())
+        ));
+    }
+
+    let summary = format!(
+        "Restored {} of {} objects: {}.",
+        count - failed,
+        count,
+        format_transfer(ctx.stats.transferred_bytes(), ctx.stats.elapsed())
+    );
+    if failed > 0 {
+        return Err(PrefixloadError::Custom(format!(
+            "{} {} object(s) could not be restored.",
+            summary, failed
+        )));
+    }
+    Ok(summary)
+}
+
+/// The main entry point for the `restore` command.
+///
+/// Finds the last run (of `--job`, if given) that finished before `--at`
+/// among the reports runs upload with `upload_run_log`, and downloads the
+/// files it uploaded or found unchanged (those `--include` and `--exclude`
+/// select) to `--to`, under their keys, up to `max_concurrency` at a time.
+/// Progress is printed as the files are restored. Every
+/// object is restored in the version that was current when the run ended,
+/// so later runs do not change the result as long as the bucket is
+/// versioned.
+///
+/// # Returns
+///
+/// A `Result` containing the summary line, or a `PrefixloadError` holding
+/// it if an object could not be restored.
+pub async fn run(args: &RestoreArgs) -> Result<String> {
+    let at = parse_at(&args.at)?;
+    let container = config::container_mode();
+    let config = if container {
+        Config::from_env()?
+    } else {
+        Config::load()?
+    };
+    let job = args
+        .job
+        .as_deref()
+        .map(|name| config.job(name))
+        .transpose()?;
+
+    let mut log = |line: &str| println!("{}", line);
+    let client = bucket_client(&config, job, container, &mut log).await?;
+
+    let report = find_report(&client, &config.bucket, at, args.job.as_deref()).await?;
+    let filter = KeyFilter {
+        include: &args.include,
+        exclude: &args.exclude,
+    };
+    let limiter = AdaptiveConcurrency::new(config.min_concurrency, config.max_concurrency);
+    restore(
+        &client,
+        &config.bucket,
+        &report,
+        &filter,
+        &args.to,
+        limiter,
+        &mut log,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::commands::test_support::client;
+    use std::fs;
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().to_utc()
+    }
+
+    fn report_json(run_id: &str, job: Option<&str>, finished_at: &str) -> String {
+        serde_json::json!({
+            "run_id": run_id,
+            "hostname": "db-01",
+            "job": job,
+            "started_at": finished_at,
+            "finished_at": finished_at,
+            "duration_secs": 1.0,
+            "success": false,
+            "matched": 3,
+            "uploaded": 1,
+            "skipped": 1,
+            "failed": 1,
+            "too_large": 0,
+            "max_file_size": 0,
+            "uploaded_bytes": 4,
+            "files": [
+                {"local_path": "/data/db_1.sql", "remote_key": "db/db_1.sql", "size": 4, "status": "uploaded"},
+                {"local_path": "/data/db_2.sql", "remote_key": "db/db_2.sql", "size": 4, "status": "skipped", "duplicate_of": "db/db_1.sql"},
+                {"local_path": "/data/db_3.sql", "remote_key": "db/db_3.sql", "size": 4, "status": "failed", "error": "boom"}
+            ]
+        })
+        .to_string()
+    }
+
+    fn versions_xml(versions: &[(&str, &str, &str)]) -> String {
+        let versions: String = versions
+            .iter()
+            .map(|(key, id, modified)| {
+                format!(
+                    "<Version><Key>{}</Key><VersionId>{}</VersionId>\
+                     <LastModified>{}</LastModified></Version>",
+                    key, id, modified
+                )
+            })
+            .collect();
+        format!(
+            "<ListVersionsResult><IsTruncated>false</IsTruncated>{}</ListVersionsResult>",
+            versions
+        )
+    }
+
+    #[test]
+    fn parse_at_accepts_local_and_rfc3339_times() {
+        assert_eq!(
+            parse_at("2024-06-01T03:00:00Z").unwrap(),
+            utc("2024-06-01T03:00:00Z")
+        );
+        let local = Local
+            .with_ymd_and_hms(2024, 6, 1, 3, 0, 0)
+            .earliest()
+            .unwrap()
+            .to_utc();
+        assert_eq!(parse_at("2024-06-01T03:00").unwrap(), local);
+        assert_eq!(parse_at(" 2024-06-01 03:00:00").unwrap(), local);
+        assert!(parse_at("yesterday").is_err());
+    }
+
+    #[test]
+    fn report_started_at_reads_the_key() {
+        assert_eq!(
+            report_started_at("_logs/20240601T030000Z-3f1c2a4e.json"),
+            Some(utc("2024-06-01T03:00:00Z"))
+        );
+        assert_eq!(
+            report_started_at("_logs/20240601T030000Z-3f1c2a4e.log"),
+            None
+        );
+        assert_eq!(report_started_at("_logs/latest.json"), None);
+    }
+
+    #[test]
+    fn version_at_picks_the_version_current_at_the_time() {
+        let version = |id: &str, modified: &str, delete_marker| ObjectVersion {
+            version_id: id.to_string(),
+            last_modified: utc(modified),
+            delete_marker,
+        };
+        let versions = [
+            version("v3", "2024-06-03T00:00:00Z", true),
+            version("v2", "2024-06-02T00:00:00Z", false),
+            version("v1", "2024-06-01T00:00:00Z", false),
+        ];
+
+        let at = |time| version_at(&versions, utc(time)).map(|v| v.version_id.as_str());
+        assert_eq!(at("2024-06-02T12:00:00Z"), Some("v2"));
+        assert_eq!(at("2024-06-01T00:00:00Z"), Some("v1"));
+        assert_eq!(at("2024-06-03T12:00:00Z"), None);
+        assert_eq!(at("2024-05-31T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn target_path_stays_in_the_directory() {
+        let dir = Path::new("/restore");
+        assert_eq!(
+            target_path(dir, "db/db_1.sql").unwrap(),
+            PathBuf::from("/restore/db/db_1.sql")
+        );
+        assert!(target_path(dir, "db/../../etc/passwd").is_err());
+        assert!(target_path(dir, "/etc/passwd").is_err());
+    }
+
+    #[tokio::test]
+    async fn find_report_picks_the_last_run_before_the_time() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("prefix", "_logs/"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>_logs/20240531T030000Z-a.json</Key></Contents>\
+                 <Contents><Key>_logs/20240531T030000Z-a.log</Key></Contents>\
+                 <Contents><Key>_logs/20240601T030000Z-b.json</Key></Contents>\
+                 <Contents><Key>_logs/20240602T030000Z-c.json</Key></Contents>\
+                 </ListBucketResult>",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/bucket/_logs/20240531T030000Z-a.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(report_json(
+                "a",
+                Some("nightly-db"),
+                "2024-05-31T03:10:00+00:00",
+            )))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/bucket/_logs/20240601T030000Z-b.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(report_json(
+                "b",
+                None,
+                "2024-06-01T03:10:00+00:00",
+            )))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/bucket/_logs/20240602T030000Z-c.json"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+
+        let at = utc("2024-06-01T12:00:00Z");
+        let report = find_report(&client, "bucket", at, None).await.unwrap();
+        assert_eq!(report.run_id, "b");
+        let report = find_report(&client, "bucket", at, Some("nightly-db"))
+            .await
+            .unwrap();
+        assert_eq!(report.run_id, "a");
+
+        // Run b was still running at 03:05.
+        let at = utc("2024-06-01T03:05:00Z");
+        let report = find_report(&client, "bucket", at, None).await.unwrap();
+        assert_eq!(report.run_id, "a");
+
+        let err = find_report(&client, "bucket", at, Some("weekly"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("No run of job 'weekly' finished"));
+    }
+
+    #[test]
+    fn key_filter_matches_names_or_keys() {
+        let include = ["db_*".to_string(), "logs/2024-*/*".to_string()];
+        let exclude = ["*.tmp".to_string()];
+        let filter = KeyFilter {
+            include: &include,
+            exclude: &exclude,
+        };
+
+        assert!(filter.selects("database/db_1.sql"));
+        assert!(filter.selects("logs/2024-06/app.log"));
+        assert!(!filter.selects("logs/2023-12/app.log"));
+        assert!(!filter.selects("database/db_1.tmp"));
+        assert!(!filter.selects("database/app_1.sql"));
+
+        let everything = KeyFilter {
+            include: &[],
+            exclude: &exclude,
+        };
+        assert!(everything.selects("database/app_1.sql"));
+    }
+
+    #[tokio::test]
+    async fn restore_downloads_the_versions_of_the_run() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("versions", ""))
+            .and(query_param("prefix", "db/db_1.sql"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(versions_xml(&[
+                ("db/db_1.sql", "v2", "2024-06-02T03:01:00.000Z"),
+                ("db/db_1.sql", "v1", "2024-06-01T03:01:00.000Z"),
+                ("db/db_1.sql.xattrs.json", "x1", "2024-06-01T03:01:00.000Z"),
+            ])))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/bucket/db/db_1.sql"))
+            .and(query_param("versionId", "v1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("old!"))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+        let report: RunReport =
+            serde_json::from_str(&report_json("b", None, "2024-06-01T03:10:00+00:00")).unwrap();
+        let dir = TempDir::new().unwrap();
+        let filter = KeyFilter {
+            include: &[],
+            exclude: &[],
+        };
+
+        let mut lines = Vec::new();
+        let summary = restore(
+            &client,
+            "bucket",
+            &report,
+            &filter,
+            dir.path(),
+            AdaptiveConcurrency::new(1, 4),
+            &mut |line| lines.push(line.to_string()),
+        )
+        .await
+        .unwrap();
+        assert!(
+            summary.starts_with("Restored 2 of 2 objects: 8 B in "),
+            "{}",
+            summary
+        );
+        assert!(lines.contains(&"Restored db/db_1.sql (4 B)".to_string()));
+        assert!(lines.contains(&"Restored db/db_2.sql (4 B)".to_string()));
+        assert!(
+            lines
+                .last()
+                .unwrap()
+                .starts_with("  - Progress: 100.0% (8 B of 8 B)")
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("db/db_1.sql")).unwrap(),
+            "old!"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("db/db_2.sql")).unwrap(),
+            "old!"
+        );
+        assert!(!dir.path().join("db/db_3.sql").exists());
+    }
+
+    #[tokio::test]
+    async fn restore_downloads_only_the_selected_files() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("versions", ""))
+            .respond_with(ResponseTemplate::new(200).set_body_string(versions_xml(&[(
+                "db/db_1.sql",
+                "null",
+                "2024-06-01T03:01:00.000Z",
+            )])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/bucket/db/db_1.sql"))
+            .and(query_param_is_missing("versionId"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("new!"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+        let report: RunReport =
+            serde_json::from_str(&report_json("b", None, "2024-06-01T03:10:00+00:00")).unwrap();
+        let dir = TempDir::new().unwrap();
+        let include = ["db_2*".to_string()];
+        let filter = KeyFilter {
+            include: &include,
+            exclude: &[],
+        };
+
+        let mut lines = Vec::new();
+        let summary = restore(
+            &client,
+            "bucket",
+            &report,
+            &filter,
+            dir.path(),
+            AdaptiveConcurrency::new(1, 4),
+            &mut |line| lines.push(line.to_string()),
+        )
+        .await
+        .unwrap();
+        assert!(
+            summary.starts_with("Restored 1 of 1 objects"),
+            "{}",
+            summary
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("db/db_2.sql")).unwrap(),
+            "new!"
+        );
+        assert!(!dir.path().join("db/db_1.sql").exists());
+    }
+
+    #[tokio::test]
+    async fn restore_fails_for_objects_overwritten_without_versioning() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("versions", ""))
+            .respond_with(ResponseTemplate::new(200).set_body_string(versions_xml(&[(
+                "db/db_1.sql",
+                "null",
+                "2024-06-02T03:01:00.000Z",
+            )])))
+            .mount(&server)
+            .await;
+        let client = client(&server).await;
+        let report: RunReport =
+            serde_json::from_str(&report_json("b", None, "2024-06-01T03:10:00+00:00")).unwrap();
+        let dir = TempDir::new().unwrap();
+        let filter = KeyFilter {
+            include: &[],
+            exclude: &[],
+        };
+
+        let mut lines = Vec::new();
+        let err = restore(
+            &client,
+            "bucket",
+            &report,
+            &filter,
+            dir.path(),
+            AdaptiveConcurrency::new(1, 4),
+            &mut |line| lines.push(line.to_string()),
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("Restored 0 of 2 objects: 0 B in "), "{}", err);
+        assert!(
+            err.ends_with("2 object(s) could not be restored."),
+            "{}",
+            err
+        );
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.contains("<db/db_1.sql> has no version from before the run ended")),
+            "{:?}",
+            lines
+        );
+    }
+}
@@ -1,5 +1,704 @@
-use crate::error::Result;
+use crate::cli::ScheduleArgs;
+use crate::config::{Config, ENV_PREFIX, Job};
+use crate::error::{PrefixloadError, Result};
+use chrono::{DateTime, Local, TimeZone};
+use croner::Cron;
+use croner::parser::{CronParser, Seconds};
 
-pub async fn run(cron: &str) -> Result<String> {
-    Ok(format!("I am a Cron expression: {}", cron))
+/// Number of upcoming execution times shown by `schedule`.
+const PREVIEW_RUNS: usize = 5;
+
+/// Secret the generated CronJob reads the AWS credentials from by default.
+const K8S_SECRET: &str = "prefixload-aws";
+
+/// Day names accepted by `weekly on <day>` and `every <day>`, in cron order (0 = Sunday).
+const WEEKDAYS: [&str; 7] = [
+    "sunday",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+];
+
+fn invalid_interval(expression: &str, reason: &str) -> PrefixloadError {
+    PrefixloadError::Custom(format!(
+        "Invalid schedule '{}': {}. Examples: \"every 6h\", \"every 15m\", \
+         \"hourly\", \"daily at 03:00\", \"weekly on mon at 22:30\".",
+        expression, reason
+    ))
+}
+
+/// Parses the optional `at HH:MM` suffix into `(minute, hour)`; midnight by default.
+fn parse_at(expression: &str, rest: &[&str]) -> Result<(u32, u32)> {
+    match rest {
+        [] => Ok((0, 0)),
+        ["at", time] => {
+            let (hour, minute) = time
+                .split_once(':')
+                .and_then(|(h, m)| Some((h.parse::<u32>().ok()?, m.parse::<u32>().ok()?)))
+                .filter(|(h, m)| *h < 24 && *m < 60)
+                .ok_or_else(|| {
+                    invalid_interval(expression, &format!("'{}' is not a valid HH:MM time", time))
+                })?;
+            Ok((minute, hour))
+        }
+        _ => Err(invalid_interval(expression, "expected 'at HH:MM'")),
+    }
+}
+
+/// Parses a weekday name or its three-letter abbreviation into its cron number.
+fn parse_weekday(expression: &str, day: &str) -> Result<usize> {
+    WEEKDAYS
+        .iter()
+        .position(|name| day.len() >= 3 && name.starts_with(day))
+        .ok_or_else(|| invalid_interval(expression, &format!("'{}' is not a day of the week", day)))
+}
+
+/// Translates `every <n><m|h>` into a cron expression. The interval must
+/// divide the hour (or the day) evenly, otherwise cron cannot express it.
+fn parse_every(expression: &str, amount: &str, unit: &str) -> Result<String> {
+    let n: u32 = amount.parse().ok().filter(|n| *n > 0).ok_or_else(|| {
+        invalid_interval(
+            expression,
+            &format!("'{}' is not a positive number", amount),
+        )
+    })?;
+
+    match unit {
+        "m" | "min" | "mins" | "minute" | "minutes" => {
+            if 60 % n != 0 {
+                return Err(invalid_interval(
+                    expression,
+                    "the number of minutes must divide 60 evenly",
+                ));
+            }
+            Ok(if n == 1 {
+                "* * * * *".to_string()
+            } else {
+                format!("*/{} * * * *", n)
+            })
+        }
+        "h" | "hour" | "hours" => {
+            if 24 % n != 0 {
+                return Err(invalid_interval(
+                    expression,
+                    "the number of hours must divide 24 evenly",
+                ));
+            }
+            Ok(if n == 1 {
+                "0 * * * *".to_string()
+            } else {
+                format!("0 */{} * * *", n)
+            })
+        }
+        _ => Err(invalid_interval(
+            expression,
+            &format!("unknown unit '{}', use m or h", unit),
+        )),
+    }
+}
+
+/// Translates a human-friendly interval into a cron expression.
+///
+/// Returns `Ok(None)` when the expression does not start with one of the
+/// keywords (`every`, `hourly`, `daily`, `weekly`) and should be treated as cron.
+pub fn translate_interval(expression: &str) -> Result<Option<String>> {
+    let lowered = expression.trim().to_ascii_lowercase();
+    let tokens: Vec<&str> = lowered.split_whitespace().collect();
+
+    let cron = match tokens.as_slice() {
+        ["hourly"] | ["every", "hour"] => "0 * * * *".to_string(),
+        ["daily", rest @ ..] | ["every", "day", rest @ ..] => {
+            let (minute, hour) = parse_at(expression, rest)?;
+            format!("{} {} * * *", minute, hour)
+        }
+        ["weekly", "on", day, rest @ ..] => {
+            let weekday = parse_weekday(expression, day)?;
+            let (minute, hour) = parse_at(expression, rest)?;
+            format!("{} {} * * {}", minute, hour, weekday)
+        }
+        ["weekly", rest @ ..] => {
+            let (minute, hour) = parse_at(expression, rest)?;
+            format!("{} {} * * 0", minute, hour)
+        }
+        ["every", amount, unit] => parse_every(expression, amount, unit)?,
+        ["every", interval] => {
+            let split = interval
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(interval.len());
+            if split == 0 {
+                let weekday = parse_weekday(expression, interval)?;
+                format!("0 0 * * {}", weekday)
+            } else {
+                let (amount, unit) = interval.split_at(split);
+                parse_every(expression, amount, unit)?
+            }
+        }
+        ["every", day, rest @ ..] => {
+            let weekday = parse_weekday(expression, day)?;
+            let (minute, hour) = parse_at(expression, rest)?;
+            format!("{} {} * * {}", minute, hour, weekday)
+        }
+        ["every" | "hourly", ..] => {
+            return Err(invalid_interval(expression, "unrecognized interval"));
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(cron))
+}
+
+/// Parses a standard five-field crontab expression (`minute hour day month weekday`).
+///
+/// Aliases such as `@daily` are accepted as well; seconds are not, since
+/// crontab has minute granularity.
+pub fn parse_cron(expression: &str) -> Result<Cron> {
+    CronParser::builder()
+        .seconds(Seconds::Disallowed)
+        .build()
+        .parse(expression.trim())
+        .map_err(|e| {
+            PrefixloadError::Custom(format!(
+                "Invalid cron expression '{}': {}. \
+                 Expected five fields: minute hour day-of-month month day-of-week.",
+                expression,
+                e.to_string().trim_end_matches('.')
+            ))
+        })
+}
+
+/// Parses a schedule: a human-friendly interval or a raw cron expression.
+pub fn parse_schedule(schedule: &str) -> Result<Cron> {
+    match translate_interval(schedule)? {
+        Some(cron) => parse_cron(&cron),
+        None => parse_cron(schedule),
+    }
+}
+
+/// Returns the next `count` execution times strictly after `after`.
+pub fn next_runs<Tz: TimeZone + Copy>(
+    cron: &Cron,
+    after: DateTime<Tz>,
+    count: usize,
+) -> Vec<DateTime<Tz>> {
+    cron.iter_after(after).take(count).collect()
+}
+
+/// Format of the execution times printed by `schedule`.
+pub(crate) const TIME_FORMAT: &str = "%Y-%m-%d %H:%M %Z (%a)";
+
+/// Validates the schedule (a human-friendly interval or a raw cron
+/// expression) and previews its next execution times.
+fn preview(schedule: &str) -> Result<String> {
+    let translated = translate_interval(schedule)?;
+    let cron = translated.as_deref().unwrap_or(schedule);
+    let parsed = parse_cron(cron)?;
+    let upcoming = next_runs(&parsed, Local::now(), PREVIEW_RUNS);
+
+    if upcoming.is_empty() {
+        return Err(PrefixloadError::Custom(format!(
+            "Cron expression '{}' never matches any date.",
+            cron
+        )));
+    }
+
+    let mut output = match &translated {
+        Some(cron) => format!(
+            "Schedule '{}' is valid (cron: {}). Next runs:",
+            schedule.trim(),
+            cron
+        ),
+        None => format!("Cron expression '{}' is valid. Next runs:", cron.trim()),
+    };
+    for time in upcoming {
+        output.push_str(&format!("\n  {}", time.format(TIME_FORMAT)));
+    }
+
+    Ok(output)
+}
+
+/// Lists every configured job with its schedule and next execution time.
+fn list_jobs(config: &Config) -> Result<String> {
+    if config.jobs.is_empty() {
+        return Err(PrefixloadError::Custom(
+            "No jobs are defined in the config. Pass a cron expression or an interval to preview it."
+                .to_string(),
+        ));
+    }
+
+    let name_width = config
+        .jobs
+        .iter()
+        .map(|job| job.name.len())
+        .max()
+        .unwrap_or(0);
+    let mut lines = Vec::with_capacity(config.jobs.len());
+
+    for job in &config.jobs {
+        let line = match &job.schedule {
+            Some(schedule) => {
+                let parsed = parse_schedule(schedule)?;
+                let next = next_runs(&parsed, Local::now(), 1)
+                    .first()
+                    .map(|time| time.format(TIME_FORMAT).to_string())
+                    .unwrap_or_else(|| "never".to_string());
+                format!(
+                    "{:<width$}  {}  next run: {}",
+                    job.name,
+                    schedule,
+                    next,
+                    width = name_width
+                )
+            }
+            None => format!("{:<width$}  not scheduled", job.name, width = name_width),
+        };
+        lines.push(line);
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Turns a job name into a valid Kubernetes object name (lowercase
+/// alphanumerics and dashes), prefixed with `prefixload`.
+fn k8s_name(job: Option<&str>) -> String {
+    let Some(job) = job else {
+        return "prefixload".to_string();
+    };
+
+    let name: String = job
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let name = format!("prefixload-{}", name.trim_matches('-'));
+
+    name.chars()
+        .take(52)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_string()
+}
+
+/// Renders a config value as a container-mode environment variable value:
+/// scalars as-is, lists and maps as flow-style YAML (JSON).
+fn env_value(value: &serde_yaml::Value) -> Result<Option<String>> {
+    Ok(match value {
+        serde_yaml::Value::Null => None,
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Sequence(_) | serde_yaml::Value::Mapping(_) => {
+            Some(serde_json::to_string(value).map_err(|err| {
+                PrefixloadError::Custom(format!("Failed to render config value: {}", err))
+            })?)
+        }
+        scalar => Some(serde_yaml::to_string(scalar)?.trim().to_string()),
+    })
+}
+
+/// Builds a Kubernetes CronJob manifest that runs `prefixload run --container`
+/// on `cron`, with the config (or the job's rules) passed as `PREFIXLOAD_*`
+/// environment variables and the credentials taken from `secret`.
+///
+/// `local_directory_path` is mounted from a PersistentVolumeClaim named
+/// `<name>-data`, which must exist along with the secret. The directories
+/// of rules with their own `local_directory_path` are mounted from
+/// `<name>-data-1`, `<name>-data-2` and so on.
+fn k8s_manifest(
+    config: &Config,
+    job: Option<&Job>,
+    cron: &str,
+    image: &str,
+    secret: &str,
+    time_zone: Option<&str>,
+) -> Result<String> {
+    let name = k8s_name(job.map(|job| job.name.as_str()));
+
+    let mut job_config = config.clone();
+    if let Some(job) = job {
+        job_config.directory_struct = config.rules_for_job(job)?;
+    }
+    job_config.jobs.clear();
+    // Host paths mean nothing in the pod; the keys come from the secret.
+    job_config.access_key_file = None;
+    job_config.secret_key_file = None;
+
+    let mut directories = vec![config.local_directory_path.as_path()];
+    for rule in &job_config.directory_struct {
+        let directory = config.rule_directory(rule);
+        if !directories.contains(&directory) {
+            directories.push(directory);
+        }
+    }
+    let volume_name = |index: usize| match index {
+        0 => "data".to_string(),
+        index => format!("data-{}", index),
+    };
+    let claims: Vec<String> = (0..directories.len())
+        .map(|index| format!("{}-{}", name, volume_name(index)))
+        .collect();
+    let volume_mounts: Vec<_> = directories
+        .iter()
+        .enumerate()
+        .map(|(index, directory)| {
+            serde_json::json!({
+                "name": volume_name(index),
+                "mountPath": directory,
+                "readOnly": true,
+            })
+        })
+        .collect();
+    let volumes: Vec<_> = claims
+        .iter()
+        .enumerate()
+        .map(|(index, claim)| {
+            serde_json::json!({
+                "name": volume_name(index),
+                "persistentVolumeClaim": { "claimName": claim },
+            })
+        })
+        .collect();
+
+    let mut env = Vec::new();
+    if let serde_yaml::Value::Mapping(fields) = serde_yaml::to_value(&job_config)? {
+        for (key, value) in &fields {
+            let (Some(key), Some(value)) = (key.as_str(), env_value(value)?) else {
+                continue;
+            };
+            env.push(serde_json::json!({
+                "name": format!("{}{}", ENV_PREFIX, key.to_ascii_uppercase()),
+                "value": value,
+            }));
+        }
+    }
+    for key in ["AWS_ACCESS_KEY_ID", "AWS_SECRET_ACCESS_KEY"] {
+        env.push(serde_json::json!({
+            "name": key,
+            "valueFrom": { "secretKeyRef": { "name": secret, "key": key } },
+        }));
+    }
+
+    let mut spec = serde_json::json!({
+        "schedule": cron,
+        "concurrencyPolicy": "Forbid",
+        "jobTemplate": { "spec": {
+            "backoffLimit": 0,
+            "template": { "spec": {
+                "restartPolicy": "Never",
+                "containers": [{
+                    "name": "prefixload",
+                    "image": image,
+                    "command": ["prefixload", "run", "--container"],
+                    "env": env,
+                    "volumeMounts": volume_mounts,
+                }],
+                "volumes": volumes,
+            }},
+        }},
+    });
+    if let Some(time_zone) = time_zone {
+        spec["timeZone"] = time_zone.into();
+    }
+
+    let manifest = serde_json::json!({
+        "apiVersion": "batch/v1",
+        "kind": "CronJob",
+        "metadata": { "name": name },
+        "spec": spec,
+    });
+
+    Ok(format!(
+        "# Generated by `prefixload schedule --k8s`. Before applying, create the secret\n\
+         # '{}' (AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY) and the PersistentVolumeClaim\n\
+         # '{}' holding the files to back up.\n{}",
+        secret,
+        claims.join("', '"),
+        serde_yaml::to_string(&manifest)?
+    ))
+}
+
+/// Prints the CronJob manifest for `--k8s`. The schedule comes from the
+/// expression or from the job given with `--job`.
+fn k8s(args: &ScheduleArgs) -> Result<String> {
+    let config = Config::load()?;
+    let job = args
+        .job
+        .as_deref()
+        .map(|name| config.job(name))
+        .transpose()?;
+
+    let schedule = match (&args.expression, job) {
+        (Some(expression), _) => expression.as_str(),
+        (None, Some(job)) => job.schedule.as_deref().ok_or_else(|| {
+            PrefixloadError::Custom(format!("Job '{}' has no schedule.", job.name))
+        })?,
+        (None, None) => {
+            return Err(PrefixloadError::Custom(
+                "--k8s needs a schedule: pass an expression or --job.".to_string(),
+            ));
+        }
+    };
+    let cron = translate_interval(schedule)?.unwrap_or_else(|| schedule.to_string());
+    parse_cron(&cron)?;
+
+    let image = args
+        .image
+        .clone()
+        .unwrap_or_else(|| format!("prefixload:{}", env!("CARGO_PKG_VERSION")));
+    // Schedules are in local time; pass the zone on when it has a name.
+    let time_zone = std::env::var("TZ")
+        .ok()
+        .filter(|tz| !tz.is_empty() && !tz.starts_with(':'));
+
+    k8s_manifest(
+        &config,
+        job,
+        &cron,
+        &image,
+        args.secret.as_deref().unwrap_or(K8S_SECRET),
+        time_zone.as_deref(),
+    )
+}
+
+/// Previews a schedule given on the command line, the schedule of one
+/// job (`--job`), or lists all jobs when no argument is given.
+/// With `--k8s`, prints a Kubernetes CronJob manifest instead.
+pub async fn run(args: &ScheduleArgs) -> Result<String> {
+    if args.k8s {
+        return k8s(args);
+    }
+
+    if let Some(expression) = &args.expression {
+        return preview(expression);
+    }
+
+    let config = Config::load()?;
+    match &args.job {
+        Some(name) => {
+            let job = config.job(name)?;
+            let schedule = job.schedule.as_deref().ok_or_else(|| {
+                PrefixloadError::Custom(format!("Job '{}' has no schedule.", job.name))
+            })?;
+            Ok(format!("Job '{}': {}", job.name, preview(schedule)?))
+        }
+        None => list_jobs(&config),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn parse_cron_rejects_missing_field() {
+        let err = parse_cron("0 3 * *").unwrap_err().to_string();
+
+        assert!(err.contains("Invalid cron expression '0 3 * *'"));
+        assert!(err.contains("Expected five fields"));
+    }
+
+    #[test]
+    fn parse_cron_rejects_out_of_range_values() {
+        assert!(parse_cron("0 25 * * *").is_err());
+        assert!(parse_cron("0 0 3 * * *").is_err());
+    }
+
+    #[test]
+    fn next_runs_lists_upcoming_times() {
+        let cron = parse_cron("30 3 * * *").unwrap();
+        let after = Utc.with_ymd_and_hms(2025, 9, 21, 12, 0, 0).unwrap();
+
+        let runs = next_runs(&cron, after, 3);
+
+        assert_eq!(
+            runs,
+            vec![
+                Utc.with_ymd_and_hms(2025, 9, 22, 3, 30, 0).unwrap(),
+                Utc.with_ymd_and_hms(2025, 9, 23, 3, 30, 0).unwrap(),
+                Utc.with_ymd_and_hms(2025, 9, 24, 3, 30, 0).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn translate_interval_supports_friendly_forms() {
+        let cases = [
+            ("every 6h", "0 */6 * * *"),
+            ("every 15 minutes", "*/15 * * * *"),
+            ("every 1h", "0 * * * *"),
+            ("hourly", "0 * * * *"),
+            ("daily", "0 0 * * *"),
+            ("daily at 03:00", "0 3 * * *"),
+            ("Every day at 22:30", "30 22 * * *"),
+            ("weekly", "0 0 * * 0"),
+            ("weekly on mon at 04:15", "15 4 * * 1"),
+            ("every friday at 18:00", "0 18 * * 5"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                translate_interval(input).unwrap().as_deref(),
+                Some(expected),
+                "input: {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn translate_interval_leaves_cron_untouched() {
+        assert_eq!(translate_interval("0 3 * * *").unwrap(), None);
+        assert_eq!(translate_interval("@daily").unwrap(), None);
+    }
+
+    #[test]
+    fn translate_interval_rejects_bad_intervals() {
+        for input in [
+            "every 45m",
+            "every 5h",
+            "every 0h",
+            "every 6d",
+            "daily at 25:00",
+            "daily at noon",
+            "weekly on funday",
+            "every",
+        ] {
+            let err = translate_interval(input).unwrap_err().to_string();
+            assert!(err.contains("Invalid schedule"), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn preview_accepts_friendly_interval() {
+        let output = preview("daily at 03:00").unwrap();
+
+        assert!(output.starts_with("Schedule 'daily at 03:00' is valid (cron: 0 3 * * *)."));
+        assert_eq!(output.lines().count(), 1 + PREVIEW_RUNS);
+    }
+
+    #[test]
+    fn preview_prints_five_next_runs() {
+        let output = preview("@daily").unwrap();
+
+        assert!(output.starts_with("Cron expression '@daily' is valid."));
+        assert_eq!(output.lines().count(), 1 + PREVIEW_RUNS);
+    }
+
+    fn config_with_jobs(jobs: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            "endpoint: e\nbucket: b\nregion: r\nforce_path_style: false\npart_size: 5MiB\n\
+             local_directory_path: /tmp\ndirectory_struct: []\njobs:\n{}",
+            jobs
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn list_jobs_shows_schedule_and_next_run() {
+        let config =
+            config_with_jobs("- name: nightly-db\n  schedule: daily at 03:00\n- name: manual\n");
+
+        let output = list_jobs(&config).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(lines[0].starts_with("nightly-db  daily at 03:00  next run: "));
+        assert!(lines[0].contains("03:00"));
+        assert_eq!(lines[1], "manual      not scheduled");
+    }
+
+    #[test]
+    fn list_jobs_reports_invalid_schedule() {
+        let config = config_with_jobs("- name: broken\n  schedule: '0 3 * *'\n");
+
+        assert!(list_jobs(&config).is_err());
+
+        let mut empty = config;
+        empty.jobs.clear();
+        assert!(list_jobs(&empty).is_err());
+    }
+
+    #[test]
+    fn k8s_name_is_a_valid_object_name() {
+        assert_eq!(k8s_name(None), "prefixload");
+        assert_eq!(k8s_name(Some("Nightly DB_")), "prefixload-nightly-db");
+    }
+
+    #[test]
+    fn k8s_manifest_passes_job_rules_as_env() {
+        let config: Config = serde_yaml::from_str(
+            "endpoint: http://minio:9000\nbucket: b\nregion: r\nforce_path_style: true\n\
+             part_size: 16MiB\nlocal_directory_path: /backups\ndirectory_struct:\n\
+             - {local_name_prefix: db_, remote_path: db/}\n\
+             - {local_name_prefix: logs_, remote_path: logs/, local_directory_path: /srv/logs}\n\
+             jobs:\n- {name: nightly, rules: [db_], schedule: daily at 03:00}\n",
+        )
+        .unwrap();
+
+        let output = k8s_manifest(
+            &config,
+            Some(&config.jobs[0]),
+            "0 3 * * *",
+            "prefixload:test",
+            "aws",
+            Some("Europe/Berlin"),
+        )
+        .unwrap();
+        assert!(output.starts_with("# Generated by `prefixload schedule --k8s`"));
+
+        let manifest: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(manifest["kind"], "CronJob");
+        assert_eq!(manifest["metadata"]["name"], "prefixload-nightly");
+        assert_eq!(manifest["spec"]["schedule"], "0 3 * * *");
+        assert_eq!(manifest["spec"]["timeZone"], "Europe/Berlin");
+
+        let pod = &manifest["spec"]["jobTemplate"]["spec"]["template"]["spec"];
+        let container = &pod["containers"][0];
+        assert_eq!(container["image"], "prefixload:test");
+        assert_eq!(container["volumeMounts"][0]["mountPath"], "/backups");
+        assert_eq!(
+            pod["volumes"][0]["persistentVolumeClaim"]["claimName"],
+            "prefixload-nightly-data"
+        );
+
+        let env: Vec<(String, String)> = container["env"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|var| {
+                Some((
+                    var["name"].as_str()?.to_string(),
+                    var["value"].as_str()?.to_string(),
+                ))
+            })
+            .collect();
+        let get = |name: &str| env.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+        assert_eq!(get("PREFIXLOAD_FORCE_PATH_STYLE"), Some("true"));
+        assert_eq!(get("PREFIXLOAD_PART_SIZE"), Some("16MiB"));
+        assert_eq!(
+            get("PREFIXLOAD_DIRECTORY_STRUCT"),
+            Some(r#"[{"local_name_prefix":"db_","remote_path":"db/"}]"#)
+        );
+        assert_eq!(get("PREFIXLOAD_JOBS"), None);
+        assert_eq!(get("PREFIXLOAD_MIN_INTERVAL"), None);
+        assert_eq!(
+            container["env"][env.len() + 1]["valueFrom"]["secretKeyRef"]["name"],
+            "aws"
+        );
+        assert_eq!(pod["volumes"].as_sequence().unwrap().len(), 1);
+
+        let output =
+            k8s_manifest(&config, None, "0 3 * * *", "prefixload:test", "aws", None).unwrap();
+        assert!(output.contains("PersistentVolumeClaim\n# 'prefixload-data', 'prefixload-data-1'"));
+        let manifest: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        let pod = &manifest["spec"]["jobTemplate"]["spec"]["template"]["spec"];
+        assert_eq!(
+            pod["containers"][0]["volumeMounts"][1]["mountPath"],
+            "/srv/logs"
+        );
+        assert_eq!(
+            pod["volumes"][1]["persistentVolumeClaim"]["claimName"],
+            "prefixload-data-1"
+        );
+    }
 }
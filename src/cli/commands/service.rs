@@ -0,0 +1,447 @@
+use crate::cli::ServiceCommand;
+use crate::error::{PrefixloadError, Result};
+use std::path::Path;
+
+/// Name of the Windows service, as used by `sc.exe` and `net start`.
+pub const SERVICE_NAME: &str = "prefixload";
+
+/// Returns the command line the service control manager starts the service
+/// with: `prefixload service run`, keeping `--read-only`.
+fn service_command(exe: &Path, read_only: bool) -> String {
+    let mut command = format!("\"{}\" service run", exe.display());
+    if read_only {
+        command.push_str(" --read-only");
+    }
+    command
+}
+
+/// Runs a `service` subcommand. The service runs the daemon's scheduler
+/// (see `daemon::serve`) as LocalSystem, so it reads the `config.yml` of
+/// that account.
+pub async fn run(command: ServiceCommand, read_only: bool) -> Result<String> {
+    let failed = |action: &str, e: std::io::Error| {
+        PrefixloadError::Custom(format!("Failed to {} the Windows service: {}", action, e))
+    };
+    match command {
+        ServiceCommand::Install => {
+            let command = service_command(&std::env::current_exe()?, read_only);
+            platform::install(&command).map_err(|e| failed("install", e))?;
+            Ok(format!(
+                "Installed the '{}' service: it starts with Windows and is restarted after \
+                 failures. Start it now with `prefixload service start`.\n",
+                SERVICE_NAME
+            ))
+        }
+        ServiceCommand::Uninstall => {
+            platform::uninstall().map_err(|e| failed("uninstall", e))?;
+            Ok(format!("Removed the '{}' service.\n", SERVICE_NAME))
+        }
+        ServiceCommand::Start => {
+            platform::start().map_err(|e| failed("start", e))?;
+            Ok(format!("Started the '{}' service.\n", SERVICE_NAME))
+        }
+        ServiceCommand::Run => {
+            platform::run(read_only).map_err(|e| failed("run", e))?;
+            Ok(String::new())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::SERVICE_NAME;
+    use crate::cancel::CancelToken;
+    use crate::cli::commands::daemon;
+    use crate::eventlog::{self, RunEvent};
+    use std::ffi::c_void;
+    use std::io;
+    use std::ptr;
+    use std::sync::OnceLock;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+    use std::time::Duration;
+    use windows_sys::Win32::Foundation::{
+        ERROR_CALL_NOT_IMPLEMENTED, ERROR_SERVICE_NOT_ACTIVE, ERROR_SERVICE_SPECIFIC_ERROR,
+        NO_ERROR,
+    };
+    use windows_sys::Win32::Storage::FileSystem::DELETE;
+    use windows_sys::Win32::System::Services::{
+        ChangeServiceConfig2W, CloseServiceHandle, ControlService, CreateServiceW, DeleteService,
+        OpenSCManagerW, OpenServiceW, RegisterServiceCtrlHandlerExW, SC_ACTION, SC_ACTION_RESTART,
+        SC_HANDLE, SC_MANAGER_CONNECT, SC_MANAGER_CREATE_SERVICE, SERVICE_ACCEPT_SHUTDOWN,
+        SERVICE_ACCEPT_STOP, SERVICE_AUTO_START, SERVICE_CHANGE_CONFIG, SERVICE_CONFIG_DESCRIPTION,
+        SERVICE_CONFIG_FAILURE_ACTIONS, SERVICE_CONFIG_FAILURE_ACTIONS_FLAG,
+        SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP,
+        SERVICE_DESCRIPTIONW, SERVICE_ERROR_NORMAL, SERVICE_FAILURE_ACTIONS_FLAG,
+        SERVICE_FAILURE_ACTIONSW, SERVICE_QUERY_STATUS, SERVICE_RUNNING, SERVICE_START,
+        SERVICE_STATUS, SERVICE_STATUS_CURRENT_STATE, SERVICE_STOP, SERVICE_STOP_PENDING,
+        SERVICE_STOPPED, SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS, SetServiceStatus,
+        StartServiceCtrlDispatcherW, StartServiceW,
+    };
+    use windows_sys::core::{BOOL, PWSTR};
+
+    /// Name of the service shown in the Services console.
+    const DISPLAY_NAME: &str = "prefixload";
+
+    /// Description of the service shown in the Services console.
+    const DESCRIPTION: &str = "Uploads local backups to S3 on the schedules of config.yml.";
+
+    /// Delays after which the service control manager restarts the service
+    /// after its first, second and further failures.
+    const RESTART_DELAYS: [Duration; 3] = [
+        Duration::from_secs(60),
+        Duration::from_secs(60),
+        Duration::from_secs(300),
+    ];
+
+    /// Time without failures after which the failure count starts over.
+    const FAILURE_RESET_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// How long the service control manager waits for the run in progress
+    /// to abort its multipart uploads when the service stops.
+    const STOP_WAIT_HINT_MS: u32 = 30_000;
+
+    /// What `service_main`, called on a thread of the service control
+    /// manager, needs from `run`.
+    struct Service {
+        runtime: tokio::runtime::Handle,
+        read_only: bool,
+        stop: CancelToken,
+    }
+
+    static SERVICE: OnceLock<Service> = OnceLock::new();
+
+    /// Handle `service_main` reports the status of the service to.
+    static STATUS_HANDLE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+    /// A handle of the service control manager or of a service, closed on
+    /// drop.
+    struct ScHandle(SC_HANDLE);
+
+    impl ScHandle {
+        fn new(handle: SC_HANDLE) -> io::Result<Self> {
+            if handle.is_null() {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(Self(handle))
+            }
+        }
+
+        fn manager(access: u32) -> io::Result<Self> {
+            // SAFETY: null names select the local service control manager
+            // and its active database.
+            Self::new(unsafe { OpenSCManagerW(ptr::null(), ptr::null(), access) })
+        }
+
+        fn service(&self, access: u32) -> io::Result<Self> {
+            let name = wide(SERVICE_NAME);
+            // SAFETY: `self` is an open manager and `name` is a
+            // NUL-terminated UTF-16 string.
+            Self::new(unsafe { OpenServiceW(self.0, name.as_ptr(), access) })
+        }
+    }
+
+    impl Drop for ScHandle {
+        fn drop(&mut self) {
+            // SAFETY: the handle was opened by `ScHandle::new` and is not
+            // used afterwards.
+            unsafe { CloseServiceHandle(self.0) };
+        }
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    fn check(result: BOOL) -> io::Result<()> {
+        if result == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn install(command: &str) -> io::Result<()> {
+        let manager = ScHandle::manager(SC_MANAGER_CREATE_SERVICE)?;
+        let name = wide(SERVICE_NAME);
+        let display_name = wide(DISPLAY_NAME);
+        let command = wide(command);
+        // SAFETY: `manager` is open and the strings are NUL-terminated
+        // UTF-16; a null account runs the service as LocalSystem.
+        let service = ScHandle::new(unsafe {
+            CreateServiceW(
+                manager.0,
+                name.as_ptr(),
+                display_name.as_ptr(),
+                SERVICE_CHANGE_CONFIG | SERVICE_START | DELETE,
+                SERVICE_WIN32_OWN_PROCESS,
+                SERVICE_AUTO_START,
+                SERVICE_ERROR_NORMAL,
+                command.as_ptr(),
+                ptr::null(),
+                ptr::null_mut(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+            )
+        })?;
+
+        // A service without its recovery options is removed again, so that
+        // installing it once more starts over.
+        if let Err(err) = configure(&service) {
+            // SAFETY: `service` was opened with DELETE access.
+            unsafe { DeleteService(service.0) };
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Sets the description of the service and has the service control
+    /// manager restart it after it crashes or stops with an error.
+    fn configure(service: &ScHandle) -> io::Result<()> {
+        let mut description = wide(DESCRIPTION);
+        let description = SERVICE_DESCRIPTIONW {
+            lpDescription: description.as_mut_ptr(),
+        };
+        // SAFETY: `service` was opened with SERVICE_CHANGE_CONFIG access and
+        // the structures outlive the calls.
+        check(unsafe {
+            ChangeServiceConfig2W(
+                service.0,
+                SERVICE_CONFIG_DESCRIPTION,
+                &description as *const _ as *const c_void,
+            )
+        })?;
+
+        let mut actions = RESTART_DELAYS.map(|delay| SC_ACTION {
+            Type: SC_ACTION_RESTART,
+            Delay: delay.as_millis() as u32,
+        });
+        let failure_actions = SERVICE_FAILURE_ACTIONSW {
+            dwResetPeriod: FAILURE_RESET_PERIOD.as_secs() as u32,
+            lpRebootMsg: ptr::null_mut(),
+            lpCommand: ptr::null_mut(),
+            cActions: actions.len() as u32,
+            lpsaActions: actions.as_mut_ptr(),
+        };
+        // SAFETY: as above; restart actions also need the SERVICE_START
+        // access the service was opened with.
+        check(unsafe {
+            ChangeServiceConfig2W(
+                service.0,
+                SERVICE_CONFIG_FAILURE_ACTIONS,
+                &failure_actions as *const _ as *const c_void,
+            )
+        })?;
+
+        // Without this flag only crashes count as failures, not a daemon
+        // that stops with an error, e.g. an invalid config.yml.
+        let flag = SERVICE_FAILURE_ACTIONS_FLAG {
+            fFailureActionsOnNonCrashFailures: 1,
+        };
+        // SAFETY: as above.
+        check(unsafe {
+            ChangeServiceConfig2W(
+                service.0,
+                SERVICE_CONFIG_FAILURE_ACTIONS_FLAG,
+                &flag as *const _ as *const c_void,
+            )
+        })
+    }
+
+    pub fn uninstall() -> io::Result<()> {
+        let manager = ScHandle::manager(SC_MANAGER_CONNECT)?;
+        let service = manager.service(SERVICE_STOP | SERVICE_QUERY_STATUS | DELETE)?;
+
+        let mut status = stopped_status();
+        // SAFETY: `service` was opened with SERVICE_STOP access and `status`
+        // is writable.
+        if let Err(err) =
+            check(unsafe { ControlService(service.0, SERVICE_CONTROL_STOP, &mut status) })
+            && err.raw_os_error() != Some(ERROR_SERVICE_NOT_ACTIVE as i32)
+        {
+            return Err(err);
+        }
+        // SAFETY: `service` was opened with DELETE access.
+        check(unsafe { DeleteService(service.0) })
+    }
+
+    pub fn start() -> io::Result<()> {
+        let manager = ScHandle::manager(SC_MANAGER_CONNECT)?;
+        let service = manager.service(SERVICE_START)?;
+        // SAFETY: `service` was opened with SERVICE_START access; the
+        // service gets no arguments.
+        check(unsafe { StartServiceW(service.0, 0, ptr::null()) })
+    }
+
+    /// Connects to the service control manager, which calls `service_main`
+    /// on a thread of its own, and returns once the service has stopped.
+    pub fn run(read_only: bool) -> io::Result<()> {
+        let service = Service {
+            runtime: tokio::runtime::Handle::current(),
+            read_only,
+            stop: CancelToken::new(),
+        };
+        if SERVICE.set(service).is_err() {
+            return Err(io::Error::other("the service is already running"));
+        }
+
+        let mut name = wide(SERVICE_NAME);
+        let table = [
+            SERVICE_TABLE_ENTRYW {
+                lpServiceName: name.as_mut_ptr(),
+                lpServiceProc: Some(service_main),
+            },
+            SERVICE_TABLE_ENTRYW::default(),
+        ];
+        // SAFETY: `table` ends with a null entry and outlives the call,
+        // which blocks until the service stops.
+        tokio::task::block_in_place(|| {
+            check(unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) })
+        })
+    }
+
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut PWSTR) {
+        let Some(service) = SERVICE.get() else {
+            return;
+        };
+        let name = wide(SERVICE_NAME);
+        // SAFETY: `name` is a NUL-terminated UTF-16 string and
+        // `control_handler` needs no context.
+        let handle = unsafe {
+            RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(control_handler), ptr::null())
+        };
+        if handle.is_null() {
+            return;
+        }
+        STATUS_HANDLE.store(handle, Ordering::SeqCst);
+
+        set_status(SERVICE_RUNNING, NO_ERROR, 0);
+        let result = service
+            .runtime
+            .block_on(daemon::serve(service.read_only, &service.stop));
+        match result {
+            Ok(_) => set_status(SERVICE_STOPPED, NO_ERROR, 0),
+            Err(err) => {
+                // Windows services have no console: the Event Log is where
+                // the error shows up.
+                let _ = eventlog::report(
+                    RunEvent::Failed,
+                    &format!("The prefixload service stopped: {}", err),
+                );
+                // A service-specific exit code counts as a failure, so the
+                // recovery options restart the service.
+                set_status(
+                    SERVICE_STOPPED,
+                    ERROR_SERVICE_SPECIFIC_ERROR,
+                    err.exit_code() as u32,
+                );
+            }
+        }
+    }
+
+    unsafe extern "system" fn control_handler(
+        control: u32,
+        _event_type: u32,
+        _event_data: *mut c_void,
+        _context: *mut c_void,
+    ) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                set_status(SERVICE_STOP_PENDING, NO_ERROR, 0);
+                if let Some(service) = SERVICE.get() {
+                    service.stop.cancel();
+                }
+                NO_ERROR
+            }
+            SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+            _ => ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+
+    fn stopped_status() -> SERVICE_STATUS {
+        SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: SERVICE_STOPPED,
+            dwControlsAccepted: 0,
+            dwWin32ExitCode: NO_ERROR,
+            dwServiceSpecificExitCode: 0,
+            dwCheckPoint: 0,
+            dwWaitHint: 0,
+        }
+    }
+
+    fn set_status(state: SERVICE_STATUS_CURRENT_STATE, exit_code: u32, specific_exit_code: u32) {
+        let status = SERVICE_STATUS {
+            dwCurrentState: state,
+            dwControlsAccepted: if state == SERVICE_RUNNING {
+                SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+            } else {
+                0
+            },
+            dwWin32ExitCode: exit_code,
+            dwServiceSpecificExitCode: specific_exit_code,
+            dwWaitHint: if state == SERVICE_STOP_PENDING {
+                STOP_WAIT_HINT_MS
+            } else {
+                0
+            },
+            ..stopped_status()
+        };
+        // SAFETY: the handle was registered by `service_main`, and
+        // SetServiceStatus copies `status`.
+        unsafe { SetServiceStatus(STATUS_HANDLE.load(Ordering::SeqCst), &status) };
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use std::io;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Windows services are only available on Windows",
+        )
+    }
+
+    pub fn install(_command: &str) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn uninstall() -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn start() -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn run(_read_only: bool) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_command_runs_the_service_and_keeps_read_only() {
+        let exe = Path::new(r"C:\Program Files\prefixload\prefixload.exe");
+        assert_eq!(
+            service_command(exe, false),
+            r#""C:\Program Files\prefixload\prefixload.exe" service run"#
+        );
+        assert_eq!(
+            service_command(exe, true),
+            r#""C:\Program Files\prefixload\prefixload.exe" service run --read-only"#
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn service_is_unsupported_outside_windows() {
+        let err = run(ServiceCommand::Install, false).await.unwrap_err();
+        assert!(err.to_string().contains("only available on Windows"));
+    }
+}
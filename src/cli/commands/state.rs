@@ -0,0 +1,193 @@
+// This module handles the `state` command, which maintains the state file runs
+// keep between each other.
+
+use crate::cli::StateCommand;
+use crate::cli::commands::run::bucket_client;
+use crate::cli::confirm::confirm;
+use crate::clients::s3::S3Client;
+use crate::config::{self, Config};
+use crate::error::{PrefixloadError, Result};
+use crate::manifest::PartManifest;
+use crate::state::State;
+use chrono::{DateTime, Local, Utc};
+use std::collections::HashSet;
+
+/// Removes the runs that finished before `cutoff` from the history of
+/// `state`, and the recorded objects of `bucket` that are no longer in it,
+/// together with their part manifests. Objects of other buckets are kept.
+///
+/// # Returns
+///
+/// The lines to print.
+async fn gc(
+    state: &mut State,
+    client: &S3Client,
+    bucket: &str,
+    cutoff: DateTime<Utc>,
+) -> Result<Vec<String>> {
+    let runs = state.prune_runs(cutoff);
+
+    let keys: HashSet<String> = client.list_keys(bucket, "").await?.into_iter().collect();
+    let removed = state.prune_objects(bucket, &keys);
+    let mut manifests = 0;
+    for key in &removed {
+        if PartManifest::remove(bucket, key)? {
+            manifests += 1;
+        }
+    }
+
+    Ok(vec![
+        format!(
+            "Removed {} run(s) finished before {} from the history.",
+            runs,
+            cutoff.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+        ),
+        format!(
+            "Removed {} object(s) no longer in bucket {} and {} part manifest(s).",
+            removed.len(),
+            bucket,
+            manifests
+        ),
+    ])
+}
+
+/// The main entry point for the `state` command.
+///
+/// `state gc` drops the runs older than `run_history_days` from the run
+/// history, and forgets the objects of the configured bucket that were
+/// deleted since a run recorded them, so the state file of long-lived hosts
+/// stays small. It asks for confirmation first unless `yes` is set.
+///
+/// # Returns
+///
+/// A `Result` containing a summary, or a `PrefixloadError`.
+pub async fn run(command: StateCommand, yes: bool) -> Result<String> {
+    match command {
+        StateCommand::Gc => {
+            if config::container_mode() {
+                return Err(PrefixloadError::Custom(
+                    "Container mode keeps no state file to clean up.".to_string(),
+                ));
+            }
+            let config = Config::load()?;
+            let question = format!(
+                "Remove the runs older than {} days and the objects no longer in bucket {} from the state file?",
+                config.run_history_days(),
+                config.bucket
+            );
+            if !confirm(&question, yes)? {
+                return Ok("Nothing was removed.".to_string());
+            }
+            let mut state = State::load()?;
+
+            let mut lines = Vec::new();
+            let client = bucket_client(&config, None, false, &mut |line| {
+                lines.push(line.to_string())
+            })
+            .await?;
+
+            let cutoff = Utc::now() - chrono::Duration::days(i64::from(config.run_history_days()));
+            lines.extend(gc(&mut state, &client, &config.bucket, cutoff).await?);
+            state.save()?;
+
+            lines.push(format!(
+                "The state file holds {} run(s) and {} object(s).",
+                state.runs.len(),
+                state.objects.len()
+            ));
+            Ok(lines.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::s3::S3ClientOptions;
+    use crate::report::RunReport;
+    use serial_test::serial;
+    use std::env;
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[cfg(windows)]
+    const DATA_LOCAL_ENV: &str = "LOCALAPPDATA";
+    #[cfg(not(windows))]
+    const DATA_LOCAL_ENV: &str = "XDG_DATA_HOME";
+
+    fn report(run_id: &str) -> RunReport {
+        serde_json::from_value(serde_json::json!({
+            "run_id": run_id,
+            "hostname": "db-01",
+            "started_at": "",
+            "finished_at": "",
+            "duration_secs": 0.0,
+            "success": true,
+            "matched": 0,
+            "uploaded": 0,
+            "skipped": 0,
+            "failed": 0,
+            "too_large": 0,
+            "max_file_size": 0,
+            "uploaded_bytes": 0,
+            "files": []
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn gc_prunes_old_runs_and_deleted_objects() {
+        let tmp = TempDir::new().unwrap();
+        unsafe { env::set_var(DATA_LOCAL_ENV, tmp.path()) };
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("prefix", ""))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>db/a.sql</Key></Contents></ListBucketResult>",
+            ))
+            .mount(&server)
+            .await;
+        let options = S3ClientOptions::default()
+            .with_access_key("AKIDTEST")
+            .with_secret_key("SECRET")
+            .with_endpoint(server.uri())
+            .with_region("us-east-1")
+            .with_force_path_style(true);
+        let client = S3Client::new(options).await.unwrap();
+
+        let now = Utc::now();
+        let mut state = State::default();
+        state.record_run(&report("old"), now);
+        state.record_run(&report("new"), now);
+        state.runs[0].finished_at = now - chrono::Duration::days(100);
+        state.record_object("bucket", "db/a.sql", "etag-1", 10);
+        state.record_object("bucket", "db/b.sql", "etag-2-2", 20);
+        let manifest = PartManifest {
+            etag: "etag-2-2".to_string(),
+            part_size: 10,
+            parts: vec!["p1".to_string(), "p2".to_string()],
+        };
+        manifest.save("bucket", "db/b.sql").unwrap();
+
+        let lines = gc(
+            &mut state,
+            &client,
+            "bucket",
+            now - chrono::Duration::days(90),
+        )
+        .await
+        .unwrap();
+        assert!(lines[0].starts_with("Removed 1 run(s) finished before "));
+        assert_eq!(
+            lines[1],
+            "Removed 1 object(s) no longer in bucket bucket and 1 part manifest(s)."
+        );
+        assert_eq!(state.runs.len(), 1);
+        assert_eq!(state.objects.len(), 1);
+        assert_eq!(PartManifest::load("bucket", "db/b.sql").unwrap(), None);
+    }
+}
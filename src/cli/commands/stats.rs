@@ -0,0 +1,270 @@
+// This module handles the `stats` command, which lists the run history kept in
+// the state file or exports it for spreadsheets and BI tools.
+
+use crate::cli::StatsArgs;
+use crate::cli::format::{format_bytes, format_duration};
+use crate::config;
+use crate::error::{PrefixloadError, Result};
+use crate::state::{RunRecord, State};
+use chrono::{Local, SecondsFormat};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Format of `stats --export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        })
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "invalid export format '{}': use 'csv' or 'json'",
+                s
+            )),
+        }
+    }
+}
+
+/// Columns of the CSV export, in the order of the fields of `RunRow`.
+const CSV_HEADER: &str = "run_id,job,started_at,finished_at,duration_secs,result,matched,uploaded,skipped,failed,uploaded_bytes";
+
+/// A run of the history as exported.
+#[derive(Debug, Serialize)]
+struct RunRow<'a> {
+    run_id: &'a str,
+    job: Option<&'a str>,
+    /// RFC 3339 timestamps in UTC.
+    started_at: String,
+    finished_at: String,
+    duration_secs: f64,
+    /// `success` or `failed`.
+    result: &'static str,
+    matched: usize,
+    uploaded: usize,
+    skipped: usize,
+    failed: usize,
+    uploaded_bytes: u64,
+}
+
+impl<'a> RunRow<'a> {
+    fn new(run: &'a RunRecord) -> Self {
+        Self {
+            run_id: &run.run_id,
+            job: run.job.as_deref(),
+            started_at: run.started_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            finished_at: run.finished_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            duration_secs: (run.finished_at - run.started_at).num_milliseconds() as f64 / 1000.0,
+            result: if run.success { "success" } else { "failed" },
+            matched: run.matched,
+            uploaded: run.uploaded,
+            skipped: run.skipped,
+            failed: run.failed,
+            uploaded_bytes: run.uploaded_bytes,
+        }
+    }
+
+    fn csv_line(&self) -> String {
+        [
+            csv_field(self.run_id),
+            csv_field(self.job.unwrap_or_default()),
+            self.started_at.clone(),
+            self.finished_at.clone(),
+            self.duration_secs.to_string(),
+            self.result.to_string(),
+            self.matched.to_string(),
+            self.uploaded.to_string(),
+            self.skipped.to_string(),
+            self.failed.to_string(),
+            self.uploaded_bytes.to_string(),
+        ]
+        .join(",")
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Renders `runs` in `format`, one row per run, oldest first.
+fn export(runs: &[&RunRecord], format: ExportFormat) -> Result<String> {
+    let rows: Vec<RunRow<'_>> = runs.iter().map(|run| RunRow::new(run)).collect();
+    match format {
+        ExportFormat::Csv => {
+            let mut csv = format!("{}\n", CSV_HEADER);
+            for row in &rows {
+                csv.push_str(&row.csv_line());
+                csv.push('\n');
+            }
+            Ok(csv)
+        }
+        ExportFormat::Json => serde_json::to_string_pretty(&rows)
+            .map(|json| json + "\n")
+            .map_err(|e| {
+                PrefixloadError::Custom(format!("Failed to serialize run history: {}", e))
+            }),
+    }
+}
+
+/// Lists `runs` one per line, followed by totals.
+fn summary(runs: &[&RunRecord]) -> String {
+    let mut lines: Vec<String> = runs
+        .iter()
+        .map(|run| {
+            format!(
+                "{}  {:<12} {:<7} {} uploaded, {} skipped, {} failed, {} in {}",
+                run.started_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S"),
+                run.job.as_deref().unwrap_or("-"),
+                if run.success { "ok" } else { "FAILED" },
+                run.uploaded,
+                run.skipped,
+                run.failed,
+                format_bytes(run.uploaded_bytes),
+                format_duration(
+                    (run.finished_at - run.started_at)
+                        .to_std()
+                        .unwrap_or_default()
+                )
+            )
+        })
+        .collect();
+
+    let failed = runs.iter().filter(|run| !run.success).count();
+    lines.push(format!(
+        "{} run(s), {} failed, {} uploaded in total.",
+        runs.len(),
+        failed,
+        format_bytes(runs.iter().map(|run| run.uploaded_bytes).sum())
+    ));
+    lines.join("\n")
+}
+
+/// The main entry point for the `stats` command.
+///
+/// Lists the runs of the history in the state file (of `--job`, if given),
+/// or with `--export`, renders them as CSV or JSON to redirect into a file.
+///
+/// # Returns
+///
+/// A `Result` containing the listing or export, or a `PrefixloadError`.
+pub async fn run(args: &StatsArgs) -> Result<String> {
+    if config::container_mode() {
+        return Err(PrefixloadError::Custom(
+            "Container mode keeps no state file, so there is no run history.".to_string(),
+        ));
+    }
+    let state = State::load()?;
+    let runs: Vec<&RunRecord> = state
+        .runs
+        .iter()
+        .filter(|run| args.job.is_none() || run.job == args.job)
+        .collect();
+
+    match args.export {
+        Some(format) => export(&runs, format),
+        None => Ok(summary(&runs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn record(run_id: &str, job: Option<&str>, success: bool) -> RunRecord {
+        let started_at = DateTime::parse_from_rfc3339("2024-06-01T03:00:00Z")
+            .unwrap()
+            .to_utc();
+        RunRecord {
+            run_id: run_id.to_string(),
+            job: job.map(str::to_string),
+            started_at,
+            finished_at: started_at + chrono::Duration::milliseconds(90_500),
+            success,
+            matched: 3,
+            uploaded: 1,
+            skipped: 1,
+            failed: if success { 0 } else { 1 },
+            uploaded_bytes: 2048,
+        }
+    }
+
+    #[test]
+    fn export_format_parses_case_insensitively() {
+        assert_eq!("CSV".parse::<ExportFormat>(), Ok(ExportFormat::Csv));
+        assert_eq!(" json".parse::<ExportFormat>(), Ok(ExportFormat::Json));
+        assert!("xlsx".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn export_writes_one_csv_row_per_run() {
+        let a = record("a", None, true);
+        let b = record("b", Some("db, nightly"), false);
+
+        let csv = export(&[&a, &b], ExportFormat::Csv).unwrap();
+        assert_eq!(
+            csv,
+            format!(
+                "{}\n\
+                 a,,2024-06-01T03:00:00Z,2024-06-01T03:01:30Z,90.5,success,3,1,1,0,2048\n\
+                 b,\"db, nightly\",2024-06-01T03:00:00Z,2024-06-01T03:01:30Z,90.5,failed,3,1,1,1,2048\n",
+                CSV_HEADER
+            )
+        );
+    }
+
+    #[test]
+    fn export_writes_a_json_array() {
+        let a = record("a", Some("nightly-db"), true);
+
+        let json = export(&[&a], ExportFormat::Json).unwrap();
+        let rows: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(rows[0]["run_id"], "a");
+        assert_eq!(rows[0]["job"], "nightly-db");
+        assert_eq!(rows[0]["started_at"], "2024-06-01T03:00:00Z");
+        assert_eq!(rows[0]["duration_secs"], 90.5);
+        assert_eq!(rows[0]["result"], "success");
+        assert_eq!(rows[0]["uploaded_bytes"], 2048);
+    }
+
+    #[test]
+    fn summary_lists_runs_and_totals() {
+        let a = record("a", None, true);
+        let b = record("b", Some("nightly-db"), false);
+
+        let text = summary(&[&a, &b]);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(
+            lines[1].contains(
+                "nightly-db   FAILED  1 uploaded, 1 skipped, 1 failed, 2.0 KiB in 1m 30s"
+            ),
+            "{}",
+            lines[1]
+        );
+        assert_eq!(lines[2], "2 run(s), 1 failed, 4.0 KiB uploaded in total.");
+    }
+}
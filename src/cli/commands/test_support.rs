@@ -0,0 +1,15 @@
+// Helpers shared by the tests of the commands.
+
+use crate::clients::s3::{S3Client, S3ClientOptions};
+use wiremock::MockServer;
+
+/// Returns a client for the bucket mocked by `server`.
+pub async fn client(server: &MockServer) -> S3Client {
+    let options = S3ClientOptions::default()
+        .with_access_key("AKIDTEST")
+        .with_secret_key("SECRET")
+        .with_endpoint(server.uri())
+        .with_region("us-east-1")
+        .with_force_path_style(true);
+    S3Client::new(options).await.unwrap()
+}
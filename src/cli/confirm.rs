@@ -0,0 +1,50 @@
+use crate::config::{self, ASSUME_YES_ENV};
+use crate::error::{PrefixloadError, Result};
+use requestty::Question;
+use std::io::IsTerminal;
+
+/// Asks the user to confirm a destructive action, such as removing a rule
+/// or pruning the state file.
+///
+/// The global `--yes` flag (`yes`) or `PREFIXLOAD_ASSUME_YES` answer yes
+/// without a prompt. Without either and without a terminal to ask on, the
+/// action is refused, so scripts never hang on a prompt.
+///
+/// # Returns
+///
+/// `true` if the action should go ahead, `false` if the user declined.
+pub fn confirm(message: &str, yes: bool) -> Result<bool> {
+    if yes || config::assume_yes() {
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(PrefixloadError::Custom(format!(
+            "{} Pass --yes or set {}=1 to confirm without a prompt.",
+            message, ASSUME_YES_ENV
+        )));
+    }
+
+    let question = Question::confirm("confirm")
+        .message(message)
+        .default(false)
+        .build();
+    Ok(requestty::prompt_one(question)?.as_bool().unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::env;
+
+    #[test]
+    #[serial]
+    fn confirm_skips_the_prompt_with_yes() {
+        assert!(confirm("Remove everything?", true).unwrap());
+
+        unsafe { env::set_var(ASSUME_YES_ENV, "true") };
+        let confirmed = confirm("Remove everything?", false);
+        unsafe { env::remove_var(ASSUME_YES_ENV) };
+        assert!(confirmed.unwrap());
+    }
+}
@@ -0,0 +1,74 @@
+use crate::size::ByteSize;
+use std::time::Duration;
+
+/// Computes a transfer rate, treating an empty interval as no progress.
+pub fn bytes_per_second(bytes: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 { bytes as f64 / secs } else { 0.0 }
+}
+
+/// Formats a byte count using binary units (e.g. `12.0 MiB`).
+pub fn format_bytes(bytes: u64) -> String {
+    ByteSize(bytes).to_string()
+}
+
+/// Formats a transfer rate (e.g. `3.1 MiB/s`).
+pub fn format_rate(bytes_per_sec: f64) -> String {
+    format!("{}/s", format_bytes(bytes_per_sec as u64))
+}
+
+/// Formats a duration compactly (e.g. `350ms`, `45s`, `2m 05s`, `1h 03m`).
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs == 0 && !duration.is_zero() {
+        format!("{}ms", duration.as_millis())
+    } else if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+/// Formats a transfer with its size, duration and rate
+/// (e.g. `3.2 GiB in 4m 12s (12.9 MiB/s)`).
+pub fn format_transfer(bytes: u64, elapsed: Duration) -> String {
+    format!(
+        "{} in {} ({})",
+        format_bytes(bytes),
+        format_duration(elapsed),
+        format_rate(bytes_per_second(bytes, elapsed))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_bytes_uses_binary_units() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(15 * 1024 * 1024), "15.0 MiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn format_duration_picks_the_two_largest_units() {
+        assert_eq!(format_duration(Duration::from_millis(350)), "350ms");
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m 05s");
+        assert_eq!(format_duration(Duration::from_secs(3780)), "1h 03m");
+    }
+
+    #[test]
+    fn format_transfer_combines_size_duration_and_rate() {
+        assert_eq!(
+            format_transfer(3 * 1024 * 1024 * 1024, Duration::from_secs(256)),
+            "3.0 GiB in 4m 16s (12.0 MiB/s)"
+        );
+        assert_eq!(format_transfer(0, Duration::ZERO), "0 B in 0s (0 B/s)");
+    }
+}
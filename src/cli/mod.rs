@@ -1,7 +1,21 @@
 pub mod commands;
+pub mod confirm;
+pub mod format;
+pub mod output;
+pub mod progress;
 
+use crate::cli::commands::stats::ExportFormat;
+use crate::cli::output::{OutputFormat, Styler};
+use crate::config::{self, UploadOrder};
+use crate::crypto::checksum::ChecksumAlgorithm;
 use crate::error::Result;
+use crate::priority::IoPriority;
+use crate::retention::RetentionMode;
+use crate::size::ByteSize;
+use crate::timezone::LogTimezone;
+use crate::window::{OutsideWindow, TimeWindow};
 use clap::{Args, Parser, Subcommand};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 /// Nested subcommands for the `config` command.
@@ -12,8 +26,14 @@ pub enum ConfigCommand {
     Show,
     /// Open configuration file in the default system editor ($EDITOR)
     Edit,
+    /// Show fields that differ from the default config and unknown keys
+    Diff,
+    /// Check the config for unknown keys and invalid values
+    Validate,
+    /// Print a JSON Schema of the config file for editors and CI
+    Schema,
     /// Update one or more top-level fields in the config
-    Set(ConfigSetArgs),
+    Set(Box<ConfigSetArgs>),
     /// Add an entry to the 'directory_struct' array
     DirAdd(DirectoryAddArgs),
     /// Remove an entry from 'directory_struct' by local_name_prefix
@@ -36,12 +56,117 @@ pub struct ConfigSetArgs {
     /// Force path-style addressing (for MinIO, etc.)
     #[arg(long)]
     pub force_path_style: Option<bool>,
-    /// Size of each upload part in bytes (default: 5MB)
-    #[arg(long)]
-    pub part_size: Option<u64>,
+    /// Size of each upload part, e.g. 15728640, 16MB or 1.5GiB
+    #[arg(long, value_name = "SIZE")]
+    pub part_size: Option<ByteSize>,
     /// Local directory path to scan for files
     #[arg(long)]
     pub local_directory_path: Option<PathBuf>,
+    /// Minimum number of parallel uploads when S3 throttles requests
+    #[arg(long)]
+    pub min_concurrency: Option<usize>,
+    /// Maximum number of parallel uploads
+    #[arg(long)]
+    pub max_concurrency: Option<usize>,
+    /// Order in which uploads start: smallest_first, largest_first or oldest_first
+    #[arg(long, value_name = "ORDER")]
+    pub upload_order: Option<UploadOrder>,
+    /// Upload each run's log and report to the '_logs/' prefix of the bucket
+    #[arg(long)]
+    pub upload_run_log: Option<bool>,
+    /// Minimum number of minutes between successful runs
+    #[arg(long)]
+    pub min_interval: Option<u64>,
+    /// Maximum number of minutes a run may take before remaining uploads are aborted
+    #[arg(long)]
+    pub max_duration: Option<u64>,
+    /// Minutes a run reuses the remote listing of an earlier run
+    #[arg(long)]
+    pub listing_cache_minutes: Option<u64>,
+    /// Local time window uploads are allowed in, e.g. 22:00-06:00
+    #[arg(long, value_name = "HH:MM-HH:MM")]
+    pub upload_window: Option<TimeWindow>,
+    /// What to do when run outside the upload window: refuse or wait
+    #[arg(long, value_name = "refuse|wait")]
+    pub outside_window: Option<OutsideWindow>,
+    /// File holding the S3 access key (e.g. a Docker or Kubernetes secret)
+    #[arg(long, value_name = "PATH")]
+    pub access_key_file: Option<PathBuf>,
+    /// File holding the S3 secret key (e.g. a Docker or Kubernetes secret)
+    #[arg(long, value_name = "PATH")]
+    pub secret_key_file: Option<PathBuf>,
+    /// Experimental: upload content-defined chunks to deduplicate unchanged data
+    #[arg(long)]
+    pub dedup: Option<bool>,
+    /// Create the keys of duplicate files with a server-side copy instead of skipping them
+    #[arg(long)]
+    pub copy_duplicates: Option<bool>,
+    /// Number of threads that hash files (default: one per CPU core)
+    #[arg(long, value_name = "N")]
+    pub hash_threads: Option<usize>,
+    /// Total size of upload parts held in memory at once, e.g. 256MiB
+    #[arg(long, value_name = "SIZE")]
+    pub max_buffer_memory: Option<ByteSize>,
+    /// Read files through io_uring (Linux, built with the io-uring feature)
+    #[arg(long)]
+    pub io_uring: Option<bool>,
+    /// Nice value (0-19) runs lower their CPU priority to
+    #[arg(long, value_name = "0-19")]
+    pub nice: Option<i32>,
+    /// I/O priority of runs on Linux: idle or best-effort[:0-7]
+    #[arg(long, value_name = "CLASS")]
+    pub ionice: Option<IoPriority>,
+    /// Leave out empty (zero-byte) files instead of uploading them
+    #[arg(long)]
+    pub skip_empty: Option<bool>,
+    /// Leave out files larger than this, e.g. 500GiB (S3 allows at most 5TiB)
+    #[arg(long, value_name = "SIZE")]
+    pub max_file_size: Option<ByteSize>,
+    /// Timezone of log timestamps: local, utc or an IANA name like Europe/Berlin
+    #[arg(long, value_name = "TZ")]
+    pub log_timezone: Option<LogTimezone>,
+    /// Write run start, finish and failure events to the Windows Event Log
+    #[arg(long)]
+    pub event_log: Option<bool>,
+    /// ARN of an SNS topic to publish run summaries to
+    #[arg(long, value_name = "ARN")]
+    pub sns_topic_arn: Option<String>,
+    /// URL of a fleet collector to POST run reports to
+    #[arg(long, value_name = "URL")]
+    pub report_url: Option<String>,
+    /// File holding the key run reports are signed with (HMAC-SHA256)
+    #[arg(long, value_name = "PATH")]
+    pub report_key_file: Option<PathBuf>,
+    /// Local port the daemon serves Prometheus metrics on
+    #[arg(long, value_name = "PORT")]
+    pub metrics_port: Option<u16>,
+    /// Address to serve the daemon's gRPC control service on (e.g. 0.0.0.0:50051)
+    #[arg(long, value_name = "ADDR")]
+    pub grpc_listen: Option<SocketAddr>,
+    /// PEM certificate chain of the gRPC control service
+    #[arg(long, value_name = "PATH")]
+    pub grpc_cert_file: Option<PathBuf>,
+    /// PEM private key of the gRPC control service
+    #[arg(long, value_name = "PATH")]
+    pub grpc_key_file: Option<PathBuf>,
+    /// PEM CA certificate that signs the certificates of allowed gRPC clients
+    #[arg(long, value_name = "PATH")]
+    pub grpc_client_ca_file: Option<PathBuf>,
+    /// Object Lock mode of uploaded objects: governance or compliance
+    #[arg(long, value_name = "governance|compliance")]
+    pub object_lock_mode: Option<RetentionMode>,
+    /// Days uploaded objects are retained under the Object Lock mode
+    #[arg(long, value_name = "DAYS")]
+    pub object_lock_days: Option<u32>,
+    /// Refuse every request that would change the bucket
+    #[arg(long)]
+    pub read_only: Option<bool>,
+    /// Combined download rate of restores per second, e.g. 50MiB
+    #[arg(long, value_name = "SIZE")]
+    pub max_download_rate: Option<ByteSize>,
+    /// Days of run history `state gc` keeps (default: 90)
+    #[arg(long, value_name = "DAYS")]
+    pub run_history_days: Option<u32>,
 }
 
 /// Arguments for the 'config directory-add' subcommand.
@@ -50,6 +175,24 @@ pub struct ConfigSetArgs {
 pub struct DirectoryAddArgs {
     pub local_name_prefix: String,
     pub remote_path: String,
+    /// Checksum sent with the uploads of this rule
+    #[arg(long, value_name = "md5|sha256|crc32c")]
+    pub checksum: Option<ChecksumAlgorithm>,
+    /// Upload extended attributes (alternate data streams on Windows) as a sidecar object
+    #[arg(long, default_value_t = false)]
+    pub xattrs: bool,
+    /// Directory this rule scans instead of the top-level local_directory_path
+    #[arg(long)]
+    pub local_directory_path: Option<PathBuf>,
+    /// Levels of subdirectories to scan for this rule (0: only the directory itself)
+    #[arg(long, default_value_t = 0)]
+    pub max_depth: u32,
+    /// Upload files of subdirectories right under remote_path instead of keeping their path
+    #[arg(long, default_value_t = false)]
+    pub flatten: bool,
+    /// WASM filter plugin deciding which files of this rule are uploaded
+    #[arg(long, value_name = "PATH")]
+    pub plugin: Option<PathBuf>,
 }
 
 /// Arguments for the 'config directory-remove' subcommand.
@@ -59,6 +202,232 @@ pub struct DirectoryRemoveArgs {
     pub local_name_prefix: String,
 }
 
+/// Nested subcommands for the `daemon` command.
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum DaemonCommand {
+    /// Run the scheduler in the foreground, listening on the control socket
+    Start,
+    /// Show what the running daemon is doing and when it runs next
+    Status,
+    /// Start a run immediately instead of waiting for the schedule
+    Trigger {
+        /// Run only the named job (all rules when omitted)
+        job: Option<String>,
+    },
+    /// Pause the daemon's uploads
+    Pause,
+    /// Resume the daemon's uploads
+    Resume,
+    /// Cancel the run in progress, aborting its multipart uploads
+    Cancel,
+}
+
+/// Nested subcommands for the `service` command.
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum ServiceCommand {
+    /// Register the daemon as a Windows service that starts with Windows and restarts after failures
+    Install,
+    /// Stop the Windows service and remove it
+    Uninstall,
+    /// Start the installed Windows service
+    Start,
+    /// Run as the Windows service (used by the service control manager)
+    #[command(hide = true)]
+    Run,
+}
+
+/// Nested subcommands for the `hold` command.
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum HoldCommand {
+    /// Place a legal hold on every object under a key prefix
+    Set(HoldArgs),
+    /// Remove the legal hold from every object under a key prefix
+    Clear(HoldArgs),
+}
+
+/// Arguments for the 'hold set' and 'hold clear' subcommands.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct HoldArgs {
+    /// Key prefix of the objects, e.g. database/ or database/db_backup_2025
+    #[arg(value_name = "KEY_PREFIX")]
+    pub prefix: String,
+    /// List the objects without changing their legal hold
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+}
+
+/// Nested subcommands for the `state` command.
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum StateCommand {
+    /// Remove old run history and cached ETags of objects no longer in the bucket
+    Gc,
+}
+
+/// Arguments for the 'login' command.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct LoginArgs {
+    /// Exchange the saved keys for temporary MFA session credentials (STS)
+    #[arg(long, default_value_t = false)]
+    pub mfa: bool,
+    /// ARN of the MFA device; prompted for when omitted
+    #[arg(long, value_name = "ARN", requires = "mfa")]
+    pub mfa_serial: Option<String>,
+    /// Lifetime of the session credentials in seconds (900-129600, default 43200)
+    #[arg(long, value_name = "SECONDS", requires = "mfa")]
+    pub duration: Option<i32>,
+    /// AWS Access Key ID to save without prompting
+    #[arg(long, value_name = "KEY", requires = "secret_key", conflicts_with_all = ["mfa", "from_env"])]
+    pub access_key: Option<String>,
+    /// AWS Secret Access Key to save without prompting (visible in the process list; prefer --from-env)
+    #[arg(long, value_name = "KEY", requires = "access_key")]
+    pub secret_key: Option<String>,
+    /// Save the keys of AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY without prompting
+    #[arg(long, default_value_t = false, conflicts_with = "mfa")]
+    pub from_env: bool,
+}
+
+/// Arguments for the 'run' command.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct RunArgs {
+    /// Suppress all output and write the log to a file instead
+    #[arg(short, long, default_value_t = false)]
+    pub quiet: bool,
+    /// Write per-file messages to the log file but still print the final summary
+    #[arg(long, default_value_t = false, conflicts_with = "quiet")]
+    pub summary_only: bool,
+    /// Write the summary and per-file outcomes as JSON to this path
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+    /// Run only the named job from the config
+    #[arg(long, value_name = "NAME")]
+    pub job: Option<String>,
+    /// Keep uploading the other files after one fails; the run still fails
+    /// at the end
+    #[arg(long, default_value_t = false)]
+    pub keep_going: bool,
+    /// Stop at the first failed file, even if `keep_going` is set in the
+    /// defaults of the config
+    #[arg(long, default_value_t = false, conflicts_with = "keep_going")]
+    pub no_keep_going: bool,
+    /// Upload the files listed in this file (one path per line, `-` for stdin)
+    /// that match the rules, instead of scanning the rule directories
+    #[arg(long, value_name = "PATH")]
+    pub files_from: Option<PathBuf>,
+    /// Container mode (also enabled by PREFIXLOAD_CONTAINER=1): settings from
+    /// PREFIXLOAD_* and credentials from AWS_* environment variables, JSON log
+    /// lines on stdout, nothing written to the home directory
+    #[arg(long, default_value_t = false, conflicts_with_all = ["quiet", "summary_only"])]
+    pub container: bool,
+    /// Set from the global `--read-only` flag.
+    #[arg(skip)]
+    pub read_only: bool,
+    /// Set from the global `--verbose` flag.
+    #[arg(skip)]
+    pub verbose: u8,
+    /// Set from the global `--output` flag.
+    #[arg(skip)]
+    pub output: OutputFormat,
+    /// Set from the global `--show-secrets` flag.
+    #[arg(skip)]
+    pub show_secrets: bool,
+}
+
+/// Arguments for the 'doctor' command.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct DoctorArgs {
+    /// Check only the rules and profile of the named job from the config
+    #[arg(long, value_name = "NAME")]
+    pub job: Option<String>,
+}
+
+/// Arguments for the 'audit' command.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct AuditArgs {
+    /// Check only the objects of the last run of the named job
+    #[arg(long, value_name = "NAME")]
+    pub job: Option<String>,
+}
+
+/// Arguments for the 'stats' command.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct StatsArgs {
+    /// List only the runs of the named job
+    #[arg(long, value_name = "NAME")]
+    pub job: Option<String>,
+    /// Print one row per run as csv or json instead, e.g. `--export csv > runs.csv`
+    #[arg(long, value_name = "FORMAT")]
+    pub export: Option<ExportFormat>,
+}
+
+/// Arguments for the 'etag' command.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct EtagArgs {
+    /// Local file to calculate the ETag of
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+    /// Part size of the multipart upload, e.g. 8MB (default: part_size of the config)
+    #[arg(long, value_name = "SIZE")]
+    pub part_size: Option<ByteSize>,
+    /// Also print the SHA-256 digest of the file
+    #[arg(long, default_value_t = false)]
+    pub sha256: bool,
+}
+
+/// Arguments for the 'head' command.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct HeadArgs {
+    /// Key of the object in the configured bucket, e.g. database/db_backup_2025-06-01.sql
+    #[arg(value_name = "KEY")]
+    pub key: String,
+}
+
+/// Arguments for the 'orphans' command.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct OrphansArgs {
+    /// Check only the rules of the named job
+    #[arg(long, value_name = "NAME")]
+    pub job: Option<String>,
+}
+
+/// Arguments for the 'restore' command.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct RestoreArgs {
+    /// Point in time to restore, e.g. 2024-06-01T03:00 (local time) or an RFC 3339 timestamp
+    #[arg(long, value_name = "TIME")]
+    pub at: String,
+    /// Directory the objects are downloaded to, under their keys
+    #[arg(long, value_name = "DIR")]
+    pub to: PathBuf,
+    /// Restore the last run of the named job instead of the last run of any job
+    #[arg(long, value_name = "NAME")]
+    pub job: Option<String>,
+    /// Restore only files whose name matches, e.g. "db_*" (patterns with a / match the key)
+    #[arg(long, value_name = "PATTERN")]
+    pub include: Vec<String>,
+    /// Leave out files whose name matches (patterns with a / match the key)
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+}
+
+/// Arguments for the 'schedule' command.
+#[derive(Args, Debug, PartialEq, Default)]
+pub struct ScheduleArgs {
+    /// Cron expression or interval such as "every 6h" or "daily at 03:00"
+    pub expression: Option<String>,
+    /// Preview the schedule of the named job from the config
+    #[arg(long, value_name = "NAME", conflicts_with = "expression")]
+    pub job: Option<String>,
+    /// Print a Kubernetes CronJob manifest for the schedule instead of a preview
+    #[arg(long, default_value_t = false)]
+    pub k8s: bool,
+    /// Container image of the CronJob (default: prefixload:<version>)
+    #[arg(long, value_name = "IMAGE", requires = "k8s")]
+    pub image: Option<String>,
+    /// Secret holding AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY (default: prefixload-aws)
+    #[arg(long, value_name = "NAME", requires = "k8s")]
+    pub secret: Option<String>,
+}
+
 /// Top-level application subcommands
 #[derive(Subcommand, Debug, PartialEq)]
 pub enum Commands {
@@ -68,15 +437,51 @@ pub enum Commands {
         #[command(subcommand)]
         command: ConfigCommand,
     },
-    /// Store your S3 credentials securely
-    Login,
-    /// Run the main backup operation (with optional 'quiet' mode)
-    Run {
-        #[arg(short, long, default_value_t = false)]
-        quiet: bool,
+    /// Store your S3 credentials securely (or start an MFA session with --mfa)
+    Login(LoginArgs),
+    /// Run the main backup operation (with optional 'quiet' or 'summary-only' mode)
+    Run(RunArgs),
+    /// Validate a cron expression or interval (e.g. "every 6h") and preview its next runs.
+    /// Without arguments, lists the schedules of all configured jobs.
+    Schedule(ScheduleArgs),
+    /// Pause running uploads (in-flight uploads finish, no new ones start)
+    Pause,
+    /// Resume paused uploads
+    Resume,
+    /// Run jobs on their schedules and control the running scheduler
+    Daemon {
+        #[command(subcommand)]
+        command: DaemonCommand,
+    },
+    /// Run the daemon as a Windows service
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommand,
     },
-    /// Schedule a backup job using a cron expression
-    Schedule { cron: String },
+    /// Check that the credentials have every S3 permission a run needs
+    Doctor(DoctorArgs),
+    /// Place or remove Object Lock legal holds on uploaded objects
+    Hold {
+        #[command(subcommand)]
+        command: HoldCommand,
+    },
+    /// Download the objects of the last run before a point in time (needs upload_run_log)
+    Restore(RestoreArgs),
+    /// Check that the objects of the last run of every job are in the bucket and unchanged
+    Audit(AuditArgs),
+    /// List the run history, or export it with --export csv|json
+    Stats(StatsArgs),
+    /// Maintain the local state file that runs keep between each other
+    State {
+        #[command(subcommand)]
+        command: StateCommand,
+    },
+    /// Print the S3 ETag of a local file, to compare it with the one S3 shows
+    Etag(EtagArgs),
+    /// Print the size, ETag, storage class, encryption and metadata of an object
+    Head(HeadArgs),
+    /// List objects no local file maps to anymore and incomplete multipart uploads
+    Orphans(OrphansArgs),
 }
 
 /// Application entrypoint.
@@ -88,20 +493,82 @@ pub enum Commands {
 pub struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Disable colored output (the NO_COLOR environment variable is honored as well)
+    #[arg(long, global = true, default_value_t = false)]
+    no_color: bool,
+    /// Refuse every request that would change the bucket, such as uploads,
+    /// copies and deletes (same as `read_only: true` in the config)
+    #[arg(long, global = true, default_value_t = false)]
+    read_only: bool,
+    /// Answer yes to the confirmation of destructive commands, such as
+    /// `config dir-rm`, `state gc` and `hold clear` (same as PREFIXLOAD_ASSUME_YES=1)
+    #[arg(short, long, global = true, default_value_t = false)]
+    yes: bool,
+    /// Format of the output of `head` and of errors on stderr: text, or JSON
+    /// (errors as objects with a code, category, message, retryable flag and
+    /// context; implied for errors by container mode); ndjson is JSON that
+    /// also makes `run` print every progress event as a JSON line
+    #[arg(long, global = true, value_name = "text|json|ndjson", default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+    /// Log the totals of the S3 requests of a run per operation (-v), and
+    /// every request with its latency, attempts and bytes (-vv)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Print secrets verbatim: without it, values of settings named like keys,
+    /// tokens and passwords, and the credentials of URLs, are masked in
+    /// `config show`, `config diff`, run logs and run reports
+    #[arg(long, global = true, default_value_t = false)]
+    show_secrets: bool,
 }
 
 impl Cli {
     /// Dispatch the parsed CLI command to the appropriate handler.
     /// Returns a Result with a String (output message or error).
     pub async fn run(self) -> Result<String> {
+        let styler = Styler::new(self.no_color);
+
         match self.command {
-            Commands::Config { command } => commands::config::run(command).await,
-            Commands::Login => commands::login::run().await,
-            Commands::Run { quiet } => commands::run::run(quiet).await,
-            Commands::Schedule { cron } => commands::schedule::run(cron.as_ref()).await,
+            Commands::Config { command } => {
+                commands::config::run(command, styler, self.yes, self.show_secrets).await
+            }
+            Commands::Login(args) => commands::login::run(&args).await,
+            Commands::Run(args) => {
+                let args = RunArgs {
+                    read_only: self.read_only,
+                    verbose: self.verbose,
+                    output: self.output,
+                    show_secrets: self.show_secrets,
+                    ..args
+                };
+                commands::run::run(&args, styler).await
+            }
+            Commands::Schedule(args) => commands::schedule::run(&args).await,
+            Commands::Pause => commands::pause::pause().await,
+            Commands::Resume => commands::pause::resume().await,
+            Commands::Daemon { command } => commands::daemon::run(command, self.read_only).await,
+            Commands::Service { command } => commands::service::run(command, self.read_only).await,
+            Commands::Doctor(args) => commands::doctor::run(&args, self.read_only).await,
+            Commands::Hold { command } => {
+                commands::hold::run(command, self.read_only, self.yes).await
+            }
+            Commands::Restore(args) => commands::restore::run(&args).await,
+            Commands::Audit(args) => commands::audit::run(&args).await,
+            Commands::Stats(args) => commands::stats::run(&args).await,
+            Commands::State { command } => commands::state::run(command, self.yes).await,
+            Commands::Etag(args) => commands::etag::run(&args).await,
+            Commands::Head(args) => commands::head::run(&args, self.output).await,
+            Commands::Orphans(args) => commands::orphans::run(&args).await,
         }
     }
 
+    /// Returns `true` if errors should be written as JSON: with
+    /// `--output json`, and in container mode, whose log lines are JSON too.
+    pub fn json_errors(&self) -> bool {
+        matches!(self.output, OutputFormat::Json | OutputFormat::Ndjson)
+            || config::container_mode()
+            || matches!(&self.command, Commands::Run(args) if args.container)
+    }
+
     /// Returns a reference to the parsed command.
     ///
     /// This method provides access to the command that was parsed from
@@ -0,0 +1,171 @@
+pub use crate::report::Status;
+use std::fmt;
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+const RESET: &str = "\x1b[0m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const DIM: &str = "\x1b[2m";
+
+/// Width of the status column, i.e. the length of the longest status label.
+const STATUS_WIDTH: usize = 8;
+
+/// Color of a status in the first column of the output.
+fn status_color(status: Status) -> &'static str {
+    match status {
+        Status::Uploaded => GREEN,
+        Status::Skipped => DIM,
+        Status::Failed => RED,
+        Status::TooLarge => YELLOW,
+    }
+}
+
+/// Format of the global `--output` flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// Errors are written to stderr as JSON objects.
+    Json,
+    /// Like `Json`, and `run` prints its progress as one JSON object per
+    /// line on stdout.
+    Ndjson,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+        })
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            _ => Err(format!(
+                "invalid output format '{}': use 'text', 'json' or 'ndjson'",
+                s
+            )),
+        }
+    }
+}
+
+/// Renders terminal output, with or without ANSI colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Styler {
+    color: bool,
+}
+
+impl Styler {
+    /// Creates a styler for stdout.
+    ///
+    /// Colors are disabled by `--no-color`, by a non-empty `NO_COLOR`
+    /// environment variable (see <https://no-color.org>) or when stdout is
+    /// not a terminal.
+    pub fn new(no_color: bool) -> Self {
+        let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+
+        Self {
+            color: !no_color && !no_color_env && std::io::stdout().is_terminal(),
+        }
+    }
+
+    /// A styler that never emits colors (used for log files and tests).
+    pub fn plain() -> Self {
+        Self { color: false }
+    }
+
+    /// A styler that always emits colors, regardless of the terminal.
+    #[cfg(test)]
+    pub(crate) fn colored() -> Self {
+        Self { color: true }
+    }
+
+    /// Returns `true` if ANSI colors are emitted.
+    pub fn is_colored(&self) -> bool {
+        self.color
+    }
+
+    /// Renders a status label padded to the width of the status column.
+    pub fn status(&self, status: Status) -> String {
+        let label = format!("{:<width$}", status.label(), width = STATUS_WIDTH);
+
+        if self.color {
+            format!("{}{}{}", status_color(status), label, RESET)
+        } else {
+            label
+        }
+    }
+
+    /// Renders a row: the status column followed by the given cells, each
+    /// left-aligned to its width. The last cell is never padded.
+    pub fn row(&self, status: Status, cells: &[(&str, usize)]) -> String {
+        let mut line = self.status(status);
+
+        for (i, (text, width)) in cells.iter().enumerate() {
+            line.push_str("  ");
+            if i + 1 == cells.len() {
+                line.push_str(text);
+            } else {
+                line.push_str(&format!("{:<width$}", text, width = width));
+            }
+        }
+
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_status_is_padded_and_uncolored() {
+        let styler = Styler::plain();
+
+        assert_eq!(styler.status(Status::Uploaded), "UPLOADED");
+        assert_eq!(styler.status(Status::Skipped), "SKIPPED ");
+        assert_eq!(styler.status(Status::Failed), "FAILED  ");
+        assert_eq!(styler.status(Status::TooLarge), "TOO BIG ");
+    }
+
+    #[test]
+    fn colored_status_wraps_label_in_ansi_codes() {
+        let styler = Styler::colored();
+
+        assert_eq!(styler.status(Status::Uploaded), "\x1b[32mUPLOADED\x1b[0m");
+        assert_eq!(styler.status(Status::Failed), "\x1b[31mFAILED  \x1b[0m");
+    }
+
+    #[test]
+    fn row_aligns_columns() {
+        let styler = Styler::plain();
+
+        let a = styler.row(
+            Status::Uploaded,
+            &[("a/short", 10), ("1 B", 6), ("done", 0)],
+        );
+        let b = styler.row(
+            Status::Skipped,
+            &[("a/longer", 10), ("2.0 KiB", 6), ("x", 0)],
+        );
+
+        assert_eq!(a, "UPLOADED  a/short     1 B     done");
+        assert_eq!(b, "SKIPPED   a/longer    2.0 KiB  x");
+    }
+
+    #[test]
+    fn no_color_flag_disables_colors() {
+        assert!(!Styler::new(true).is_colored());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::cli::format::{bytes_per_second, format_bytes, format_duration, format_rate};
+use crate::events::BackupEvent;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Aggregated transfer counters of a run or restore, used for rate and ETA
+/// reporting.
+pub struct TransferStats {
+    started: Instant,
+    /// Total size of all files.
+    total_bytes: u64,
+    /// Size of the files that were already handled (transferred or skipped).
+    processed_bytes: AtomicU64,
+    /// Size of the files that were actually transferred.
+    transferred_bytes: AtomicU64,
+}
+
+impl TransferStats {
+    pub fn new(total_bytes: u64) -> Self {
+        Self {
+            started: Instant::now(),
+            total_bytes,
+            processed_bytes: AtomicU64::new(0),
+            transferred_bytes: AtomicU64::new(0),
+        }
+    }
+
+    /// Records a finished file and returns a progress line with the overall
+    /// transfer rate and an estimate of the remaining time.
+    pub fn record(&self, bytes: u64, transferred: bool) -> String {
+        if transferred {
+            self.transferred_bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+        let processed = self.processed_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        let remaining = self.total_bytes.saturating_sub(processed);
+
+        let percent = if self.total_bytes == 0 {
+            100.0
+        } else {
+            processed as f64 * 100.0 / self.total_bytes as f64
+        };
+
+        let rate = self.rate();
+        let eta = if remaining == 0 {
+            format_duration(Duration::ZERO)
+        } else if rate > 0.0 {
+            format_duration(Duration::from_secs_f64(remaining as f64 / rate))
+        } else {
+            "unknown".to_string()
+        };
+
+        format!(
+            "Progress: {:.1}% ({} of {}), {}, ETA {}",
+            percent,
+            format_bytes(processed),
+            format_bytes(self.total_bytes),
+            format_rate(rate),
+            eta
+        )
+    }
+
+    /// Size of the files that were transferred so far.
+    pub fn transferred_bytes(&self) -> u64 {
+        self.transferred_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Time since the start of the transfers.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Overall transfer rate in bytes per second since the start of the transfers.
+    fn rate(&self) -> f64 {
+        bytes_per_second(self.transferred_bytes(), self.elapsed())
+    }
+}
+
+/// An event as printed by `--output ndjson`: the event and its fields
+/// after the time and run.
+#[derive(Serialize)]
+struct EventLine<'a> {
+    timestamp: &'a str,
+    /// Left out for `run_started`, which names the run itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run_id: Option<&'a str>,
+    #[serde(flatten)]
+    event: &'a BackupEvent,
+}
+
+/// Renders an event of the run `run_id` as a single line of JSON.
+pub fn ndjson_line(event: &BackupEvent, timestamp: &str, run_id: &str) -> String {
+    let line = EventLine {
+        timestamp,
+        run_id: (!matches!(event, BackupEvent::Started { .. })).then_some(run_id),
+        event,
+    };
+    // Nothing in an event can fail to serialize.
+    serde_json::to_string(&line).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::FailureKind;
+    use std::path::PathBuf;
+
+    #[test]
+    fn record_reports_progress_and_eta() {
+        let stats = TransferStats::new(4096);
+
+        let line = stats.record(1024, false);
+        assert!(line.starts_with("Progress: 25.0% (1.0 KiB of 4.0 KiB)"));
+        assert!(
+            line.ends_with("ETA unknown"),
+            "nothing transferred yet: {}",
+            line
+        );
+
+        let line = stats.record(3072, true);
+        assert!(line.starts_with("Progress: 100.0% (4.0 KiB of 4.0 KiB)"));
+        assert!(line.ends_with("ETA 0s"));
+        assert_eq!(stats.transferred_bytes(), 3072);
+    }
+
+    #[test]
+    fn events_are_single_json_lines_tagged_with_their_kind() {
+        let event = BackupEvent::Matched {
+            path: PathBuf::from("/backups/db.sql"),
+            key: "database/db.sql".to_string(),
+            size: 2048,
+        };
+        assert_eq!(
+            ndjson_line(&event, "2024-06-01T03:00:00+00:00", "run-1"),
+            "{\"timestamp\":\"2024-06-01T03:00:00+00:00\",\"run_id\":\"run-1\",\
+             \"event\":\"file_matched\",\"path\":\"/backups/db.sql\",\
+             \"key\":\"database/db.sql\",\"size\":2048}"
+        );
+
+        let event = BackupEvent::Failed {
+            key: "database/db.sql".to_string(),
+            message: "Access Denied".to_string(),
+            failure: FailureKind::Permanent,
+        };
+        let line = ndjson_line(&event, "2024-06-01T03:00:01+00:00", "run-1");
+        let fields: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(fields["event"], "error");
+        assert_eq!(fields["failure"], "permanent");
+        assert!(!line.contains('\n'));
+
+        let event = BackupEvent::Started {
+            run_id: "run-1".to_string(),
+        };
+        assert_eq!(
+            ndjson_line(&event, "2024-06-01T03:00:00+00:00", "run-1"),
+            "{\"timestamp\":\"2024-06-01T03:00:00+00:00\",\"event\":\"run_started\",\"run_id\":\"run-1\"}"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::clients::{HttpClient, http_client};
+use crate::error::{PrefixloadError, Result};
+use crate::report::RunReport;
+use bytes::Bytes;
+use hmac::{Hmac, Mac};
+use http_body_util::{BodyExt, Full};
+use sha2::Sha256;
+
+/// Header carrying the HMAC-SHA256 signature of the request body.
+pub const SIGNATURE_HEADER: &str = "x-prefixload-signature";
+
+/// Header carrying the hostname of the machine that ran the backup.
+pub const HOST_HEADER: &str = "x-prefixload-host";
+
+/// Header carrying the ID of the run.
+pub const RUN_ID_HEADER: &str = "x-prefixload-run-id";
+
+/// Longest part of an error response quoted in the error message.
+const MAX_ERROR_BODY_LEN: usize = 200;
+
+/// Client of a fleet reporting collector (`report_url`).
+///
+/// Every run POSTs its report as JSON. The body is signed with
+/// HMAC-SHA256 under a key shared with the collector, so the collector can
+/// reject reports that were not sent by one of its hosts.
+#[derive(Debug, Clone)]
+pub struct CollectorClient {
+    http: HttpClient,
+    key: Vec<u8>,
+}
+
+impl CollectorClient {
+    /// Creates a client that signs with `key`.
+    pub fn new(key: impl Into<Vec<u8>>) -> Result<Self> {
+        Ok(Self {
+            http: http_client()?,
+            key: key.into(),
+        })
+    }
+
+    /// POSTs `report` to `url`.
+    pub async fn send(&self, url: &str, report: &RunReport) -> Result<()> {
+        let body = report.to_json()?;
+
+        let request = http::Request::builder()
+            .method("POST")
+            .uri(url)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign(&self.key, body.as_bytes()))
+            .header(HOST_HEADER, &report.hostname)
+            .header(RUN_ID_HEADER, &report.run_id)
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| PrefixloadError::Custom(format!("Invalid report_url {}: {}", url, e)))?;
+
+        let response = self.http.request(request).await.map_err(|e| {
+            PrefixloadError::Custom(format!("Failed to send the run report to {}: {}", url, e))
+        })?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map(|body| body.to_bytes())
+            .unwrap_or_default();
+        let reason: String = String::from_utf8_lossy(&body)
+            .trim()
+            .chars()
+            .take(MAX_ERROR_BODY_LEN)
+            .collect();
+        Err(PrefixloadError::Custom(format!(
+            "The collector at {} rejected the run report: {} {}",
+            url, status, reason
+        )))
+    }
+}
+
+/// Signs `body` with HMAC-SHA256, as `sha256=<hex digest>`.
+pub fn sign(key: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_string_contains, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn report() -> RunReport {
+        RunReport {
+            run_id: "run-1".to_string(),
+            hostname: "db-01".to_string(),
+            job: None,
+            started_at: String::new(),
+            finished_at: String::new(),
+            duration_secs: 1.0,
+            success: true,
+            matched: 0,
+            uploaded: 0,
+            skipped: 0,
+            failed: 0,
+            too_large: 0,
+            max_file_size: 0,
+            uploaded_bytes: 0,
+            error: None,
+            rules: Vec::new(),
+            requests: None,
+            interrupted: Vec::new(),
+            failure: None,
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn sign_produces_hmac_sha256() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn send_posts_the_signed_report() {
+        let server = MockServer::start().await;
+        let report = report();
+        let body = report.to_json().unwrap();
+        Mock::given(method("POST"))
+            .and(path("/reports"))
+            .and(header("content-type", "application/json"))
+            .and(header(SIGNATURE_HEADER, sign(b"secret", body.as_bytes())))
+            .and(header(HOST_HEADER, "db-01"))
+            .and(header(RUN_ID_HEADER, "run-1"))
+            .and(body_string_contains("\"hostname\": \"db-01\""))
+            .respond_with(ResponseTemplate::new(202))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = CollectorClient::new("secret").unwrap();
+        client
+            .send(&format!("{}/reports", server.uri()), &report)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_reports_rejections() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("bad signature"))
+            .mount(&server)
+            .await;
+
+        let err = CollectorClient::new("wrong")
+            .unwrap()
+            .send(&server.uri(), &report())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("401 Unauthorized bad signature"));
+    }
+}
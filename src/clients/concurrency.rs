@@ -0,0 +1,198 @@
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Mutable part of the limiter, guarded by a mutex.
+#[derive(Debug)]
+struct LimiterState {
+    /// Current number of requests allowed in flight.
+    limit: usize,
+    /// Successful operations since the limit was last changed.
+    successes: usize,
+    /// Permits that must be dropped instead of returned to the semaphore
+    /// because the limit was lowered while they were held.
+    debt: usize,
+}
+
+/// An AIMD (additive increase, multiplicative decrease) concurrency limiter,
+/// similar in spirit to the AWS CLI "adaptive" retry mode.
+///
+/// * Every throttling response (e.g. `503 SlowDown`) halves the limit,
+///   but never below `min`.
+/// * After a full window of successes (as many as the current limit)
+///   the limit grows by one, up to `max`.
+#[derive(Debug)]
+pub struct AdaptiveConcurrency {
+    semaphore: Arc<Semaphore>,
+    state: Arc<Mutex<LimiterState>>,
+    min: usize,
+    max: usize,
+}
+
+/// A slot for a single in-flight operation.
+///
+/// Returned to the limiter when dropped, unless the limit was lowered in the
+/// meantime, in which case the slot is retired.
+#[derive(Debug)]
+pub struct ConcurrencyPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    state: Arc<Mutex<LimiterState>>,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(permit) = self.permit.take()
+            && state.debt > 0
+        {
+            state.debt -= 1;
+            permit.forget();
+        }
+    }
+}
+
+impl AdaptiveConcurrency {
+    /// Creates a limiter that starts at `max` in-flight operations.
+    ///
+    /// `min` is raised to at least 1 and `max` to at least `min`, so a
+    /// misconfigured pair never deadlocks the uploader.
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        let max = max.max(min);
+
+        Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            state: Arc::new(Mutex::new(LimiterState {
+                limit: max,
+                successes: 0,
+                debt: 0,
+            })),
+            min,
+            max,
+        }
+    }
+
+    /// Waits until a slot is available.
+    pub async fn acquire(&self) -> ConcurrencyPermit {
+        let permit = Arc::clone(&self.semaphore)
+            .acquire_owned()
+            .await
+            .expect("concurrency semaphore is never closed");
+
+        ConcurrencyPermit {
+            permit: Some(permit),
+            state: Arc::clone(&self.state),
+        }
+    }
+
+    /// Returns the current in-flight limit.
+    pub fn limit(&self) -> usize {
+        self.lock().limit
+    }
+
+    /// Records a successful operation and grows the limit by one after
+    /// a full window of successes.
+    pub fn on_success(&self) {
+        let mut state = self.lock();
+        state.successes += 1;
+
+        if state.successes >= state.limit && state.limit < self.max {
+            state.limit += 1;
+            state.successes = 0;
+
+            if state.debt > 0 {
+                state.debt -= 1;
+            } else {
+                self.semaphore.add_permits(1);
+            }
+        }
+    }
+
+    /// Records a throttling response and halves the limit (bounded by `min`).
+    ///
+    /// Returns the new limit.
+    pub fn on_throttled(&self) -> usize {
+        let mut state = self.lock();
+        let new_limit = (state.limit / 2).max(self.min);
+        let reduce_by = state.limit - new_limit;
+
+        state.limit = new_limit;
+        state.successes = 0;
+
+        // Retire idle permits right away; the rest are retired on release.
+        let forgotten = self.semaphore.forget_permits(reduce_by);
+        state.debt += reduce_by - forgotten;
+
+        new_limit
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LimiterState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_clamps_bounds() {
+        let limiter = AdaptiveConcurrency::new(0, 0);
+        assert_eq!(limiter.limit(), 1);
+
+        let limiter = AdaptiveConcurrency::new(4, 2);
+        assert_eq!(limiter.limit(), 4);
+    }
+
+    #[tokio::test]
+    async fn throttling_halves_limit_down_to_min() {
+        let limiter = AdaptiveConcurrency::new(2, 16);
+
+        assert_eq!(limiter.on_throttled(), 8);
+        assert_eq!(limiter.on_throttled(), 4);
+        assert_eq!(limiter.on_throttled(), 2);
+        assert_eq!(limiter.on_throttled(), 2);
+
+        // Only `limit` permits can be held at the same time.
+        let _a = limiter.acquire().await;
+        let _b = limiter.acquire().await;
+        assert_eq!(limiter.semaphore.available_permits(), 0);
+    }
+
+    #[tokio::test]
+    async fn held_permits_are_retired_on_release() {
+        let limiter = AdaptiveConcurrency::new(1, 4);
+
+        let permits = vec![
+            limiter.acquire().await,
+            limiter.acquire().await,
+            limiter.acquire().await,
+            limiter.acquire().await,
+        ];
+
+        assert_eq!(limiter.on_throttled(), 2);
+        drop(permits);
+
+        assert_eq!(limiter.semaphore.available_permits(), 2);
+    }
+
+    #[test]
+    fn successes_ramp_limit_back_up_to_max() {
+        let limiter = AdaptiveConcurrency::new(1, 3);
+        limiter.on_throttled();
+        limiter.on_throttled();
+        assert_eq!(limiter.limit(), 1);
+
+        limiter.on_success();
+        assert_eq!(limiter.limit(), 2);
+
+        limiter.on_success();
+        limiter.on_success();
+        assert_eq!(limiter.limit(), 3);
+
+        for _ in 0..10 {
+            limiter.on_success();
+        }
+        assert_eq!(limiter.limit(), 3);
+        assert_eq!(limiter.semaphore.available_permits(), 3);
+    }
+}
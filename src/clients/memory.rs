@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Bytes one semaphore permit stands for. Counting in KiB keeps the 5 GiB
+/// S3 part limit within the `u32` permits a single acquire may take.
+const BYTES_PER_PERMIT: u64 = 1024;
+
+/// A limit on the bytes of request bodies held in memory at the same time
+/// (`max_buffer_memory`), shared by all concurrent uploads of a run.
+///
+/// Uploads reserve the size of a part before reading it and wait while the
+/// budget is used up, so a small machine gets backpressure instead of
+/// running out of memory.
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    semaphore: Arc<Semaphore>,
+    permits: u32,
+}
+
+/// Bytes reserved from a `MemoryBudget`, given back when dropped.
+#[derive(Debug)]
+pub struct MemoryReservation {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl MemoryBudget {
+    /// Creates a budget of `bytes`, rounded up to whole KiB.
+    pub fn new(bytes: u64) -> Self {
+        let permits = u32::try_from(permits_for(bytes)).unwrap_or(u32::MAX).max(1);
+
+        Self {
+            semaphore: Arc::new(Semaphore::new(permits as usize)),
+            permits,
+        }
+    }
+
+    /// Waits until `bytes` fit into the budget and reserves them.
+    ///
+    /// A buffer larger than the whole budget waits until it is unused
+    /// instead of never being admitted.
+    pub async fn reserve(&self, bytes: u64) -> MemoryReservation {
+        let permits = u32::try_from(permits_for(bytes))
+            .unwrap_or(u32::MAX)
+            .min(self.permits);
+        let permit = Arc::clone(&self.semaphore)
+            .acquire_many_owned(permits)
+            .await
+            .expect("memory budget semaphore is never closed");
+
+        MemoryReservation { _permit: permit }
+    }
+
+    /// Returns the bytes that can currently be reserved without waiting.
+    pub fn available(&self) -> u64 {
+        self.semaphore.available_permits() as u64 * BYTES_PER_PERMIT
+    }
+}
+
+fn permits_for(bytes: u64) -> u64 {
+    bytes.div_ceil(BYTES_PER_PERMIT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn reserve_waits_until_bytes_are_released() {
+        let budget = MemoryBudget::new(10 * 1024);
+
+        let first = budget.reserve(6 * 1024).await;
+        assert_eq!(budget.available(), 4 * 1024);
+
+        let second = tokio::time::timeout(Duration::from_millis(50), budget.reserve(6 * 1024));
+        assert!(second.await.is_err());
+
+        drop(first);
+        let _second = budget.reserve(6 * 1024).await;
+        assert_eq!(budget.available(), 4 * 1024);
+    }
+
+    #[tokio::test]
+    async fn reserve_admits_buffers_larger_than_the_budget_alone() {
+        let budget = MemoryBudget::new(1024);
+
+        let whole = budget.reserve(1024 * 1024).await;
+        assert_eq!(budget.available(), 0);
+
+        drop(whole);
+        assert_eq!(budget.available(), 1024);
+    }
+}
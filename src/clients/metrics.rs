@@ -0,0 +1,293 @@
+use aws_sdk_s3::config::interceptors::{
+    BeforeSerializationInterceptorContextRef, BeforeTransmitInterceptorContextRef,
+    FinalizerInterceptorContextRef,
+};
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_smithy_runtime_api::box_error::BoxError;
+use aws_smithy_runtime_api::client::orchestrator::Metadata;
+use aws_smithy_runtime_api::http::Headers;
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A request of an S3 client, from the start of its first attempt to its
+/// final response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallRecord {
+    /// Name of the S3 operation, e.g. `PutObject`.
+    pub operation: String,
+    pub latency: Duration,
+    /// Attempts that were sent; more than one if the request was retried.
+    pub attempts: u32,
+    /// Bytes of the request bodies of all attempts, as sent: chunked
+    /// bodies include their framing and checksum trailers.
+    pub bytes_sent: u64,
+    /// Bytes of the body of the final response.
+    pub bytes_received: u64,
+    /// HTTP status of the final response; `None` if none was received.
+    pub status: Option<u16>,
+}
+
+/// Totals of the requests of a run, as listed in its JSON report.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestStats {
+    pub calls: usize,
+    /// Attempts beyond the first of each request.
+    pub retries: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub p50_latency_ms: u64,
+    pub p95_latency_ms: u64,
+    pub max_latency_ms: u64,
+    /// Totals per S3 operation.
+    pub operations: BTreeMap<String, OperationStats>,
+}
+
+/// Totals of the requests of a single S3 operation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OperationStats {
+    pub calls: usize,
+    pub retries: u64,
+    pub p95_latency_ms: u64,
+}
+
+impl RequestStats {
+    /// Adds up `calls`.
+    pub fn new(calls: &[CallRecord]) -> Self {
+        let mut by_operation: BTreeMap<String, Vec<&CallRecord>> = BTreeMap::new();
+        for call in calls {
+            by_operation
+                .entry(call.operation.clone())
+                .or_default()
+                .push(call);
+        }
+        let all: Vec<&CallRecord> = calls.iter().collect();
+
+        Self {
+            calls: calls.len(),
+            retries: retries(&all),
+            bytes_sent: calls.iter().map(|call| call.bytes_sent).sum(),
+            bytes_received: calls.iter().map(|call| call.bytes_received).sum(),
+            p50_latency_ms: latency_percentile(&all, 50),
+            p95_latency_ms: latency_percentile(&all, 95),
+            max_latency_ms: latency_percentile(&all, 100),
+            operations: by_operation
+                .into_iter()
+                .map(|(operation, calls)| {
+                    let stats = OperationStats {
+                        calls: calls.len(),
+                        retries: retries(&calls),
+                        p95_latency_ms: latency_percentile(&calls, 95),
+                    };
+                    (operation, stats)
+                })
+                .collect(),
+        }
+    }
+}
+
+fn retries(calls: &[&CallRecord]) -> u64 {
+    calls
+        .iter()
+        .map(|call| u64::from(call.attempts.saturating_sub(1)))
+        .sum()
+}
+
+/// Returns the latency `percent` of `calls` took at most, in milliseconds
+/// (nearest rank), or 0 without calls.
+fn latency_percentile(calls: &[&CallRecord], percent: usize) -> u64 {
+    let mut latencies: Vec<Duration> = calls.iter().map(|call| call.latency).collect();
+    latencies.sort_unstable();
+    let rank = (latencies.len() * percent).div_ceil(100).max(1);
+    latencies
+        .get(rank - 1)
+        .map_or(0, |latency| latency.as_millis() as u64)
+}
+
+/// Function called with every finished request.
+type CallListener = Box<dyn Fn(&CallRecord) + Send + Sync>;
+
+#[derive(Default)]
+struct MetricsState {
+    calls: Vec<CallRecord>,
+    listener: Option<CallListener>,
+}
+
+/// Records the latency, attempts and bytes of every request of an S3
+/// client. Clones share the records.
+#[derive(Clone, Default)]
+pub struct RequestMetrics {
+    state: Arc<Mutex<MetricsState>>,
+}
+
+impl fmt::Debug for RequestMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestMetrics")
+            .field("calls", &self.lock().calls.len())
+            .finish()
+    }
+}
+
+impl RequestMetrics {
+    fn lock(&self) -> std::sync::MutexGuard<'_, MetricsState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the interceptor that records the requests of an SDK client.
+    pub fn interceptor(&self) -> impl Intercept + 'static {
+        MetricsInterceptor {
+            metrics: self.clone(),
+        }
+    }
+
+    /// Calls `listener` with every request that finishes from now on.
+    pub fn on_call(&self, listener: impl Fn(&CallRecord) + Send + Sync + 'static) {
+        self.lock().listener = Some(Box::new(listener));
+    }
+
+    fn record(&self, call: CallRecord) {
+        let mut state = self.lock();
+        if let Some(listener) = &state.listener {
+            listener(&call);
+        }
+        state.calls.push(call);
+    }
+
+    /// Returns the totals of the requests recorded so far.
+    pub fn stats(&self) -> RequestStats {
+        RequestStats::new(&self.lock().calls)
+    }
+}
+
+/// Start, attempts and bytes sent of the request in flight, kept in its
+/// config bag.
+#[derive(Debug, Clone)]
+struct CallState {
+    started: Instant,
+    attempts: u32,
+    bytes_sent: u64,
+}
+
+impl Storable for CallState {
+    type Storer = StoreReplace<Self>;
+}
+
+#[derive(Debug)]
+struct MetricsInterceptor {
+    metrics: RequestMetrics,
+}
+
+/// Returns the `Content-Length` of `headers`, 0 if there is none.
+fn content_length(headers: &Headers) -> u64 {
+    headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0)
+}
+
+impl Intercept for MetricsInterceptor {
+    fn name(&self) -> &'static str {
+        "RequestMetrics"
+    }
+
+    fn read_before_execution(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        cfg.interceptor_state().store_put(CallState {
+            started: Instant::now(),
+            attempts: 0,
+            bytes_sent: 0,
+        });
+        Ok(())
+    }
+
+    fn read_before_transmit(
+        &self,
+        context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if let Some(state) = cfg.load::<CallState>().cloned() {
+            cfg.interceptor_state().store_put(CallState {
+                attempts: state.attempts + 1,
+                bytes_sent: state.bytes_sent + content_length(context.request().headers()),
+                ..state
+            });
+        }
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some(state) = cfg.load::<CallState>() else {
+            return Ok(());
+        };
+        let response = context.response();
+        self.metrics.record(CallRecord {
+            operation: cfg.load::<Metadata>().map_or_else(
+                || "Unknown".to_string(),
+                |metadata| metadata.name().to_string(),
+            ),
+            latency: state.started.elapsed(),
+            attempts: state.attempts,
+            bytes_sent: state.bytes_sent,
+            bytes_received: response.map_or(0, |response| content_length(response.headers())),
+            status: response.map(|response| response.status().as_u16()),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(operation: &str, latency_ms: u64, attempts: u32) -> CallRecord {
+        CallRecord {
+            operation: operation.to_string(),
+            latency: Duration::from_millis(latency_ms),
+            attempts,
+            bytes_sent: 10,
+            bytes_received: 5,
+            status: Some(200),
+        }
+    }
+
+    #[test]
+    fn stats_add_up_calls_per_operation() {
+        let mut calls: Vec<CallRecord> = (1..=19).map(|ms| call("HeadObject", ms, 1)).collect();
+        calls.push(call("PutObject", 100, 3));
+
+        let stats = RequestStats::new(&calls);
+        assert_eq!(stats.calls, 20);
+        assert_eq!(stats.retries, 2);
+        assert_eq!(stats.bytes_sent, 200);
+        assert_eq!(stats.bytes_received, 100);
+        assert_eq!(stats.p50_latency_ms, 10);
+        assert_eq!(stats.p95_latency_ms, 19);
+        assert_eq!(stats.max_latency_ms, 100);
+        assert_eq!(
+            stats.operations["PutObject"],
+            OperationStats {
+                calls: 1,
+                retries: 2,
+                p95_latency_ms: 100,
+            }
+        );
+        assert_eq!(stats.operations["HeadObject"].p95_latency_ms, 19);
+    }
+
+    #[test]
+    fn stats_of_no_calls_are_zero() {
+        assert_eq!(RequestStats::new(&[]), RequestStats::default());
+    }
+}
@@ -1 +1,33 @@
+pub mod collector;
+pub mod concurrency;
+pub mod memory;
+pub mod metrics;
+pub mod rate;
 pub mod s3;
+pub mod sns;
+pub mod sts;
+
+use crate::error::{PrefixloadError, Result};
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+
+/// HTTP(S) client of the requests that do not go through an AWS SDK.
+pub type HttpClient = Client<HttpsConnector<HttpConnector>, Full<Bytes>>;
+
+/// Creates an `HttpClient` that trusts the native root certificates.
+pub fn http_client() -> Result<HttpClient> {
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .map_err(|e| {
+            PrefixloadError::Custom(format!("Failed to load the TLS root certificates: {}", e))
+        })?
+        .https_or_http()
+        .enable_http1()
+        .build();
+
+    Ok(Client::builder(TokioExecutor::new()).build(connector))
+}
@@ -0,0 +1,62 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A limit on the combined transfer rate of all concurrent downloads of a
+/// client (`max_download_rate`).
+///
+/// Every transferred chunk books the time it takes at the limit on a clock
+/// shared by all downloads, and the download waits until that time has
+/// passed before reading on. Idle time is not saved up for later bursts.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    bytes_per_second: u64,
+    /// When the bytes booked so far have been transferred at the limit.
+    booked_until: Arc<Mutex<Instant>>,
+}
+
+impl RateLimit {
+    /// Creates a limit of `bytes_per_second` (at least 1).
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1),
+            booked_until: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Books `bytes` and waits until they fit into the limit.
+    pub async fn consume(&self, bytes: u64) {
+        let until = {
+            let mut booked_until = self.booked_until.lock().unwrap_or_else(|e| e.into_inner());
+            let start = (*booked_until).max(Instant::now());
+            *booked_until =
+                start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+            *booked_until
+        };
+        tokio::time::sleep_until(until).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn consume_paces_all_transfers_together() {
+        let limit = RateLimit::new(1000);
+        let started = Instant::now();
+
+        limit.consume(500).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(500));
+
+        // Two concurrent transfers share the limit.
+        tokio::join!(limit.consume(1000), limit.consume(1000));
+        assert_eq!(started.elapsed(), Duration::from_millis(2500));
+
+        // Idle time is not saved up.
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        let resumed = Instant::now();
+        limit.consume(1000).await;
+        assert_eq!(resumed.elapsed(), Duration::from_secs(1));
+    }
+}
@@ -1,18 +1,60 @@
+use crate::cancel::CancelToken;
+use crate::clients::memory::{MemoryBudget, MemoryReservation};
+use crate::clients::metrics::RequestMetrics;
+use crate::clients::rate::RateLimit;
+use crate::crypto::checksum::{ChecksumAlgorithm, content_md5};
 use crate::error::{PrefixloadError, Result};
+use crate::retention::Retention;
+use crate::uring::ReadMode;
 use aws_config::profile::ProfileFileCredentialsProvider;
 use aws_credential_types::provider::ProvideCredentials;
 use aws_sdk_s3 as s3;
 use aws_sdk_s3::config::Builder as S3ConfigBuilder;
 use aws_sdk_s3::config::Credentials;
-use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
 use aws_sdk_s3::operation::head_object::HeadObjectError;
-use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::primitives::DateTime as S3DateTime;
+use aws_sdk_s3::primitives::{ByteStream, Length};
+use aws_sdk_s3::types::{
+    CompletedMultipartUpload, CompletedPart, MetadataDirective, ObjectLockLegalHold,
+    ObjectLockLegalHoldStatus, ObjectLockMode,
+};
+use aws_types::app_name::AppName;
 use aws_types::region::Region;
-use std::path::Path;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::io::AsyncWriteExt;
+
+/// Suffix of the temporary file a download is written to.
+const PARTIAL_DOWNLOAD_SUFFIX: &str = ".prefixload-download";
+
+/// Object metadata key (`x-amz-meta-prefixload-run-id`) holding the run ID.
+pub const RUN_ID_METADATA_KEY: &str = "prefixload-run-id";
 
 #[derive(Debug, Clone)]
 pub struct S3Client {
-    inner: s3::Client,
+    /// Rebuilt with the bucket's region when S3 redirects a request.
+    inner: Arc<RwLock<s3::Client>>,
+    /// Credentials the client was created with.
+    credentials: Credentials,
+    run_id: Option<String>,
+    /// Bytes of part bodies that may be in flight at the same time.
+    buffer_budget: Option<MemoryBudget>,
+    /// How the parts of multipart uploads are read.
+    read_mode: ReadMode,
+    /// Object Lock retention set on every uploaded object.
+    retention: Option<Retention>,
+    /// Refuses every request that would change the bucket.
+    read_only: bool,
+    /// Combined rate limit of all downloads.
+    download_rate: Option<RateLimit>,
+    /// Latency, attempts and bytes of every request.
+    metrics: RequestMetrics,
+    /// Stops uploads midway, aborting their multipart uploads.
+    cancel: Option<CancelToken>,
 }
 
 /// Client creation parameters.
@@ -22,13 +64,41 @@ pub struct S3Client {
 /// * if `endpoint` is not specified, the standard one for the selected region is used.
 /// * 'force_path_style' is useful for MinIO, Ceph RGW, Wasabi and other
 /// S3-compatible services that require path-style URLs.
+/// * `run_id` tags requests (User-Agent) and uploaded objects (metadata) so
+///   they can be traced back to a single `run` in the S3 server logs.
+/// * `access_key_file` / `secret_key_file` take precedence over the keys:
+///   the files are read when the client is created (Docker/Kubernetes secrets).
+/// * `session_token` is set for temporary credentials (STS, `login --mfa`).
+/// * `max_buffer_memory` bounds the bytes of upload parts held in memory
+///   across all concurrent uploads of the client; unbounded when `None`.
+/// * `read_mode` selects how parts of multipart uploads are read; with
+///   `ReadMode::IoUring` they are read into memory through io_uring.
+/// * `retention` sets Object Lock retention on every object the client
+///   uploads or copies.
+/// * `read_only` makes every request that would change the bucket (uploads,
+///   copies, multipart uploads, deletes, legal holds) fail before it is sent.
+/// * `max_download_rate` bounds the combined rate of all downloads of the
+///   client in bytes per second; unbounded when `None`. Uploads are not
+///   limited by it.
+/// * `cancel` stops the uploads of the client once it is cancelled; the
+///   multipart uploads in flight are aborted, so no parts are left behind.
 #[derive(Debug, Clone)]
 pub struct S3ClientOptions {
     pub access_key: String,
     pub secret_key: String,
+    pub session_token: Option<String>,
+    pub access_key_file: Option<PathBuf>,
+    pub secret_key_file: Option<PathBuf>,
     pub region: Option<String>,
     pub endpoint: Option<String>,
     pub force_path_style: bool,
+    pub run_id: Option<String>,
+    pub max_buffer_memory: Option<u64>,
+    pub read_mode: ReadMode,
+    pub retention: Option<Retention>,
+    pub read_only: bool,
+    pub max_download_rate: Option<u64>,
+    pub cancel: Option<CancelToken>,
 }
 
 impl Default for S3ClientOptions {
@@ -36,9 +106,19 @@ impl Default for S3ClientOptions {
         Self {
             access_key: "".to_string(),
             secret_key: "".to_string(),
+            session_token: None,
+            access_key_file: None,
+            secret_key_file: None,
             region: None,
             endpoint: None,
             force_path_style: false,
+            run_id: None,
+            max_buffer_memory: None,
+            read_mode: ReadMode::Standard,
+            retention: None,
+            read_only: false,
+            max_download_rate: None,
+            cancel: None,
         }
     }
 }
@@ -57,11 +137,22 @@ impl S3ClientOptions {
     /// A `Result` with `S3ClientOptions` containing the access key and secret key,
     /// or a `PrefixloadError` on failure.
     pub async fn from_aws_config() -> Result<Self> {
-        let provider = ProfileFileCredentialsProvider::builder().build();
+        Self::from_aws_profile(None).await
+    }
+
+    /// Same as [`S3ClientOptions::from_aws_config`], but reads the named
+    /// profile instead of `default` when `profile` is set.
+    pub async fn from_aws_profile(profile: Option<&str>) -> Result<Self> {
+        let mut builder = ProfileFileCredentialsProvider::builder();
+        if let Some(profile) = profile {
+            builder = builder.profile_name(profile);
+        }
+        let provider = builder.build();
 
         let credentials = provider.provide_credentials().await.map_err(|err| {
             PrefixloadError::Custom(format!(
-                "Failed to load credentials from AWS profile: {}",
+                "Failed to load credentials from AWS profile{}: {}",
+                profile.map(|p| format!(" '{}'", p)).unwrap_or_default(),
                 err
             ))
         })?;
@@ -69,10 +160,31 @@ impl S3ClientOptions {
         Ok(Self {
             access_key: credentials.access_key_id().to_string(),
             secret_key: credentials.secret_access_key().to_string(),
+            session_token: credentials.session_token().map(String::from),
             ..Self::default()
         })
     }
 
+    /// Reads the access key and secret key from the `AWS_ACCESS_KEY_ID`
+    /// and `AWS_SECRET_ACCESS_KEY` environment variables (container mode).
+    ///
+    /// Following the Docker secrets convention, `AWS_ACCESS_KEY_ID_FILE` and
+    /// `AWS_SECRET_ACCESS_KEY_FILE` name files holding the keys instead.
+    pub fn from_env() -> Result<Self> {
+        let mut opts = Self::default();
+
+        match std::env::var_os("AWS_ACCESS_KEY_ID_FILE") {
+            Some(path) => opts.access_key_file = Some(PathBuf::from(path)),
+            None => opts.access_key = env_var("AWS_ACCESS_KEY_ID")?,
+        }
+        match std::env::var_os("AWS_SECRET_ACCESS_KEY_FILE") {
+            Some(path) => opts.secret_key_file = Some(PathBuf::from(path)),
+            None => opts.secret_key = env_var("AWS_SECRET_ACCESS_KEY")?,
+        }
+
+        Ok(opts)
+    }
+
     /// Sets the access key.
     pub fn with_access_key<S: Into<String>>(mut self, access_key: S) -> Self {
         self.access_key = access_key.into();
@@ -85,6 +197,24 @@ impl S3ClientOptions {
         self
     }
 
+    /// Sets the session token of temporary credentials.
+    pub fn with_session_token<S: Into<String>>(mut self, session_token: S) -> Self {
+        self.session_token = Some(session_token.into());
+        self
+    }
+
+    /// Reads the access key from `path` when the client is created.
+    pub fn with_access_key_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.access_key_file = Some(path.into());
+        self
+    }
+
+    /// Reads the secret key from `path` when the client is created.
+    pub fn with_secret_key_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.secret_key_file = Some(path.into());
+        self
+    }
+
     /// Sets the AWS region.
     pub fn with_region<S: Into<String>>(mut self, region: S) -> Self {
         self.region = Some(region.into());
@@ -104,21 +234,382 @@ impl S3ClientOptions {
         self.force_path_style = force_path_style;
         self
     }
+
+    /// Sets the run ID used to tag requests and uploaded objects.
+    pub fn with_run_id<S: Into<String>>(mut self, run_id: S) -> Self {
+        self.run_id = Some(run_id.into());
+        self
+    }
+
+    /// Bounds the bytes of upload parts held in memory at the same time.
+    pub fn with_max_buffer_memory(mut self, bytes: u64) -> Self {
+        self.max_buffer_memory = Some(bytes);
+        self
+    }
+
+    /// Sets how the parts of multipart uploads are read.
+    pub fn with_read_mode(mut self, read_mode: ReadMode) -> Self {
+        self.read_mode = read_mode;
+        self
+    }
+
+    /// Sets the Object Lock retention of uploaded objects.
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Makes the client refuse every request that would change the bucket.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Limits the combined rate of all downloads to `bytes_per_second`.
+    pub fn with_max_download_rate(mut self, bytes_per_second: u64) -> Self {
+        self.max_download_rate = Some(bytes_per_second);
+        self
+    }
+
+    /// Stops the uploads of the client once `cancel` is cancelled.
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+}
+
+/// Reads a required environment variable.
+fn env_var(name: &str) -> Result<String> {
+    std::env::var(name)
+        .map_err(|_| PrefixloadError::Custom(format!("Neither {} nor {}_FILE is set.", name, name)))
+}
+
+/// Reads a key from a secret file, ignoring the trailing newline.
+pub(crate) fn read_key_file(path: &Path, option: &str) -> Result<String> {
+    let key = std::fs::read_to_string(path).map_err(|err| {
+        PrefixloadError::Custom(format!(
+            "Failed to read {} {}: {}",
+            option,
+            path.display(),
+            err
+        ))
+    })?;
+    let key = key.trim();
+
+    if key.is_empty() {
+        return Err(PrefixloadError::Custom(format!(
+            "{} {} is empty.",
+            option,
+            path.display()
+        )));
+    }
+
+    Ok(key.to_string())
+}
+
+/// Per-file settings of `S3Client::upload_file` and `S3Client::upload_file_parts`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UploadOptions {
+    /// The checksum to send with the object; `None` keeps the SDK default.
+    pub checksum: Option<ChecksumAlgorithm>,
+    /// User metadata stored with the object in addition to the run ID.
+    pub metadata: HashMap<String, String>,
+    /// Key of the object that parts marked `copy` are copied from; the
+    /// uploaded object itself when `None`.
+    pub copy_from: Option<String>,
+}
+
+/// One part of a multipart upload, see `S3Client::upload_file_parts`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartPlan {
+    /// Hex MD5 of the local part.
+    pub md5: String,
+    /// Copy the part from the current version of the object instead of uploading it.
+    pub copy: bool,
+}
+
+/// Result of `S3Client::upload_file_hashing`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashedUpload {
+    /// ETag of the new object.
+    pub etag: String,
+    /// MD5 digest of every part of the file.
+    pub part_md5s: Vec<md5::Digest>,
+    /// Bytes of the parts that were copied instead of uploaded.
+    pub copied_bytes: u64,
+}
+
+/// What `HEAD` reports about an object, see `S3Client::head_object`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct ObjectHead {
+    /// The ETag without quotes.
+    pub etag: Option<String>,
+    pub size: u64,
+    pub last_modified: Option<DateTime<Utc>>,
+    /// `None` for `STANDARD`, which S3 does not report.
+    pub storage_class: Option<String>,
+    /// `AES256`, `aws:kms` or `aws:kms:dsse`.
+    pub server_side_encryption: Option<String>,
+    pub sse_kms_key_id: Option<String>,
+    pub content_type: Option<String>,
+    /// `None` for objects of unversioned buckets.
+    pub version_id: Option<String>,
+    /// `GOVERNANCE` or `COMPLIANCE`, see `Retention`.
+    pub object_lock_mode: Option<String>,
+    pub object_lock_retain_until: Option<DateTime<Utc>>,
+    pub legal_hold: bool,
+    /// User metadata (`x-amz-meta-*` headers) by name.
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// An object as `ListObjectsV2` reports it, see `S3Client::list_objects`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListedObject {
+    pub key: String,
+    /// The ETag without quotes.
+    pub etag: Option<String>,
+    pub size: u64,
+}
+
+/// A version of an object, see `S3Client::list_versions`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectVersion {
+    /// `null` for objects written while the bucket was unversioned.
+    pub version_id: String,
+    pub last_modified: DateTime<Utc>,
+    /// `true` for a delete marker, which hides the older versions.
+    pub delete_marker: bool,
+}
+
+/// A multipart upload that was started but neither completed nor aborted,
+/// see `S3Client::list_multipart_uploads`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncompleteUpload {
+    pub key: String,
+    pub upload_id: String,
+    pub initiated: Option<DateTime<Utc>>,
+}
+
+/// A started multipart upload, see `S3Client::create_multipart_upload`.
+struct MultipartUpload<'a> {
+    client: s3::Client,
+    bucket: &'a str,
+    object_name: &'a str,
+    upload_id: String,
+    algorithm: Option<aws_sdk_s3::types::ChecksumAlgorithm>,
+}
+
+/// ETag and checksums S3 returned for an uploaded or copied part.
+struct UploadedPart {
+    etag: Option<String>,
+    checksum_sha256: Option<String>,
+    checksum_crc32_c: Option<String>,
+}
+
+impl UploadedPart {
+    /// Checks the ETag of part `number` against its local MD5.
+    fn verify(self, number: i32, md5: &str, path: &Path) -> Result<CompletedPart> {
+        let etag = self.etag.unwrap_or_default();
+        if etag.trim_matches('"') != md5 {
+            return Err(PrefixloadError::Custom(format!(
+                "Part {} of {} does not match its local MD5 (ETag {}, expected {}).",
+                number,
+                path.display(),
+                etag,
+                md5
+            )));
+        }
+
+        Ok(CompletedPart::builder()
+            .part_number(number)
+            .e_tag(etag)
+            .set_checksum_sha256(self.checksum_sha256)
+            .set_checksum_crc32_c(self.checksum_crc32_c)
+            .build())
+    }
+}
+
+impl MultipartUpload<'_> {
+    /// Copies `length` bytes at `offset` of `source` as part `number`.
+    async fn copy_part(
+        &self,
+        number: i32,
+        source: &str,
+        offset: u64,
+        length: u64,
+    ) -> Result<UploadedPart> {
+        let output = self
+            .client
+            .upload_part_copy()
+            .bucket(self.bucket)
+            .key(self.object_name)
+            .upload_id(&self.upload_id)
+            .part_number(number)
+            .copy_source(source)
+            .copy_source_range(format!("bytes={}-{}", offset, offset + length - 1))
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+        let result = output.copy_part_result();
+
+        Ok(UploadedPart {
+            etag: result.and_then(|r| r.e_tag()).map(str::to_string),
+            checksum_sha256: result.and_then(|r| r.checksum_sha256()).map(str::to_string),
+            checksum_crc32_c: result
+                .and_then(|r| r.checksum_crc32_c())
+                .map(str::to_string),
+        })
+    }
+
+    /// Uploads `body` as part `number`.
+    async fn send_part(&self, number: i32, body: ByteStream) -> Result<UploadedPart> {
+        let output = self
+            .client
+            .upload_part()
+            .bucket(self.bucket)
+            .key(self.object_name)
+            .upload_id(&self.upload_id)
+            .part_number(number)
+            .set_checksum_algorithm(self.algorithm.clone())
+            .body(body)
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+
+        Ok(UploadedPart {
+            etag: output.e_tag().map(str::to_string),
+            checksum_sha256: output.checksum_sha256().map(str::to_string),
+            checksum_crc32_c: output.checksum_crc32_c().map(str::to_string),
+        })
+    }
+
+    /// Completes the upload and returns the ETag of the new object.
+    async fn complete(&self, parts: Vec<CompletedPart>) -> Result<String> {
+        let output = self
+            .client
+            .complete_multipart_upload()
+            .bucket(self.bucket)
+            .key(self.object_name)
+            .upload_id(&self.upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+
+        Ok(output
+            .e_tag()
+            .unwrap_or_default()
+            .trim_matches('"')
+            .to_string())
+    }
+
+    /// Aborts the upload after a failed part.
+    async fn abort(&self) {
+        // Best effort: an upload that is never completed or aborted keeps
+        // its parts (and their storage costs) until a lifecycle rule removes them.
+        let _ = self
+            .client
+            .abort_multipart_upload()
+            .bucket(self.bucket)
+            .key(self.object_name)
+            .upload_id(&self.upload_id)
+            .send()
+            .await;
+    }
+}
+
+/// Returns the `x-amz-copy-source` value for an object: `<bucket>/<key>`,
+/// percent-encoded except for the unreserved characters and `/`.
+fn copy_source(bucket: &str, key: &str) -> String {
+    let mut source = String::with_capacity(bucket.len() + key.len() + 1);
+    for byte in format!("{}/{}", bucket, key).bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                source.push(byte as char)
+            }
+            _ => source.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    source
+}
+
+/// Whether the credentials may perform an operation, as probed by `doctor`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Access {
+    Allowed,
+    /// S3 refused the request with 401/403; holds its error code.
+    Denied(String),
+}
+
+/// Maps the result of a probe request to `Access`. Errors other than
+/// 401/403 (network, unknown bucket, ...) are returned as they are.
+fn access<T, E>(result: std::result::Result<T, SdkError<E>>) -> Result<Access>
+where
+    E: ProvideErrorMetadata,
+    aws_sdk_s3::Error: From<SdkError<E>>,
+{
+    let Err(err) = result else {
+        return Ok(Access::Allowed);
+    };
+    // HEAD responses have no body, so only the status tells a denial apart.
+    if let Some(response) = err.raw_response()
+        && matches!(response.status().as_u16(), 401 | 403)
+    {
+        let code = err.code().unwrap_or("AccessDenied").to_string();
+        return Ok(Access::Denied(code));
+    }
+
+    Err(aws_sdk_s3::Error::from(err).into())
+}
+
+/// Returns the region S3 expects when a request was sent to the wrong one:
+/// a `301 PermanentRedirect` or a `400 AuthorizationHeaderMalformed` names it
+/// in the `x-amz-bucket-region` header or in the `<Region>` element of the body.
+fn redirect_region<E: ProvideErrorMetadata>(err: &SdkError<E>) -> Option<String> {
+    let response = err.raw_response()?;
+    let status = response.status().as_u16();
+    let wrong_region =
+        status == 301 || (status == 400 && err.code() == Some("AuthorizationHeaderMalformed"));
+    if !wrong_region {
+        return None;
+    }
+
+    if let Some(region) = response.headers().get("x-amz-bucket-region") {
+        return Some(region.to_string());
+    }
+
+    let body = std::str::from_utf8(response.body().bytes()?).ok()?;
+    let start = body.find("<Region>")? + "<Region>".len();
+    let end = start + body[start..].find("</Region>")?;
+    Some(body[start..end].trim().to_string()).filter(|region| !region.is_empty())
 }
 
 impl S3Client {
     /// Creates a new client capable of working with both AWS
     /// and any S3-compatible service.
     pub async fn new(opts: S3ClientOptions) -> Result<Self> {
+        let access_key = match &opts.access_key_file {
+            Some(path) => read_key_file(path, "access_key_file")?,
+            None => opts.access_key,
+        };
+        let secret_key = match &opts.secret_key_file {
+            Some(path) => read_key_file(path, "secret_key_file")?,
+            None => opts.secret_key,
+        };
+
         let credentials = Credentials::new(
-            opts.access_key,
-            opts.secret_key,
-            None,            // session-token
+            access_key,
+            secret_key,
+            opts.session_token,
             None,            // expires-at
             "user-supplied", // provider-name
         );
 
-        let cred_provider = s3::config::SharedCredentialsProvider::new(credentials);
+        let cred_provider = s3::config::SharedCredentialsProvider::new(credentials.clone());
 
         let mut cfg_loader = aws_config::defaults(aws_config::BehaviorVersion::latest())
             .credentials_provider(cred_provider);
@@ -127,6 +618,13 @@ impl S3Client {
 
         cfg_loader = cfg_loader.region(Region::new(region));
 
+        // The app name ends up in the User-Agent recorded by S3 server access logs.
+        if let Some(run_id) = &opts.run_id
+            && let Ok(app_name) = AppName::new(format!("prefixload-{}", run_id))
+        {
+            cfg_loader = cfg_loader.app_name(app_name);
+        }
+
         let shared_cfg = cfg_loader.load().await;
 
         let mut s3_cfg = S3ConfigBuilder::from(&shared_cfg);
@@ -138,9 +636,130 @@ impl S3Client {
         // Always apply force_path_style from options, regardless of endpoint
         s3_cfg = s3_cfg.force_path_style(opts.force_path_style);
 
+        let metrics = RequestMetrics::default();
+        s3_cfg = s3_cfg.interceptor(metrics.interceptor());
+
         let client = s3::Client::from_conf(s3_cfg.build());
 
-        Ok(Self { inner: client })
+        Ok(Self {
+            inner: Arc::new(RwLock::new(client)),
+            credentials,
+            run_id: opts.run_id,
+            buffer_budget: opts.max_buffer_memory.map(MemoryBudget::new),
+            read_mode: opts.read_mode,
+            retention: opts.retention,
+            read_only: opts.read_only,
+            download_rate: opts.max_download_rate.map(RateLimit::new),
+            metrics,
+            cancel: opts.cancel,
+        })
+    }
+
+    /// Returns the metrics of the requests sent by the client.
+    pub fn metrics(&self) -> &RequestMetrics {
+        &self.metrics
+    }
+
+    /// Waits until `bytes` fit into the `max_buffer_memory` budget and
+    /// reserves them until the returned value is dropped. Returns right
+    /// away when the client has no budget.
+    pub async fn reserve_buffer(&self, bytes: u64) -> Option<MemoryReservation> {
+        match &self.buffer_budget {
+            Some(budget) => Some(budget.reserve(bytes).await),
+            None => None,
+        }
+    }
+
+    /// Runs an upload of `object_name` unless the cancel token of the client
+    /// stops it first.
+    async fn cancellable<T>(
+        &self,
+        object_name: &str,
+        upload: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        match &self.cancel {
+            Some(cancel) => {
+                cancel
+                    .run(&format!("the upload of {}", object_name), upload)
+                    .await?
+            }
+            None => upload.await,
+        }
+    }
+
+    /// Returns the current SDK client (cheap to clone).
+    fn client(&self) -> s3::Client {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Returns the credentials the client signs its requests with, so other
+    /// AWS services of the run (such as SNS) use the same identity.
+    pub fn credentials(&self) -> Credentials {
+        self.credentials.clone()
+    }
+
+    /// Returns `true` if `result` failed because the bucket lives in another
+    /// region, after switching the client to that region. The caller then
+    /// retries the request once.
+    fn redirected<T, E: ProvideErrorMetadata>(
+        &self,
+        result: &std::result::Result<T, SdkError<E>>,
+    ) -> bool {
+        let Err(err) = result else {
+            return false;
+        };
+        let Some(region) = redirect_region(err) else {
+            return false;
+        };
+
+        let mut client = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        if client.config().region().map(|r| r.as_ref()) == Some(region.as_str()) {
+            return false;
+        }
+
+        let config = client
+            .config()
+            .to_builder()
+            .region(Region::new(region))
+            .build();
+        *client = s3::Client::from_conf(config);
+        true
+    }
+
+    /// Returns `true` if the client refuses requests that change the bucket.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Fails with a read-only mode error before `operation` of `object_name`
+    /// is sent, if the client is read-only.
+    fn check_writable(&self, operation: &str, object_name: &str) -> Result<()> {
+        if self.read_only {
+            return Err(PrefixloadError::Custom(format!(
+                "Read-only mode: refusing to send {} for {}.",
+                operation, object_name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Object Lock mode of uploaded objects, if the client retains them.
+    fn lock_mode(&self) -> Option<ObjectLockMode> {
+        self.retention.map(|retention| retention.mode.s3_mode())
+    }
+
+    /// Date until which uploaded objects are retained.
+    fn lock_until(&self) -> Option<S3DateTime> {
+        self.retention.map(|retention| retention.s3_retain_until())
+    }
+
+    /// User metadata attached to an uploaded object: `extra` plus the run ID.
+    fn object_metadata(&self, extra: &HashMap<String, String>) -> Option<HashMap<String, String>> {
+        let mut metadata = extra.clone();
+        if let Some(run_id) = &self.run_id {
+            metadata.insert(RUN_ID_METADATA_KEY.to_string(), run_id.clone());
+        }
+        (!metadata.is_empty()).then_some(metadata)
     }
 
     /// Checks the availability of the bucket
@@ -149,7 +768,11 @@ impl S3Client {
     /// - `Ok(false)` – the key is valid, but there are no rights (401/403)
     /// - `Err(e)`    – other errors (network, DNS, incorrect region, etc.)
     pub async fn check_bucket_access(&self, bucket: &str) -> Result<bool> {
-        let result = self.inner.head_bucket().bucket(bucket).send().await;
+        let head_bucket = || self.client().head_bucket().bucket(bucket).send();
+        let mut result = head_bucket().await;
+        if self.redirected(&result) {
+            result = head_bucket().await;
+        }
         match result {
             Ok(_) => Ok(true),
             Err(sdk_err) => {
@@ -171,6 +794,345 @@ impl S3Client {
         }
     }
 
+    /// Returns the keys of all objects under `prefix`, in the order S3 lists them.
+    pub async fn list_keys(&self, bucket: &str, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .list_objects(bucket, prefix, usize::MAX)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|object| object.key)
+            .collect())
+    }
+
+    /// Returns the objects under `prefix` with their ETags and sizes, in the
+    /// order S3 lists them, or `None` if listing them takes more than
+    /// `max_requests` requests (of up to 1,000 objects each).
+    pub async fn list_objects(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        max_requests: usize,
+    ) -> Result<Option<Vec<ListedObject>>> {
+        let mut objects = Vec::new();
+        let mut token = None;
+        for _ in 0..max_requests {
+            let list = || {
+                self.client()
+                    .list_objects_v2()
+                    .bucket(bucket)
+                    .prefix(prefix)
+                    .set_continuation_token(token.clone())
+                    .send()
+            };
+            let mut result = list().await;
+            if self.redirected(&result) {
+                result = list().await;
+            }
+
+            let output = result.map_err(aws_sdk_s3::Error::from)?;
+            objects.extend(output.contents().iter().filter_map(|object| {
+                Some(ListedObject {
+                    key: object.key()?.to_string(),
+                    etag: object
+                        .e_tag()
+                        .map(|etag| etag.trim_matches('"').to_string()),
+                    size: object.size().unwrap_or(0).max(0) as u64,
+                })
+            }));
+            token = output.next_continuation_token().map(str::to_string);
+            if !output.is_truncated().unwrap_or(false) || token.is_none() {
+                return Ok(Some(objects));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the multipart uploads under `prefix` that were started but
+    /// neither completed nor aborted. S3 keeps (and bills) their parts until
+    /// they are.
+    pub async fn list_multipart_uploads(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<IncompleteUpload>> {
+        let mut uploads = Vec::new();
+        let mut key_marker = None;
+        let mut upload_id_marker = None;
+        loop {
+            let list = || {
+                self.client()
+                    .list_multipart_uploads()
+                    .bucket(bucket)
+                    .prefix(prefix)
+                    .set_key_marker(key_marker.clone())
+                    .set_upload_id_marker(upload_id_marker.clone())
+                    .send()
+            };
+            let mut result = list().await;
+            if self.redirected(&result) {
+                result = list().await;
+            }
+
+            let output = result.map_err(aws_sdk_s3::Error::from)?;
+            uploads.extend(output.uploads().iter().filter_map(|upload| {
+                Some(IncompleteUpload {
+                    key: upload.key()?.to_string(),
+                    upload_id: upload.upload_id()?.to_string(),
+                    initiated: upload.initiated().and_then(|time| {
+                        DateTime::from_timestamp(time.secs(), time.subsec_nanos())
+                    }),
+                })
+            }));
+
+            key_marker = output.next_key_marker().map(str::to_string);
+            upload_id_marker = output.next_upload_id_marker().map(str::to_string);
+            if !output.is_truncated().unwrap_or(false) || key_marker.is_none() {
+                return Ok(uploads);
+            }
+        }
+    }
+
+    /// Returns the versions and delete markers of the object `key` (not of
+    /// other keys it is a prefix of), newest first. An unversioned bucket
+    /// lists its object as version `null`.
+    pub async fn list_versions(&self, bucket: &str, key: &str) -> Result<Vec<ObjectVersion>> {
+        let mut versions = Vec::new();
+        let mut key_marker = None;
+        let mut version_id_marker = None;
+        loop {
+            let list = || {
+                self.client()
+                    .list_object_versions()
+                    .bucket(bucket)
+                    .prefix(key)
+                    .set_key_marker(key_marker.clone())
+                    .set_version_id_marker(version_id_marker.clone())
+                    .send()
+            };
+            let mut result = list().await;
+            if self.redirected(&result) {
+                result = list().await;
+            }
+
+            let output = result.map_err(aws_sdk_s3::Error::from)?;
+            let listed = output
+                .versions()
+                .iter()
+                .map(|version| {
+                    (
+                        version.key(),
+                        version.version_id(),
+                        version.last_modified(),
+                        false,
+                    )
+                })
+                .chain(output.delete_markers().iter().map(|marker| {
+                    (
+                        marker.key(),
+                        marker.version_id(),
+                        marker.last_modified(),
+                        true,
+                    )
+                }));
+            for (version_key, version_id, last_modified, delete_marker) in listed {
+                if version_key != Some(key) {
+                    continue;
+                }
+                let Some(last_modified) = last_modified
+                    .and_then(|time| DateTime::from_timestamp(time.secs(), time.subsec_nanos()))
+                else {
+                    continue;
+                };
+                versions.push(ObjectVersion {
+                    version_id: version_id.unwrap_or("null").to_string(),
+                    last_modified,
+                    delete_marker,
+                });
+            }
+
+            key_marker = output.next_key_marker().map(str::to_string);
+            version_id_marker = output.next_version_id_marker().map(str::to_string);
+            if !output.is_truncated().unwrap_or(false) || key_marker.is_none() {
+                break;
+            }
+        }
+
+        versions.sort_by_key(|version| std::cmp::Reverse(version.last_modified));
+        Ok(versions)
+    }
+
+    /// Reads the current version of a small object, such as a run report,
+    /// into memory.
+    pub async fn get_bytes(&self, bucket: &str, object_name: &str) -> Result<Vec<u8>> {
+        let get_object = || {
+            self.client()
+                .get_object()
+                .bucket(bucket)
+                .key(object_name)
+                .send()
+        };
+        let mut result = get_object().await;
+        if self.redirected(&result) {
+            result = get_object().await;
+        }
+
+        let body = result.map_err(aws_sdk_s3::Error::from)?.body;
+        let data = body.collect().await.map_err(|e| {
+            PrefixloadError::Custom(format!("Failed to read <{}>: {}", object_name, e))
+        })?;
+        Ok(data.into_bytes().to_vec())
+    }
+
+    /// Downloads a version of an object (the current one if `version_id` is
+    /// `None`) to `path`. The body is streamed to a temporary file next to
+    /// `path`, which replaces `path` once the download is complete.
+    ///
+    /// # Returns
+    /// - `Ok(metadata)` with the user metadata of the object, which holds
+    ///   the attributes of the uploaded file.
+    /// - `Err` if the download or writing the file fails.
+    pub async fn download_file(
+        &self,
+        bucket: &str,
+        object_name: &str,
+        version_id: Option<&str>,
+        path: &Path,
+    ) -> Result<HashMap<String, String>> {
+        let get_object = || {
+            self.client()
+                .get_object()
+                .bucket(bucket)
+                .key(object_name)
+                .set_version_id(version_id.map(str::to_string))
+                .send()
+        };
+        let mut result = get_object().await;
+        if self.redirected(&result) {
+            result = get_object().await;
+        }
+        let output = result.map_err(aws_sdk_s3::Error::from)?;
+        let metadata = output.metadata().cloned().unwrap_or_default();
+
+        let partial = PathBuf::from(format!("{}{}", path.display(), PARTIAL_DOWNLOAD_SUFFIX));
+        let written = async {
+            let mut file = tokio::fs::File::create(&partial).await?;
+            let mut body = output.body;
+            while let Some(chunk) = body.try_next().await.map_err(|e| {
+                PrefixloadError::Custom(format!("Failed to download <{}>: {}", object_name, e))
+            })? {
+                if let Some(rate) = &self.download_rate {
+                    rate.consume(chunk.len() as u64).await;
+                }
+                file.write_all(&chunk).await?;
+            }
+            file.sync_all().await?;
+            Ok::<_, PrefixloadError>(())
+        }
+        .await;
+        if let Err(err) = written {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(err);
+        }
+
+        tokio::fs::rename(&partial, path).await?;
+        Ok(metadata)
+    }
+
+    /// Places (`on`) or removes an Object Lock legal hold on the current
+    /// version of an object.
+    pub async fn set_legal_hold(&self, bucket: &str, object_name: &str, on: bool) -> Result<()> {
+        self.check_writable("PutObjectLegalHold", object_name)?;
+        let status = if on {
+            ObjectLockLegalHoldStatus::On
+        } else {
+            ObjectLockLegalHoldStatus::Off
+        };
+        let legal_hold = ObjectLockLegalHold::builder().status(status).build();
+        let put_legal_hold = || {
+            self.client()
+                .put_object_legal_hold()
+                .bucket(bucket)
+                .key(object_name)
+                .legal_hold(legal_hold.clone())
+                .send()
+        };
+
+        let mut result = put_legal_hold().await;
+        if self.redirected(&result) {
+            result = put_legal_hold().await;
+        }
+
+        result
+            .map(|_| ())
+            .map_err(|err| aws_sdk_s3::Error::from(err).into())
+    }
+
+    /// Probes `s3:ListBucket` by listing at most one key under `prefix`.
+    pub async fn probe_list(&self, bucket: &str, prefix: &str) -> Result<Access> {
+        let list = || {
+            self.client()
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(prefix)
+                .max_keys(1)
+                .send()
+        };
+        let mut result = list().await;
+        if self.redirected(&result) {
+            result = list().await;
+        }
+        access(result)
+    }
+
+    /// Probes `s3:GetObject` with a HEAD request of `key`. A missing object
+    /// counts as allowed; without `s3:ListBucket`, S3 denies it instead.
+    pub async fn probe_head(&self, bucket: &str, key: &str) -> Result<Access> {
+        let head_object = || self.client().head_object().bucket(bucket).key(key).send();
+        let mut result = head_object().await;
+        if self.redirected(&result) {
+            result = head_object().await;
+        }
+        match result {
+            Err(SdkError::ServiceError(err))
+                if matches!(err.err(), HeadObjectError::NotFound(_)) =>
+            {
+                Ok(Access::Allowed)
+            }
+            result => access(result),
+        }
+    }
+
+    /// Probes `s3:PutObject` by writing an empty object at `key`.
+    pub async fn probe_put(&self, bucket: &str, key: &str) -> Result<Access> {
+        self.check_writable("PutObject", key)?;
+        let put_object = || {
+            self.client()
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .set_metadata(self.object_metadata(&HashMap::new()))
+                .body(ByteStream::from_static(b""))
+                .send()
+        };
+        let mut result = put_object().await;
+        if self.redirected(&result) {
+            result = put_object().await;
+        }
+        access(result)
+    }
+
+    /// Probes `s3:DeleteObject` by deleting `key`.
+    pub async fn probe_delete(&self, bucket: &str, key: &str) -> Result<Access> {
+        self.check_writable("DeleteObject", key)?;
+        let delete_object = || self.client().delete_object().bucket(bucket).key(key).send();
+        let mut result = delete_object().await;
+        if self.redirected(&result) {
+            result = delete_object().await;
+        }
+        access(result)
+    }
+
     /// Checks if the object in S3 is synced with the local file version.
     ///
     /// "Synced" means the object exists in the bucket and its ETag matches
@@ -192,55 +1154,471 @@ impl S3Client {
         bucket: &str,
         object_name: &str,
     ) -> Result<bool> {
-        match self
-            .inner
-            .head_object()
-            .bucket(bucket)
-            .key(object_name)
-            .send()
-            .await
-        {
+        let remote_md5 = self.object_etag(bucket, object_name).await?;
+        Ok(remote_md5.as_deref() == Some(local_file_md5))
+    }
+
+    /// Returns the ETag of an object without quotes, or `None` if the object
+    /// does not exist or has no ETag.
+    pub async fn object_etag(&self, bucket: &str, object_name: &str) -> Result<Option<String>> {
+        Ok(self
+            .head_object(bucket, object_name)
+            .await?
+            .and_then(|head| head.etag))
+    }
+
+    /// Returns the ETag, size and the other properties `HEAD` reports of an
+    /// object, or `None` if it does not exist.
+    pub async fn head_object(&self, bucket: &str, object_name: &str) -> Result<Option<ObjectHead>> {
+        let head_object = || {
+            self.client()
+                .head_object()
+                .bucket(bucket)
+                .key(object_name)
+                .send()
+        };
+        let mut result = head_object().await;
+        if self.redirected(&result) {
+            result = head_object().await;
+        }
+
+        match result {
             Ok(output) => {
-                if let Some(etag) = output.e_tag() {
-                    let remote_md5 = etag.trim_matches('"');
-                    Ok(remote_md5 == local_file_md5)
+                let to_utc =
+                    |time: &S3DateTime| DateTime::from_timestamp(time.secs(), time.subsec_nanos());
+                Ok(Some(ObjectHead {
+                    etag: output
+                        .e_tag()
+                        .map(|etag| etag.trim_matches('"').to_string()),
+                    size: output.content_length().unwrap_or(0).max(0) as u64,
+                    last_modified: output.last_modified().and_then(to_utc),
+                    storage_class: output.storage_class().map(|class| class.to_string()),
+                    server_side_encryption: output
+                        .server_side_encryption()
+                        .map(|sse| sse.to_string()),
+                    sse_kms_key_id: output.ssekms_key_id().map(str::to_string),
+                    content_type: output.content_type().map(str::to_string),
+                    version_id: output.version_id().map(str::to_string),
+                    object_lock_mode: output.object_lock_mode().map(|mode| mode.to_string()),
+                    object_lock_retain_until: output
+                        .object_lock_retain_until_date()
+                        .and_then(to_utc),
+                    legal_hold: output.object_lock_legal_hold_status()
+                        == Some(&ObjectLockLegalHoldStatus::On),
+                    metadata: output
+                        .metadata()
+                        .map(|metadata| {
+                            metadata
+                                .iter()
+                                .map(|(name, value)| (name.clone(), value.clone()))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                }))
+            }
+            Err(SdkError::ServiceError(service_error)) => match service_error.into_err() {
+                HeadObjectError::NotFound(_) => Ok(None),
+                other => Err(aws_sdk_s3::Error::from(other).into()),
+            },
+            Err(sdk_err) => Err(aws_sdk_s3::Error::from(sdk_err).into()),
+        }
+    }
+
+    /// Uploads a file to the specified S3 bucket.
+    ///
+    /// This method streams the file from disk, making it suitable for large files.
+    ///
+    /// # Parameters
+    /// - `bucket`: The name of the S3 bucket.
+    /// - `object_name`: The name for the object in S3.
+    /// - `path`: The local path to the file to upload.
+    /// - `options`: The checksum and metadata of the object.
+    ///
+    /// # Returns
+    /// - `Ok(())` on successful upload.
+    /// - `Err` if the file cannot be read or the upload fails.
+    pub async fn upload_file(
+        &self,
+        bucket: &str,
+        object_name: &str,
+        path: &Path,
+        options: &UploadOptions,
+    ) -> Result<()> {
+        self.check_writable("PutObject", object_name)?;
+        let checksum = options.checksum;
+        let content_md5 = match checksum {
+            Some(ChecksumAlgorithm::Md5) => {
+                let path = path.to_path_buf();
+                let md5 = tokio::task::spawn_blocking(move || content_md5(path))
+                    .await
+                    .map_err(|e| {
+                        PrefixloadError::Custom(format!("MD5 calculation task failed: {}", e))
+                    })??;
+                Some(md5)
+            }
+            _ => None,
+        };
+
+        // A file that cannot be read fails below, when the body is opened.
+        let size = tokio::fs::metadata(path).await.map_or(0, |m| m.len());
+        let _buffer = self.reserve_buffer(size).await;
+
+        // The body stream is consumed by a request, so a retry reopens the file.
+        let put_object = || async {
+            let body = ByteStream::from_path(path).await.map_err(|e| {
+                PrefixloadError::Custom(format!("Failed to read file {}: {}", path.display(), e))
+            })?;
+
+            Ok::<_, PrefixloadError>(
+                self.client()
+                    .put_object()
+                    .bucket(bucket)
+                    .key(object_name)
+                    .content_type("application/octet-stream")
+                    .set_metadata(self.object_metadata(&options.metadata))
+                    .set_object_lock_mode(self.lock_mode())
+                    .set_object_lock_retain_until_date(self.lock_until())
+                    .set_checksum_algorithm(checksum.and_then(ChecksumAlgorithm::s3_algorithm))
+                    .set_content_md5(content_md5.clone())
+                    .body(body)
+                    .send()
+                    .await,
+            )
+        };
+
+        let uploaded = async {
+            let mut result = put_object().await?;
+            if self.redirected(&result) {
+                result = put_object().await?;
+            }
+
+            result
+                .map(|_| ())
+                .map_err(|err| aws_sdk_s3::Error::from(err).into())
+        };
+        self.cancellable(object_name, uploaded).await
+    }
+
+    /// Copies an object of the bucket to another key server-side (`CopyObject`).
+    ///
+    /// The copy gets the checksum and metadata of `options` instead of those
+    /// of the source. `CopyObject` is limited to objects of up to 5 GiB; larger
+    /// ones are copied part by part with `upload_file_parts`.
+    ///
+    /// # Parameters
+    /// - `bucket`: The name of the S3 bucket.
+    /// - `source_name`: The name of the object to copy.
+    /// - `object_name`: The name for the copy in S3.
+    /// - `options`: The checksum and metadata of the copy.
+    ///
+    /// # Returns
+    /// - `Ok(())` on successful copy.
+    /// - `Err` if the copy fails.
+    pub async fn copy_object(
+        &self,
+        bucket: &str,
+        source_name: &str,
+        object_name: &str,
+        options: &UploadOptions,
+    ) -> Result<()> {
+        self.check_writable("CopyObject", object_name)?;
+        let copy_object = || {
+            self.client()
+                .copy_object()
+                .bucket(bucket)
+                .key(object_name)
+                .copy_source(copy_source(bucket, source_name))
+                .content_type("application/octet-stream")
+                .metadata_directive(MetadataDirective::Replace)
+                .set_metadata(self.object_metadata(&options.metadata))
+                .set_object_lock_mode(self.lock_mode())
+                .set_object_lock_retain_until_date(self.lock_until())
+                .set_checksum_algorithm(options.checksum.and_then(ChecksumAlgorithm::s3_algorithm))
+                .send()
+        };
+
+        let mut result = copy_object().await;
+        if self.redirected(&result) {
+            result = copy_object().await;
+        }
+
+        result
+            .map(|_| ())
+            .map_err(|err| aws_sdk_s3::Error::from(err).into())
+    }
+
+    /// Uploads a file as a multipart upload of `part_size` parts.
+    ///
+    /// Parts marked `copy` are copied server-side from the current version of
+    /// the object, or of `options.copy_from` (`UploadPartCopy`), instead of
+    /// being sent again. The ETag S3 returns for every part is checked against
+    /// the part's local MD5. Parts that are sent wait for their size to fit
+    /// into the `max_buffer_memory` budget of the client.
+    ///
+    /// # Parameters
+    /// - `bucket`: The name of the S3 bucket.
+    /// - `object_name`: The name for the object in S3.
+    /// - `path`: The local path to the file to upload.
+    /// - `part_size`: The size of every part but the last.
+    /// - `parts`: One entry per part of the file.
+    /// - `options`: The checksum, metadata and copy source of the object.
+    ///
+    /// # Returns
+    /// - `Ok(etag)` with the ETag of the new object.
+    /// - `Err` if a part fails or the upload is cancelled, after the
+    ///   multipart upload has been aborted.
+    pub async fn upload_file_parts(
+        &self,
+        bucket: &str,
+        object_name: &str,
+        path: &Path,
+        part_size: u64,
+        parts: &[PartPlan],
+        options: &UploadOptions,
+    ) -> Result<String> {
+        let upload = self
+            .create_multipart_upload(bucket, object_name, options)
+            .await?;
+        let file_size = tokio::fs::metadata(path).await?.len();
+        let source = copy_source(bucket, options.copy_from.as_deref().unwrap_or(object_name));
+
+        let uploaded = async {
+            let mut completed = Vec::with_capacity(parts.len());
+            for (index, part) in parts.iter().enumerate() {
+                let number = index as i32 + 1;
+                let offset = index as u64 * part_size;
+                let length = part_size.min(file_size - offset);
+
+                let uploaded = if part.copy {
+                    upload.copy_part(number, &source, offset, length).await?
                 } else {
-                    Ok(false)
-                }
+                    // Held until S3 has answered, the part body may be buffered until then.
+                    let _buffer = self.reserve_buffer(length).await;
+                    let body = self.part_body(path, offset, length).await?;
+                    upload.send_part(number, body).await?
+                };
+                completed.push(uploaded.verify(number, &part.md5, path)?);
+            }
+
+            upload.complete(completed).await
+        };
+        let uploaded = self.cancellable(object_name, uploaded).await;
+
+        if uploaded.is_err() {
+            upload.abort().await;
+        }
+
+        uploaded
+    }
+
+    /// Uploads a file as a multipart upload of `part_size` parts, hashing
+    /// every part while it is held in memory for the upload, so the file is
+    /// read only once.
+    ///
+    /// Parts whose MD5 equals the entry of `previous_parts` at the same
+    /// position are copied server-side from the current version of the
+    /// object instead of being sent. Every part is read after its size fits
+    /// into the `max_buffer_memory` budget of the client.
+    ///
+    /// # Parameters
+    /// - `bucket`: The name of the S3 bucket.
+    /// - `object_name`: The name for the object in S3.
+    /// - `path`: The local path to the file to upload.
+    /// - `part_size`: The size of every part but the last.
+    /// - `previous_parts`: The part MD5s of the current object; empty if
+    ///   none of its parts may be reused.
+    /// - `options`: The checksum and metadata of the object.
+    ///
+    /// # Returns
+    /// - `Ok(HashedUpload)` with the ETag of the new object and the part MD5s.
+    /// - `Err` if a part fails or the upload is cancelled, after the
+    ///   multipart upload has been aborted.
+    pub async fn upload_file_hashing(
+        &self,
+        bucket: &str,
+        object_name: &str,
+        path: &Path,
+        part_size: u64,
+        previous_parts: &[String],
+        options: &UploadOptions,
+    ) -> Result<HashedUpload> {
+        let upload = self
+            .create_multipart_upload(bucket, object_name, options)
+            .await?;
+        let file_size = tokio::fs::metadata(path).await?.len();
+        let source = copy_source(bucket, object_name);
+
+        let uploaded = async {
+            let part_count = file_size.div_ceil(part_size).max(1);
+            let mut completed = Vec::with_capacity(part_count as usize);
+            let mut part_md5s = Vec::with_capacity(part_count as usize);
+            let mut copied_bytes = 0;
+            for index in 0..part_count {
+                let number = index as i32 + 1;
+                let offset = index * part_size;
+                let length = part_size.min(file_size - offset);
+
+                let _buffer = self.reserve_buffer(length).await;
+                let (data, digest) = self.read_hashed_part(path, offset, length).await?;
+                let md5 = format!("{:x}", digest);
+
+                let uploaded = if previous_parts.get(index as usize) == Some(&md5) {
+                    drop(data);
+                    copied_bytes += length;
+                    upload.copy_part(number, &source, offset, length).await?
+                } else {
+                    upload.send_part(number, ByteStream::from(data)).await?
+                };
+                completed.push(uploaded.verify(number, &md5, path)?);
+                part_md5s.push(digest);
+            }
+
+            Ok(HashedUpload {
+                etag: upload.complete(completed).await?,
+                part_md5s,
+                copied_bytes,
+            })
+        };
+        let uploaded = self.cancellable(object_name, uploaded).await;
+
+        if uploaded.is_err() {
+            upload.abort().await;
+        }
+
+        uploaded
+    }
+
+    /// Starts a multipart upload of `object_name` with the checksum and
+    /// metadata of `options`.
+    async fn create_multipart_upload<'a>(
+        &self,
+        bucket: &'a str,
+        object_name: &'a str,
+        options: &UploadOptions,
+    ) -> Result<MultipartUpload<'a>> {
+        self.check_writable("CreateMultipartUpload", object_name)?;
+        let algorithm = options.checksum.and_then(ChecksumAlgorithm::s3_algorithm);
+        let create = || {
+            self.client()
+                .create_multipart_upload()
+                .bucket(bucket)
+                .key(object_name)
+                .content_type("application/octet-stream")
+                .set_metadata(self.object_metadata(&options.metadata))
+                .set_object_lock_mode(self.lock_mode())
+                .set_object_lock_retain_until_date(self.lock_until())
+                .set_checksum_algorithm(algorithm.clone())
+                .send()
+        };
+        let mut result = create().await;
+        if self.redirected(&result) {
+            result = create().await;
+        }
+        let upload_id = result
+            .map_err(aws_sdk_s3::Error::from)?
+            .upload_id()
+            .ok_or_else(|| {
+                PrefixloadError::Custom("S3 returned no multipart upload ID.".to_string())
+            })?
+            .to_string();
+
+        Ok(MultipartUpload {
+            client: self.client(),
+            bucket,
+            object_name,
+            upload_id,
+            algorithm,
+        })
+    }
+
+    /// Reads `length` bytes of the file at `offset` into memory and
+    /// calculates their MD5 digest.
+    async fn read_hashed_part(
+        &self,
+        path: &Path,
+        offset: u64,
+        length: u64,
+    ) -> Result<(Vec<u8>, md5::Digest)> {
+        let file_path = path.to_path_buf();
+        let read_mode = self.read_mode;
+        tokio::task::spawn_blocking(move || {
+            let data = read_mode.read_range(&std::fs::File::open(file_path)?, offset, length)?;
+            let digest = md5::compute(&data);
+            Ok::<_, std::io::Error>((data, digest))
+        })
+        .await
+        .map_err(|e| PrefixloadError::Custom(format!("Part read task failed: {}", e)))?
+        .map_err(|e| {
+            PrefixloadError::Custom(format!("Failed to read file {}: {}", path.display(), e))
+        })
+    }
+
+    /// Opens `length` bytes of the file at `offset` as the body of a part:
+    /// streamed from the file, or read into memory through io_uring.
+    async fn part_body(&self, path: &Path, offset: u64, length: u64) -> Result<ByteStream> {
+        let read_error = |e: &dyn std::fmt::Display| {
+            PrefixloadError::Custom(format!("Failed to read file {}: {}", path.display(), e))
+        };
+
+        match self.read_mode {
+            ReadMode::Standard => ByteStream::read_from()
+                .path(path)
+                .offset(offset)
+                .length(Length::Exact(length))
+                .build()
+                .await
+                .map_err(|e| read_error(&e)),
+            ReadMode::IoUring => {
+                let file_path = path.to_path_buf();
+                let data = tokio::task::spawn_blocking(move || {
+                    ReadMode::IoUring.read_range(&std::fs::File::open(file_path)?, offset, length)
+                })
+                .await
+                .map_err(|e| PrefixloadError::Custom(format!("Part read task failed: {}", e)))?
+                .map_err(|e| read_error(&e))?;
+                Ok(ByteStream::from(data))
             }
-            Err(SdkError::ServiceError(service_error)) => match service_error.into_err() {
-                HeadObjectError::NotFound(_) => Ok(false),
-                other => Err(aws_sdk_s3::Error::from(other).into()),
-            },
-            Err(sdk_err) => Err(aws_sdk_s3::Error::from(sdk_err).into()),
         }
     }
 
-    /// Uploads a file to the specified S3 bucket.
-    ///
-    /// This method streams the file from disk, making it suitable for large files.
+    /// Uploads an in-memory buffer to the specified S3 bucket.
     ///
     /// # Parameters
     /// - `bucket`: The name of the S3 bucket.
     /// - `object_name`: The name for the object in S3.
-    /// - `path`: The local path to the file to upload.
+    /// - `body`: The object contents.
+    /// - `content_type`: The MIME type stored with the object.
     ///
     /// # Returns
     /// - `Ok(())` on successful upload.
-    /// - `Err` if the file cannot be read or the upload fails.
-    pub async fn upload_file(&self, bucket: &str, object_name: &str, path: &Path) -> Result<()> {
-        let body = ByteStream::from_path(path).await.map_err(|e| {
-            PrefixloadError::Custom(format!("Failed to read file {}: {}", path.display(), e))
-        })?;
+    /// - `Err` if the upload fails.
+    pub async fn upload_bytes(
+        &self,
+        bucket: &str,
+        object_name: &str,
+        body: Vec<u8>,
+        content_type: &str,
+    ) -> Result<()> {
+        self.check_writable("PutObject", object_name)?;
+        let put_object = || {
+            self.client()
+                .put_object()
+                .bucket(bucket)
+                .key(object_name)
+                .content_type(content_type)
+                .set_metadata(self.object_metadata(&HashMap::new()))
+                .set_object_lock_mode(self.lock_mode())
+                .set_object_lock_retain_until_date(self.lock_until())
+                .body(ByteStream::from(body.clone()))
+                .send()
+        };
+
+        let mut result = put_object().await;
+        if self.redirected(&result) {
+            result = put_object().await;
+        }
 
-        self.inner
-            .put_object()
-            .bucket(bucket)
-            .key(object_name)
-            .content_type("application/octet-stream")
-            .body(body)
-            .send()
-            .await
+        result
             .map(|_| ())
             .map_err(|err| aws_sdk_s3::Error::from(err).into())
     }
@@ -249,10 +1627,15 @@ impl S3Client {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::retention::RetentionMode;
     use serial_test::serial;
     use std::fs;
+    use std::time::Duration;
     use tempfile::tempdir;
-    use wiremock::matchers::{header, header_exists, method, path_regex};
+    use wiremock::matchers::{
+        body_string, body_string_contains, header, header_exists, header_regex, method, path_regex,
+        query_param, query_param_is_missing,
+    };
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     const AK: &str = "TEST_AK";
@@ -263,9 +1646,19 @@ mod tests {
         S3Client::new(S3ClientOptions {
             access_key: AK.to_string(),
             secret_key: SK.to_string(),
+            session_token: None,
+            access_key_file: None,
+            secret_key_file: None,
             region: None,                 // default us-east-1
             endpoint: Some(server.uri()), // plain-http mock
             force_path_style: true,
+            run_id: None,
+            max_buffer_memory: None,
+            read_mode: ReadMode::Standard,
+            retention: None,
+            read_only: false,
+            max_download_rate: None,
+            cancel: None,
         })
         .await
         .expect("client init")
@@ -360,7 +1753,8 @@ mod tests {
         let server = MockServer::start().await;
         let cli = client(&server).await;
 
-        let region = cli.inner.config().region().unwrap().as_ref();
+        let inner = cli.client();
+        let region = inner.config().region().unwrap().as_ref();
         assert_eq!(region, "us-east-1");
     }
 
@@ -372,14 +1766,25 @@ mod tests {
         let cli = S3Client::new(S3ClientOptions {
             access_key: AK.to_string(),
             secret_key: SK.to_string(),
+            session_token: None,
+            access_key_file: None,
+            secret_key_file: None,
             region: Some(region_name.to_string()),
             endpoint: Some(server.uri()),
             force_path_style: true,
+            run_id: None,
+            max_buffer_memory: None,
+            read_mode: ReadMode::Standard,
+            retention: None,
+            read_only: false,
+            max_download_rate: None,
+            cancel: None,
         })
         .await
         .expect("client init");
 
-        let region = cli.inner.config().region().unwrap().as_ref();
+        let inner = cli.client();
+        let region = inner.config().region().unwrap().as_ref();
         assert_eq!(region, region_name);
     }
 
@@ -408,9 +1813,19 @@ mod tests {
         let cli_path_style = S3Client::new(S3ClientOptions {
             access_key: AK.to_string(),
             secret_key: SK.to_string(),
+            session_token: None,
+            access_key_file: None,
+            secret_key_file: None,
             region: Some("us-east-1".to_string()),
             endpoint: Some(server.uri()),
             force_path_style: true,
+            run_id: None,
+            max_buffer_memory: None,
+            read_mode: ReadMode::Standard,
+            retention: None,
+            read_only: false,
+            max_download_rate: None,
+            cancel: None,
         })
         .await
         .expect("client init with path style");
@@ -422,9 +1837,19 @@ mod tests {
         let cli_virtual_hosted = S3Client::new(S3ClientOptions {
             access_key: AK.to_string(),
             secret_key: SK.to_string(),
+            session_token: None,
+            access_key_file: None,
+            secret_key_file: None,
             region: Some("us-east-1".to_string()),
             endpoint: Some(server.uri()),
             force_path_style: false,
+            run_id: None,
+            max_buffer_memory: None,
+            read_mode: ReadMode::Standard,
+            retention: None,
+            read_only: false,
+            max_download_rate: None,
+            cancel: None,
         })
         .await
         .expect("client init with virtual-hosted style");
@@ -438,15 +1863,25 @@ mod tests {
         let opts = S3ClientOptions::default()
             .with_access_key("ak")
             .with_secret_key("sk")
+            .with_session_token("st")
             .with_region("eu-central-1")
             .with_endpoint("http://localhost:9000")
-            .with_force_path_style(true);
+            .with_force_path_style(true)
+            .with_run_id("run-1");
 
         assert_eq!(opts.access_key, "ak");
         assert_eq!(opts.secret_key, "sk");
+        assert_eq!(opts.session_token, Some("st".to_string()));
         assert_eq!(opts.region, Some("eu-central-1".to_string()));
         assert_eq!(opts.endpoint, Some("http://localhost:9000".to_string()));
         assert!(opts.force_path_style);
+        assert_eq!(opts.run_id, Some("run-1".to_string()));
+
+        let opts = S3ClientOptions::default()
+            .with_access_key_file("/run/secrets/ak")
+            .with_secret_key_file("/run/secrets/sk");
+        assert_eq!(opts.access_key_file, Some(PathBuf::from("/run/secrets/ak")));
+        assert_eq!(opts.secret_key_file, Some(PathBuf::from("/run/secrets/sk")));
     }
 
     #[test]
@@ -454,9 +1889,13 @@ mod tests {
         let opts = S3ClientOptions::default();
         assert_eq!(opts.access_key, "");
         assert_eq!(opts.secret_key, "");
+        assert_eq!(opts.session_token, None);
+        assert_eq!(opts.access_key_file, None);
+        assert_eq!(opts.secret_key_file, None);
         assert_eq!(opts.region, None);
         assert_eq!(opts.endpoint, None);
         assert!(!opts.force_path_style);
+        assert_eq!(opts.run_id, None);
     }
 
     #[tokio::test]
@@ -534,6 +1973,66 @@ mod tests {
         }
     }
 
+    #[test]
+    #[serial]
+    fn from_env_reads_aws_variables() {
+        unsafe {
+            std::env::set_var("AWS_ACCESS_KEY_ID", "ENVKEY");
+            std::env::set_var("AWS_SECRET_ACCESS_KEY", "ENVSECRET");
+        }
+        let opts = S3ClientOptions::from_env();
+        unsafe { std::env::remove_var("AWS_SECRET_ACCESS_KEY") };
+        let missing = S3ClientOptions::from_env().unwrap_err();
+        unsafe { std::env::remove_var("AWS_ACCESS_KEY_ID") };
+
+        let opts = opts.unwrap();
+        assert_eq!(opts.access_key, "ENVKEY");
+        assert_eq!(opts.secret_key, "ENVSECRET");
+        assert!(missing.to_string().contains("AWS_SECRET_ACCESS_KEY"));
+
+        unsafe { std::env::set_var("AWS_SECRET_ACCESS_KEY_FILE", "/run/secrets/sk") };
+        let from_file = S3ClientOptions::from_env();
+        unsafe { std::env::remove_var("AWS_SECRET_ACCESS_KEY_FILE") };
+
+        let from_file = from_file.unwrap_err();
+        assert!(from_file.to_string().contains("AWS_ACCESS_KEY_ID"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn key_files_are_read_when_client_is_created() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("ak"), "FILEKEY\n").unwrap();
+        fs::write(dir.path().join("sk"), "FILESECRET\n").unwrap();
+        fs::write(dir.path().join("empty"), "\n").unwrap();
+
+        let opts = S3ClientOptions::default()
+            .with_access_key_file(dir.path().join("ak"))
+            .with_secret_key_file(dir.path().join("sk"))
+            .with_endpoint(server.uri())
+            .with_force_path_style(true);
+        let client = S3Client::new(opts.clone()).await.unwrap();
+        assert!(client.check_bucket_access("bucket").await.unwrap());
+
+        let requests = server.received_requests().await.unwrap();
+        let auth = requests[0].headers.get("authorization").unwrap();
+        assert!(auth.to_str().unwrap().contains("Credential=FILEKEY/"));
+
+        let empty = opts.with_secret_key_file(dir.path().join("empty"));
+        let err = S3Client::new(empty).await.unwrap_err();
+        assert!(err.to_string().contains("is empty"));
+
+        let missing = S3ClientOptions::default().with_access_key_file(dir.path().join("none"));
+        let err = S3Client::new(missing).await.unwrap_err();
+        assert!(err.to_string().contains("Failed to read access_key_file"));
+    }
+
     #[tokio::test]
     async fn is_object_synced_matches() {
         let server = MockServer::start().await;
@@ -662,7 +2161,7 @@ mod tests {
 
         // 4. Call the function
         let result = s3_client
-            .upload_file(bucket, object_name, &file_path)
+            .upload_file(bucket, object_name, &file_path, &UploadOptions::default())
             .await;
 
         // 5. Assert success
@@ -691,7 +2190,7 @@ mod tests {
 
         // 4. Call the function
         let result = s3_client
-            .upload_file(bucket, object_name, &file_path)
+            .upload_file(bucket, object_name, &file_path, &UploadOptions::default())
             .await;
 
         // 5. Assert error
@@ -709,7 +2208,12 @@ mod tests {
         // No need to mock the server, as it should fail before the request.
 
         let result = s3_client
-            .upload_file(bucket, object_name, non_existent_path)
+            .upload_file(
+                bucket,
+                object_name,
+                non_existent_path,
+                &UploadOptions::default(),
+            )
             .await;
 
         assert!(result.is_err());
@@ -719,4 +2223,452 @@ mod tests {
             panic!("Expected a custom error for file not found");
         }
     }
+
+    #[tokio::test]
+    async fn upload_bytes_sends_body_and_content_type() {
+        let server = MockServer::start().await;
+        let s3_client = client(&server).await;
+
+        Mock::given(method("PUT"))
+            .and(path_regex("/log-bucket/_logs/run.log"))
+            .and(header("content-type", "text/plain"))
+            .and(body_string("hello log"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let result = s3_client
+            .upload_bytes(
+                "log-bucket",
+                "_logs/run.log",
+                b"hello log".to_vec(),
+                "text/plain",
+            )
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn run_id_is_sent_as_metadata_and_user_agent() {
+        let server = MockServer::start().await;
+        let s3_client = S3Client::new(
+            S3ClientOptions::default()
+                .with_access_key(AK)
+                .with_secret_key(SK)
+                .with_endpoint(server.uri())
+                .with_force_path_style(true)
+                .with_run_id("1b2c3d4e"),
+        )
+        .await
+        .expect("client init");
+
+        Mock::given(method("PUT"))
+            .and(path_regex("/bucket/key"))
+            .and(header("x-amz-meta-prefixload-run-id", "1b2c3d4e"))
+            .and(header_regex("x-amz-user-agent", "app/prefixload-1b2c3d4e"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let result = s3_client
+            .upload_bytes("bucket", "key", b"data".to_vec(), "text/plain")
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn retention_is_sent_with_uploads_and_copies() {
+        let server = MockServer::start().await;
+        let now = chrono::DateTime::parse_from_rfc3339("2030-01-01T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        let s3_client = S3Client::new(
+            S3ClientOptions::default()
+                .with_access_key(AK)
+                .with_secret_key(SK)
+                .with_endpoint(server.uri())
+                .with_force_path_style(true)
+                .with_retention(Retention::new(RetentionMode::Compliance, 30, now)),
+        )
+        .await
+        .expect("client init");
+
+        Mock::given(method("PUT"))
+            .and(header("x-amz-object-lock-mode", "COMPLIANCE"))
+            .and(header(
+                "x-amz-object-lock-retain-until-date",
+                "2030-01-31T12:00:00Z",
+            ))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("<CopyObjectResult><ETag>\"abc\"</ETag></CopyObjectResult>"),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        s3_client
+            .upload_bytes("bucket", "key", b"data".to_vec(), "text/plain")
+            .await
+            .unwrap();
+        s3_client
+            .copy_object("bucket", "key", "copy", &UploadOptions::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancelled_upload_aborts_its_multipart_upload() {
+        let server = MockServer::start().await;
+        let cancel = CancelToken::new();
+        let s3_client = S3Client::new(
+            S3ClientOptions::default()
+                .with_access_key(AK)
+                .with_secret_key(SK)
+                .with_endpoint(server.uri())
+                .with_force_path_style(true)
+                .with_cancel(cancel.clone()),
+        )
+        .await
+        .expect("client init");
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<InitiateMultipartUploadResult><UploadId>UP1</UploadId></InitiateMultipartUploadResult>",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(30)))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(query_param("uploadId", "UP1"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("big.bin");
+        fs::write(&file, b"data").unwrap();
+        let parts = [PartPlan {
+            md5: format!("{:x}", md5::compute(b"data")),
+            copy: false,
+        }];
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            cancel.cancel();
+        });
+
+        let err = s3_client
+            .upload_file_parts("bucket", "big", &file, 5, &parts, &UploadOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error [Cancelled]: Cancelled the upload of big."
+        );
+    }
+
+    #[tokio::test]
+    async fn read_only_client_sends_no_mutating_requests() {
+        let server = MockServer::start().await;
+        let s3_client = S3Client::new(
+            S3ClientOptions::default()
+                .with_access_key(AK)
+                .with_secret_key(SK)
+                .with_endpoint(server.uri())
+                .with_force_path_style(true)
+                .with_read_only(true),
+        )
+        .await
+        .expect("client init");
+
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"abc\""))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        assert!(
+            s3_client
+                .is_object_synced("abc", "bucket", "key")
+                .await
+                .unwrap()
+        );
+
+        let err = s3_client
+            .upload_bytes("bucket", "key", b"data".to_vec(), "text/plain")
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Read-only mode: refusing to send PutObject for key."),
+            "{}",
+            err
+        );
+        assert!(
+            s3_client
+                .copy_object("bucket", "key", "copy", &UploadOptions::default())
+                .await
+                .is_err()
+        );
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("big.bin");
+        fs::write(&file, b"data").unwrap();
+        let err = s3_client
+            .upload_file_hashing("bucket", "big", &file, 5, &[], &UploadOptions::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("CreateMultipartUpload"), "{}", err);
+        assert!(s3_client.probe_delete("bucket", "key").await.is_err());
+        assert!(
+            s3_client
+                .set_legal_hold("bucket", "key", true)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn list_keys_follows_continuation_tokens() {
+        let server = MockServer::start().await;
+        let s3_client = client(&server).await;
+
+        Mock::given(method("GET"))
+            .and(query_param("prefix", "db/"))
+            .and(query_param("continuation-token", "next"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<ListBucketResult><IsTruncated>false</IsTruncated>\
+                 <Contents><Key>db/2.sql</Key></Contents></ListBucketResult>",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(query_param("prefix", "db/"))
+            .and(query_param_is_missing("continuation-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<ListBucketResult><IsTruncated>true</IsTruncated>\
+                 <Contents><Key>db/1.sql</Key></Contents>\
+                 <NextContinuationToken>next</NextContinuationToken></ListBucketResult>",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let keys = s3_client.list_keys("bucket", "db/").await.unwrap();
+        assert_eq!(keys, ["db/1.sql", "db/2.sql"]);
+    }
+
+    #[tokio::test]
+    async fn set_legal_hold_sends_the_status() {
+        let server = MockServer::start().await;
+        let s3_client = client(&server).await;
+
+        Mock::given(method("PUT"))
+            .and(path_regex("/bucket/db/1.sql"))
+            .and(query_param("legal-hold", ""))
+            .and(body_string_contains("<Status>ON</Status>"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        s3_client
+            .set_legal_hold("bucket", "db/1.sql", true)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn copy_source_is_percent_encoded() {
+        assert_eq!(
+            copy_source("bucket", "db/dump 2025+1.sql"),
+            "bucket/db/dump%202025%2B1.sql"
+        );
+    }
+
+    #[tokio::test]
+    async fn permanent_redirect_retries_in_bucket_region() {
+        let server = MockServer::start().await;
+        let s3_client = client(&server).await;
+
+        Mock::given(method("HEAD"))
+            .and(path_regex(r"^/eu-bucket/?$"))
+            .and(header_regex("authorization", "/us-east-1/s3/"))
+            .respond_with(
+                ResponseTemplate::new(301).insert_header("x-amz-bucket-region", "eu-west-1"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path_regex(r"^/eu-bucket/?$"))
+            .and(header_regex("authorization", "/eu-west-1/s3/"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        assert!(s3_client.check_bucket_access("eu-bucket").await.unwrap());
+
+        // The client keeps the corrected region for later requests.
+        let inner = s3_client.client();
+        assert_eq!(inner.config().region().unwrap().as_ref(), "eu-west-1");
+    }
+
+    #[tokio::test]
+    async fn malformed_authorization_retries_upload_in_expected_region() {
+        let server = MockServer::start().await;
+        let s3_client = client(&server).await;
+
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, "hello world").unwrap();
+
+        Mock::given(method("PUT"))
+            .and(path_regex("/bucket/key"))
+            .and(header_regex("authorization", "/us-east-1/s3/"))
+            .respond_with(ResponseTemplate::new(400).set_body_string(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error><Code>AuthorizationHeaderMalformed</Code><Message>The authorization header is malformed; the region 'us-east-1' is wrong; expecting 'ap-south-1'</Message><Region>ap-south-1</Region><RequestId>1</RequestId></Error>"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path_regex("/bucket/key"))
+            .and(header_regex("authorization", "/ap-south-1/s3/"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let result = s3_client
+            .upload_file("bucket", "key", &file_path, &UploadOptions::default())
+            .await;
+        assert!(result.is_ok(), "{result:?}");
+    }
+
+    #[tokio::test]
+    async fn upload_file_sends_requested_checksum() {
+        let server = MockServer::start().await;
+        let s3_client = client(&server).await;
+
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, "hello world").unwrap();
+
+        Mock::given(method("PUT"))
+            .and(path_regex("/bucket/sha256"))
+            .and(header("x-amz-sdk-checksum-algorithm", "SHA256"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path_regex("/bucket/md5"))
+            .and(header("content-md5", "XrY7u+Ae7tCTyyK7j1rNww=="))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        s3_client
+            .upload_file(
+                "bucket",
+                "sha256",
+                &file_path,
+                &UploadOptions {
+                    checksum: Some(ChecksumAlgorithm::Sha256),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        s3_client
+            .upload_file(
+                "bucket",
+                "md5",
+                &file_path,
+                &UploadOptions {
+                    checksum: Some(ChecksumAlgorithm::Md5),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn uploads_wait_for_the_buffer_budget() {
+        let server = MockServer::start().await;
+        let mut s3_client = client(&server).await;
+        // Room for one 11-byte body at a time.
+        s3_client.buffer_budget = Some(MemoryBudget::new(11));
+
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, "hello world").unwrap();
+
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(200)))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let options = UploadOptions::default();
+        let started = std::time::Instant::now();
+        let (first, second) = tokio::join!(
+            s3_client.upload_file("bucket", "a", &file_path, &options),
+            s3_client.upload_file("bucket", "b", &file_path, &options),
+        );
+        first.unwrap();
+        second.unwrap();
+
+        assert!(started.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn redirect_is_retried_only_once() {
+        let server = MockServer::start().await;
+        let s3_client = client(&server).await;
+
+        Mock::given(method("PUT"))
+            .and(path_regex("/bucket/key"))
+            .respond_with(
+                ResponseTemplate::new(301).insert_header("x-amz-bucket-region", "eu-west-1"),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let result = s3_client
+            .upload_bytes("bucket", "key", b"data".to_vec(), "text/plain")
+            .await;
+
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,235 @@
+use crate::clients::{HttpClient, http_client};
+use crate::error::{PrefixloadError, Result};
+use aws_credential_types::Credentials;
+use aws_sigv4::http_request::{SignableBody, SignableRequest, SigningSettings, sign};
+use aws_sigv4::sign::v4;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use std::time::SystemTime;
+
+/// API version of the SNS Query API.
+const API_VERSION: &str = "2010-03-31";
+
+/// Content type of Query API request bodies.
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded; charset=utf-8";
+
+/// SNS allows at most 100 characters in a subject.
+const MAX_SUBJECT_LEN: usize = 100;
+
+/// Minimal SNS client that publishes run summaries to a topic.
+///
+/// It signs `Publish` requests of the Query API with SigV4 itself, with the
+/// credentials the run already uses for S3.
+#[derive(Debug, Clone)]
+pub struct SnsClient {
+    http: HttpClient,
+    credentials: Credentials,
+    region: String,
+    endpoint: String,
+}
+
+impl SnsClient {
+    /// Creates a client for the SNS endpoint of `region`. `endpoint`
+    /// overrides it (`https://sns.<region>.amazonaws.com`).
+    pub fn new(credentials: Credentials, region: &str, endpoint: Option<String>) -> Result<Self> {
+        Ok(Self {
+            http: http_client()?,
+            credentials,
+            region: region.to_string(),
+            endpoint: endpoint.unwrap_or_else(|| format!("https://sns.{}.amazonaws.com/", region)),
+        })
+    }
+
+    /// Publishes `message` to the topic `topic_arn` and returns the ID SNS
+    /// assigned to it. Subjects longer than SNS allows are cut off.
+    pub async fn publish(&self, topic_arn: &str, subject: &str, message: &str) -> Result<String> {
+        let subject: String = subject.chars().take(MAX_SUBJECT_LEN).collect();
+        let body = form_body(&[
+            ("Action", "Publish"),
+            ("Version", API_VERSION),
+            ("TopicArn", topic_arn),
+            ("Subject", &subject),
+            ("Message", message),
+        ]);
+
+        let mut request = http::Request::builder()
+            .method("POST")
+            .uri(&self.endpoint)
+            .header(http::header::CONTENT_TYPE, FORM_CONTENT_TYPE)
+            .body(Full::new(Bytes::from(body.clone())))
+            .map_err(|e| PrefixloadError::Custom(format!("Invalid SNS endpoint: {}", e)))?;
+        self.sign(&mut request, body.as_bytes())?;
+
+        let response = self.http.request(request).await.map_err(|e| {
+            PrefixloadError::Custom(format!(
+                "Failed to publish to SNS topic {}: {}",
+                topic_arn, e
+            ))
+        })?;
+        let status = response.status();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| {
+                PrefixloadError::Custom(format!("Failed to read the SNS response: {}", e))
+            })?
+            .to_bytes();
+        let body = String::from_utf8_lossy(&body);
+
+        if !status.is_success() {
+            let code = xml_value(&body, "Code").unwrap_or(status.as_str());
+            let reason = xml_value(&body, "Message").unwrap_or("no error message");
+            return Err(PrefixloadError::Custom(format!(
+                "Failed to publish to SNS topic {}: {} ({})",
+                topic_arn, reason, code
+            )));
+        }
+
+        Ok(xml_value(&body, "MessageId")
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Adds the SigV4 `Authorization` headers for `body` to `request`.
+    fn sign(&self, request: &mut http::Request<Full<Bytes>>, body: &[u8]) -> Result<()> {
+        let identity = self.credentials.clone().into();
+        let params = v4::SigningParams::builder()
+            .identity(&identity)
+            .region(&self.region)
+            .name("sns")
+            .time(SystemTime::now())
+            .settings(SigningSettings::default())
+            .build()
+            .map_err(|e| PrefixloadError::Custom(format!("Failed to sign SNS request: {}", e)))?
+            .into();
+
+        let signable = SignableRequest::new(
+            "POST",
+            self.endpoint.as_str(),
+            [("content-type", FORM_CONTENT_TYPE)].into_iter(),
+            SignableBody::Bytes(body),
+        )
+        .map_err(|e| PrefixloadError::Custom(format!("Failed to sign SNS request: {}", e)))?;
+        let (instructions, _signature) = sign(signable, &params)
+            .map_err(|e| PrefixloadError::Custom(format!("Failed to sign SNS request: {}", e)))?
+            .into_parts();
+        instructions.apply_to_request_http1x(request);
+
+        Ok(())
+    }
+}
+
+/// Returns the region of an SNS topic ARN (`arn:<partition>:sns:<region>:<account>:<name>`).
+pub fn topic_region(topic_arn: &str) -> Option<&str> {
+    match topic_arn.split(':').collect::<Vec<_>>()[..] {
+        ["arn", _, "sns", region, _, name] if !region.is_empty() && !name.is_empty() => {
+            Some(region)
+        }
+        _ => None,
+    }
+}
+
+/// Encodes `pairs` as an `application/x-www-form-urlencoded` body.
+fn form_body(pairs: &[(&str, &str)]) -> String {
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", url_encode(key), url_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+fn url_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Returns the text of the first `<tag>` element of an XML response.
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml[start..end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_string_contains, header_exists, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const TOPIC: &str = "arn:aws:sns:eu-central-1:123456789012:backups";
+
+    fn client(server: &MockServer) -> SnsClient {
+        let credentials = Credentials::new("AKIDTEST", "SECRET", None, None, "test");
+        SnsClient::new(credentials, "eu-central-1", Some(server.uri())).unwrap()
+    }
+
+    #[test]
+    fn topic_region_is_read_from_the_arn() {
+        assert_eq!(topic_region(TOPIC), Some("eu-central-1"));
+        assert_eq!(
+            topic_region("arn:aws-cn:sns:cn-north-1:123456789012:backups"),
+            Some("cn-north-1")
+        );
+        assert_eq!(
+            topic_region("arn:aws:sqs:eu-central-1:123456789012:q"),
+            None
+        );
+        assert_eq!(topic_region("backups"), None);
+    }
+
+    #[tokio::test]
+    async fn publish_sends_a_signed_request_and_returns_the_message_id() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header_exists("authorization"))
+            .and(body_string_contains("Action=Publish"))
+            .and(body_string_contains(
+                "TopicArn=arn%3Aaws%3Asns%3Aeu-central-1%3A123456789012%3Abackups",
+            ))
+            .and(body_string_contains("Message=Run%20finished."))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<PublishResponse><PublishResult><MessageId>msg-1</MessageId>\
+                 </PublishResult></PublishResponse>",
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let id = client(&server)
+            .publish(TOPIC, "prefixload run succeeded", "Run finished.")
+            .await
+            .unwrap();
+        assert_eq!(id, "msg-1");
+    }
+
+    #[tokio::test]
+    async fn publish_reports_the_error_of_sns() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(403).set_body_string(
+                "<ErrorResponse><Error><Code>AuthorizationError</Code>\
+                 <Message>User is not authorized to perform SNS:Publish</Message>\
+                 </Error></ErrorResponse>",
+            ))
+            .mount(&server)
+            .await;
+
+        let err = client(&server)
+            .publish(TOPIC, "subject", "message")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not authorized"));
+        assert!(err.to_string().contains("AuthorizationError"));
+    }
+}
@@ -0,0 +1,396 @@
+use crate::clients::s3::S3ClientOptions;
+use crate::error::{PrefixloadError, Result};
+use aws_sdk_sts as sts;
+use aws_sdk_sts::config::{Credentials, Region};
+use aws_sdk_sts::error::ProvideErrorMetadata;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+/// Lifetime requested for MFA session credentials (12 hours, the STS default).
+pub const DEFAULT_SESSION_DURATION_SECS: i32 = 43_200;
+
+/// Sessions this close to their expiry are treated as expired, so a run
+/// does not start with credentials that lapse halfway through an upload.
+const EXPIRY_MARGIN_MINUTES: i64 = 5;
+
+/// Runs warn when the session expires within this many minutes.
+const EXPIRY_WARNING_MINUTES: i64 = 60;
+
+/// How cached session credentials were obtained, which decides how they are renewed.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionSource {
+    /// STS `GetSessionToken` with an MFA code (`login --mfa`).
+    #[default]
+    Mfa,
+    /// Temporary credentials from the environment (`login --from-env`).
+    Env,
+}
+
+impl SessionSource {
+    /// Describes the credentials in messages.
+    pub fn describe(self) -> &'static str {
+        match self {
+            SessionSource::Mfa => "MFA session credentials",
+            SessionSource::Env => "temporary credentials",
+        }
+    }
+
+    /// The command that renews the credentials.
+    pub fn login_command(self) -> &'static str {
+        match self {
+            SessionSource::Mfa => "prefixload login --mfa",
+            SessionSource::Env => "prefixload login --from-env",
+        }
+    }
+}
+
+/// Temporary credentials obtained with `login --mfa` (or saved with
+/// `login --from-env`), cached between runs.
+/// - Linux: ~/.local/share/prefixload/session.json
+/// - macOS: ~/Library/Application Support/prefixload/session.json
+/// - Windows: %LOCALAPPDATA%\prefixload\session.json
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionCredentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: String,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub source: SessionSource,
+}
+
+impl SessionCredentials {
+    /// Returns the full path to the session cache, creating its directory on demand.
+    fn cache_path() -> Result<PathBuf> {
+        let mut dir = dirs_next::data_local_dir().ok_or_else(|| {
+            PrefixloadError::Custom("Could not find local data directory.".to_string())
+        })?;
+
+        dir.push("prefixload");
+        fs::create_dir_all(&dir)?;
+
+        dir.push("session.json");
+
+        Ok(dir)
+    }
+
+    /// Loads the cached session, if there is one.
+    pub fn load() -> Result<Option<Self>> {
+        let path = Self::cache_path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content).map(Some).map_err(|e| {
+            PrefixloadError::Custom(format!(
+                "Failed to parse session cache {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Writes the session to the cache, readable by the owner only on Unix.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::cache_path()?;
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| PrefixloadError::Custom(format!("Failed to serialize session: {}", e)))?;
+
+        fs::write(&path, json)?;
+
+        #[cfg(unix)]
+        {
+            let mut perms = fs::metadata(&path)?.permissions();
+            perms.set_mode(0o600);
+            fs::set_permissions(&path, perms)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the cached session. Returns `false` if there was none.
+    pub fn clear() -> Result<bool> {
+        let path = Self::cache_path()?;
+        if !path.exists() {
+            return Ok(false);
+        }
+
+        fs::remove_file(&path)?;
+        Ok(true)
+    }
+
+    /// Returns `true` if the session expires within `EXPIRY_MARGIN_MINUTES` of `now`.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now + Duration::minutes(EXPIRY_MARGIN_MINUTES)
+    }
+
+    /// Returns `true` if the session expires within `EXPIRY_WARNING_MINUTES` of `now`.
+    pub fn expires_soon(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now + Duration::minutes(EXPIRY_WARNING_MINUTES)
+    }
+
+    /// Local time the session expires at, for messages.
+    pub fn expires_at_local(&self) -> String {
+        self.expires_at
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M %Z")
+            .to_string()
+    }
+
+    /// Returns why runs fail if the session is expired at `now`.
+    pub fn expiry_error(&self, now: DateTime<Utc>) -> Option<String> {
+        self.is_expired(now).then(|| {
+            format!(
+                "Credentials expired: the {} are valid until {} only. Run `{}` to renew them.",
+                self.source.describe(),
+                self.expires_at_local(),
+                self.source.login_command()
+            )
+        })
+    }
+
+    /// Returns a warning if the session is not expired at `now` but expires
+    /// within `EXPIRY_WARNING_MINUTES`.
+    pub fn expiry_warning(&self, now: DateTime<Utc>) -> Option<String> {
+        (self.expires_soon(now) && !self.is_expired(now)).then(|| {
+            format!(
+                "Warning: the {} expire at {}. Run `{}` to renew them before uploads fail.",
+                self.source.describe(),
+                self.expires_at_local(),
+                self.source.login_command()
+            )
+        })
+    }
+
+    /// Returns S3 client options carrying the session credentials.
+    pub fn to_s3_options(&self) -> S3ClientOptions {
+        S3ClientOptions::default()
+            .with_access_key(self.access_key.clone())
+            .with_secret_key(self.secret_key.clone())
+            .with_session_token(self.session_token.clone())
+    }
+}
+
+/// Minimal STS client used to exchange long-term keys for MFA session credentials.
+#[derive(Debug, Clone)]
+pub struct StsClient {
+    inner: sts::Client,
+}
+
+impl StsClient {
+    /// Creates a client signing with the long-term keys. `endpoint` overrides
+    /// the global STS endpoint (`https://sts.amazonaws.com`).
+    pub fn new(access_key: &str, secret_key: &str, endpoint: Option<String>) -> Self {
+        let credentials = Credentials::new(access_key, secret_key, None, None, "user-supplied");
+
+        let mut cfg = sts::Config::builder()
+            .behavior_version(sts::config::BehaviorVersion::latest())
+            .credentials_provider(credentials)
+            .region(Region::new("us-east-1"));
+
+        if let Some(url) = endpoint {
+            cfg = cfg.endpoint_url(url);
+        }
+
+        Self {
+            inner: sts::Client::from_conf(cfg.build()),
+        }
+    }
+
+    /// Calls `GetSessionToken` with the TOTP `token_code` of the MFA device
+    /// `serial_number` and returns the temporary credentials.
+    pub async fn get_session_token(
+        &self,
+        serial_number: &str,
+        token_code: &str,
+        duration_secs: i32,
+    ) -> Result<SessionCredentials> {
+        let output = self
+            .inner
+            .get_session_token()
+            .serial_number(serial_number)
+            .token_code(token_code)
+            .duration_seconds(duration_secs)
+            .send()
+            .await
+            .map_err(|err| {
+                // Unmodeled errors such as AccessDenied only show their code,
+                // so surface the message that explains a wrong MFA code.
+                let err = sts::Error::from(err);
+                match err.message() {
+                    Some(message) => PrefixloadError::Custom(format!(
+                        "STS GetSessionToken failed ({}): {}",
+                        err.code().unwrap_or("unknown"),
+                        message
+                    )),
+                    None => err.into(),
+                }
+            })?;
+
+        let credentials = output.credentials().ok_or_else(|| {
+            PrefixloadError::Custom("STS returned no session credentials.".to_string())
+        })?;
+        let expires_at =
+            DateTime::from_timestamp(credentials.expiration().secs(), 0).ok_or_else(|| {
+                PrefixloadError::Custom("STS returned an invalid expiration time.".to_string())
+            })?;
+
+        Ok(SessionCredentials {
+            access_key: credentials.access_key_id().to_string(),
+            secret_key: credentials.secret_access_key().to_string(),
+            session_token: credentials.session_token().to_string(),
+            expires_at,
+            source: SessionSource::Mfa,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::env;
+    use tempfile::TempDir;
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[cfg(windows)]
+    const DATA_LOCAL_ENV: &str = "LOCALAPPDATA";
+    #[cfg(not(windows))]
+    const DATA_LOCAL_ENV: &str = "XDG_DATA_HOME";
+
+    fn temp_data_dir() -> TempDir {
+        let tmp = TempDir::new().expect("temp dir");
+        unsafe { env::set_var(DATA_LOCAL_ENV, tmp.path()) };
+        tmp
+    }
+
+    fn session(expires_at: DateTime<Utc>) -> SessionCredentials {
+        SessionCredentials {
+            access_key: "ASIAKEY".to_string(),
+            secret_key: "SECRET".to_string(),
+            session_token: "TOKEN".to_string(),
+            expires_at,
+            source: SessionSource::Mfa,
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn session_cache_roundtrip() {
+        let guard = temp_data_dir();
+        assert_eq!(SessionCredentials::load().unwrap(), None);
+
+        let saved = session(DateTime::from_timestamp(1_900_000_000, 0).unwrap());
+        saved.save().unwrap();
+        assert_eq!(SessionCredentials::load().unwrap(), Some(saved));
+
+        #[cfg(unix)]
+        {
+            let path = guard.path().join("prefixload/session.json");
+            let perms = fs::metadata(path).unwrap().permissions();
+            assert_eq!(perms.mode() & 0o777, 0o600);
+        }
+
+        assert!(SessionCredentials::clear().unwrap());
+        assert!(!SessionCredentials::clear().unwrap());
+        assert_eq!(SessionCredentials::load().unwrap(), None);
+    }
+
+    #[test]
+    fn session_expires_with_margin() {
+        let now = Utc::now();
+
+        assert!(session(now - Duration::hours(1)).is_expired(now));
+        assert!(session(now + Duration::minutes(2)).is_expired(now));
+        assert!(!session(now + Duration::hours(1)).is_expired(now));
+        assert!(session(now + Duration::minutes(30)).expires_soon(now));
+        assert!(!session(now + Duration::hours(2)).expires_soon(now));
+
+        let (expired, soon, later) = (
+            session(now - Duration::hours(1)),
+            session(now + Duration::minutes(30)),
+            session(now + Duration::hours(2)),
+        );
+        let error = expired.expiry_error(now).unwrap();
+        assert!(error.starts_with("Credentials expired: the MFA session credentials are valid"));
+        assert_eq!(expired.expiry_warning(now), None);
+        let warning = soon.expiry_warning(now).unwrap();
+        assert!(warning.contains("Run `prefixload login --mfa` to renew them"));
+        assert_eq!(soon.expiry_error(now), None);
+        assert_eq!(later.expiry_warning(now), None);
+
+        let opts = session(now).to_s3_options();
+        assert_eq!(opts.access_key, "ASIAKEY");
+        assert_eq!(opts.session_token, Some("TOKEN".to_string()));
+    }
+
+    #[tokio::test]
+    async fn get_session_token_parses_credentials() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("Action=GetSessionToken"))
+            .and(body_string_contains("TokenCode=123456"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"<GetSessionTokenResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <GetSessionTokenResult>
+    <Credentials>
+      <SessionToken>TOKEN</SessionToken>
+      <SecretAccessKey>SECRET</SecretAccessKey>
+      <Expiration>2030-01-01T12:00:00Z</Expiration>
+      <AccessKeyId>ASIAKEY</AccessKeyId>
+    </Credentials>
+  </GetSessionTokenResult>
+  <ResponseMetadata><RequestId>1</RequestId></ResponseMetadata>
+</GetSessionTokenResponse>"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let client = StsClient::new("AKIA", "LONGTERM", Some(server.uri()));
+        let session = client
+            .get_session_token("arn:aws:iam::123456789012:mfa/user", "123456", 3600)
+            .await
+            .unwrap();
+
+        assert_eq!(session.access_key, "ASIAKEY");
+        assert_eq!(session.session_token, "TOKEN");
+        assert_eq!(
+            session.expires_at,
+            DateTime::parse_from_rfc3339("2030-01-01T12:00:00Z").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn get_session_token_reports_invalid_code() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(403).set_body_string(
+                r#"<ErrorResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <Error><Type>Sender</Type><Code>AccessDenied</Code><Message>MultiFactorAuthentication failed with invalid MFA one time pass code.</Message></Error>
+  <RequestId>1</RequestId>
+</ErrorResponse>"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let client = StsClient::new("AKIA", "LONGTERM", Some(server.uri()));
+        let err = client
+            .get_session_token("arn:aws:iam::123456789012:mfa/user", "000000", 3600)
+            .await
+            .unwrap_err();
+
+        assert!(
+            err.to_string().contains("invalid MFA one time pass code"),
+            "{}",
+            err
+        );
+    }
+}
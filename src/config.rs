@@ -1,8 +1,23 @@
+use crate::clients::sns::topic_region;
+use crate::crypto::checksum::ChecksumAlgorithm;
 use crate::error::{PrefixloadError, Result};
+use crate::glob::glob_match;
+use crate::priority::{IoPriority, MAX_NICE};
+use crate::report::local_hostname;
+use crate::retention::{Retention, RetentionMode};
+use crate::size::ByteSize;
+use crate::timezone::LogTimezone;
+use crate::window::{OutsideWindow, TimeWindow};
 use rust_embed::RustEmbed;
-use serde::{Deserialize, Serialize};
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 /// Embeds the contents of the assets/ directory into the binary for access at runtime.
 /// Used for providing a default config.yml if one does not exist on disk.
@@ -11,22 +26,409 @@ use std::path::PathBuf;
 struct Asset;
 
 /// Represents a mapping from a file prefix to a cloud directory.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct DirectoryEntry {
     pub local_name_prefix: String,
     pub remote_path: String,
+    /// Checksum sent with every upload of this rule. Without it the SDK
+    /// default applies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<ChecksumAlgorithm>,
+    /// Upload the extended attributes (alternate data streams on Windows)
+    /// of every file as a `<key>.xattrs.json` sidecar object.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub xattrs: bool,
+    /// Directory this rule scans instead of the top-level
+    /// `local_directory_path`, so one config can back up several roots.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_directory_path: Option<PathBuf>,
+    /// Levels of subdirectories scanned for this rule; 0 scans only the
+    /// directory itself. Files in subdirectories keep their relative path
+    /// under `remote_path`, unless `preserve_structure` is `flatten`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub max_depth: u32,
+    /// Whether files in subdirectories keep their relative path under
+    /// `remote_path` (`true`, the default) or are uploaded right under it
+    /// (`flatten`).
+    #[serde(default, skip_serializing_if = "DirectoryStructure::is_preserve")]
+    pub preserve_structure: DirectoryStructure,
+    /// WASM filter plugin that decides which files of this rule are
+    /// uploaded and may change their keys. Needs a build with the
+    /// `wasm-plugins` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<PathBuf>,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+/// How the keys of files in subdirectories of a rule are built, written as
+/// `preserve_structure: true` or `preserve_structure: flatten`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DirectoryStructure {
+    /// `sub/dir/file` is uploaded as `remote_path/sub/dir/file`.
+    #[default]
+    Preserve,
+    /// `sub/dir/file` is uploaded as `remote_path/file`. Two files of the
+    /// same name in different subdirectories fail the run.
+    Flatten,
+}
+
+impl DirectoryStructure {
+    fn is_preserve(&self) -> bool {
+        *self == Self::Preserve
+    }
+}
+
+impl Serialize for DirectoryStructure {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Self::Preserve => serializer.serialize_bool(true),
+            Self::Flatten => serializer.serialize_str("flatten"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DirectoryStructure {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Bool(bool),
+            Name(String),
+        }
+
+        match Value::deserialize(deserializer)? {
+            Value::Bool(true) => Ok(Self::Preserve),
+            Value::Name(name) if name == "flatten" => Ok(Self::Flatten),
+            _ => Err(serde::de::Error::custom(
+                "invalid preserve_structure: use true or flatten",
+            )),
+        }
+    }
+}
+
+impl JsonSchema for DirectoryStructure {
+    fn schema_name() -> Cow<'static, str> {
+        "DirectoryStructure".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "true keeps the subdirectories of files in their keys, flatten drops them",
+            "enum": [true, "flatten"]
+        })
+    }
+}
+
+/// A named subset of the backup, executed with `prefixload run --job <name>`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    pub name: String,
+    /// AWS profile to load credentials from instead of `default`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// `local_name_prefix` values of the `directory_struct` rules this job
+    /// uploads. An empty list means all rules.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<String>,
+    /// When the job should run: a cron expression or an interval such as `daily at 03:00`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+}
+
+/// A file of `include`: rules added to the `directory_struct` of the config.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    #[serde(default)]
+    directory_struct: Vec<DirectoryEntry>,
+}
+
+/// Settings of a `hosts` entry that replace the top-level ones on that host,
+/// so one config file can be shared by a whole fleet.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct HostOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_directory_path: Option<PathBuf>,
+    /// Rules of the host, replacing the top-level rules of `config.yml`.
+    /// Rules of `include` files are still added after them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory_struct: Option<Vec<DirectoryEntry>>,
+    /// Jobs of the host, replacing all top-level jobs and their schedules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<Vec<Job>>,
+}
+
+/// Flags of `prefixload run` that apply when they are not given on the
+/// command line, so scheduled invocations stay short.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RunDefaults {
+    /// Like `--quiet`: write everything to the log file instead of stdout.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub quiet: bool,
+    /// Like `--summary-only`: print only the summary of the run.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub summary_only: bool,
+    /// Like `--report`: write the JSON report of every run to this path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<PathBuf>,
+    /// Like `--keep-going`: upload the other files after one fails.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keep_going: bool,
+    /// Only `false` is accepted: `prefixload run` has no dry-run mode.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+impl RunDefaults {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Order in which `run` starts the uploads of the matched files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadOrder {
+    SmallestFirst,
+    LargestFirst,
+    /// By modification time, oldest first.
+    OldestFirst,
+}
+
+impl FromStr for UploadOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "smallest_first" => Ok(Self::SmallestFirst),
+            "largest_first" => Ok(Self::LargestFirst),
+            "oldest_first" => Ok(Self::OldestFirst),
+            _ => Err(format!(
+                "invalid upload order '{}': use 'smallest_first', 'largest_first' or 'oldest_first'",
+                s
+            )),
+        }
+    }
+}
+
+/// Smallest part size S3 accepts for every part but the last one of a multipart upload.
+pub const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Largest part size S3 accepts.
+pub const MAX_PART_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Maximum number of parts in a single multipart upload.
+pub const MAX_PARTS: u64 = 10_000;
+
+/// Largest object S3 accepts.
+pub const MAX_OBJECT_SIZE: u64 = 5 * 1024 * 1024 * 1024 * 1024;
+
+/// Days of run history `state gc` keeps when `run_history_days` is not set.
+pub const DEFAULT_RUN_HISTORY_DAYS: u32 = 90;
+
+/// Prefix of the environment variables that hold the settings in container mode,
+/// e.g. `PREFIXLOAD_BUCKET` for `bucket`.
+pub const ENV_PREFIX: &str = "PREFIXLOAD_";
+
+/// Environment variable that enables container mode for every command.
+pub const CONTAINER_ENV: &str = "PREFIXLOAD_CONTAINER";
+
+/// Environment variable that answers every confirmation prompt with yes,
+/// like the global `--yes` flag.
+pub const ASSUME_YES_ENV: &str = "PREFIXLOAD_ASSUME_YES";
+
+/// Returns `true` if `PREFIXLOAD_CONTAINER` is set to `1`, `true` or `yes`.
+pub fn container_mode() -> bool {
+    env_flag(CONTAINER_ENV)
+}
+
+/// Returns `true` if `PREFIXLOAD_ASSUME_YES` is set to `1`, `true` or `yes`.
+pub fn assume_yes() -> bool {
+    env_flag(ASSUME_YES_ENV)
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes"
+        )
+    })
 }
 
 /// Represents the application's YAML configuration file.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub endpoint: String,
     pub bucket: String,
     pub region: String,
     pub force_path_style: bool,
-    pub part_size: u64,
+    pub part_size: ByteSize,
     pub local_directory_path: PathBuf,
     pub directory_struct: Vec<DirectoryEntry>,
+    /// Lower bound for parallel uploads while S3 is throttling requests.
+    #[serde(default = "default_min_concurrency")]
+    pub min_concurrency: usize,
+    /// Upper bound for parallel uploads; runs start at this value.
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    /// Order in which the uploads of a run start; the order the files are
+    /// found in when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_order: Option<UploadOrder>,
+    /// Upload each run's log and JSON report to the `_logs/` prefix of the bucket.
+    #[serde(default)]
+    pub upload_run_log: bool,
+    /// Minimum number of minutes between successful runs; earlier runs exit early.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_interval: Option<u64>,
+    /// Maximum number of minutes a run may take; remaining uploads are aborted after it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_duration: Option<u64>,
+    /// Minutes a run reuses the listing of a remote path made by an earlier
+    /// run instead of listing it again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listing_cache_minutes: Option<u64>,
+    /// Local time window uploads are allowed in, e.g. `22:00-06:00`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_window: Option<TimeWindow>,
+    /// What `run` does outside `upload_window`: `refuse` to start or `wait` for it.
+    #[serde(default)]
+    pub outside_window: OutsideWindow,
+    /// File holding the S3 access key, read at startup instead of the AWS profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_key_file: Option<PathBuf>,
+    /// File holding the S3 secret key, read at startup instead of the AWS profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_key_file: Option<PathBuf>,
+    /// Experimental: upload files as content-defined chunks under `chunks/`
+    /// plus a per-file index object, so only changed chunks are sent.
+    #[serde(default)]
+    pub dedup: bool,
+    /// Create the keys of files whose content another file of the same run
+    /// already uploaded with a server-side copy. Without it such duplicates
+    /// are only recorded in the run report.
+    #[serde(default)]
+    pub copy_duplicates: bool,
+    /// Number of threads that hash files. Without it hashing uses one
+    /// thread per CPU core.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_threads: Option<usize>,
+    /// Total size of the upload parts held in memory at the same time across
+    /// all parallel uploads; uploads wait for room instead of exceeding it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_buffer_memory: Option<ByteSize>,
+    /// Read files for hashing and multipart uploads through io_uring. Needs
+    /// Linux and a build with the `io-uring` feature; ignored otherwise.
+    #[serde(default)]
+    pub io_uring: bool,
+    /// Nice value (0-19) runs lower their CPU priority to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    /// I/O priority runs lower their disk access to (Linux only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ionice: Option<IoPriority>,
+    /// Leave out matched files of zero bytes, such as placeholders created
+    /// with `touch`, instead of uploading them.
+    #[serde(default)]
+    pub skip_empty: bool,
+    /// Files larger than this are left out with a warning instead of being
+    /// uploaded. Files above the S3 object size limit (5TiB) always are.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<ByteSize>,
+    /// Timezone of the timestamps in the run log.
+    #[serde(default)]
+    pub log_timezone: LogTimezone,
+    /// Write run start, finish and failure events to the Windows Event Log.
+    /// Runs on other platforms log a warning instead.
+    #[serde(default)]
+    pub event_log: bool,
+    /// ARN of an SNS topic every run publishes its summary to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sns_topic_arn: Option<String>,
+    /// URL of a fleet collector every run POSTs its report to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report_url: Option<String>,
+    /// File holding the key the reports sent to `report_url` are signed with
+    /// (HMAC-SHA256).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report_key_file: Option<PathBuf>,
+    /// Local port the daemon serves `/metrics` and `/healthz` on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_port: Option<u16>,
+    /// Address the daemon serves its gRPC control service on, with mutual
+    /// TLS. Needs a build with the `grpc` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_listen: Option<SocketAddr>,
+    /// PEM certificate chain the gRPC control service presents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_cert_file: Option<PathBuf>,
+    /// PEM private key of `grpc_cert_file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_key_file: Option<PathBuf>,
+    /// PEM CA certificate that signs the certificates of allowed gRPC clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_client_ca_file: Option<PathBuf>,
+    /// Object Lock mode set on uploaded objects, `governance` or `compliance`.
+    /// The bucket needs Object Lock enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_lock_mode: Option<RetentionMode>,
+    /// Days uploaded objects are retained under `object_lock_mode`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_lock_days: Option<u32>,
+    /// Refuse every request that would change the bucket (uploads, copies,
+    /// multipart uploads, deletes, legal holds), to try out a configuration
+    /// without writing anything. Also enabled by the global `--read-only` flag.
+    #[serde(default)]
+    pub read_only: bool,
+    /// Combined rate of all downloads of `restore`, in bytes per second.
+    /// Uploads are not limited by it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_download_rate: Option<ByteSize>,
+    /// Days of run history `state gc` keeps; 90 when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_history_days: Option<u32>,
+    /// Flags of `run` that apply when they are not given on the command line.
+    #[serde(default, skip_serializing_if = "RunDefaults::is_empty")]
+    pub defaults: RunDefaults,
+    /// Named jobs, each running a subset of `directory_struct`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub jobs: Vec<Job>,
+    /// Files whose `directory_struct` rules are added to the ones of this
+    /// file, e.g. `rules.d/*.yml`. Relative paths start at the config
+    /// directory; `*` and `?` match within file names.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Rules added from `include` files, by `local_name_prefix`, with the
+    /// file they come from. They are left out when the config is saved.
+    #[serde(skip)]
+    pub included_rules: BTreeMap<String, PathBuf>,
+    /// Settings that differ per host, by hostname. A host matches by its
+    /// full hostname or its first label (`db-01` matches `db-01.example.com`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hosts: BTreeMap<String, HostOverrides>,
+}
+
+fn default_min_concurrency() -> usize {
+    1
+}
+
+fn default_max_concurrency() -> usize {
+    4
 }
 
 /// Returns the default text editor command for this platform.
@@ -47,7 +449,7 @@ impl Config {
     /// Returns the full path to the platform-native config file.
     /// - Linux/macOS: ~/.config/prefixload/config.yml
     /// - Windows: %APPDATA%\prefixload\config.yml
-    fn config_path() -> Result<PathBuf> {
+    pub(crate) fn config_path() -> Result<PathBuf> {
         let mut dir = dirs_next::config_dir()
             .ok_or_else(|| PrefixloadError::Custom("Failed to get config directory".into()))?;
 
@@ -97,23 +499,502 @@ impl Config {
     }
 
     /// Loads the configuration from disk and deserialises it into a
-    /// typed `Config` struct.
+    /// typed `Config` struct, with the `hosts` entry of this host applied.
     ///
-    /// Fails if YAML is syntactically invalid or cannot be read.
+    /// Fails if YAML is syntactically invalid, cannot be read, holds keys
+    /// no setting reads (with a suggestion for misspelled ones) or values S3
+    /// would reject (see [`Config::validate`]).
     pub fn load() -> Result<Self> {
-        let s = Self::read_to_string()?;
-        Ok(serde_yaml::from_str(&s)?)
+        Ok(Self::load_file()?.for_host(&local_hostname()))
+    }
+
+    /// Loads the configuration like [`Config::load`], but as written in the
+    /// file, without applying the `hosts` entry of this host, for commands
+    /// that change and save it.
+    pub fn load_file() -> Result<Self> {
+        Self::parse(&Self::read_to_string()?)
+    }
+
+    /// Deserialises and validates the contents of a config file, with the
+    /// same checks as [`Config::load`]. The rules of its `include` files
+    /// are added first.
+    pub fn parse(yaml: &str) -> Result<Self> {
+        let value: serde_yaml::Value = serde_yaml::from_str(yaml)?;
+        reject_unknown_keys(&value, "config.yml")?;
+        let mut config: Self = serde_yaml::from_str(yaml)?;
+        if !config.include.is_empty() {
+            let path = Self::config_path()?;
+            config.add_included_rules(path.parent().unwrap_or(Path::new(".")))?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Adds the rules of the `include` files, relative ones resolved
+    /// against `base`, to `directory_struct`.
+    fn add_included_rules(&mut self, base: &Path) -> Result<()> {
+        for file in include_files(&self.include, base)? {
+            let yaml = fs::read_to_string(&file)?;
+            let rules: RuleFile = serde_yaml::from_str(&yaml).map_err(|e| {
+                PrefixloadError::Custom(format!("Invalid rule file {}: {}", file.display(), e))
+            })?;
+            for rule in rules.directory_struct {
+                if self
+                    .directory_struct
+                    .iter()
+                    .any(|existing| existing.local_name_prefix == rule.local_name_prefix)
+                {
+                    return Err(PrefixloadError::Custom(format!(
+                        "Rule '{}' of {} is already defined.",
+                        rule.local_name_prefix,
+                        file.display()
+                    )));
+                }
+                self.included_rules
+                    .insert(rule.local_name_prefix.clone(), file.clone());
+                self.directory_struct.push(rule);
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the configuration from `PREFIXLOAD_*` environment variables
+    /// instead of `config.yml`, for container mode. Each variable sets the
+    /// field of the same name (`PREFIXLOAD_PART_SIZE` → `part_size`) and its
+    /// value is parsed as YAML, so lists such as `directory_struct` can be
+    /// given in flow style: `[{local_name_prefix: db_, remote_path: db/}]`.
+    ///
+    /// Nothing is read from or written to the config directory.
+    pub fn from_env() -> Result<Self> {
+        let mut fields = serde_yaml::Mapping::new();
+
+        for (key, value) in std::env::vars() {
+            let Some(field) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            if key == CONTAINER_ENV || key == ASSUME_YES_ENV {
+                continue;
+            }
+
+            let value: serde_yaml::Value = serde_yaml::from_str(&value).map_err(|err| {
+                PrefixloadError::Custom(format!("Invalid value in {}: {}", key, err))
+            })?;
+            fields.insert(field.to_ascii_lowercase().into(), value);
+        }
+
+        let fields = serde_yaml::Value::Mapping(fields);
+        reject_unknown_keys(&fields, &format!("{}* environment variables", ENV_PREFIX))?;
+        let mut config: Self = serde_yaml::from_value(fields).map_err(|err| {
+            PrefixloadError::Custom(format!(
+                "Invalid configuration in {}* environment variables: {}",
+                ENV_PREFIX, err
+            ))
+        })?;
+        // Without a config directory, relative includes start at the
+        // working directory.
+        config.add_included_rules(Path::new("."))?;
+        config.validate()?;
+        Ok(config.for_host(&local_hostname()))
+    }
+
+    /// Returns the config as it applies on `hostname`: the settings of its
+    /// `hosts` entry replace the top-level ones. Rules added from `include`
+    /// files are kept after the `directory_struct` of the entry.
+    pub fn for_host(mut self, hostname: &str) -> Self {
+        let short_name = hostname.split('.').next().unwrap_or(hostname);
+        let overrides = self
+            .hosts
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(hostname))
+            .or_else(|| {
+                self.hosts
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(short_name))
+            })
+            .map(|(_, overrides)| overrides.clone());
+        let Some(overrides) = overrides else {
+            return self;
+        };
+
+        if let Some(endpoint) = overrides.endpoint {
+            self.endpoint = endpoint;
+        }
+        if let Some(bucket) = overrides.bucket {
+            self.bucket = bucket;
+        }
+        if let Some(region) = overrides.region {
+            self.region = region;
+        }
+        if let Some(path) = overrides.local_directory_path {
+            self.local_directory_path = path;
+        }
+        if let Some(rules) = overrides.directory_struct {
+            // Included rules apply on every host, after the ones of its entry.
+            let included = std::mem::replace(&mut self.directory_struct, rules)
+                .into_iter()
+                .filter(|rule| self.included_rules.contains_key(&rule.local_name_prefix));
+            self.directory_struct.extend(included);
+        }
+        if let Some(jobs) = overrides.jobs {
+            self.jobs = jobs;
+        }
+        self
+    }
+
+    /// Checks values that parse fine but would fail later on: part sizes
+    /// S3 rejects, an empty hashing pool, a buffer budget that cannot hold a
+    /// part, nice values out of range, malformed SNS topic ARNs, jobs that
+    /// are duplicated or refer to unknown rules, and `hosts` entries that
+    /// would make any of these fail on their host.
+    pub fn validate(&self) -> Result<()> {
+        let part_size = self.part_size.as_u64();
+
+        if part_size < MIN_PART_SIZE {
+            return Err(PrefixloadError::Custom(format!(
+                "part_size {} is below the S3 minimum part size. Set part_size to at least {}.",
+                self.part_size,
+                ByteSize(MIN_PART_SIZE).to_exact_string()
+            )));
+        }
+        if part_size > MAX_PART_SIZE {
+            return Err(PrefixloadError::Custom(format!(
+                "part_size {} is above the S3 maximum part size. Set part_size to at most {}.",
+                self.part_size,
+                ByteSize(MAX_PART_SIZE).to_exact_string()
+            )));
+        }
+
+        if self.hash_threads == Some(0) {
+            return Err(PrefixloadError::Custom(
+                "hash_threads must be at least 1.".to_string(),
+            ));
+        }
+
+        if self.defaults.quiet && self.defaults.summary_only {
+            return Err(PrefixloadError::Custom(
+                "defaults.quiet and defaults.summary_only cannot both be set.".to_string(),
+            ));
+        }
+
+        if self.defaults.dry_run {
+            return Err(PrefixloadError::Custom(
+                "defaults.dry_run: true is not supported: `prefixload run` has no dry-run \
+                 mode. Remove it from config.yml or set it to false."
+                    .to_string(),
+            ));
+        }
+
+        if self.run_history_days == Some(0) {
+            return Err(PrefixloadError::Custom(
+                "run_history_days must be at least 1.".to_string(),
+            ));
+        }
+
+        if let Some(budget) = self.max_buffer_memory
+            && budget.as_u64() < part_size
+        {
+            return Err(PrefixloadError::Custom(format!(
+                "max_buffer_memory {} cannot hold a single part. Set max_buffer_memory to at least part_size ({}).",
+                budget,
+                self.part_size.to_exact_string()
+            )));
+        }
+
+        if self
+            .max_download_rate
+            .is_some_and(|rate| rate.as_u64() == 0)
+        {
+            return Err(PrefixloadError::Custom(
+                "max_download_rate must be greater than 0. Leave it unset for unlimited downloads."
+                    .to_string(),
+            ));
+        }
+
+        if let Some(nice) = self.nice
+            && !(0..=MAX_NICE).contains(&nice)
+        {
+            return Err(PrefixloadError::Custom(format!(
+                "nice {} is out of range. Set nice between 0 and {}.",
+                nice, MAX_NICE
+            )));
+        }
+
+        if let Some(arn) = &self.sns_topic_arn
+            && topic_region(arn).is_none()
+        {
+            return Err(PrefixloadError::Custom(format!(
+                "sns_topic_arn '{}' is not an SNS topic ARN. Use the form \
+                 arn:aws:sns:<region>:<account>:<topic>.",
+                arn
+            )));
+        }
+
+        if let Some(url) = &self.report_url {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                return Err(PrefixloadError::Custom(format!(
+                    "report_url '{}' is not an HTTP(S) URL.",
+                    url
+                )));
+            }
+            if self.report_key_file.is_none() {
+                return Err(PrefixloadError::Custom(
+                    "report_url needs report_key_file, the key reports are signed with."
+                        .to_string(),
+                ));
+            }
+        }
+
+        if let Some(addr) = self.grpc_listen
+            && (self.grpc_cert_file.is_none()
+                || self.grpc_key_file.is_none()
+                || self.grpc_client_ca_file.is_none())
+        {
+            return Err(PrefixloadError::Custom(format!(
+                "grpc_listen {} needs mutual TLS. Set grpc_cert_file, grpc_key_file \
+                 and grpc_client_ca_file.",
+                addr
+            )));
+        }
+
+        match (self.object_lock_mode, self.object_lock_days) {
+            (Some(_), None) => {
+                return Err(PrefixloadError::Custom(
+                    "object_lock_mode needs object_lock_days, the retention period.".to_string(),
+                ));
+            }
+            (None, Some(_)) => {
+                return Err(PrefixloadError::Custom(
+                    "object_lock_days needs object_lock_mode (governance or compliance)."
+                        .to_string(),
+                ));
+            }
+            (_, Some(0)) => {
+                return Err(PrefixloadError::Custom(
+                    "object_lock_days must be at least 1.".to_string(),
+                ));
+            }
+            _ => {}
+        }
+
+        let mut names = HashSet::new();
+        for job in &self.jobs {
+            if !names.insert(job.name.as_str()) {
+                return Err(PrefixloadError::Custom(format!(
+                    "Job '{}' is defined more than once.",
+                    job.name
+                )));
+            }
+            self.rules_for_job(job)?;
+        }
+
+        for name in self.hosts.keys() {
+            let mut on_host = self.clone().for_host(name);
+            on_host.hosts.clear();
+            on_host.validate().map_err(|err| match err {
+                PrefixloadError::Custom(msg) => {
+                    PrefixloadError::Custom(format!("hosts.{}: {}", name, msg))
+                }
+                err => err,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the Object Lock retention of objects uploaded at `now`, if
+    /// `object_lock_mode` is set.
+    pub fn retention(&self, now: chrono::DateTime<chrono::Utc>) -> Option<Retention> {
+        Some(Retention::new(
+            self.object_lock_mode?,
+            self.object_lock_days?,
+            now,
+        ))
+    }
+
+    /// Looks up a job by name.
+    pub fn job(&self, name: &str) -> Result<&Job> {
+        self.jobs
+            .iter()
+            .find(|job| job.name == name)
+            .ok_or_else(|| {
+                let known: Vec<&str> = self.jobs.iter().map(|job| job.name.as_str()).collect();
+                PrefixloadError::Custom(if known.is_empty() {
+                    format!(
+                        "Job '{}' not found: no jobs are defined in the config.",
+                        name
+                    )
+                } else {
+                    format!(
+                        "Job '{}' not found. Known jobs: {}.",
+                        name,
+                        known.join(", ")
+                    )
+                })
+            })
+    }
+
+    /// Returns the `directory_struct` rules a job uploads, in config order.
+    pub fn rules_for_job(&self, job: &Job) -> Result<Vec<DirectoryEntry>> {
+        if job.rules.is_empty() {
+            return Ok(self.directory_struct.clone());
+        }
+
+        if let Some(unknown) = job.rules.iter().find(|prefix| {
+            !self
+                .directory_struct
+                .iter()
+                .any(|entry| &entry.local_name_prefix == *prefix)
+        }) {
+            return Err(PrefixloadError::Custom(format!(
+                "Job '{}' refers to unknown rule '{}'. Rules are referenced by local_name_prefix.",
+                job.name, unknown
+            )));
+        }
+
+        Ok(self
+            .directory_struct
+            .iter()
+            .filter(|entry| job.rules.contains(&entry.local_name_prefix))
+            .cloned()
+            .collect())
+    }
+
+    /// Returns the directory `rule` scans: its own `local_directory_path`,
+    /// or the top-level one.
+    pub fn rule_directory<'a>(&'a self, rule: &'a DirectoryEntry) -> &'a Path {
+        rule.local_directory_path
+            .as_deref()
+            .unwrap_or(&self.local_directory_path)
+    }
+
+    /// Groups `rules` by the directory they scan, in the order the
+    /// directories first appear. Rules keep their order within a group, so
+    /// the first matching rule still wins.
+    pub fn rules_by_directory<'a>(
+        &'a self,
+        rules: &'a [DirectoryEntry],
+    ) -> Vec<(&'a Path, Vec<DirectoryEntry>)> {
+        let mut groups: Vec<(&Path, Vec<DirectoryEntry>)> = Vec::new();
+        for rule in rules {
+            let directory = self.rule_directory(rule);
+            match groups.iter_mut().find(|(dir, _)| *dir == directory) {
+                Some((_, group)) => group.push(rule.clone()),
+                None => groups.push((directory, vec![rule.clone()])),
+            }
+        }
+        groups
+    }
+
+    /// Returns a warning for every pair of `rules` that scan the same
+    /// directory where one `local_name_prefix` starts with the other. Since
+    /// the first matching rule wins, the warning names the rule that gets the
+    /// files both prefixes match.
+    pub fn overlapping_rules(&self, rules: &[DirectoryEntry]) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (directory, group) in self.rules_by_directory(rules) {
+            for (index, first) in group.iter().enumerate() {
+                for second in &group[index + 1..] {
+                    let (first_prefix, second_prefix) =
+                        (&first.local_name_prefix, &second.local_name_prefix);
+                    if first_prefix == second_prefix {
+                        warnings.push(format!(
+                            "Rule '{}' is defined twice for {}: files match the first one and \
+                             are uploaded to '{}', not '{}'.",
+                            first_prefix,
+                            directory.display(),
+                            first.remote_path,
+                            second.remote_path
+                        ));
+                    } else if second_prefix.starts_with(first_prefix.as_str()) {
+                        warnings.push(format!(
+                            "Rules '{}' and '{}' overlap in {}: files starting with '{}' match \
+                             '{}' first and are uploaded to '{}', not '{}'. List '{}' before \
+                             '{}' to route them to '{}'.",
+                            first_prefix,
+                            second_prefix,
+                            directory.display(),
+                            second_prefix,
+                            first_prefix,
+                            first.remote_path,
+                            second.remote_path,
+                            second_prefix,
+                            first_prefix,
+                            second.remote_path
+                        ));
+                    } else if first_prefix.starts_with(second_prefix.as_str()) {
+                        warnings.push(format!(
+                            "Rules '{}' and '{}' overlap in {}: files starting with '{}' are \
+                             uploaded to '{}' since '{}' comes first; the other files starting \
+                             with '{}' go to '{}'.",
+                            first_prefix,
+                            second_prefix,
+                            directory.display(),
+                            first_prefix,
+                            first.remote_path,
+                            first_prefix,
+                            second_prefix,
+                            second.remote_path
+                        ));
+                    }
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Returns the size of the largest file a run uploads: `max_file_size`,
+    /// capped at the S3 object size limit.
+    pub fn file_size_limit(&self) -> u64 {
+        self.max_file_size
+            .map_or(MAX_OBJECT_SIZE, |size| size.as_u64().min(MAX_OBJECT_SIZE))
+    }
+
+    /// Returns the days of run history `state gc` keeps: `run_history_days`,
+    /// or `DEFAULT_RUN_HISTORY_DAYS`.
+    pub fn run_history_days(&self) -> u32 {
+        self.run_history_days.unwrap_or(DEFAULT_RUN_HISTORY_DAYS)
+    }
+
+    /// Checks that a file of `file_size` bytes fits into `MAX_PARTS` parts.
+    ///
+    /// The error names the smallest part size that would work.
+    pub fn check_part_count(&self, file_name: &str, file_size: u64) -> Result<()> {
+        let part_size = self.part_size.as_u64();
+        let parts = file_size.div_ceil(part_size);
+
+        if parts > MAX_PARTS {
+            let required = ByteSize(file_size.div_ceil(MAX_PARTS).next_multiple_of(1024 * 1024));
+            return Err(PrefixloadError::Custom(format!(
+                "File {} ({}) would need {} parts with part_size {}, but S3 allows at most {}. \
+                 Set part_size to at least {}.",
+                file_name,
+                ByteSize(file_size),
+                parts,
+                self.part_size,
+                MAX_PARTS,
+                required.to_exact_string()
+            )));
+        }
+
+        Ok(())
     }
 
     /// Persists the current `Config` instance to disk in YAML form.
     ///
     /// *Creates/updates* `config.yml.bak` before overwriting the primary file
-    /// to guard against accidental data loss.
+    /// to guard against accidental data loss. Invalid values are rejected
+    /// before anything is written.
     pub fn save(&self) -> Result<()> {
+        self.validate()?;
+
         let path = Self::config_path()?;
         Self::backup_config()?;
 
-        let s = serde_yaml::to_string(self)?;
+        // Included rules stay in their own files.
+        let mut file = self.clone();
+        file.directory_struct
+            .retain(|rule| !self.included_rules.contains_key(&rule.local_name_prefix));
+        let s = serde_yaml::to_string(&file)?;
         fs::write(path, s)?;
 
         Ok(())
@@ -124,8 +1005,17 @@ impl Config {
     ///
     /// Before editing a **backup** is created as `config.yml.bak`.
     pub fn edit() -> Result<()> {
-        let path = Self::config_path()?;
+        Self::check_editor_allowed()?;
         Self::backup_config()?;
+        Self::open_editor()
+    }
+
+    /// Opens the configuration file in the editor again, keeping the backup
+    /// of the previous [`Config::edit`], e.g. to fix a file that no longer
+    /// loads.
+    pub fn open_editor() -> Result<()> {
+        Self::check_editor_allowed()?;
+        let path = Self::config_path()?;
 
         // Try $EDITOR, otherwise platform default
         let editor = std::env::var("EDITOR").unwrap_or_else(|_| default_editor());
@@ -138,6 +1028,321 @@ impl Config {
 
         Ok(())
     }
+
+    /// Replaces the config file with `config.yml.bak`, undoing the last
+    /// [`Config::edit`] or [`Config::save`].
+    pub fn restore_backup() -> Result<()> {
+        let path = Self::config_path()?;
+        let mut backup_path = path.clone();
+        backup_path.set_extension("yml.bak");
+
+        if !backup_path.exists() {
+            return Err(PrefixloadError::Custom(format!(
+                "No backup to restore: {} does not exist.",
+                backup_path.display()
+            )));
+        }
+        fs::copy(&backup_path, &path)?;
+
+        Ok(())
+    }
+
+    fn check_editor_allowed() -> Result<()> {
+        if container_mode() {
+            return Err(PrefixloadError::Custom(format!(
+                "The editor is disabled in container mode ({} is set). Set {}* environment variables instead.",
+                CONTAINER_ENV, ENV_PREFIX
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A top-level field whose value differs between two configs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    /// The value in the first config, `None` if it leaves the field unset.
+    pub before: Option<String>,
+    /// The value in the second config, `None` if it leaves the field unset.
+    pub after: Option<String>,
+}
+
+/// A key in the config that no setting reads, usually a misspelled one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownKey {
+    /// Dotted path of the key, e.g. `directory_struct.0.remot_path`.
+    pub path: String,
+    /// The known key the unknown one most likely misspells.
+    pub suggestion: Option<&'static str>,
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, " (did you mean '{}'?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of comparing `config.yml` with the embedded default config.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigDiff {
+    pub fields: Vec<FieldDiff>,
+    pub unknown_keys: Vec<UnknownKey>,
+}
+
+impl Config {
+    /// Compares the config file with the embedded default config, to find
+    /// the settings that take effect and the keys that are ignored.
+    pub fn diff_defaults() -> Result<ConfigDiff> {
+        Self::diff_yaml(&Self::read_to_string()?)
+    }
+
+    /// Returns a JSON Schema of the config file, generated from `Config`,
+    /// for editors and CI pipelines that check `config.yml` before it is
+    /// deployed.
+    pub fn json_schema() -> schemars::Schema {
+        schemars::schema_for!(Config)
+    }
+
+    /// Compares the given YAML config with the embedded default config.
+    ///
+    /// Unknown keys are reported instead of rejected, so the rest can still
+    /// be compared. Values are compared after deserialization, so `15MB` and
+    /// `15MiB` are equal.
+    pub fn diff_yaml(yaml: &str) -> Result<ConfigDiff> {
+        let default_yaml = Asset::get("config.yml")
+            .expect("Embedded config.yml not found")
+            .data;
+        let default: Self = serde_yaml::from_slice(&default_yaml)?;
+        let mut current: serde_yaml::Value = serde_yaml::from_str(yaml)?;
+        let unknown_keys = take_unknown_keys(&mut current);
+        let current: Self = serde_yaml::from_value(current)?;
+
+        Ok(ConfigDiff {
+            fields: Self::diff_fields(&default, &current)?,
+            unknown_keys,
+        })
+    }
+
+    /// Lists the top-level fields whose values differ between `before` and
+    /// `after`, in the order `Config` declares them.
+    pub fn diff_fields(before: &Self, after: &Self) -> Result<Vec<FieldDiff>> {
+        let before = serde_yaml::to_value(before)?;
+        let after = serde_yaml::to_value(after)?;
+
+        Ok(field_names::<Self>()
+            .iter()
+            .filter_map(|&field| {
+                let before = before.get(field);
+                let after = after.get(field);
+                (before != after).then(|| FieldDiff {
+                    field,
+                    before: before.map(display_value),
+                    after: after.map(display_value),
+                })
+            })
+            .collect())
+    }
+}
+
+/// Returns the files of the `include` patterns, relative ones resolved
+/// against `base`: the files of each pattern in name order. A pattern
+/// with wildcards in a missing directory names no files; a file named
+/// without wildcards must exist.
+pub fn include_files(patterns: &[String], base: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let pattern = base.join(pattern);
+        let name = pattern
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !name.contains(['*', '?']) {
+            if !pattern.is_file() {
+                return Err(PrefixloadError::Custom(format!(
+                    "Included rule file {} not found.",
+                    pattern.display()
+                )));
+            }
+            files.push(pattern);
+            continue;
+        }
+
+        let dir = pattern.parent().unwrap_or(base);
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        let mut matched: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .file_name()
+                        .is_some_and(|file| glob_match(&name, &file.to_string_lossy()))
+            })
+            .collect();
+        matched.sort();
+        files.extend(matched);
+    }
+    Ok(files)
+}
+
+/// Renders a YAML value on a single line: scalars as written in YAML,
+/// lists and mappings in JSON flow style.
+fn display_value(value: &serde_yaml::Value) -> String {
+    match value {
+        serde_yaml::Value::Sequence(_) | serde_yaml::Value::Mapping(_) => {
+            serde_json::to_string(value).unwrap_or_else(|_| format!("{:?}", value))
+        }
+        _ => serde_yaml::to_string(value)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Removes the keys of `config` that neither `Config`, its `defaults` nor
+/// the entries of its `directory_struct` and `jobs` lists read, and returns
+/// them.
+fn take_unknown_keys(config: &mut serde_yaml::Value) -> Vec<UnknownKey> {
+    let mut unknown = Vec::new();
+    retain_known_keys(config, field_names::<Config>(), "", &mut unknown);
+    if let Some(defaults) = config.get_mut("defaults") {
+        retain_known_keys(
+            defaults,
+            field_names::<RunDefaults>(),
+            "defaults.",
+            &mut unknown,
+        );
+    }
+
+    retain_known_list_keys(config, "", &mut unknown);
+
+    if let Some(hosts) = config.get_mut("hosts").and_then(|v| v.as_mapping_mut()) {
+        for (name, overrides) in hosts.iter_mut() {
+            let prefix = format!("hosts.{}.", display_value(name));
+            retain_known_keys(
+                overrides,
+                field_names::<HostOverrides>(),
+                &prefix,
+                &mut unknown,
+            );
+            retain_known_list_keys(overrides, &prefix, &mut unknown);
+        }
+    }
+
+    unknown
+}
+
+/// Removes the unknown keys of the `directory_struct` and `jobs` entries
+/// of `config`, whose keys start with `prefix`.
+fn retain_known_list_keys(
+    config: &mut serde_yaml::Value,
+    prefix: &str,
+    unknown: &mut Vec<UnknownKey>,
+) {
+    for (list, fields) in [
+        ("directory_struct", field_names::<DirectoryEntry>()),
+        ("jobs", field_names::<Job>()),
+    ] {
+        if let Some(entries) = config.get_mut(list).and_then(|v| v.as_sequence_mut()) {
+            for (i, entry) in entries.iter_mut().enumerate() {
+                let prefix = format!("{}{}.{}.", prefix, list, i);
+                retain_known_keys(entry, fields, &prefix, unknown);
+            }
+        }
+    }
+}
+
+fn retain_known_keys(
+    value: &mut serde_yaml::Value,
+    fields: &'static [&'static str],
+    prefix: &str,
+    unknown: &mut Vec<UnknownKey>,
+) {
+    let Some(mapping) = value.as_mapping_mut() else {
+        return;
+    };
+
+    mapping.retain(|key, _| {
+        let key = match key.as_str() {
+            Some(key) => key.to_string(),
+            None => display_value(key),
+        };
+        if fields.contains(&key.as_str()) {
+            return true;
+        }
+
+        let suggestion = fields
+            .iter()
+            .map(|&field| (field, strsim::damerau_levenshtein(&key, field)))
+            .filter(|&(field, distance)| distance <= field.len().min(key.len()) / 3 + 1)
+            .min_by_key(|&(_, distance)| distance)
+            .map(|(field, _)| field);
+        unknown.push(UnknownKey {
+            path: format!("{}{}", prefix, key),
+            suggestion,
+        });
+        false
+    });
+}
+
+/// Fails with every unknown key of `config`, read from `source`, and the
+/// known key each one most likely misspells.
+fn reject_unknown_keys(config: &serde_yaml::Value, source: &str) -> Result<()> {
+    let unknown = take_unknown_keys(&mut config.clone());
+    if unknown.is_empty() {
+        return Ok(());
+    }
+
+    let keys: Vec<String> = unknown.iter().map(ToString::to_string).collect();
+    Err(PrefixloadError::Custom(format!(
+        "Unknown keys in {}: {}. Fix their spelling or remove them.",
+        source,
+        keys.join(", ")
+    )))
+}
+
+/// Returns the field names of a struct that derives `Deserialize`, which
+/// serde hands to `Deserializer::deserialize_struct`.
+fn field_names<T: serde::de::DeserializeOwned>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// A deserializer that only records the field names it is asked for.
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> serde::Deserializer<'de> for FieldNames<'_> {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: serde::de::Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        Err(serde::de::Error::custom("expected a struct"))
+    }
+
+    fn deserialize_struct<V: serde::de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(serde::de::Error::custom("field names recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
 }
 
 #[cfg(test)]
@@ -202,6 +1407,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn diff_yaml_reports_changed_fields_and_misspelled_keys() {
+        let yaml = String::from_utf8(Asset::get("config.yml").unwrap().data.into_owned())
+            .unwrap()
+            .replace("part_size: 15MiB", "part_size: 15MB\nhash_thread: 2")
+            .replace("io_uring: false", "io_uring: true")
+            .replace(
+                "remote_path: \"prefix_2\"",
+                "remot_path: \"x\"\n    remote_path: \"prefix_2\"",
+            );
+
+        let diff = Config::diff_yaml(&yaml).unwrap();
+        assert_eq!(
+            diff.fields,
+            vec![FieldDiff {
+                field: "io_uring",
+                before: Some("false".to_string()),
+                after: Some("true".to_string()),
+            }]
+        );
+        assert_eq!(diff.unknown_keys[0].path, "hash_thread");
+        assert_eq!(diff.unknown_keys[0].suggestion, Some("hash_threads"));
+        assert_eq!(diff.unknown_keys.len(), 2);
+        assert_eq!(diff.unknown_keys[1].path, "directory_struct.1.remot_path");
+        assert_eq!(diff.unknown_keys[1].suggestion, Some("remote_path"));
+
+        assert!(Config::diff_yaml("bucket: [").is_err());
+    }
+
+    #[test]
+    fn json_schema_covers_every_field_and_rejects_unknown_keys() {
+        let schema = serde_json::to_value(Config::json_schema()).unwrap();
+
+        let properties = schema["properties"].as_object().unwrap();
+        for field in field_names::<Config>() {
+            assert!(properties.contains_key(*field), "missing {}", field);
+        }
+        assert!(
+            schema["required"]
+                .as_array()
+                .unwrap()
+                .contains(&"bucket".into())
+        );
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(
+            schema["$defs"]["DirectoryEntry"]["additionalProperties"],
+            false
+        );
+        assert_eq!(schema["$defs"]["ByteSize"]["type"][1], "string");
+    }
+
     /// Checks that `load` reads and deserializes YAML correctly.
     #[test]
     #[serial]
@@ -213,13 +1469,29 @@ mod tests {
             !cfg.endpoint.is_empty(),
             "`endpoint` should be populated in default YAML"
         );
-        assert!(cfg.part_size > 0, "`part_size` must be > 0");
+        assert!(cfg.part_size.as_u64() > 0, "`part_size` must be > 0");
         assert!(
             !cfg.directory_struct.is_empty(),
             "`directory_struct` should not be empty"
         );
     }
 
+    #[test]
+    fn upload_order_parses_names() {
+        assert_eq!(
+            "largest_first".parse::<UploadOrder>(),
+            Ok(UploadOrder::LargestFirst)
+        );
+        assert_eq!(
+            " Oldest-First".parse::<UploadOrder>(),
+            Ok(UploadOrder::OldestFirst)
+        );
+        assert!("newest_first".parse::<UploadOrder>().is_err());
+
+        let order: UploadOrder = serde_yaml::from_str("smallest_first").unwrap();
+        assert_eq!(order, UploadOrder::SmallestFirst);
+    }
+
     /// Ensures `save` creates a `.bak` and writes new data to the main file.
     #[test]
     #[serial]
@@ -251,6 +1523,656 @@ mod tests {
         );
     }
 
+    /// Ensures `restore_backup` brings back the file `save` replaced.
+    #[test]
+    #[serial]
+    fn restore_backup_undoes_the_last_save() {
+        let _guard = temp_config_dir();
+
+        let mut config = Config::load().unwrap();
+        let original = config.bucket.clone();
+        config.bucket = "changed".to_string();
+        config.save().unwrap();
+
+        Config::restore_backup().unwrap();
+        assert_eq!(Config::load().unwrap().bucket, original);
+    }
+
+    /// Ensures optional fields fall back to their defaults when missing from the file.
+    #[test]
+    #[serial]
+    fn load_applies_defaults_for_optional_fields() {
+        let _guard = temp_config_dir();
+        let path = Config::config_path().unwrap();
+
+        fs::write(
+            &path,
+            "endpoint: e\nbucket: b\nregion: r\nforce_path_style: false\npart_size: 5MiB\n\
+             local_directory_path: /tmp\ndirectory_struct: []\n",
+        )
+        .unwrap();
+
+        let cfg = Config::load().unwrap();
+        assert_eq!(cfg.min_concurrency, 1);
+        assert_eq!(cfg.max_concurrency, 4);
+        assert!(!cfg.upload_run_log);
+        assert_eq!(cfg.min_interval, None);
+        assert_eq!(cfg.max_duration, None);
+        assert_eq!(cfg.upload_window, None);
+        assert_eq!(cfg.outside_window, OutsideWindow::Refuse);
+        assert_eq!(cfg.access_key_file, None);
+        assert_eq!(cfg.secret_key_file, None);
+        assert!(!cfg.dedup);
+        assert!(!cfg.copy_duplicates);
+        assert_eq!(cfg.hash_threads, None);
+        assert_eq!(cfg.max_buffer_memory, None);
+        assert!(!cfg.io_uring);
+        assert_eq!(cfg.nice, None);
+        assert_eq!(cfg.ionice, None);
+        assert!(!cfg.skip_empty);
+        assert_eq!(cfg.max_file_size, None);
+        assert_eq!(cfg.log_timezone, LogTimezone::Local);
+        assert!(!cfg.event_log);
+        assert_eq!(cfg.sns_topic_arn, None);
+        assert_eq!(cfg.report_url, None);
+        assert_eq!(cfg.report_key_file, None);
+        assert_eq!(cfg.metrics_port, None);
+        assert_eq!(cfg.grpc_listen, None);
+        assert_eq!(cfg.grpc_client_ca_file, None);
+        assert_eq!(cfg.object_lock_mode, None);
+        assert!(!cfg.read_only);
+        assert_eq!(cfg.max_download_rate, None);
+        assert_eq!(cfg.run_history_days(), DEFAULT_RUN_HISTORY_DAYS);
+        assert_eq!(cfg.defaults, RunDefaults::default());
+        assert_eq!(cfg.retention(chrono::Utc::now()), None);
+        assert_eq!(cfg.file_size_limit(), MAX_OBJECT_SIZE);
+        assert!(cfg.jobs.is_empty());
+    }
+
+    /// Ensures misspelled keys are rejected instead of silently ignored.
+    #[test]
+    #[serial]
+    fn load_rejects_unknown_keys_with_suggestions() {
+        let _guard = temp_config_dir();
+        let path = Config::config_path().unwrap();
+
+        fs::write(
+            &path,
+            "endpoint: e\nbukcet: b\nregion: r\nforce_path_style: false\npart_size: 5MiB\n\
+             local_directory_path: /tmp\ndirectory_struct:\n  - local_name_prefix: db_\n    \
+             remote_path: db/\n    checksm: sha256\nretries: 3\ndefaults:\n  quite: true\n",
+        )
+        .unwrap();
+
+        let err = Config::load().unwrap_err().to_string();
+        assert!(err.contains("bukcet (did you mean 'bucket'?)"), "{}", err);
+        assert!(err.contains("directory_struct.0.checksm (did you mean 'checksum'?)"));
+        assert!(err.contains("retries, "), "{}", err);
+        assert!(
+            err.contains("defaults.quite (did you mean 'quiet'?)"),
+            "{}",
+            err
+        );
+    }
+
+    /// Ensures `preserve_structure` reads `true` and `flatten` only.
+    #[test]
+    fn directory_entry_reads_preserve_structure() {
+        let rule = |line: &str| {
+            serde_yaml::from_str::<DirectoryEntry>(&format!(
+                "local_name_prefix: db_\nremote_path: db/\n{}",
+                line
+            ))
+        };
+        assert_eq!(
+            rule("").unwrap().preserve_structure,
+            DirectoryStructure::Preserve
+        );
+        assert_eq!(
+            rule("preserve_structure: true\n")
+                .unwrap()
+                .preserve_structure,
+            DirectoryStructure::Preserve
+        );
+        let flat = rule("preserve_structure: flatten\n").unwrap();
+        assert_eq!(flat.preserve_structure, DirectoryStructure::Flatten);
+        assert!(
+            serde_yaml::to_string(&flat)
+                .unwrap()
+                .contains("preserve_structure: flatten")
+        );
+        assert!(
+            rule("preserve_structure: false\n")
+                .unwrap_err()
+                .to_string()
+                .contains("use true or flatten")
+        );
+    }
+
+    /// Ensures `defaults.dry_run: false` is accepted and `true` is rejected
+    /// with a clear error.
+    #[test]
+    fn parse_rejects_dry_run_default() {
+        let yaml = "endpoint: e\nbucket: b\nregion: r\nforce_path_style: false\n\
+                    part_size: 5MiB\nlocal_directory_path: /tmp\ndirectory_struct: []\n\
+                    defaults:\n  keep_going: true\n";
+        assert!(Config::parse(yaml).unwrap().defaults.keep_going);
+
+        assert!(
+            !Config::parse(&format!("{}  dry_run: false\n", yaml))
+                .unwrap()
+                .defaults
+                .dry_run
+        );
+        let err = Config::parse(&format!("{}  dry_run: true\n", yaml))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("defaults.dry_run: true is not supported"),
+            "{}",
+            err
+        );
+    }
+
+    /// Ensures container mode reads every field from `PREFIXLOAD_*` variables.
+    #[test]
+    #[serial]
+    fn from_env_reads_prefixed_variables() {
+        let vars = [
+            ("PREFIXLOAD_ENDPOINT", "http://minio:9000"),
+            ("PREFIXLOAD_BUCKET", "backups"),
+            ("PREFIXLOAD_REGION", "us-east-1"),
+            ("PREFIXLOAD_FORCE_PATH_STYLE", "true"),
+            ("PREFIXLOAD_PART_SIZE", "16MiB"),
+            ("PREFIXLOAD_LOCAL_DIRECTORY_PATH", "/data"),
+            (
+                "PREFIXLOAD_DIRECTORY_STRUCT",
+                "[{local_name_prefix: db_, remote_path: db/}]",
+            ),
+            ("PREFIXLOAD_MAX_DURATION", "60"),
+            (CONTAINER_ENV, "1"),
+            (ASSUME_YES_ENV, "1"),
+        ];
+        for (key, value) in vars {
+            unsafe { env::set_var(key, value) };
+        }
+
+        let cfg = Config::from_env();
+        assert!(container_mode());
+        unsafe { env::remove_var("PREFIXLOAD_BUCKET") };
+        let missing = Config::from_env().unwrap_err();
+        for (key, _) in vars {
+            unsafe { env::remove_var(key) };
+        }
+
+        let cfg = cfg.unwrap();
+        assert_eq!(cfg.endpoint, "http://minio:9000");
+        assert_eq!(cfg.bucket, "backups");
+        assert!(cfg.force_path_style);
+        assert_eq!(cfg.part_size, "16MiB".parse().unwrap());
+        assert_eq!(cfg.local_directory_path, PathBuf::from("/data"));
+        assert_eq!(cfg.directory_struct[0].remote_path, "db/");
+        assert_eq!(cfg.max_duration, Some(60));
+        assert_eq!(cfg.max_concurrency, 4);
+        assert!(missing.to_string().contains("missing field `bucket`"));
+        assert!(!container_mode());
+    }
+
+    fn config_with_part_size(part_size: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            "endpoint: e\nbucket: b\nregion: r\nforce_path_style: false\npart_size: {}\n\
+             local_directory_path: /tmp\ndirectory_struct: []\n",
+            part_size
+        ))
+        .unwrap()
+    }
+
+    /// Ensures `load` rejects part sizes outside the S3 limits.
+    #[test]
+    #[serial]
+    fn load_rejects_part_size_outside_s3_limits() {
+        let _guard = temp_config_dir();
+        let path = Config::config_path().unwrap();
+
+        for part_size in ["1MiB", "6GiB"] {
+            fs::write(
+                &path,
+                serde_yaml::to_string(&config_with_part_size(part_size)).unwrap(),
+            )
+            .unwrap();
+
+            let err = Config::load().unwrap_err().to_string();
+            assert!(err.contains("part_size"), "unexpected error: {}", err);
+        }
+    }
+
+    /// Ensures `save` refuses to write an invalid config.
+    #[test]
+    #[serial]
+    fn save_rejects_invalid_part_size() {
+        let _guard = temp_config_dir();
+
+        assert!(config_with_part_size("1KiB").save().is_err());
+        assert!(!Config::config_path().unwrap().exists());
+    }
+
+    fn config_with_jobs(jobs: &str) -> Config {
+        serde_yaml::from_str(&format!(
+            "endpoint: e\nbucket: b\nregion: r\nforce_path_style: false\npart_size: 5MiB\n\
+             local_directory_path: /tmp\ndirectory_struct:\n\
+             - local_name_prefix: db_\n  remote_path: db\n\
+             - local_name_prefix: logs_\n  remote_path: logs\n\
+             jobs:\n{}",
+            jobs
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn overlapping_rules_names_the_rule_that_wins() {
+        let mut cfg = config_with_jobs("- name: a\n");
+        let rule = |prefix: &str, remote_path: &str| DirectoryEntry {
+            local_name_prefix: prefix.to_string(),
+            remote_path: remote_path.to_string(),
+            checksum: None,
+            xattrs: false,
+            local_directory_path: None,
+            max_depth: 0,
+            preserve_structure: DirectoryStructure::Preserve,
+            plugin: None,
+        };
+        assert!(cfg.overlapping_rules(&cfg.directory_struct).is_empty());
+
+        cfg.directory_struct = vec![
+            rule("db", "db"),
+            rule("db_prod", "prod"),
+            rule("logs_app", "app"),
+            rule("logs", "logs"),
+        ];
+        assert_eq!(
+            cfg.overlapping_rules(&cfg.directory_struct),
+            [
+                "Rules 'db' and 'db_prod' overlap in /tmp: files starting with 'db_prod' match \
+                 'db' first and are uploaded to 'db', not 'prod'. List 'db_prod' before 'db' \
+                 to route them to 'prod'.",
+                "Rules 'logs_app' and 'logs' overlap in /tmp: files starting with 'logs_app' \
+                 are uploaded to 'app' since 'logs_app' comes first; the other files starting \
+                 with 'logs' go to 'logs'."
+            ]
+        );
+
+        // Rules of different directories never compete for a file.
+        cfg.directory_struct[1].local_directory_path = Some(PathBuf::from("/srv/prod"));
+        cfg.directory_struct[3] = rule("logs_app", "other");
+        assert_eq!(
+            cfg.overlapping_rules(&cfg.directory_struct),
+            [
+                "Rule 'logs_app' is defined twice for /tmp: files match the first one and are \
+              uploaded to 'app', not 'other'."
+            ]
+        );
+    }
+
+    #[test]
+    fn rules_for_job_selects_subset() {
+        let cfg = config_with_jobs(
+            "- name: nightly-db\n  rules: [db_]\n  schedule: daily at 03:00\n- name: all\n",
+        );
+        assert!(cfg.validate().is_ok());
+
+        let nightly = cfg.job("nightly-db").unwrap();
+        assert_eq!(nightly.schedule.as_deref(), Some("daily at 03:00"));
+        let rules = cfg.rules_for_job(nightly).unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].remote_path, "db");
+
+        assert_eq!(cfg.rules_for_job(cfg.job("all").unwrap()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn job_lookup_lists_known_jobs() {
+        let cfg = config_with_jobs("- name: a\n- name: b\n");
+
+        let err = cfg.job("c").unwrap_err().to_string();
+        assert!(err.contains("Job 'c' not found. Known jobs: a, b."));
+    }
+
+    #[test]
+    fn validate_rejects_bad_jobs() {
+        let unknown_rule = config_with_jobs("- name: a\n  rules: [tmp_]\n");
+        assert!(
+            unknown_rule
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("unknown rule 'tmp_'")
+        );
+
+        let duplicate = config_with_jobs("- name: a\n- name: a\n");
+        assert!(
+            duplicate
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("defined more than once")
+        );
+    }
+
+    #[test]
+    fn for_host_applies_the_entry_of_the_host() {
+        let cfg = config_with_jobs(
+            "- {name: nightly, schedule: daily at 03:00}\n\
+             hosts:\n  \
+               db-01:\n    \
+                 bucket: db-backups\n    \
+                 directory_struct: [{local_name_prefix: pg_, remote_path: pg}]\n    \
+                 jobs: [{name: nightly, schedule: daily at 01:00}]\n",
+        );
+        cfg.validate().unwrap();
+
+        let db = cfg.clone().for_host("DB-01.example.com");
+        assert_eq!(db.bucket, "db-backups");
+        assert_eq!(db.region, "r");
+        assert_eq!(db.directory_struct.len(), 1);
+        assert_eq!(db.directory_struct[0].local_name_prefix, "pg_");
+        assert_eq!(db.jobs[0].schedule.as_deref(), Some("daily at 01:00"));
+
+        let web = cfg.for_host("web-01");
+        assert_eq!(web.bucket, "b");
+        assert_eq!(web.directory_struct.len(), 2);
+        assert_eq!(web.jobs[0].schedule.as_deref(), Some("daily at 03:00"));
+    }
+
+    #[test]
+    #[serial]
+    fn load_adds_the_rules_of_included_files() {
+        let _guard = temp_config_dir();
+        let path = Config::config_path().unwrap();
+        let rules_dir = path.parent().unwrap().join("rules.d");
+        fs::create_dir(&rules_dir).unwrap();
+        fs::write(
+            rules_dir.join("b-media.yml"),
+            "directory_struct:\n- {local_name_prefix: media_, remote_path: media}\n",
+        )
+        .unwrap();
+        fs::write(
+            rules_dir.join("a-pg.yml"),
+            "directory_struct:\n- {local_name_prefix: pg_, remote_path: pg}\n",
+        )
+        .unwrap();
+        fs::write(rules_dir.join("notes.txt"), "not a rule file").unwrap();
+        let yaml = serde_yaml::to_string(&config_with_jobs("- {name: media, rules: [media_]}\n"))
+            .unwrap()
+            + "include: [rules.d/*.yml]\n";
+        fs::write(&path, &yaml).unwrap();
+
+        let mut config = Config::load().unwrap();
+        let prefixes: Vec<&str> = config
+            .directory_struct
+            .iter()
+            .map(|rule| rule.local_name_prefix.as_str())
+            .collect();
+        assert_eq!(prefixes, ["db_", "logs_", "pg_", "media_"]);
+        assert_eq!(config.included_rules["pg_"], rules_dir.join("a-pg.yml"));
+
+        // Saving leaves the included rules in their own files.
+        config.bucket = "backups".to_string();
+        config.save().unwrap();
+        let saved: Config = serde_yaml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.directory_struct.len(), 2);
+        assert_eq!(saved.include, ["rules.d/*.yml"]);
+        assert_eq!(Config::load().unwrap().directory_struct.len(), 4);
+
+        fs::write(
+            rules_dir.join("c-db.yml"),
+            "directory_struct:\n- {local_name_prefix: db_, remote_path: other}\n",
+        )
+        .unwrap();
+        let err = Config::load().unwrap_err().to_string();
+        assert!(err.contains("Rule 'db_' of "), "{}", err);
+        assert!(err.contains("c-db.yml is already defined."), "{}", err);
+
+        fs::write(rules_dir.join("c-db.yml"), "directory_struct: 5\n").unwrap();
+        let err = Config::load().unwrap_err().to_string();
+        assert!(err.contains("Invalid rule file "), "{}", err);
+    }
+
+    #[test]
+    fn for_host_keeps_included_rules_after_the_rules_of_the_host() {
+        let mut cfg = config_with_jobs(
+            "  []\n\
+             hosts:\n  \
+               db-01:\n    \
+                 directory_struct: [{local_name_prefix: pg_, remote_path: pg}]\n",
+        );
+        let mut media = cfg.directory_struct[0].clone();
+        media.local_name_prefix = "media_".to_string();
+        cfg.directory_struct.push(media);
+        cfg.included_rules
+            .insert("media_".to_string(), PathBuf::from("rules.d/media.yml"));
+
+        let prefixes = |cfg: &Config| -> Vec<String> {
+            cfg.directory_struct
+                .iter()
+                .map(|rule| rule.local_name_prefix.clone())
+                .collect()
+        };
+        assert_eq!(prefixes(&cfg.clone().for_host("db-01")), ["pg_", "media_"]);
+        assert_eq!(
+            prefixes(&cfg.for_host("web-01")),
+            ["db_", "logs_", "media_"]
+        );
+    }
+
+    #[test]
+    fn validate_rejects_bad_host_entries() {
+        let cfg = config_with_jobs(
+            "- {name: nightly, rules: [logs_]}\n\
+             hosts:\n  \
+               db-01:\n    \
+                 directory_struct: [{local_name_prefix: pg_, remote_path: pg}]\n",
+        );
+        let err = cfg.validate().unwrap_err().to_string();
+        assert!(err.contains("hosts.db-01: "), "{}", err);
+        assert!(err.contains("unknown rule 'logs_'"), "{}", err);
+
+        let yaml = serde_yaml::to_string(&config_with_jobs("- name: a\n")).unwrap()
+            + "hosts:\n  db-01:\n    bukcet: b\n";
+        let err = Config::parse(&yaml).unwrap_err().to_string();
+        assert!(
+            err.contains("hosts.db-01.bukcet (did you mean 'bucket'?)"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_rejects_zero_hash_threads() {
+        let mut cfg = config_with_jobs("- name: a\n");
+        cfg.hash_threads = Some(0);
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("hash_threads must be at least 1")
+        );
+
+        cfg.hash_threads = Some(2);
+        assert!(cfg.validate().is_ok());
+
+        cfg.run_history_days = Some(0);
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("run_history_days must be at least 1")
+        );
+    }
+
+    #[test]
+    fn validate_rejects_buffer_memory_below_part_size() {
+        let mut cfg = config_with_jobs("- name: a\n");
+        cfg.part_size = ByteSize(16 * 1024 * 1024);
+        cfg.max_buffer_memory = Some(ByteSize(8 * 1024 * 1024));
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("at least part_size (16MiB)")
+        );
+
+        cfg.max_buffer_memory = Some(ByteSize(64 * 1024 * 1024));
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_zero_download_rate() {
+        let mut cfg = config_with_jobs("- name: a\n");
+        cfg.max_download_rate = Some(ByteSize(0));
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("max_download_rate must be greater than 0")
+        );
+
+        cfg.max_download_rate = Some(ByteSize(50 * 1024 * 1024));
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_quiet_and_summary_only_defaults() {
+        let mut cfg = config_with_jobs("- name: a\n");
+        cfg.defaults.quiet = true;
+        assert!(cfg.validate().is_ok());
+
+        cfg.defaults.summary_only = true;
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("defaults.quiet and defaults.summary_only")
+        );
+    }
+
+    #[test]
+    fn validate_rejects_nice_out_of_range() {
+        let mut cfg = config_with_jobs("- name: a\n");
+        cfg.nice = Some(20);
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("nice 20 is out of range")
+        );
+
+        cfg.nice = Some(-5);
+        assert!(cfg.validate().is_err());
+
+        cfg.nice = Some(10);
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_malformed_sns_topic_arn() {
+        let mut cfg = config_with_jobs("- name: a\n");
+        cfg.sns_topic_arn = Some("backups".to_string());
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("is not an SNS topic ARN")
+        );
+
+        cfg.sns_topic_arn = Some("arn:aws:sns:eu-central-1:123456789012:backups".to_string());
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_requires_a_signing_key_for_report_url() {
+        let mut cfg = config_with_jobs("- name: a\n");
+        cfg.report_url = Some("collector.example.com/reports".to_string());
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("not an HTTP(S) URL")
+        );
+
+        cfg.report_url = Some("https://collector.example.com/reports".to_string());
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("needs report_key_file")
+        );
+
+        cfg.report_key_file = Some(PathBuf::from("/run/secrets/report_key"));
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_requires_mutual_tls_for_grpc_listen() {
+        let mut cfg = config_with_jobs("- name: a\n");
+        cfg.grpc_listen = Some("0.0.0.0:50051".parse().unwrap());
+        cfg.grpc_cert_file = Some(PathBuf::from("server.pem"));
+        cfg.grpc_key_file = Some(PathBuf::from("server.key"));
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("needs mutual TLS")
+        );
+
+        cfg.grpc_client_ca_file = Some(PathBuf::from("ca.pem"));
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_requires_object_lock_mode_and_days_together() {
+        let mut cfg = config_with_jobs("- name: a\n");
+        cfg.object_lock_mode = Some(RetentionMode::Compliance);
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("needs object_lock_days")
+        );
+
+        cfg.object_lock_days = Some(0);
+        assert!(
+            cfg.validate()
+                .unwrap_err()
+                .to_string()
+                .contains("at least 1")
+        );
+
+        cfg.object_lock_days = Some(30);
+        assert!(cfg.validate().is_ok());
+        let now = chrono::Utc::now();
+        assert_eq!(
+            cfg.retention(now),
+            Some(Retention::new(RetentionMode::Compliance, 30, now))
+        );
+    }
+
+    #[test]
+    fn check_part_count_suggests_larger_part_size() {
+        let cfg = config_with_part_size("5MiB");
+
+        assert!(
+            cfg.check_part_count("small", 5 * 1024 * 1024 * 10_000)
+                .is_ok()
+        );
+
+        let err = cfg
+            .check_part_count("big.tar", 100 * 1024 * 1024 * 1024)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("big.tar (100.0 GiB) would need 20480 parts"));
+        assert!(err.contains("Set part_size to at least 11MiB"));
+    }
+
     /// Ensures `load` fails when the configuration file contains invalid YAML.
     #[test]
     #[serial]
@@ -0,0 +1,98 @@
+use crate::error::{PrefixloadError, Result};
+use std::fs;
+use std::path::PathBuf;
+
+/// Returns the path of the pause flag file, next to `state.json`.
+/// While the file exists, runs finish the uploads already in flight and
+/// start no new ones.
+/// - Linux: ~/.local/share/prefixload/paused
+/// - macOS: ~/Library/Application Support/prefixload/paused
+/// - Windows: %LOCALAPPDATA%\prefixload\paused
+fn pause_path() -> Result<PathBuf> {
+    let mut dir = dirs_next::data_local_dir().ok_or_else(|| {
+        PrefixloadError::Custom("Could not find local data directory.".to_string())
+    })?;
+
+    dir.push("prefixload");
+    fs::create_dir_all(&dir)?;
+
+    dir.push("paused");
+
+    Ok(dir)
+}
+
+/// Pauses runs. Returns `false` if they were already paused.
+pub fn pause() -> Result<bool> {
+    let path = pause_path()?;
+    if path.exists() {
+        return Ok(false);
+    }
+
+    fs::write(&path, chrono::Utc::now().to_rfc3339())?;
+    Ok(true)
+}
+
+/// Resumes paused runs. Returns `false` if they were not paused.
+pub fn resume() -> Result<bool> {
+    let path = pause_path()?;
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    fs::remove_file(&path)?;
+    Ok(true)
+}
+
+/// Returns `true` while runs are paused.
+pub fn is_paused() -> bool {
+    pause_path().is_ok_and(|path| path.exists())
+}
+
+/// Toggles the pause state. Returns `true` if runs are paused afterwards.
+pub fn toggle_pause() -> Result<bool> {
+    if is_paused() {
+        resume()?;
+        Ok(false)
+    } else {
+        pause()?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::env;
+    use tempfile::TempDir;
+
+    #[cfg(windows)]
+    const DATA_LOCAL_ENV: &str = "LOCALAPPDATA";
+    #[cfg(not(windows))]
+    const DATA_LOCAL_ENV: &str = "XDG_DATA_HOME";
+
+    fn temp_data_dir() -> TempDir {
+        let tmp = TempDir::new().expect("temp dir");
+        unsafe { env::set_var(DATA_LOCAL_ENV, tmp.path()) };
+        tmp
+    }
+
+    #[test]
+    #[serial]
+    fn pause_and_resume_toggle_flag_file() {
+        let guard = temp_data_dir();
+
+        assert!(!is_paused());
+        assert!(pause().unwrap());
+        assert!(!pause().unwrap(), "second pause is a no-op");
+        assert!(guard.path().join("prefixload/paused").exists());
+        assert!(is_paused());
+
+        assert!(resume().unwrap());
+        assert!(!resume().unwrap(), "second resume is a no-op");
+        assert!(!is_paused());
+
+        assert!(toggle_pause().unwrap());
+        assert!(!toggle_pause().unwrap());
+    }
+}
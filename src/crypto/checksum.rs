@@ -0,0 +1,108 @@
+use crate::error::Result;
+use aws_sdk_s3::types::ChecksumAlgorithm as S3ChecksumAlgorithm;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Integrity checksum sent with the uploads of a `directory_struct` rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    /// `Content-MD5` header, verified by S3 when it receives the object.
+    Md5,
+    /// `x-amz-checksum-sha256`, stored with the object.
+    Sha256,
+    /// `x-amz-checksum-crc32c`, stored with the object.
+    Crc32c,
+}
+
+impl ChecksumAlgorithm {
+    /// Returns the algorithm of the S3 Checksum API, or `None` for MD5,
+    /// which is sent as a `Content-MD5` header instead.
+    pub fn s3_algorithm(self) -> Option<S3ChecksumAlgorithm> {
+        match self {
+            Self::Md5 => None,
+            Self::Sha256 => Some(S3ChecksumAlgorithm::Sha256),
+            Self::Crc32c => Some(S3ChecksumAlgorithm::Crc32C),
+        }
+    }
+}
+
+impl fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Md5 => "md5",
+            Self::Sha256 => "sha256",
+            Self::Crc32c => "crc32c",
+        })
+    }
+}
+
+impl FromStr for ChecksumAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "md5" => Ok(Self::Md5),
+            "sha256" => Ok(Self::Sha256),
+            "crc32c" => Ok(Self::Crc32c),
+            _ => Err(format!(
+                "invalid checksum '{}': use 'md5', 'sha256' or 'crc32c'",
+                s
+            )),
+        }
+    }
+}
+
+/// Calculates the base64-encoded MD5 digest of a file for the `Content-MD5` header.
+pub fn content_md5<P: AsRef<Path>>(file_path: P) -> Result<String> {
+    let mut file = File::open(file_path)?;
+    let mut md5_context = md5::Context::new();
+    io::copy(&mut file, &mut md5_context)?;
+    Ok(aws_smithy_types::base64::encode(md5_context.finalize().0))
+}
+
+/// Calculates the hex-encoded SHA-256 digest of a file.
+pub fn sha256_hex<P: AsRef<Path>>(file_path: P) -> Result<String> {
+    let mut file = File::open(file_path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn checksum_parses_and_serializes_lowercase() {
+        assert_eq!("SHA256".parse(), Ok(ChecksumAlgorithm::Sha256));
+        assert_eq!("crc32c".parse(), Ok(ChecksumAlgorithm::Crc32c));
+        assert!("sha1".parse::<ChecksumAlgorithm>().is_err());
+        assert_eq!(
+            serde_yaml::to_string(&ChecksumAlgorithm::Crc32c).unwrap(),
+            "crc32c\n"
+        );
+        assert_eq!(ChecksumAlgorithm::Md5.s3_algorithm(), None);
+    }
+
+    #[test]
+    fn content_md5_is_base64_digest() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "hello world").unwrap();
+
+        assert_eq!(content_md5(&path).unwrap(), "XrY7u+Ae7tCTyyK7j1rNww==");
+        assert_eq!(
+            sha256_hex(&path).unwrap(),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+    }
+}
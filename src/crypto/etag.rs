@@ -1,8 +1,11 @@
-use crate::error::Result;
+use crate::cancel::CancelToken;
+use crate::error::{PrefixloadError, Result};
+use crate::uring::{self, ReadMode};
 use rayon::prelude::*;
 use std::fs::File;
 use std::io::{self, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 // --- Helper for Streaming Memory-Efficient Reads ---
 
@@ -53,9 +56,101 @@ impl<'a> Read for FilePartReader<'a> {
     }
 }
 
-/// Calculates the S3 ETag for a file.
-pub fn calculate_s3_etag<P: AsRef<Path> + Sync>(file_path: P, part_size: u64) -> Result<String> {
-    let metadata = std::fs::metadata(file_path.as_ref())?;
+/// Counts the bytes hashed by all parts of a calculation, reports them to
+/// the progress callback and fails reads once the calculation is cancelled.
+struct Tracker<'a, F> {
+    hashed: AtomicU64,
+    progress: F,
+    cancel: &'a CancelToken,
+}
+
+impl<F: Fn(u64) + Sync> Tracker<'_, F> {
+    fn check(&self) -> io::Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(io::Error::other("ETag calculation cancelled"));
+        }
+        Ok(())
+    }
+
+    fn add(&self, bytes: u64) {
+        if bytes > 0 {
+            let hashed = self.hashed.fetch_add(bytes, Ordering::Relaxed) + bytes;
+            (self.progress)(hashed);
+        }
+    }
+}
+
+/// A reader that reports every read to a `Tracker`.
+struct TrackedReader<'a, R, F> {
+    inner: R,
+    tracker: &'a Tracker<'a, F>,
+}
+
+impl<R: Read, F: Fn(u64) + Sync> Read for TrackedReader<'_, R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.tracker.check()?;
+        let bytes_read = self.inner.read(buf)?;
+        self.tracker.add(bytes_read as u64);
+        Ok(bytes_read)
+    }
+}
+
+/// Calculates the S3 ETag for a file, reading it as `read_mode` says.
+pub fn calculate_s3_etag<P: AsRef<Path> + Sync>(
+    file_path: P,
+    part_size: u64,
+    read_mode: ReadMode,
+) -> Result<String> {
+    calculate_s3_etag_with_progress(file_path, part_size, read_mode, |_| {}, &CancelToken::new())
+}
+
+/// Calculates the S3 ETag for a file like [`calculate_s3_etag`], calling
+/// `progress` with the number of bytes hashed so far, so multi-gigabyte
+/// files can show progress. Parts are hashed in parallel, so `progress` is
+/// called from several threads and should return quickly. With io_uring
+/// reads, it is called once per part.
+///
+/// Once `cancel` is cancelled, the calculation stops at the next read and
+/// returns an error.
+pub fn calculate_s3_etag_with_progress<P, F>(
+    file_path: P,
+    part_size: u64,
+    read_mode: ReadMode,
+    progress: F,
+    cancel: &CancelToken,
+) -> Result<String>
+where
+    P: AsRef<Path> + Sync,
+    F: Fn(u64) + Sync,
+{
+    let tracker = Tracker {
+        hashed: AtomicU64::new(0),
+        progress,
+        cancel,
+    };
+    let result = tracked_etag(file_path.as_ref(), part_size, read_mode, &tracker);
+    unless_cancelled(result, file_path.as_ref(), cancel)
+}
+
+/// Replaces the error of a calculation that failed because it was
+/// cancelled with `PrefixloadError::Cancelled`.
+fn unless_cancelled<T>(result: Result<T>, file_path: &Path, cancel: &CancelToken) -> Result<T> {
+    match result {
+        Err(_) if cancel.is_cancelled() => Err(PrefixloadError::Cancelled(format!(
+            "Cancelled the ETag calculation of {}.",
+            file_path.display()
+        ))),
+        result => result,
+    }
+}
+
+fn tracked_etag<F: Fn(u64) + Sync>(
+    file_path: &Path,
+    part_size: u64,
+    read_mode: ReadMode,
+    tracker: &Tracker<'_, F>,
+) -> Result<String> {
+    let metadata = std::fs::metadata(file_path)?;
     let file_size = metadata.len();
 
     if file_size == 0 {
@@ -63,54 +158,114 @@ pub fn calculate_s3_etag<P: AsRef<Path> + Sync>(file_path: P, part_size: u64) ->
     }
 
     if file_size <= part_size {
-        let mut file = File::open(file_path.as_ref())?;
+        tracker.check()?;
+        if read_mode == ReadMode::IoUring {
+            let file = File::open(file_path)?;
+            let digest = uring::md5_range(&file, 0, file_size)?;
+            tracker.add(file_size);
+            return Ok(format!("{:x}", digest));
+        }
+
+        let mut file = TrackedReader {
+            inner: File::open(file_path)?,
+            tracker,
+        };
         let mut md5_context = md5::Context::new();
         io::copy(&mut file, &mut md5_context)?;
         let digest = md5_context.finalize();
         Ok(format!("{:x}", digest))
     } else {
-        let file = File::open(file_path.as_ref())?;
-        let num_parts = (file_size + part_size - 1) / part_size;
+        let digests = part_md5s(file_path, part_size, read_mode, tracker)?;
+        Ok(multipart_etag(&digests))
+    }
+}
+
+/// Calculates the MD5 digest of every `part_size` part of a file, i.e. the
+/// ETags S3 reports for the parts of a multipart upload. Once `cancel` is
+/// cancelled, the calculation stops at the next read and returns an error.
+pub fn calculate_part_md5s<P: AsRef<Path> + Sync>(
+    file_path: P,
+    part_size: u64,
+    read_mode: ReadMode,
+    cancel: &CancelToken,
+) -> Result<Vec<md5::Digest>> {
+    let tracker = Tracker {
+        hashed: AtomicU64::new(0),
+        progress: |_| {},
+        cancel,
+    };
+    let result = part_md5s(file_path.as_ref(), part_size, read_mode, &tracker);
+    unless_cancelled(result, file_path.as_ref(), cancel)
+}
+
+fn part_md5s<F: Fn(u64) + Sync>(
+    file_path: &Path,
+    part_size: u64,
+    read_mode: ReadMode,
+    tracker: &Tracker<'_, F>,
+) -> Result<Vec<md5::Digest>> {
+    let file_size = std::fs::metadata(file_path)?.len();
+    if file_size == 0 {
+        return Ok(vec![md5::compute(b"")]);
+    }
 
-        // Collect the md5::Digest results directly to avoid intermediate Vec<u8> allocations.
-        let part_digests: Result<Vec<md5::Digest>> = (0..num_parts)
-            .into_par_iter()
-            .map(|part_num| -> Result<md5::Digest> {
-                let offset = part_num * part_size;
-                let bytes_to_read = std::cmp::min(part_size, file_size - offset);
+    let file = File::open(file_path)?;
+    let num_parts = (file_size + part_size - 1) / part_size;
 
-                let mut md5_context = md5::Context::new();
+    // Collect the md5::Digest results directly to avoid intermediate Vec<u8> allocations.
+    (0..num_parts)
+        .into_par_iter()
+        .map(|part_num| -> Result<md5::Digest> {
+            tracker.check()?;
+            let offset = part_num * part_size;
+            let bytes_to_read = std::cmp::min(part_size, file_size - offset);
 
-                let mut part_reader = FilePartReader {
+            if read_mode == ReadMode::IoUring {
+                let digest = uring::md5_range(&file, offset, bytes_to_read)?;
+                tracker.add(bytes_to_read);
+                return Ok(digest);
+            }
+
+            let mut md5_context = md5::Context::new();
+
+            let mut part_reader = TrackedReader {
+                inner: FilePartReader {
                     file: &file,
                     cursor: offset,
                     end: offset + bytes_to_read,
-                };
-
-                if let Err(e) = io::copy(&mut part_reader, &mut md5_context) {
-                    if e.kind() == io::ErrorKind::Unsupported {
-                        let mut f = File::open(file_path.as_ref())?;
-                        f.seek(SeekFrom::Start(offset))?;
-                        let mut limited_reader = f.take(bytes_to_read);
-                        io::copy(&mut limited_reader, &mut md5_context)?;
-                    } else {
-                        return Err(e.into());
-                    }
-                }
+                },
+                tracker,
+            };
 
-                Ok(md5_context.finalize())
-            })
-            .collect();
+            if let Err(e) = io::copy(&mut part_reader, &mut md5_context) {
+                if e.kind() == io::ErrorKind::Unsupported {
+                    let mut f = File::open(file_path)?;
+                    f.seek(SeekFrom::Start(offset))?;
+                    let mut limited_reader = TrackedReader {
+                        inner: f.take(bytes_to_read),
+                        tracker,
+                    };
+                    io::copy(&mut limited_reader, &mut md5_context)?;
+                } else {
+                    return Err(e.into());
+                }
+            }
 
-        let digests = part_digests?;
-        let mut combined_hashes = Vec::with_capacity(digests.len() * 16);
-        for digest in digests {
-            combined_hashes.extend_from_slice(&digest.0);
-        }
+            Ok(md5_context.finalize())
+        })
+        .collect()
+}
 
-        let final_digest = md5::compute(&combined_hashes);
-        Ok(format!("{:x}-{}", final_digest, num_parts))
+/// Combines part digests into the ETag of a multipart object: the MD5 of the
+/// concatenated part digests, followed by the number of parts.
+pub fn multipart_etag(part_digests: &[md5::Digest]) -> String {
+    let mut combined_hashes = Vec::with_capacity(part_digests.len() * 16);
+    for digest in part_digests {
+        combined_hashes.extend_from_slice(&digest.0);
     }
+
+    let final_digest = md5::compute(&combined_hashes);
+    format!("{:x}-{}", final_digest, part_digests.len())
 }
 
 #[cfg(test)]
@@ -124,7 +279,7 @@ mod tests {
     #[test]
     fn test_etag_empty_file() {
         let file = NamedTempFile::new().unwrap();
-        let etag = calculate_s3_etag(file.path(), 5 * MB).unwrap();
+        let etag = calculate_s3_etag(file.path(), 5 * MB, ReadMode::Standard).unwrap();
         assert_eq!(etag, "d41d8cd98f00b204e9800998ecf8427e");
     }
 
@@ -133,7 +288,7 @@ mod tests {
         let mut file = NamedTempFile::new().unwrap();
         let content = b"hello world";
         file.write_all(content).unwrap();
-        let etag = calculate_s3_etag(file.path(), 5 * MB).unwrap();
+        let etag = calculate_s3_etag(file.path(), 5 * MB, ReadMode::Standard).unwrap();
         let expected_etag = format!("{:x}", md5::compute(content));
         assert_eq!(etag, expected_etag);
     }
@@ -145,7 +300,7 @@ mod tests {
         let mut file = NamedTempFile::new().unwrap();
         let content = vec![b'b'; file_size as usize];
         file.write_all(&content).unwrap();
-        let etag = calculate_s3_etag(file.path(), part_size).unwrap();
+        let etag = calculate_s3_etag(file.path(), part_size, ReadMode::Standard).unwrap();
         let expected_etag = format!("{:x}", md5::compute(&content));
         assert_eq!(etag, expected_etag);
     }
@@ -170,7 +325,61 @@ mod tests {
         let final_md5 = md5::compute(&combined_md5s);
         let expected_etag = format!("{:x}-3", final_md5);
 
-        let etag = calculate_s3_etag(file.path(), part_size).unwrap();
+        let etag = calculate_s3_etag(file.path(), part_size, ReadMode::Standard).unwrap();
         assert_eq!(etag, expected_etag);
+
+        let parts = calculate_part_md5s(
+            file.path(),
+            part_size,
+            ReadMode::Standard,
+            &CancelToken::new(),
+        )
+        .unwrap();
+        assert_eq!(parts, vec![part1_md5, part2_md5, part3_md5]);
+        assert_eq!(multipart_etag(&parts), expected_etag);
+    }
+
+    #[test]
+    fn test_etag_with_progress_reports_every_byte() {
+        let part_size = MB;
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&vec![b'c'; (2.5 * MB as f64) as usize])
+            .unwrap();
+
+        let hashed = AtomicU64::new(0);
+        let etag = calculate_s3_etag_with_progress(
+            file.path(),
+            part_size,
+            ReadMode::Standard,
+            |bytes| {
+                hashed.fetch_max(bytes, Ordering::Relaxed);
+            },
+            &CancelToken::new(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            etag,
+            calculate_s3_etag(file.path(), part_size, ReadMode::Standard).unwrap()
+        );
+        assert_eq!(hashed.into_inner(), (2.5 * MB as f64) as u64);
+    }
+
+    #[test]
+    fn test_etag_with_progress_stops_when_cancelled() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&vec![b'd'; 2 * MB as usize]).unwrap();
+
+        let cancel = CancelToken::new();
+        let err = calculate_s3_etag_with_progress(
+            file.path(),
+            MB,
+            ReadMode::Standard,
+            |_| cancel.cancel(),
+            &cancel,
+        )
+        .unwrap_err();
+        assert!(matches!(err, PrefixloadError::Cancelled(_)), "{}", err);
+        assert!(err.to_string().contains("Cancelled the ETag calculation"));
     }
 }
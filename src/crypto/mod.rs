@@ -1 +1,2 @@
+pub mod checksum;
 pub mod etag;
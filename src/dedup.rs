@@ -0,0 +1,265 @@
+use crate::error::{PrefixloadError, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Bucket prefix holding the content-addressed chunks of all files.
+pub const CHUNK_PREFIX: &str = "chunks";
+
+/// Suffix appended to a file's remote key to name its index object.
+pub const INDEX_SUFFIX: &str = ".index.json";
+
+/// Version of the index object format.
+const INDEX_VERSION: u32 = 1;
+
+/// Chunk size bounds; boundaries are found on average every `avg` bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkSizes {
+    pub min: usize,
+    pub avg: usize,
+    pub max: usize,
+}
+
+impl ChunkSizes {
+    /// 512 KiB to 8 MiB, 2 MiB on average.
+    pub const DEFAULT: Self = Self {
+        min: 512 * 1024,
+        avg: 2 * 1024 * 1024,
+        max: 8 * 1024 * 1024,
+    };
+
+    /// Bit mask a gear hash must not intersect at a chunk boundary.
+    fn mask(&self) -> u64 {
+        self.avg.next_power_of_two() as u64 - 1
+    }
+}
+
+/// Random values mixed into the gear hash, one per byte value.
+/// Generated with SplitMix64 so that chunk boundaries never change between builds.
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x7072_6566_6978_6c64;
+    let mut i = 0;
+    while i < table.len() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// A chunk of a file, referenced by the SHA-256 of its content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    pub sha256: String,
+    pub size: u64,
+    /// MD5 of the content, i.e. the ETag of the uploaded chunk object.
+    #[serde(skip)]
+    pub md5: String,
+}
+
+/// The index object written for every file: its chunks in order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileIndex {
+    pub version: u32,
+    pub size: u64,
+    /// SHA-256 of the whole file, to verify a reassembled copy.
+    pub sha256: String,
+    pub chunks: Vec<Chunk>,
+}
+
+impl FileIndex {
+    /// Serializes the index to the JSON body of the index object.
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self)
+            .map_err(|e| PrefixloadError::Custom(format!("Failed to serialize chunk index: {}", e)))
+    }
+}
+
+/// Returns the object key of the chunk with the given SHA-256.
+pub fn chunk_key(sha256: &str) -> String {
+    format!("{}/{}", CHUNK_PREFIX, sha256)
+}
+
+/// Returns the object key of the index of the file stored at `remote_key`.
+pub fn index_key(remote_key: &str) -> String {
+    format!("{}{}", remote_key, INDEX_SUFFIX)
+}
+
+/// Splits a file into content-defined chunks for the `dedup` mode and hashes them.
+///
+/// Boundaries are placed where a rolling gear hash of the content matches a
+/// pattern, so an insertion or deletion only changes the chunks around it
+/// and the rest of the file deduplicates against the previous upload.
+pub fn chunk_file<P: AsRef<Path>>(file_path: P, sizes: ChunkSizes) -> Result<FileIndex> {
+    let mut reader = BufReader::new(File::open(file_path)?);
+    let mask = sizes.mask();
+
+    let mut file_hasher = Sha256::new();
+    let mut chunks = Vec::new();
+    let mut size = 0u64;
+
+    let mut current = Vec::with_capacity(sizes.min);
+    let mut hash = 0u64;
+    let mut buf = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        file_hasher.update(&buf[..read]);
+        size += read as u64;
+
+        for &byte in &buf[..read] {
+            current.push(byte);
+            hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+
+            let at_boundary = current.len() >= sizes.min && hash & mask == 0;
+            if at_boundary || current.len() >= sizes.max {
+                chunks.push(hash_chunk(&current));
+                current.clear();
+                hash = 0;
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(hash_chunk(&current));
+    }
+
+    Ok(FileIndex {
+        version: INDEX_VERSION,
+        size,
+        sha256: format!("{:x}", file_hasher.finalize()),
+        chunks,
+    })
+}
+
+fn hash_chunk(data: &[u8]) -> Chunk {
+    Chunk {
+        sha256: format!("{:x}", Sha256::digest(data)),
+        size: data.len() as u64,
+        md5: format!("{:x}", md5::compute(data)),
+    }
+}
+
+/// Reads the chunk at `offset` back from the file, failing if the content
+/// no longer matches the chunk's hash (i.e. the file changed since it was chunked).
+pub fn read_chunk<P: AsRef<Path>>(file_path: P, offset: u64, chunk: &Chunk) -> Result<Vec<u8>> {
+    let file_path = file_path.as_ref();
+    let mut file = File::open(file_path)?;
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut data = vec![0u8; chunk.size as usize];
+    file.read_exact(&mut data)?;
+
+    if format!("{:x}", Sha256::digest(&data)) != chunk.sha256 {
+        return Err(PrefixloadError::Custom(format!(
+            "File {} changed while it was being uploaded.",
+            file_path.display()
+        )));
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const SMALL: ChunkSizes = ChunkSizes {
+        min: 256,
+        avg: 1024,
+        max: 4096,
+    };
+
+    /// Deterministic pseudo-random content.
+    fn content(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunks_respect_bounds_and_cover_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.bin");
+        let data = content(64 * 1024, 1);
+        fs::write(&path, &data).unwrap();
+
+        let index = chunk_file(&path, SMALL).unwrap();
+
+        assert_eq!(index.size, data.len() as u64);
+        assert_eq!(index.sha256, format!("{:x}", Sha256::digest(&data)));
+        assert!(index.chunks.len() > 1);
+        assert_eq!(
+            index.chunks.iter().map(|c| c.size).sum::<u64>(),
+            data.len() as u64
+        );
+        let (last, rest) = index.chunks.split_last().unwrap();
+        assert!(last.size <= SMALL.max as u64);
+        for chunk in rest {
+            assert!(chunk.size >= SMALL.min as u64 && chunk.size <= SMALL.max as u64);
+        }
+
+        let mut offset = 0;
+        for chunk in &index.chunks {
+            let data = read_chunk(&path, offset, chunk).unwrap();
+            assert_eq!(format!("{:x}", md5::compute(&data)), chunk.md5);
+            offset += chunk.size;
+        }
+    }
+
+    #[test]
+    fn insertion_only_changes_nearby_chunks() {
+        let dir = tempdir().unwrap();
+        let original = content(64 * 1024, 2);
+        let mut edited = original.clone();
+        edited.splice(100..100, b"a few new bytes".iter().copied());
+
+        let path = dir.path().join("file.bin");
+        fs::write(&path, &original).unwrap();
+        let before = chunk_file(&path, SMALL).unwrap();
+        fs::write(&path, &edited).unwrap();
+        let after = chunk_file(&path, SMALL).unwrap();
+
+        let new_chunks = after
+            .chunks
+            .iter()
+            .filter(|chunk| !before.chunks.iter().any(|c| c.sha256 == chunk.sha256))
+            .count();
+        assert!(new_chunks <= 2, "{} new chunks", new_chunks);
+    }
+
+    #[test]
+    fn read_chunk_detects_changed_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.bin");
+        fs::write(&path, content(1000, 3)).unwrap();
+        let index = chunk_file(&path, SMALL).unwrap();
+
+        fs::write(&path, content(1000, 4)).unwrap();
+        let err = read_chunk(&path, 0, &index.chunks[0]).unwrap_err();
+        assert!(err.to_string().contains("changed"));
+    }
+
+    #[test]
+    fn keys_use_chunk_prefix_and_index_suffix() {
+        assert_eq!(chunk_key("abc"), "chunks/abc");
+        assert_eq!(index_key("db/dump.sql"), "db/dump.sql.index.json");
+    }
+}
@@ -1,3 +1,6 @@
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_types::request_id::RequestId;
+use serde_json::json;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -17,8 +20,231 @@ pub enum PrefixloadError {
     #[error("Error [AWS SDK S3]: {0}")]
     AWS(#[from] aws_sdk_s3::Error),
 
+    #[error("Error [AWS SDK STS]: {0}")]
+    STS(#[from] aws_sdk_sts::Error),
+
     #[error("Error [Requestty]: {0}")]
     Requestty(#[from] requestty::ErrorKind),
+
+    #[error("Error [Timeout]: {0}")]
+    TimedOut(String),
+
+    #[error("Error [Cancelled]: {0}")]
+    Cancelled(String),
+}
+
+/// Process exit code for a run aborted by `max_duration`.
+pub const EXIT_TIMED_OUT: i32 = 3;
+
+/// Process exit code for a run cancelled midway, e.g. with Ctrl-C, as
+/// shells report commands stopped by SIGINT.
+pub const EXIT_CANCELLED: i32 = 130;
+
+/// Process exit code for other errors that running again later may get
+/// past, see `PrefixloadError::is_retryable`.
+pub const EXIT_TRANSIENT: i32 = 4;
+
+/// S3 error code returned when the request rate is too high for the bucket
+/// (and by MinIO when it is overloaded).
+const SLOW_DOWN_CODE: &str = "SlowDown";
+
+/// S3 error codes that signal request-rate throttling rather than a failure.
+const THROTTLING_CODES: &[&str] = &[
+    SLOW_DOWN_CODE,
+    "Throttling",
+    "ThrottlingException",
+    "RequestLimitExceeded",
+    "TooManyRequests",
+];
+
+/// Error codes of requests signed with an expired session token.
+const EXPIRED_TOKEN_CODES: &[&str] = &["ExpiredToken", "ExpiredTokenException"];
+
+/// Error codes of S3 and STS failures that usually go away on their own.
+const TRANSIENT_CODES: &[&str] = &[
+    "InternalError",
+    "ServiceUnavailable",
+    "RequestTimeout",
+    "IDPCommunicationError",
+];
+
+impl PrefixloadError {
+    /// Returns the process exit code for the error: `EXIT_TIMED_OUT` for
+    /// runs aborted by `max_duration`, `EXIT_CANCELLED` for cancelled runs,
+    /// `EXIT_TRANSIENT` for other retryable errors, `1` for everything else.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            PrefixloadError::TimedOut(_) => EXIT_TIMED_OUT,
+            PrefixloadError::Cancelled(_) => EXIT_CANCELLED,
+            _ if self.is_retryable() => EXIT_TRANSIENT,
+            _ => 1,
+        }
+    }
+
+    /// Returns `true` if the error is an S3 throttling response
+    /// (e.g. `503 SlowDown`) that should be retried with less concurrency.
+    pub fn is_throttling(&self) -> bool {
+        match self {
+            PrefixloadError::AWS(err) => err
+                .code()
+                .is_some_and(|code| THROTTLING_CODES.contains(&code)),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if S3 rejected the request because its session token
+    /// expired.
+    pub fn is_expired_credentials(&self) -> bool {
+        match self {
+            PrefixloadError::AWS(err) => err
+                .code()
+                .is_some_and(|code| EXPIRED_TOKEN_CODES.contains(&code)),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the error is a `503 SlowDown` response, which is
+    /// retried after a longer backoff than the other throttling errors.
+    pub fn is_slow_down(&self) -> bool {
+        match self {
+            PrefixloadError::AWS(err) => err.code() == Some(SLOW_DOWN_CODE),
+            _ => false,
+        }
+    }
+
+    /// Returns the broad kind of the error, e.g. `s3` or `config`.
+    pub fn category(&self) -> &'static str {
+        match self {
+            PrefixloadError::Custom(_) => "prefixload",
+            PrefixloadError::Io(_) => "io",
+            PrefixloadError::SerdeYAML(_) => "config",
+            PrefixloadError::Syntect(_) => "highlighting",
+            PrefixloadError::AWS(_) => "s3",
+            PrefixloadError::STS(_) => "sts",
+            PrefixloadError::Requestty(_) => "prompt",
+            PrefixloadError::TimedOut(_) => "timeout",
+            PrefixloadError::Cancelled(_) => "cancelled",
+        }
+    }
+
+    /// Returns a code to branch on: the error code of S3 and STS responses
+    /// (e.g. `AccessDenied`), the kind of I/O errors (e.g. `NotFound`), or a
+    /// fixed code for the other categories.
+    pub fn code(&self) -> String {
+        match self {
+            PrefixloadError::Custom(_) => "Failed".to_string(),
+            PrefixloadError::Io(err) => format!("{:?}", err.kind()),
+            PrefixloadError::SerdeYAML(_) => "InvalidYaml".to_string(),
+            PrefixloadError::Syntect(_) => "HighlightingFailed".to_string(),
+            PrefixloadError::AWS(err) => err.code().unwrap_or("Unknown").to_string(),
+            PrefixloadError::STS(err) => err.code().unwrap_or("Unknown").to_string(),
+            PrefixloadError::Requestty(_) => "PromptFailed".to_string(),
+            PrefixloadError::TimedOut(_) => "TimedOut".to_string(),
+            PrefixloadError::Cancelled(_) => "Cancelled".to_string(),
+        }
+    }
+
+    /// Returns `true` if running the command again later may succeed:
+    /// throttling, transient S3 and STS failures, interrupted connections and
+    /// runs aborted by `max_duration` or cancelled.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PrefixloadError::AWS(err) => {
+                self.is_throttling()
+                    || err
+                        .code()
+                        .is_some_and(|code| TRANSIENT_CODES.contains(&code))
+            }
+            PrefixloadError::STS(err) => err
+                .code()
+                .is_some_and(|code| TRANSIENT_CODES.contains(&code)),
+            PrefixloadError::Io(err) => matches!(
+                err.kind(),
+                std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+            ),
+            PrefixloadError::TimedOut(_) | PrefixloadError::Cancelled(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Renders the error as a JSON object for `--output json`, with its
+    /// `code`, `category`, `message`, whether it is `retryable`, and a
+    /// `context` holding the exit code and the request ID of S3 and STS errors.
+    pub fn to_json(&self) -> serde_json::Value {
+        let message = match self {
+            PrefixloadError::Custom(msg)
+            | PrefixloadError::TimedOut(msg)
+            | PrefixloadError::Cancelled(msg) => msg.clone(),
+            PrefixloadError::Io(err) => err.to_string(),
+            PrefixloadError::SerdeYAML(err) => err.to_string(),
+            PrefixloadError::Syntect(err) => err.to_string(),
+            PrefixloadError::AWS(err) => err.to_string(),
+            PrefixloadError::STS(err) => err.to_string(),
+            PrefixloadError::Requestty(err) => err.to_string(),
+        };
+        let request_id = match self {
+            PrefixloadError::AWS(err) => err.request_id(),
+            PrefixloadError::STS(err) => err.request_id(),
+            _ => None,
+        };
+
+        let mut context = json!({ "exit_code": self.exit_code() });
+        if let Some(request_id) = request_id {
+            context["request_id"] = request_id.into();
+        }
+        json!({
+            "code": self.code(),
+            "category": self.category(),
+            "message": message,
+            "retryable": self.is_retryable(),
+            "context": context,
+        })
+    }
 }
 
 pub type Result<T> = std::result::Result<T, PrefixloadError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_code_tells_transient_errors_apart() {
+        let timed_out = PrefixloadError::TimedOut("max_duration reached".to_string());
+        assert_eq!(timed_out.exit_code(), EXIT_TIMED_OUT);
+
+        let reset = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+        assert_eq!(PrefixloadError::Io(reset).exit_code(), EXIT_TRANSIENT);
+
+        let cancelled = PrefixloadError::Cancelled("Cancelled the run.".to_string());
+        assert_eq!(cancelled.exit_code(), EXIT_CANCELLED);
+
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(PrefixloadError::Io(missing).exit_code(), 1);
+    }
+
+    #[test]
+    fn to_json_describes_the_error() {
+        let err = PrefixloadError::TimedOut("max_duration of 60 minutes reached".to_string());
+        assert_eq!(
+            err.to_json(),
+            json!({
+                "code": "TimedOut",
+                "category": "timeout",
+                "message": "max_duration of 60 minutes reached",
+                "retryable": true,
+                "context": { "exit_code": EXIT_TIMED_OUT },
+            })
+        );
+
+        let err = PrefixloadError::Io(std::io::Error::from(std::io::ErrorKind::NotFound));
+        let json = err.to_json();
+        assert_eq!(json["code"], "NotFound");
+        assert_eq!(json["category"], "io");
+        assert_eq!(json["retryable"], false);
+        assert_eq!(json["context"]["exit_code"], 1);
+    }
+}
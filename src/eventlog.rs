@@ -0,0 +1,119 @@
+use crate::error::{PrefixloadError, Result};
+
+/// Source of the events in the Windows Application log.
+pub const EVENT_SOURCE: &str = "prefixload";
+
+/// Run events written to the Windows Event Log (`event_log`), each with an
+/// event ID of its own for monitoring rules to match on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunEvent {
+    /// A run started (information, ID 1000).
+    Started,
+    /// A run finished successfully (information, ID 1001).
+    Finished,
+    /// A run was aborted by an error (error, ID 1002).
+    Failed,
+}
+
+impl RunEvent {
+    /// Returns the event ID of the event.
+    pub fn id(self) -> u32 {
+        match self {
+            RunEvent::Started => 1000,
+            RunEvent::Finished => 1001,
+            RunEvent::Failed => 1002,
+        }
+    }
+}
+
+/// Writes `event` with `message` as its description to the Application
+/// log, under the `EVENT_SOURCE` source.
+pub fn report(event: RunEvent, message: &str) -> Result<()> {
+    platform::report(event, message).map_err(|e| {
+        PrefixloadError::Custom(format!("Failed to write to the Windows Event Log: {}", e))
+    })
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{EVENT_SOURCE, RunEvent};
+    use std::io;
+    use std::ptr;
+    use windows_sys::Win32::System::EventLog::{
+        DeregisterEventSource, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+        RegisterEventSourceW, ReportEventW,
+    };
+
+    pub fn report(event: RunEvent, message: &str) -> io::Result<()> {
+        let source: Vec<u16> = EVENT_SOURCE.encode_utf16().chain(Some(0)).collect();
+        let message: Vec<u16> = message.encode_utf16().chain(Some(0)).collect();
+        let kind = match event {
+            RunEvent::Failed => EVENTLOG_ERROR_TYPE,
+            RunEvent::Started | RunEvent::Finished => EVENTLOG_INFORMATION_TYPE,
+        };
+
+        // SAFETY: `source` is a NUL-terminated UTF-16 string.
+        let handle = unsafe { RegisterEventSourceW(ptr::null(), source.as_ptr()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let strings = [message.as_ptr()];
+        // SAFETY: `handle` is a registered event source and `strings` holds
+        // one NUL-terminated UTF-16 string that outlives the call.
+        let reported = unsafe {
+            ReportEventW(
+                handle,
+                kind,
+                0,
+                event.id(),
+                ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                ptr::null(),
+            )
+        };
+        let result = if reported == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        };
+
+        // SAFETY: `handle` was registered above and is not used afterwards.
+        unsafe { DeregisterEventSource(handle) };
+        result
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::RunEvent;
+    use std::io;
+
+    pub fn report(_event: RunEvent, _message: &str) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the Windows Event Log is only available on Windows",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_events_have_distinct_ids() {
+        assert_eq!(RunEvent::Started.id(), 1000);
+        assert_eq!(RunEvent::Finished.id(), 1001);
+        assert_eq!(RunEvent::Failed.id(), 1002);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn report_is_unsupported_outside_windows() {
+        let err = report(RunEvent::Started, "Run started.").unwrap_err();
+        assert!(err.to_string().contains("only available on Windows"));
+    }
+}
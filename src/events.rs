@@ -0,0 +1,83 @@
+use crate::report::{FailureKind, InterruptedRun};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// What happens during a run, in the order it happens, for frontends that
+/// show its progress. The CLI prints them with `--output ndjson`, named
+/// after their `event` tag.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BackupEvent {
+    #[serde(rename = "run_started")]
+    Started {
+        run_id: String,
+    },
+    /// The rule directories (or the `--files-from` list) were read.
+    #[serde(rename = "files_scanned")]
+    Scanned {
+        files: usize,
+    },
+    /// A file matched a rule and will be synced.
+    #[serde(rename = "file_matched")]
+    Matched {
+        path: PathBuf,
+        key: String,
+        size: u64,
+    },
+    /// The file was hashed; `etag` is `None` for the chunks of `dedup` mode.
+    #[serde(rename = "hash_done")]
+    Hashed {
+        key: String,
+        etag: Option<String>,
+    },
+    UploadStarted {
+        key: String,
+        size: u64,
+    },
+    #[serde(rename = "upload_finished")]
+    Uploaded {
+        key: String,
+        size: u64,
+        /// E.g. the transfer rate, or the key the object was copied from.
+        note: String,
+    },
+    /// The file was not uploaded, e.g. because it is already synced or
+    /// too large.
+    #[serde(rename = "file_skipped")]
+    Skipped {
+        key: String,
+        size: u64,
+        reason: String,
+    },
+    /// Syncing a file failed.
+    #[serde(rename = "error")]
+    Failed {
+        key: String,
+        message: String,
+        failure: FailureKind,
+    },
+    #[serde(rename = "run_finished")]
+    Finished {
+        success: bool,
+        matched: usize,
+        uploaded: usize,
+        skipped: usize,
+        failed: usize,
+        uploaded_bytes: u64,
+        duration_secs: f64,
+        /// Error that aborted the run.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        failure: Option<FailureKind>,
+        /// Earlier runs that never finished.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        interrupted: Vec<InterruptedRun>,
+    },
+}
+
+/// Receives the events of a run as they happen. It is called from the
+/// upload tasks, so it should return quickly, e.g. by sending the event to
+/// a channel.
+pub type EventSink = Arc<dyn Fn(&BackupEvent) + Send + Sync>;
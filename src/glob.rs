@@ -0,0 +1,47 @@
+/// Matches `text` against a pattern of `*` and `?` wildcards.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The last `*` and the position in `text` it matches up to, for
+    // backtracking when the rest of the pattern does not match.
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_supports_wildcards() {
+        assert!(glob_match("db_*", "db_1.sql"));
+        assert!(glob_match("db_?.sql", "db_1.sql"));
+        assert!(glob_match("*.sql", "db_1.sql"));
+        assert!(glob_match("*_*_*", "a_b_c"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("db_?.sql", "db_10.sql"));
+        assert!(!glob_match("*.sql", "db_1.sql.gz"));
+        assert!(!glob_match("app_*", "db_1.sql"));
+    }
+}
@@ -1,5 +1,25 @@
+pub mod attributes;
+pub mod cancel;
 pub mod cli;
 pub mod clients;
 pub mod config;
+pub mod control;
 pub mod crypto;
+pub mod dedup;
 pub mod error;
+pub mod eventlog;
+pub mod events;
+pub mod glob;
+pub mod manifest;
+pub mod marker;
+pub mod plugin;
+pub mod priority;
+pub mod redact;
+pub mod report;
+pub mod retention;
+pub mod size;
+pub mod state;
+pub mod timezone;
+pub mod uring;
+pub mod window;
+pub mod xattrs;
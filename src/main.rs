@@ -4,12 +4,17 @@ use prefixload::cli::Cli;
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    let json_errors = cli.json_errors();
 
     match cli.run().await {
         Ok(result) => print!("{}", result),
         Err(err) => {
-            eprintln!("{}", err);
-            std::process::exit(1);
+            if json_errors {
+                eprintln!("{}", err.to_json());
+            } else {
+                eprintln!("{}", err);
+            }
+            std::process::exit(err.exit_code());
         }
     }
 }
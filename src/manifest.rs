@@ -0,0 +1,156 @@
+use crate::error::{PrefixloadError, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Part MD5s of a multipart object uploaded by an earlier run, so that the
+/// next upload of a changed file copies its unchanged parts server-side.
+/// Stored per object, named after the MD5 of `<bucket>/<key>`:
+/// - Linux: ~/.local/share/prefixload/parts/<hash>.json
+/// - macOS: ~/Library/Application Support/prefixload/parts/<hash>.json
+/// - Windows: %LOCALAPPDATA%\prefixload\parts\<hash>.json
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PartManifest {
+    /// ETag of the object the parts belong to.
+    pub etag: String,
+    pub part_size: u64,
+    /// Hex MD5 of every part, in order.
+    pub parts: Vec<String>,
+}
+
+impl PartManifest {
+    /// Returns the manifest path for an object, creating its directory on demand.
+    fn manifest_path(bucket: &str, key: &str) -> Result<PathBuf> {
+        let mut dir = dirs_next::data_local_dir().ok_or_else(|| {
+            PrefixloadError::Custom("Could not find local data directory.".to_string())
+        })?;
+
+        dir.push("prefixload");
+        dir.push("parts");
+        fs::create_dir_all(&dir)?;
+
+        dir.push(format!(
+            "{:x}.json",
+            md5::compute(format!("{}/{}", bucket, key))
+        ));
+
+        Ok(dir)
+    }
+
+    /// Loads the manifest of an object, if one was saved.
+    pub fn load(bucket: &str, key: &str) -> Result<Option<Self>> {
+        let path = Self::manifest_path(bucket, key)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content).map(Some).map_err(|e| {
+            PrefixloadError::Custom(format!(
+                "Failed to parse part manifest {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Writes the manifest of an object.
+    pub fn save(&self, bucket: &str, key: &str) -> Result<()> {
+        let path = Self::manifest_path(bucket, key)?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| {
+            PrefixloadError::Custom(format!("Failed to serialize part manifest: {}", e))
+        })?;
+
+        fs::write(&path, json)?;
+        Ok(())
+    }
+
+    /// Deletes the manifest of an object. Returns `false` if there was none.
+    pub fn remove(bucket: &str, key: &str) -> Result<bool> {
+        match fs::remove_file(Self::manifest_path(bucket, key)?) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Returns, for every part in `parts`, whether the remote object already
+    /// holds it at the same position. Nothing is reused if the object has
+    /// changed since the manifest was written (its ETag is not `remote_etag`)
+    /// or the part size differs.
+    pub fn unchanged_parts(
+        &self,
+        remote_etag: &str,
+        part_size: u64,
+        parts: &[String],
+    ) -> Vec<bool> {
+        let valid = self.etag == remote_etag && self.part_size == part_size;
+        parts
+            .iter()
+            .enumerate()
+            .map(|(i, md5)| valid && self.parts.get(i) == Some(md5))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::env;
+    use tempfile::TempDir;
+
+    #[cfg(windows)]
+    const DATA_LOCAL_ENV: &str = "LOCALAPPDATA";
+    #[cfg(not(windows))]
+    const DATA_LOCAL_ENV: &str = "XDG_DATA_HOME";
+
+    fn manifest() -> PartManifest {
+        PartManifest {
+            etag: "abc-3".to_string(),
+            part_size: 5,
+            parts: vec!["p1".to_string(), "p2".to_string(), "p3".to_string()],
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn manifest_roundtrip_per_object() {
+        let tmp = TempDir::new().unwrap();
+        unsafe { env::set_var(DATA_LOCAL_ENV, tmp.path()) };
+
+        assert_eq!(PartManifest::load("bucket", "key").unwrap(), None);
+
+        manifest().save("bucket", "key").unwrap();
+        assert_eq!(
+            PartManifest::load("bucket", "key").unwrap(),
+            Some(manifest())
+        );
+        assert_eq!(PartManifest::load("bucket", "other").unwrap(), None);
+
+        assert!(PartManifest::remove("bucket", "key").unwrap());
+        assert!(!PartManifest::remove("bucket", "key").unwrap());
+        assert_eq!(PartManifest::load("bucket", "key").unwrap(), None);
+    }
+
+    #[test]
+    fn unchanged_parts_compares_positions() {
+        let parts: Vec<String> = ["p1", "changed", "p3", "p4"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+
+        assert_eq!(
+            manifest().unchanged_parts("abc-3", 5, &parts),
+            vec![true, false, true, false]
+        );
+        assert_eq!(
+            manifest().unchanged_parts("other-3", 5, &parts),
+            vec![false; 4]
+        );
+        assert_eq!(
+            manifest().unchanged_parts("abc-3", 8, &parts),
+            vec![false; 4]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::error::{PrefixloadError, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Marker of a run in progress, written (and synced to disk) when the run
+/// starts and removed when it ends. A marker that outlives its process
+/// means the run never finished, e.g. because the host crashed, so its
+/// lines at the end of `run.log` may be missing. Stored per run:
+/// - Linux: ~/.local/share/prefixload/running/<run_id>.json
+/// - macOS: ~/Library/Application Support/prefixload/running/<run_id>.json
+/// - Windows: %LOCALAPPDATA%\prefixload\running\<run_id>.json
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunMarker {
+    pub run_id: String,
+    pub job: Option<String>,
+    pub started_at: DateTime<Utc>,
+    /// Process of the run, to tell a crashed run from one still going.
+    pub pid: u32,
+}
+
+impl RunMarker {
+    /// Returns the directory of the markers, creating it on demand.
+    fn dir() -> Result<PathBuf> {
+        let mut dir = dirs_next::data_local_dir().ok_or_else(|| {
+            PrefixloadError::Custom("Could not find local data directory.".to_string())
+        })?;
+
+        dir.push("prefixload");
+        dir.push("running");
+        fs::create_dir_all(&dir)?;
+
+        Ok(dir)
+    }
+
+    fn path(run_id: &str) -> Result<PathBuf> {
+        Ok(Self::dir()?.join(format!("{}.json", run_id)))
+    }
+
+    /// Writes the marker of a run of this process that starts now, and
+    /// waits until it is on disk. The marker is removed when the returned
+    /// guard is dropped.
+    pub fn start(run_id: &str, job: Option<&str>) -> Result<RunMarkerGuard> {
+        let marker = Self {
+            run_id: run_id.to_string(),
+            job: job.map(str::to_string),
+            started_at: Utc::now(),
+            pid: std::process::id(),
+        };
+        let json = serde_json::to_string_pretty(&marker).map_err(|e| {
+            PrefixloadError::Custom(format!("Failed to serialize run marker: {}", e))
+        })?;
+
+        let path = Self::path(run_id)?;
+        let mut file = File::create(&path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+
+        Ok(RunMarkerGuard { path })
+    }
+
+    /// Returns the markers of the runs that ended without removing them,
+    /// oldest first, and removes them so each is only reported once.
+    /// Markers of processes that are still running are left alone.
+    pub fn take_unfinished() -> Result<Vec<Self>> {
+        let mut markers = Vec::new();
+        for entry in fs::read_dir(Self::dir()?)? {
+            let path = entry?.path();
+            // A marker cut short by the crash itself is unreadable; it is
+            // dropped without being reported.
+            let marker = fs::read_to_string(&path)
+                .ok()
+                .and_then(|json| serde_json::from_str::<Self>(&json).ok());
+            if marker.as_ref().is_some_and(|marker| is_running(marker.pid)) {
+                continue;
+            }
+            fs::remove_file(&path)?;
+            markers.extend(marker);
+        }
+
+        markers.sort_by_key(|marker| marker.started_at);
+        Ok(markers)
+    }
+}
+
+/// Removes the marker of a run when the run ends, including when it fails.
+pub struct RunMarkerGuard {
+    path: PathBuf,
+}
+
+impl Drop for RunMarkerGuard {
+    fn drop(&mut self) {
+        // A marker left behind is reported once by the next run, which
+        // is all a failure to remove it costs.
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Returns `true` if a process with this ID exists.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks whether the process exists; EPERM means it
+    // does, but belongs to another user.
+    let exists = unsafe { libc::kill(pid, 0) } == 0;
+    exists || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Returns `true` if a process with this ID exists. Only the current
+/// process is known to be running; markers of concurrent runs of other
+/// processes are reported as unfinished.
+#[cfg(not(unix))]
+fn is_running(pid: u32) -> bool {
+    pid == std::process::id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::env;
+    use tempfile::TempDir;
+
+    #[cfg(windows)]
+    const DATA_LOCAL_ENV: &str = "LOCALAPPDATA";
+    #[cfg(not(windows))]
+    const DATA_LOCAL_ENV: &str = "XDG_DATA_HOME";
+
+    #[test]
+    #[serial]
+    fn markers_of_ended_processes_are_taken_once() {
+        let tmp = TempDir::new().unwrap();
+        unsafe { env::set_var(DATA_LOCAL_ENV, tmp.path()) };
+
+        let running = RunMarker::start("run-1", Some("nightly")).unwrap();
+        let crashed = RunMarker {
+            run_id: "run-0".to_string(),
+            job: None,
+            started_at: Utc::now() - chrono::Duration::hours(1),
+            // Above the highest PID Linux hands out.
+            pid: 4_194_305,
+        };
+        fs::write(
+            RunMarker::path("run-0").unwrap(),
+            serde_json::to_string(&crashed).unwrap(),
+        )
+        .unwrap();
+        fs::write(RunMarker::path("run-torn").unwrap(), "{\"run_id\":").unwrap();
+
+        assert_eq!(RunMarker::take_unfinished().unwrap(), vec![crashed]);
+        assert_eq!(RunMarker::take_unfinished().unwrap(), vec![]);
+        assert!(RunMarker::path("run-1").unwrap().exists());
+
+        drop(running);
+        assert!(!RunMarker::path("run-1").unwrap().exists());
+    }
+}
@@ -0,0 +1,346 @@
+use crate::error::{PrefixloadError, Result};
+use serde::Serialize;
+use std::path::Path;
+
+/// Fuel a plugin may burn per call, roughly one unit per WASM instruction.
+/// Calls that run out trap instead of stalling the run.
+pub const FUEL_PER_CALL: u64 = 10_000_000;
+
+/// Most linear memory a plugin may grow to.
+pub const MAX_MEMORY: usize = 16 * 1024 * 1024;
+
+/// A matched file, as handed to a plugin (serialized as JSON).
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginFile<'a> {
+    /// File name.
+    pub name: &'a str,
+    /// Full local path.
+    pub path: &'a str,
+    pub size: u64,
+    /// Modification time as Unix seconds, 0 if unknown.
+    pub modified: i64,
+    /// `local_name_prefix` of the rule the file matched.
+    pub rule: &'a str,
+    /// Key the file is uploaded under unless the plugin changes it.
+    pub remote_key: &'a str,
+}
+
+/// A WASM filter plugin of a `directory_struct` rule (`plugin`).
+///
+/// A plugin is a WASM module without imports, so it has no access to the
+/// file system, the network or the clock. It exports:
+///
+/// * `memory` and `alloc(len: i32) -> i32`, which returns the address of
+///   `len` bytes for the host to write a `PluginFile` JSON document to;
+/// * `should_upload(ptr: i32, len: i32) -> i32`: non-zero uploads the file,
+///   zero leaves it out;
+/// * optionally `transform_key(ptr: i32, len: i32) -> i64`: returns the new
+///   key as `(address << 32) | length` of UTF-8 bytes in `memory`, or 0 to
+///   keep the key.
+///
+/// Every call is limited to `FUEL_PER_CALL` and memory to `MAX_MEMORY`.
+pub struct Plugin {
+    name: String,
+    runtime: runtime::Runtime,
+}
+
+impl Plugin {
+    /// Compiles and instantiates the plugin at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let name = path.display().to_string();
+        let runtime = runtime::Runtime::load(path).map_err(|e| {
+            PrefixloadError::Custom(format!("Failed to load plugin {}: {}", name, e))
+        })?;
+
+        Ok(Self { name, runtime })
+    }
+
+    /// Asks the plugin whether `file` is uploaded.
+    pub fn should_upload(&mut self, file: &PluginFile) -> Result<bool> {
+        let json = self.to_json(file)?;
+        self.runtime
+            .should_upload(&json)
+            .map_err(|e| self.error("should_upload", e))
+    }
+
+    /// Asks the plugin for the key of `file`. `None` keeps `file.remote_key`.
+    pub fn transform_key(&mut self, file: &PluginFile) -> Result<Option<String>> {
+        let json = self.to_json(file)?;
+        let key = self
+            .runtime
+            .transform_key(&json)
+            .map_err(|e| self.error("transform_key", e))?;
+
+        match key {
+            Some(key) if key.is_empty() => Err(PrefixloadError::Custom(format!(
+                "Plugin {} returned an empty key for <{}>.",
+                self.name, file.name
+            ))),
+            key => Ok(key),
+        }
+    }
+
+    fn to_json(&self, file: &PluginFile) -> Result<String> {
+        serde_json::to_string(file).map_err(|e| {
+            PrefixloadError::Custom(format!("Failed to serialize file for plugin: {}", e))
+        })
+    }
+
+    fn error(&self, export: &str, err: String) -> PrefixloadError {
+        PrefixloadError::Custom(format!(
+            "Plugin {} failed in {}: {}",
+            self.name, export, err
+        ))
+    }
+}
+
+#[cfg(feature = "wasm-plugins")]
+mod runtime {
+    use super::{FUEL_PER_CALL, MAX_MEMORY};
+    use std::path::Path;
+    use wasmtime::{
+        Config, Engine, Instance, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+    };
+
+    pub struct Runtime {
+        store: Store<StoreLimits>,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        should_upload: TypedFunc<(i32, i32), i32>,
+        transform_key: Option<TypedFunc<(i32, i32), i64>>,
+    }
+
+    impl Runtime {
+        pub fn load(path: &Path) -> Result<Self, String> {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config).map_err(|e| format!("{:#}", e))?;
+            let module = Module::from_file(&engine, path).map_err(|e| format!("{:#}", e))?;
+            if let Some(import) = module.imports().next() {
+                return Err(format!(
+                    "plugins cannot import anything, but it imports {}::{}",
+                    import.module(),
+                    import.name()
+                ));
+            }
+
+            let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+            let mut store = Store::new(&engine, limits);
+            store.limiter(|limits| limits);
+            store
+                .set_fuel(FUEL_PER_CALL)
+                .map_err(|e| format!("{:#}", e))?;
+            let instance =
+                Instance::new(&mut store, &module, &[]).map_err(|e| format!("{:#}", e))?;
+
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or("it does not export `memory`")?;
+            let alloc = instance
+                .get_typed_func(&mut store, "alloc")
+                .map_err(|e| format!("`alloc`: {:#}", e))?;
+            let should_upload = instance
+                .get_typed_func(&mut store, "should_upload")
+                .map_err(|e| format!("`should_upload`: {:#}", e))?;
+            let transform_key = match instance.get_export(&mut store, "transform_key") {
+                Some(_) => Some(
+                    instance
+                        .get_typed_func(&mut store, "transform_key")
+                        .map_err(|e| format!("`transform_key`: {:#}", e))?,
+                ),
+                None => None,
+            };
+
+            Ok(Self {
+                store,
+                memory,
+                alloc,
+                should_upload,
+                transform_key,
+            })
+        }
+
+        pub fn should_upload(&mut self, json: &str) -> Result<bool, String> {
+            let (ptr, len) = self.write(json)?;
+            let verdict = self
+                .should_upload
+                .call(&mut self.store, (ptr, len))
+                .map_err(|e| format!("{:#}", e))?;
+            Ok(verdict != 0)
+        }
+
+        pub fn transform_key(&mut self, json: &str) -> Result<Option<String>, String> {
+            let Some(transform_key) = self.transform_key.clone() else {
+                return Ok(None);
+            };
+            let (ptr, len) = self.write(json)?;
+            let packed = transform_key
+                .call(&mut self.store, (ptr, len))
+                .map_err(|e| format!("{:#}", e))?;
+            if packed == 0 {
+                return Ok(None);
+            }
+
+            let (ptr, len) = ((packed as u64 >> 32) as usize, packed as u32 as usize);
+            let mut key = vec![0; len];
+            self.memory
+                .read(&self.store, ptr, &mut key)
+                .map_err(|_| "it returned a key outside its memory".to_string())?;
+            String::from_utf8(key)
+                .map(Some)
+                .map_err(|_| "it returned a key that is not UTF-8".to_string())
+        }
+
+        /// Refuels the store and copies `json` into memory from `alloc`.
+        fn write(&mut self, json: &str) -> Result<(i32, i32), String> {
+            self.store
+                .set_fuel(FUEL_PER_CALL)
+                .map_err(|e| format!("{:#}", e))?;
+            let len = i32::try_from(json.len()).map_err(|_| "input too large".to_string())?;
+            let ptr = self
+                .alloc
+                .call(&mut self.store, len)
+                .map_err(|e| format!("alloc: {:#}", e))?;
+            self.memory
+                .write(&mut self.store, ptr as u32 as usize, json.as_bytes())
+                .map_err(|_| "alloc returned an address outside its memory".to_string())?;
+            Ok((ptr, len))
+        }
+    }
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+mod runtime {
+    use std::path::Path;
+
+    pub enum Runtime {}
+
+    impl Runtime {
+        pub fn load(_path: &Path) -> Result<Self, String> {
+            Err("WASM plugins need a build with the `wasm-plugins` feature".to_string())
+        }
+
+        pub fn should_upload(&mut self, _json: &str) -> Result<bool, String> {
+            match *self {}
+        }
+
+        pub fn transform_key(&mut self, _json: &str) -> Result<Option<String>, String> {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "wasm-plugins"))]
+    #[test]
+    fn load_needs_the_wasm_plugins_feature() {
+        let err = Plugin::load(Path::new("filter.wasm")).err().unwrap();
+        assert!(err.to_string().contains("`wasm-plugins` feature"));
+    }
+
+    #[cfg(feature = "wasm-plugins")]
+    mod wasm {
+        use super::*;
+        use std::fs;
+        use tempfile::TempDir;
+
+        fn file(name: &str, size: u64) -> PluginFile<'_> {
+            PluginFile {
+                name,
+                path: name,
+                size,
+                modified: 0,
+                rule: "db_",
+                remote_key: name,
+            }
+        }
+
+        /// Uploads files up to 100 bytes and moves them under `small/`.
+        /// The JSON starts with `{"name":"`, so the name is at offset 9.
+        const FILTER: &str = r#"(module
+            (memory (export "memory") 1)
+            (global $next (mut i32) (i32.const 1024))
+            (func (export "alloc") (param $len i32) (result i32)
+                (local $ptr i32)
+                (local.set $ptr (global.get $next))
+                (global.set $next (i32.add (global.get $next) (local.get $len)))
+                (local.get $ptr))
+            (func $size (param $ptr i32) (param $len i32) (result i32)
+                ;; Reads the digits after `"size":`.
+                (local $i i32) (local $n i32) (local $c i32)
+                (local.set $i (local.get $ptr))
+                (block $found (loop $scan
+                    (br_if $found (i32.and
+                        (i32.eq (i32.load8_u (local.get $i)) (i32.const 122))
+                        (i32.eq (i32.load8_u offset=1 (local.get $i)) (i32.const 101))))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br $scan)))
+                (local.set $i (i32.add (local.get $i) (i32.const 4)))
+                (block $done (loop $digits
+                    (local.set $c (i32.sub (i32.load8_u (local.get $i)) (i32.const 48)))
+                    (br_if $done (i32.gt_u (local.get $c) (i32.const 9)))
+                    (local.set $n (i32.add (i32.mul (local.get $n) (i32.const 10)) (local.get $c)))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br $digits)))
+                (local.get $n))
+            (func (export "should_upload") (param $ptr i32) (param $len i32) (result i32)
+                (i32.le_u (call $size (local.get $ptr) (local.get $len)) (i32.const 100)))
+            (data (i32.const 0) "small/db_1.sql")
+            (func (export "transform_key") (param $ptr i32) (param $len i32) (result i64)
+                (i64.const 14)))
+        "#;
+
+        fn plugin(dir: &TempDir, source: &str) -> Result<Plugin> {
+            let path = dir.path().join("filter.wat");
+            fs::write(&path, source).unwrap();
+            Plugin::load(&path)
+        }
+
+        #[test]
+        fn plugin_filters_files_and_rewrites_keys() {
+            let dir = TempDir::new().unwrap();
+            let mut plugin = plugin(&dir, FILTER).unwrap();
+
+            assert!(plugin.should_upload(&file("db_1.sql", 42)).unwrap());
+            assert!(!plugin.should_upload(&file("db_2.sql", 4096)).unwrap());
+            assert_eq!(
+                plugin
+                    .transform_key(&file("db_1.sql", 42))
+                    .unwrap()
+                    .as_deref(),
+                Some("small/db_1.sql")
+            );
+        }
+
+        #[test]
+        fn plugin_is_sandboxed() {
+            let dir = TempDir::new().unwrap();
+
+            let err = plugin(
+                &dir,
+                r#"(module (import "wasi_snapshot_preview1" "fd_write"
+                    (func (param i32 i32 i32 i32) (result i32))))"#,
+            )
+            .err()
+            .unwrap();
+            assert!(err.to_string().contains("cannot import anything"));
+
+            let mut plugin = plugin(
+                &dir,
+                r#"(module
+                    (memory (export "memory") 1)
+                    (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                    (func (export "should_upload") (param i32 i32) (result i32)
+                        (loop $forever (br $forever))
+                        (i32.const 1)))"#,
+            )
+            .unwrap();
+            let err = plugin.should_upload(&file("db_1.sql", 1)).unwrap_err();
+            assert!(err.to_string().contains("failed in should_upload"));
+            assert_eq!(plugin.transform_key(&file("db_1.sql", 1)).unwrap(), None);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::error::{PrefixloadError, Result};
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// Highest (least favorable) nice value.
+pub const MAX_NICE: i32 = 19;
+
+/// Level `best-effort` uses when none is given, as with `ionice -c 2`.
+const DEFAULT_BEST_EFFORT_LEVEL: u8 = 4;
+
+/// Lowest priority level within the best-effort class.
+const MAX_BEST_EFFORT_LEVEL: u8 = 7;
+
+/// I/O scheduling priority of a run (Linux only), written like the
+/// classes of `ionice`: `idle`, or `best-effort` with an optional level
+/// from 0 (highest) to 7 (lowest), e.g. `best-effort:7`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Disk access only when no other process needs the disk.
+    Idle,
+    /// The default class; the level orders processes within it.
+    BestEffort(u8),
+}
+
+impl fmt::Display for IoPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IoPriority::Idle => write!(f, "idle"),
+            IoPriority::BestEffort(level) => write!(f, "best-effort:{}", level),
+        }
+    }
+}
+
+impl FromStr for IoPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let value = s.trim().to_ascii_lowercase();
+        let (class, level) = match value.split_once(':') {
+            Some((class, level)) => (class, Some(level)),
+            None => (value.as_str(), None),
+        };
+
+        match (class, level) {
+            ("idle", None) => Ok(Self::Idle),
+            ("best-effort", None) => Ok(Self::BestEffort(DEFAULT_BEST_EFFORT_LEVEL)),
+            ("best-effort", Some(level)) => match level.parse() {
+                Ok(level) if level <= MAX_BEST_EFFORT_LEVEL => Ok(Self::BestEffort(level)),
+                _ => Err(format!(
+                    "invalid I/O priority '{}': the best-effort level must be 0-{}",
+                    s, MAX_BEST_EFFORT_LEVEL
+                )),
+            },
+            _ => Err(format!(
+                "invalid I/O priority '{}': use 'idle' or 'best-effort[:0-{}]'",
+                s, MAX_BEST_EFFORT_LEVEL
+            )),
+        }
+    }
+}
+
+impl Serialize for IoPriority {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for IoPriority {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for IoPriority {
+    fn schema_name() -> Cow<'static, str> {
+        "IoPriority".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "An ionice class: idle, or best-effort with an optional level from 0 to 7",
+            "type": "string",
+            "pattern": "^(idle|best-effort(:[0-7])?)$"
+        })
+    }
+}
+
+/// Lowers the CPU (`nice`) and I/O (`ionice`) priority of the process, so
+/// that a backup leaves the machine to interactive work. Threads started
+/// afterwards get the lowered priority as well.
+///
+/// Without root only lowering works: a `nice` value below the current one
+/// fails.
+pub fn lower_priority(nice: Option<i32>, ionice: Option<IoPriority>) -> Result<()> {
+    platform::lower_priority(nice, ionice).map_err(|e| {
+        PrefixloadError::Custom(format!("Failed to lower the process priority: {}", e))
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::IoPriority;
+    use std::fs;
+    use std::io;
+
+    /// `IOPRIO_WHO_PROCESS`: the target of `ioprio_set` is a thread ID.
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: u32 = 13;
+    const IOPRIO_CLASS_BE: u32 = 2;
+    const IOPRIO_CLASS_IDLE: u32 = 3;
+
+    pub fn lower_priority(nice: Option<i32>, ionice: Option<IoPriority>) -> io::Result<()> {
+        // Linux keeps both priorities per thread, so every running thread is
+        // changed; threads started later inherit them from their parent.
+        for entry in fs::read_dir("/proc/self/task")? {
+            let Some(tid) = entry?.file_name().to_str().and_then(|n| n.parse().ok()) else {
+                continue;
+            };
+            // A thread that exited in the meantime is skipped.
+            match set_thread_priority(tid, nice, ionice) {
+                Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {}
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    fn set_thread_priority(
+        tid: libc::pid_t,
+        nice: Option<i32>,
+        ionice: Option<IoPriority>,
+    ) -> io::Result<()> {
+        if let Some(nice) = nice {
+            // SAFETY: plain system call without pointers.
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        if let Some(ionice) = ionice {
+            let priority = match ionice {
+                IoPriority::Idle => IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+                IoPriority::BestEffort(level) => {
+                    IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT | u32::from(level)
+                }
+            };
+            // SAFETY: plain system call without pointers.
+            let result =
+                unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, priority) };
+            if result == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+mod platform {
+    use super::IoPriority;
+    use std::io;
+
+    pub fn lower_priority(nice: Option<i32>, ionice: Option<IoPriority>) -> io::Result<()> {
+        if ionice.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "ionice is only supported on Linux",
+            ));
+        }
+
+        if let Some(nice) = nice {
+            // SAFETY: plain system call without pointers.
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{IoPriority, MAX_NICE};
+    use std::io;
+    use windows_sys::Win32::System::Threading::{
+        BELOW_NORMAL_PRIORITY_CLASS, GetCurrentProcess, IDLE_PRIORITY_CLASS, SetPriorityClass,
+    };
+
+    pub fn lower_priority(nice: Option<i32>, ionice: Option<IoPriority>) -> io::Result<()> {
+        if ionice.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "ionice is only supported on Linux",
+            ));
+        }
+
+        // Windows has priority classes instead of nice values: the upper
+        // half of the nice range maps to idle, the rest to below normal.
+        let class = match nice {
+            Some(nice) if nice > MAX_NICE / 2 => IDLE_PRIORITY_CLASS,
+            Some(nice) if nice > 0 => BELOW_NORMAL_PRIORITY_CLASS,
+            _ => return Ok(()),
+        };
+        // SAFETY: the pseudo handle of the current process is always valid.
+        if unsafe { SetPriorityClass(GetCurrentProcess(), class) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::IoPriority;
+    use std::io;
+
+    pub fn lower_priority(nice: Option<i32>, ionice: Option<IoPriority>) -> io::Result<()> {
+        if nice.is_some() || ionice.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "process priorities are not supported on this platform",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_priority_parses_and_displays_classes() {
+        assert_eq!("idle".parse(), Ok(IoPriority::Idle));
+        assert_eq!("Best-Effort".parse(), Ok(IoPriority::BestEffort(4)));
+        assert_eq!("best-effort:7".parse(), Ok(IoPriority::BestEffort(7)));
+        assert!("best-effort:8".parse::<IoPriority>().is_err());
+        assert!("realtime".parse::<IoPriority>().is_err());
+        assert!("idle:3".parse::<IoPriority>().is_err());
+
+        assert_eq!(IoPriority::BestEffort(7).to_string(), "best-effort:7");
+        assert_eq!(serde_yaml::to_string(&IoPriority::Idle).unwrap(), "idle\n");
+    }
+}
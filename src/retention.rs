@@ -0,0 +1,111 @@
+use aws_sdk_s3::primitives::DateTime as S3DateTime;
+use aws_sdk_s3::types::ObjectLockMode;
+use chrono::{DateTime, Duration, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Object Lock retention mode of uploaded objects (`object_lock_mode`).
+///
+/// The bucket must have been created with Object Lock enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionMode {
+    /// Users with `s3:BypassGovernanceRetention` can still shorten or lift
+    /// the retention.
+    Governance,
+    /// Nobody, not even the root user, can delete or overwrite an object
+    /// version before its retention ends.
+    Compliance,
+}
+
+impl RetentionMode {
+    /// Returns the mode of the S3 Object Lock API.
+    pub fn s3_mode(self) -> ObjectLockMode {
+        match self {
+            Self::Governance => ObjectLockMode::Governance,
+            Self::Compliance => ObjectLockMode::Compliance,
+        }
+    }
+}
+
+impl fmt::Display for RetentionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Governance => "governance",
+            Self::Compliance => "compliance",
+        })
+    }
+}
+
+impl FromStr for RetentionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "governance" => Ok(Self::Governance),
+            "compliance" => Ok(Self::Compliance),
+            _ => Err(format!(
+                "invalid Object Lock mode '{}': use 'governance' or 'compliance'",
+                s
+            )),
+        }
+    }
+}
+
+/// Object Lock retention set on every object a run uploads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    pub mode: RetentionMode,
+    pub retain_until: DateTime<Utc>,
+}
+
+impl Retention {
+    /// Retention in `mode` for `days` days from `now`.
+    pub fn new(mode: RetentionMode, days: u32, now: DateTime<Utc>) -> Self {
+        Self {
+            mode,
+            retain_until: now + Duration::days(i64::from(days)),
+        }
+    }
+
+    /// Returns the retain-until date as sent to S3 (whole seconds).
+    pub fn s3_retain_until(&self) -> S3DateTime {
+        S3DateTime::from_secs(self.retain_until.timestamp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_displays_mode() {
+        assert_eq!(" Compliance".parse(), Ok(RetentionMode::Compliance));
+        assert_eq!("governance".parse(), Ok(RetentionMode::Governance));
+        assert!("legal-hold".parse::<RetentionMode>().is_err());
+        assert_eq!(RetentionMode::Governance.to_string(), "governance");
+        assert_eq!(
+            RetentionMode::Compliance.s3_mode(),
+            ObjectLockMode::Compliance
+        );
+    }
+
+    #[test]
+    fn retention_ends_after_the_given_days() {
+        let now = DateTime::parse_from_rfc3339("2030-01-01T12:00:00.750Z")
+            .unwrap()
+            .to_utc();
+        let retention = Retention::new(RetentionMode::Compliance, 30, now);
+
+        assert_eq!(
+            retention.retain_until.to_rfc3339(),
+            "2030-01-31T12:00:00.750+00:00"
+        );
+        assert_eq!(
+            retention.s3_retain_until().secs(),
+            retention.retain_until.timestamp()
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+const KIB: u64 = 1024;
+
+/// Binary unit suffixes used when printing sizes, from KiB upwards.
+const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+/// A size in bytes that can be written in a human-friendly way.
+///
+/// Accepted forms (case-insensitive, optional space before the unit):
+/// * plain numbers: `15728640`
+/// * units: `B`, `K`/`KB`/`KiB`, `M`/`MB`/`MiB`, `G`/`GB`/`GiB`, `T`/`TB`/`TiB`
+/// * fractions: `1.5GiB`
+///
+/// Like the AWS CLI, all units are binary: `16MB` is `16 * 1024 * 1024` bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    /// Returns the size in bytes.
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Renders the size exactly, using the largest binary unit that divides
+    /// it evenly (e.g. `15MiB`), or plain bytes otherwise.
+    pub fn to_exact_string(self) -> String {
+        let mut value = self.0;
+        let mut unit = None;
+
+        while value != 0 && value.is_multiple_of(KIB) && unit.map_or(0, |u| u + 1) < UNITS.len() {
+            value /= KIB;
+            unit = Some(unit.map_or(0, |u| u + 1));
+        }
+
+        match unit {
+            Some(u) => format!("{}{}", value, UNITS[u]),
+            None => value.to_string(),
+        }
+    }
+}
+
+impl From<u64> for ByteSize {
+    fn from(bytes: u64) -> Self {
+        Self(bytes)
+    }
+}
+
+/// Human-readable, rounded form (e.g. `15.0 MiB`) for output.
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 < KIB {
+            return write!(f, "{} B", self.0);
+        }
+
+        let mut value = self.0 as f64 / KIB as f64;
+        let mut unit = 0;
+        while value >= KIB as f64 && unit < UNITS.len() - 1 {
+            value /= KIB as f64;
+            unit += 1;
+        }
+        write!(f, "{:.1} {}", value, UNITS[unit])
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let input = s.trim();
+        let split = input
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(input.len());
+        let (number, unit) = input.split_at(split);
+
+        let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kb" | "kib" => KIB,
+            "m" | "mb" | "mib" => KIB.pow(2),
+            "g" | "gb" | "gib" => KIB.pow(3),
+            "t" | "tb" | "tib" => KIB.pow(4),
+            _ => {
+                return Err(format!(
+                    "invalid size '{}': unknown unit '{}' (use B, KiB, MiB, GiB or TiB)",
+                    s,
+                    unit.trim()
+                ));
+            }
+        };
+
+        if number.is_empty() {
+            return Err(format!("invalid size '{}': missing number", s));
+        }
+
+        if let Ok(whole) = number.parse::<u64>() {
+            return whole
+                .checked_mul(multiplier)
+                .map(ByteSize)
+                .ok_or_else(|| format!("invalid size '{}': value is too large", s));
+        }
+
+        let fraction: f64 = number
+            .parse()
+            .map_err(|_| format!("invalid size '{}': '{}' is not a number", s, number))?;
+        let bytes = fraction * multiplier as f64;
+        if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+            return Err(format!("invalid size '{}': value is too large", s));
+        }
+
+        Ok(ByteSize(bytes as u64))
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0 != 0 && self.0.is_multiple_of(KIB) {
+            serializer.serialize_str(&self.to_exact_string())
+        } else {
+            serializer.serialize_u64(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ByteSizeVisitor;
+
+        impl Visitor<'_> for ByteSizeVisitor {
+            type Value = ByteSize;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a size in bytes or a string such as \"16MiB\"")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(ByteSize(v))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                u64::try_from(v)
+                    .map(ByteSize)
+                    .map_err(|_| E::custom("size must not be negative"))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(ByteSizeVisitor)
+    }
+}
+
+impl JsonSchema for ByteSize {
+    fn schema_name() -> Cow<'static, str> {
+        "ByteSize".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A size in bytes, or a number with a binary unit such as \"16MiB\"",
+            "type": ["integer", "string"],
+            "minimum": 0,
+            "pattern": "^\\s*[0-9.]+\\s*([KkMmGgTt]([Ii]?[Bb])?|[Bb])?\\s*$"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    #[test]
+    fn parses_plain_numbers_and_units() {
+        assert_eq!("15728640".parse(), Ok(ByteSize(15 * MIB)));
+        assert_eq!("512B".parse(), Ok(ByteSize(512)));
+        assert_eq!("16MB".parse(), Ok(ByteSize(16 * MIB)));
+        assert_eq!("16 mib".parse(), Ok(ByteSize(16 * MIB)));
+        assert_eq!("2GiB".parse(), Ok(ByteSize(2 * 1024 * MIB)));
+        assert_eq!("8k".parse(), Ok(ByteSize(8 * 1024)));
+        assert_eq!("1.5GiB".parse(), Ok(ByteSize(1536 * MIB)));
+        assert_eq!("5TB".parse(), Ok(ByteSize(5 * 1024 * 1024 * MIB)));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        assert!("".parse::<ByteSize>().is_err());
+        assert!("MB".parse::<ByteSize>().is_err());
+        assert!(
+            "12XB"
+                .parse::<ByteSize>()
+                .unwrap_err()
+                .contains("unknown unit")
+        );
+        assert!("1.2.3MB".parse::<ByteSize>().is_err());
+        assert!(
+            "99999999999TiB"
+                .parse::<ByteSize>()
+                .unwrap_err()
+                .contains("too large")
+        );
+    }
+
+    #[test]
+    fn displays_human_readable() {
+        assert_eq!(ByteSize(0).to_string(), "0 B");
+        assert_eq!(ByteSize(1023).to_string(), "1023 B");
+        assert_eq!(ByteSize(1536).to_string(), "1.5 KiB");
+        assert_eq!(ByteSize(15 * MIB).to_string(), "15.0 MiB");
+    }
+
+    #[test]
+    fn exact_string_uses_largest_even_unit() {
+        assert_eq!(ByteSize(15 * MIB).to_exact_string(), "15MiB");
+        assert_eq!(ByteSize(1536).to_exact_string(), "1536");
+        assert_eq!(ByteSize(2048).to_exact_string(), "2KiB");
+        assert_eq!(ByteSize(0).to_exact_string(), "0");
+    }
+
+    #[test]
+    fn serde_roundtrip_in_yaml() {
+        #[derive(Serialize, Deserialize)]
+        struct Doc {
+            a: ByteSize,
+            b: ByteSize,
+        }
+
+        let doc: Doc = serde_yaml::from_str("a: 15728640\nb: \"2GiB\"\n").unwrap();
+        assert_eq!(doc.a, ByteSize(15 * MIB));
+        assert_eq!(doc.b, ByteSize(2048 * MIB));
+
+        let yaml = serde_yaml::to_string(&Doc {
+            a: ByteSize(15 * MIB),
+            b: ByteSize(1000),
+        })
+        .unwrap();
+        assert_eq!(yaml, "a: 15MiB\nb: 1000\n");
+
+        assert!(serde_yaml::from_str::<Doc>("a: -1\nb: 1\n").is_err());
+    }
+}
@@ -0,0 +1,610 @@
+use crate::error::{PrefixloadError, Result};
+use crate::report::RunReport;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+
+/// Summary of a finished run, as kept in the run history.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunRecord {
+    pub run_id: String,
+    /// Job the run executed; `None` for a plain `run`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub success: bool,
+    pub matched: usize,
+    pub uploaded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub uploaded_bytes: u64,
+}
+
+/// An object of a bucket and the ETag it had when a run last synced it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StoredObject {
+    pub bucket: String,
+    pub key: String,
+    pub etag: String,
+    /// Size of the content; `None` for objects recorded before sizes were kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// The objects under a prefix of a bucket, as listed by a run and kept for
+/// the runs that follow within `listing_cache_minutes`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachedListing {
+    pub bucket: String,
+    pub prefix: String,
+    pub listed_at: DateTime<Utc>,
+    pub objects: Vec<CachedObject>,
+}
+
+/// An object of a `CachedListing`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachedObject {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    pub size: u64,
+}
+
+/// Persistent state shared between runs, stored as JSON next to `run.log`.
+/// - Linux: ~/.local/share/prefixload/state.json
+/// - macOS: ~/Library/Application Support/prefixload/state.json
+/// - Windows: %LOCALAPPDATA%\prefixload\state.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct State {
+    /// History of finished runs, oldest first.
+    #[serde(default)]
+    pub runs: Vec<RunRecord>,
+    /// Objects synced by earlier runs, to find the old key of a renamed file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub objects: Vec<StoredObject>,
+    /// Listings of remote paths made by recent runs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listings: Vec<CachedListing>,
+    /// The state as loaded, so `save` can merge the changes of this process
+    /// into those other runs saved in the meantime.
+    #[serde(skip)]
+    loaded: Option<Box<State>>,
+}
+
+impl State {
+    /// Returns the full path to the state file, creating its directory on demand.
+    fn state_path() -> Result<PathBuf> {
+        let mut dir = dirs_next::data_local_dir().ok_or_else(|| {
+            PrefixloadError::Custom("Could not find local data directory.".to_string())
+        })?;
+
+        dir.push("prefixload");
+        fs::create_dir_all(&dir)?;
+
+        dir.push("state.json");
+
+        Ok(dir)
+    }
+
+    /// Loads the state from disk. A missing file yields an empty state.
+    pub fn load() -> Result<Self> {
+        let path = Self::state_path()?;
+        let _lock = lock(&path)?;
+        Self::read(&path).map(Self::loaded)
+    }
+
+    /// Loads the state like `load`, but moves a state file that cannot be
+    /// parsed aside and starts over with an empty state instead of failing.
+    ///
+    /// # Returns
+    ///
+    /// The state, and the warning to log when the file was moved aside.
+    pub fn load_or_reset() -> Result<(Self, Option<String>)> {
+        let path = Self::state_path()?;
+        let _lock = lock(&path)?;
+        match Self::read(&path) {
+            Ok(state) => Ok((Self::loaded(state), None)),
+            Err(PrefixloadError::Custom(err)) if path.exists() => {
+                let backup = path.with_extension(format!(
+                    "json.corrupt-{}",
+                    Utc::now().format("%Y%m%dT%H%M%SZ")
+                ));
+                fs::rename(&path, &backup)?;
+                let warning = format!(
+                    "{}. It was moved to {} and the run starts with an empty state.",
+                    err,
+                    backup.display()
+                );
+                Ok((Self::default(), Some(warning)))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Reads the state file at `path`. A missing file yields an empty state.
+    fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            PrefixloadError::Custom(format!(
+                "Failed to parse state file {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Keeps a copy of a freshly read `state` to merge against on `save`.
+    fn loaded(mut state: Self) -> Self {
+        state.loaded = Some(Box::new(state.clone()));
+        state
+    }
+
+    /// Writes the state to disk.
+    ///
+    /// Runs may overlap, so the file is read again under a lock and only the
+    /// changes made since `load` are applied to it: runs, objects and
+    /// listings added or updated here are written, and those removed here
+    /// are removed, while whatever another run saved in the meantime is
+    /// kept. The file is replaced atomically so a crash never leaves a
+    /// truncated state.
+    pub fn save(&self) -> Result<()> {
+        let path = Self::state_path()?;
+        let _lock = lock(&path)?;
+
+        // A file that became unreadable since `load` is overwritten.
+        let mut merged = Self::read(&path).unwrap_or_default();
+        let loaded = self.loaded.as_deref().cloned().unwrap_or_default();
+        merge(&mut merged.runs, &loaded.runs, &self.runs, |run| {
+            run.run_id.clone()
+        });
+        merged.runs.sort_by_key(|run| run.finished_at);
+        merge(
+            &mut merged.objects,
+            &loaded.objects,
+            &self.objects,
+            |object| (object.bucket.clone(), object.key.clone()),
+        );
+        merge(
+            &mut merged.listings,
+            &loaded.listings,
+            &self.listings,
+            |listing| (listing.bucket.clone(), listing.prefix.clone()),
+        );
+
+        let json = serde_json::to_string_pretty(&merged)
+            .map_err(|e| PrefixloadError::Custom(format!("Failed to serialize state: {}", e)))?;
+
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(())
+    }
+
+    /// Appends a finished run to the history.
+    pub fn record_run(&mut self, report: &RunReport, started_at: DateTime<Utc>) {
+        self.runs.push(RunRecord {
+            run_id: report.run_id.clone(),
+            job: report.job.clone(),
+            started_at,
+            finished_at: Utc::now(),
+            success: report.success,
+            matched: report.matched,
+            uploaded: report.uploaded,
+            skipped: report.skipped,
+            failed: report.failed,
+            uploaded_bytes: report.uploaded_bytes,
+        });
+    }
+
+    /// Records that `key` in `bucket` holds `size` bytes of content with `etag`,
+    /// also in the cached listings that cover it.
+    pub fn record_object(&mut self, bucket: &str, key: &str, etag: &str, size: u64) {
+        for listing in self
+            .listings
+            .iter_mut()
+            .filter(|listing| listing.bucket == bucket && key.starts_with(&listing.prefix))
+        {
+            let object = CachedObject {
+                key: key.to_string(),
+                etag: Some(etag.to_string()),
+                size,
+            };
+            match listing.objects.iter_mut().find(|object| object.key == key) {
+                Some(cached) => *cached = object,
+                None => listing.objects.push(object),
+            }
+        }
+
+        match self
+            .objects
+            .iter_mut()
+            .find(|object| object.bucket == bucket && object.key == key)
+        {
+            Some(object) => {
+                object.etag = etag.to_string();
+                object.size = Some(size);
+            }
+            None => self.objects.push(StoredObject {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+                etag: etag.to_string(),
+                size: Some(size),
+            }),
+        }
+    }
+
+    /// Removes the runs that finished before `cutoff` from the history and
+    /// returns how many were removed.
+    pub fn prune_runs(&mut self, cutoff: DateTime<Utc>) -> usize {
+        let count = self.runs.len();
+        self.runs.retain(|run| run.finished_at >= cutoff);
+        count - self.runs.len()
+    }
+
+    /// Removes the recorded objects of `bucket` whose keys are not in `keys`
+    /// and returns their keys.
+    pub fn prune_objects(&mut self, bucket: &str, keys: &HashSet<String>) -> Vec<String> {
+        let mut removed = Vec::new();
+        self.objects.retain(|object| {
+            let keep = object.bucket != bucket || keys.contains(&object.key);
+            if !keep {
+                removed.push(object.key.clone());
+            }
+            keep
+        });
+        removed
+    }
+
+    /// Removes the cached listings made before `cutoff` and returns how many
+    /// were removed.
+    pub fn prune_listings(&mut self, cutoff: DateTime<Utc>) -> usize {
+        let count = self.listings.len();
+        self.listings.retain(|listing| listing.listed_at >= cutoff);
+        count - self.listings.len()
+    }
+
+    /// Returns the cached listing of `prefix` in `bucket`, if any.
+    pub fn listing(&self, bucket: &str, prefix: &str) -> Option<&CachedListing> {
+        self.listings
+            .iter()
+            .find(|listing| listing.bucket == bucket && listing.prefix == prefix)
+    }
+
+    /// Caches `listing`, replacing an earlier listing of the same prefix.
+    pub fn store_listing(&mut self, listing: CachedListing) {
+        self.listings
+            .retain(|cached| cached.bucket != listing.bucket || cached.prefix != listing.prefix);
+        self.listings.push(listing);
+    }
+
+    /// Returns the recorded keys of `bucket` by ETag. Of several keys with
+    /// the same content, the one recorded last is returned.
+    pub fn keys_by_etag(&self, bucket: &str) -> HashMap<String, String> {
+        self.objects
+            .iter()
+            .filter(|object| object.bucket == bucket)
+            .map(|object| (object.etag.clone(), object.key.clone()))
+            .collect()
+    }
+
+    /// Returns the sizes of the recorded objects of `bucket`. Objects
+    /// recorded without a size are left out.
+    pub fn object_sizes(&self, bucket: &str) -> HashSet<u64> {
+        self.objects
+            .iter()
+            .filter(|object| object.bucket == bucket)
+            .filter_map(|object| object.size)
+            .collect()
+    }
+
+    /// Returns the most recent successful run of `job` (or of plain `run`
+    /// when `job` is `None`), if any.
+    pub fn last_successful_run(&self, job: Option<&str>) -> Option<&RunRecord> {
+        self.runs
+            .iter()
+            .rev()
+            .find(|run| run.success && run.job.as_deref() == job)
+    }
+}
+
+/// Takes an exclusive advisory lock next to the state file at `path`, held
+/// until the returned file is dropped. The state file itself is replaced on
+/// every save, so it cannot carry the lock.
+fn lock(path: &Path) -> Result<File> {
+    let file = File::create(path.with_extension("json.lock"))?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Applies the changes from `loaded` to `ours` onto `current`: the items
+/// added or changed in `ours` replace those with the same key in `current`,
+/// and the items of `loaded` missing from `ours` are removed from it.
+fn merge<T, K>(current: &mut Vec<T>, loaded: &[T], ours: &[T], key: impl Fn(&T) -> K)
+where
+    T: Clone + PartialEq,
+    K: Eq + Hash,
+{
+    let before: HashMap<K, &T> = loaded.iter().map(|item| (key(item), item)).collect();
+    let kept: HashSet<K> = ours.iter().map(&key).collect();
+    current.retain(|item| {
+        let item_key = key(item);
+        kept.contains(&item_key) || !before.contains_key(&item_key)
+    });
+    for item in ours {
+        let item_key = key(item);
+        if before.get(&item_key) == Some(&item) {
+            continue;
+        }
+        match current.iter_mut().find(|current| key(current) == item_key) {
+            Some(current) => *current = item.clone(),
+            None => current.push(item.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::env;
+    use tempfile::TempDir;
+
+    #[cfg(windows)]
+    const DATA_LOCAL_ENV: &str = "LOCALAPPDATA";
+    #[cfg(not(windows))]
+    const DATA_LOCAL_ENV: &str = "XDG_DATA_HOME";
+
+    /// Points the local data directory at a temporary directory.
+    fn temp_data_dir() -> TempDir {
+        let tmp = TempDir::new().expect("temp dir");
+        unsafe { env::set_var(DATA_LOCAL_ENV, tmp.path()) };
+        tmp
+    }
+
+    fn report(run_id: &str, success: bool) -> RunReport {
+        RunReport {
+            run_id: run_id.to_string(),
+            hostname: String::new(),
+            job: None,
+            started_at: String::new(),
+            finished_at: String::new(),
+            duration_secs: 0.0,
+            success,
+            matched: 1,
+            uploaded: 1,
+            skipped: 0,
+            failed: 0,
+            too_large: 0,
+            max_file_size: 0,
+            uploaded_bytes: 10,
+            error: None,
+            rules: Vec::new(),
+            requests: None,
+            interrupted: Vec::new(),
+            failure: None,
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn load_returns_empty_state_when_missing() {
+        let _guard = temp_data_dir();
+
+        let state = State::load().unwrap();
+        assert!(state.runs.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn save_and_load_roundtrip() {
+        let guard = temp_data_dir();
+
+        let mut state = State::default();
+        state.record_run(&report("a", true), Utc::now());
+        state.save().unwrap();
+
+        assert!(guard.path().join("prefixload/state.json").exists());
+        let loaded = State::load().unwrap();
+        assert_eq!(loaded.runs, state.runs);
+    }
+
+    #[test]
+    #[serial]
+    fn load_fails_on_corrupt_file() {
+        let guard = temp_data_dir();
+        let path = guard.path().join("prefixload/state.json");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+
+        let err = State::load().unwrap_err();
+        assert!(err.to_string().contains("Failed to parse state file"));
+    }
+
+    #[test]
+    #[serial]
+    fn load_or_reset_moves_corrupt_file_aside() {
+        let guard = temp_data_dir();
+        let dir = guard.path().join("prefixload");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("state.json"), "{ not json").unwrap();
+
+        let (state, warning) = State::load_or_reset().unwrap();
+        assert!(state.runs.is_empty());
+        let warning = warning.unwrap();
+        assert!(warning.starts_with("Failed to parse state file"));
+        assert!(warning.ends_with("the run starts with an empty state."));
+
+        assert!(!dir.join("state.json").exists());
+        let backups: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("state.json.corrupt-"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            fs::read_to_string(dir.join(&backups[0])).unwrap(),
+            "{ not json"
+        );
+
+        let (_, warning) = State::load_or_reset().unwrap();
+        assert_eq!(warning, None);
+    }
+
+    #[test]
+    #[serial]
+    fn save_keeps_the_changes_of_overlapping_runs() {
+        let _guard = temp_data_dir();
+        let mut state = State::default();
+        state.record_run(&report("old", true), Utc::now());
+        state.record_object("bucket", "db/a.sql", "etag-1", 10);
+        state.record_object("bucket", "db/b.sql", "etag-2", 20);
+        state.save().unwrap();
+
+        let mut first = State::load().unwrap();
+        let mut second = State::load().unwrap();
+
+        first.record_run(&report("first", true), Utc::now());
+        first.record_object("bucket", "db/a.sql", "etag-3", 30);
+        first.save().unwrap();
+
+        second.record_run(&report("second", true), Utc::now());
+        second.record_object("bucket", "db/c.sql", "etag-4", 40);
+        let keys = HashSet::from(["db/a.sql".to_string(), "db/c.sql".to_string()]);
+        assert_eq!(second.prune_objects("bucket", &keys), ["db/b.sql"]);
+        second.save().unwrap();
+
+        let state = State::load().unwrap();
+        let runs: Vec<_> = state.runs.iter().map(|run| run.run_id.as_str()).collect();
+        assert_eq!(runs, ["old", "first", "second"]);
+        let keys = state.keys_by_etag("bucket");
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys["etag-3"], "db/a.sql");
+        assert_eq!(keys["etag-4"], "db/c.sql");
+    }
+
+    #[test]
+    fn record_object_updates_key_in_place() {
+        let mut state = State::default();
+        state.record_object("bucket", "db/a.sql", "etag-1", 10);
+        state.record_object("bucket", "db/b.sql", "etag-2", 20);
+        state.record_object("other", "db/c.sql", "etag-3", 30);
+        state.record_object("bucket", "db/a.sql", "etag-4", 40);
+
+        assert_eq!(state.objects.len(), 3);
+        let keys = state.keys_by_etag("bucket");
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys["etag-4"], "db/a.sql");
+        assert_eq!(keys["etag-2"], "db/b.sql");
+        assert_eq!(state.object_sizes("bucket"), HashSet::from([40, 20]));
+    }
+
+    #[test]
+    fn cached_listings_follow_recorded_objects() {
+        let now = Utc::now();
+        let mut state = State::default();
+        state.store_listing(CachedListing {
+            bucket: "bucket".to_string(),
+            prefix: "db/".to_string(),
+            listed_at: now - chrono::Duration::minutes(20),
+            objects: vec![CachedObject {
+                key: "db/a.sql".to_string(),
+                etag: Some("etag-1".to_string()),
+                size: 10,
+            }],
+        });
+        state.store_listing(CachedListing {
+            bucket: "bucket".to_string(),
+            prefix: "logs/".to_string(),
+            listed_at: now,
+            objects: Vec::new(),
+        });
+
+        state.record_object("bucket", "db/a.sql", "etag-2", 20);
+        state.record_object("bucket", "db/b.sql", "etag-3", 30);
+        state.record_object("other", "db/c.sql", "etag-4", 40);
+        let listing = state.listing("bucket", "db/").unwrap();
+        assert_eq!(
+            listing.objects,
+            [
+                CachedObject {
+                    key: "db/a.sql".to_string(),
+                    etag: Some("etag-2".to_string()),
+                    size: 20,
+                },
+                CachedObject {
+                    key: "db/b.sql".to_string(),
+                    etag: Some("etag-3".to_string()),
+                    size: 30,
+                },
+            ]
+        );
+        assert!(state.listing("bucket", "logs/").unwrap().objects.is_empty());
+
+        assert_eq!(state.prune_listings(now - chrono::Duration::minutes(10)), 1);
+        assert_eq!(state.listing("bucket", "db/"), None);
+        assert!(state.listing("bucket", "logs/").is_some());
+    }
+
+    #[test]
+    fn prune_removes_old_runs_and_deleted_objects() {
+        let now = Utc::now();
+        let mut state = State::default();
+        state.record_run(&report("old", true), now);
+        state.record_run(&report("new", true), now);
+        state.runs[0].finished_at = now - chrono::Duration::days(100);
+        state.record_object("bucket", "db/a.sql", "etag-1", 10);
+        state.record_object("bucket", "db/b.sql", "etag-2", 20);
+        state.record_object("other", "db/b.sql", "etag-3", 30);
+
+        assert_eq!(state.prune_runs(now - chrono::Duration::days(90)), 1);
+        assert_eq!(state.runs.len(), 1);
+        assert_eq!(state.runs[0].run_id, "new");
+
+        let keys = HashSet::from(["db/a.sql".to_string()]);
+        assert_eq!(state.prune_objects("bucket", &keys), ["db/b.sql"]);
+        let remaining: Vec<_> = state
+            .objects
+            .iter()
+            .map(|object| (object.bucket.as_str(), object.key.as_str()))
+            .collect();
+        assert_eq!(remaining, [("bucket", "db/a.sql"), ("other", "db/b.sql")]);
+    }
+
+    #[test]
+    fn last_successful_run_skips_failures() {
+        let mut state = State::default();
+        state.record_run(&report("ok", true), Utc::now());
+        state.record_run(&report("failed", false), Utc::now());
+
+        assert_eq!(state.last_successful_run(None).unwrap().run_id, "ok");
+    }
+
+    #[test]
+    fn last_successful_run_is_tracked_per_job() {
+        let mut state = State::default();
+        state.record_run(&report("plain", true), Utc::now());
+        let mut nightly = report("nightly", true);
+        nightly.job = Some("nightly-db".to_string());
+        state.record_run(&nightly, Utc::now());
+
+        assert_eq!(state.last_successful_run(None).unwrap().run_id, "plain");
+        assert_eq!(
+            state
+                .last_successful_run(Some("nightly-db"))
+                .unwrap()
+                .run_id,
+            "nightly"
+        );
+        assert!(state.last_successful_run(Some("weekly")).is_none());
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::Utc;
+use chrono_tz::Tz;
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// Timezone of the timestamps in the run log: `local`, `utc` or an IANA
+/// name such as `Europe/Berlin`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogTimezone {
+    /// The timezone of the machine.
+    #[default]
+    Local,
+    /// Coordinated Universal Time.
+    Utc,
+    /// A timezone of the IANA database.
+    Named(Tz),
+}
+
+impl LogTimezone {
+    /// Formats the current time in the timezone with a `strftime` format.
+    pub fn format_now(self, format: &str) -> String {
+        let now = Utc::now();
+        match self {
+            LogTimezone::Local => now.with_timezone(&chrono::Local).format(format).to_string(),
+            LogTimezone::Utc => now.format(format).to_string(),
+            LogTimezone::Named(tz) => now.with_timezone(&tz).format(format).to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LogTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogTimezone::Local => write!(f, "local"),
+            LogTimezone::Utc => write!(f, "utc"),
+            LogTimezone::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
+
+impl FromStr for LogTimezone {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let value = s.trim();
+        if value.eq_ignore_ascii_case("local") {
+            Ok(Self::Local)
+        } else if value.eq_ignore_ascii_case("utc") {
+            Ok(Self::Utc)
+        } else {
+            value.parse().map(Self::Named).map_err(|_| {
+                format!(
+                    "invalid timezone '{}': use 'local', 'utc' or an IANA name such as 'Europe/Berlin'",
+                    s
+                )
+            })
+        }
+    }
+}
+
+impl Serialize for LogTimezone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for LogTimezone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for LogTimezone {
+    fn schema_name() -> Cow<'static, str> {
+        "LogTimezone".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "local, utc or an IANA timezone name such as Europe/Berlin",
+            "type": "string"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_timezone_parses_and_displays_names() {
+        assert_eq!("Local".parse(), Ok(LogTimezone::Local));
+        assert_eq!("UTC".parse(), Ok(LogTimezone::Utc));
+        assert_eq!(
+            "Europe/Berlin".parse(),
+            Ok(LogTimezone::Named(chrono_tz::Europe::Berlin))
+        );
+        assert!("Mars/Olympus".parse::<LogTimezone>().is_err());
+
+        assert_eq!(LogTimezone::Utc.format_now("%z"), "+0000");
+        assert_eq!(
+            LogTimezone::Named(chrono_tz::Asia::Kolkata).format_now("%z"),
+            "+0530"
+        );
+        assert_eq!(
+            serde_yaml::to_string(&LogTimezone::Named(chrono_tz::Asia::Tokyo)).unwrap(),
+            "Asia/Tokyo\n"
+        );
+    }
+}
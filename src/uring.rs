@@ -0,0 +1,280 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// How file parts are read for hashing and uploading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Positioned reads through the standard library.
+    #[default]
+    Standard,
+    /// Reads with several requests in flight through io_uring (Linux, built
+    /// with the `io-uring` feature), which keeps NVMe drives busy.
+    IoUring,
+}
+
+impl ReadMode {
+    /// Reads `len` bytes of `file` starting at `offset` into memory.
+    pub fn read_range(self, file: &File, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        match self {
+            ReadMode::Standard => {
+                let mut reader = file;
+                reader.seek(SeekFrom::Start(offset))?;
+                let mut data = Vec::with_capacity(len as usize);
+                reader.take(len).read_to_end(&mut data)?;
+                if (data.len() as u64) < len {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "file ended before the range was read",
+                    ));
+                }
+                Ok(data)
+            }
+            ReadMode::IoUring => read_range(file, offset, len),
+        }
+    }
+}
+
+/// Checks whether io_uring can be used: the binary was built with the
+/// `io-uring` feature, runs on Linux, and the kernel (or a seccomp profile,
+/// as in many containers) allows creating a ring.
+pub fn probe() -> io::Result<()> {
+    platform::probe()
+}
+
+/// Calculates the MD5 digest of `len` bytes of `file` starting at `offset`.
+pub fn md5_range(file: &File, offset: u64, len: u64) -> io::Result<md5::Digest> {
+    let mut context = md5::Context::new();
+    platform::read_in_order(file, offset, len, |block| context.consume(block))?;
+    Ok(context.finalize())
+}
+
+/// Reads `len` bytes of `file` starting at `offset` into memory.
+pub fn read_range(file: &File, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(len as usize);
+    platform::read_in_order(file, offset, len, |block| data.extend_from_slice(block))?;
+    Ok(data)
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod platform {
+    use io_uring::{IoUring, opcode, types};
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::AsRawFd;
+
+    /// Size of a single read request.
+    const BLOCK_SIZE: usize = 1024 * 1024;
+
+    /// Number of read requests kept in flight.
+    const QUEUE_DEPTH: usize = 8;
+
+    pub fn probe() -> io::Result<()> {
+        IoUring::new(1).map(|_| ())
+    }
+
+    /// Reads the range in blocks of `BLOCK_SIZE` with up to `QUEUE_DEPTH`
+    /// requests in flight, and hands the blocks to `consume` in file order.
+    pub fn read_in_order(
+        file: &File,
+        offset: u64,
+        len: u64,
+        mut consume: impl FnMut(&[u8]),
+    ) -> io::Result<()> {
+        let blocks = len.div_ceil(BLOCK_SIZE as u64);
+        let depth = blocks.min(QUEUE_DEPTH as u64) as usize;
+        if depth == 0 {
+            return Ok(());
+        }
+        let block_len =
+            |block: u64| (len - block * BLOCK_SIZE as u64).min(BLOCK_SIZE as u64) as usize;
+        let block_offset = |block: u64| offset + block * BLOCK_SIZE as u64;
+
+        let mut ring = IoUring::new(depth as u32)?;
+        let fd = types::Fd(file.as_raw_fd());
+
+        // Block `n` is read into slot `n % depth`, which is reused once the
+        // block was consumed. A short read queues the rest of the block.
+        let mut buffers = vec![vec![0u8; BLOCK_SIZE]; depth];
+        let mut filled = vec![0usize; depth];
+        let mut slot_block: Vec<u64> = (0..depth as u64).collect();
+        let mut in_flight = 0;
+        let mut next_read = depth as u64;
+        let mut next_consume = 0;
+        let mut failure = None;
+
+        for (slot, buffer) in buffers.iter_mut().enumerate() {
+            let block = slot as u64;
+            queue_read(
+                &mut ring,
+                fd,
+                &mut buffer[..block_len(block)],
+                block_offset(block),
+                slot,
+            );
+            in_flight += 1;
+        }
+
+        // Every queued request is waited for, even after a failure, as the
+        // kernel writes into `buffers` until it completes.
+        while in_flight > 0 {
+            match ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    // The requests still in flight can no longer be waited
+                    // for, so their buffers must never be freed.
+                    std::mem::forget(buffers);
+                    return Err(e);
+                }
+            }
+
+            let completions: Vec<(usize, i32)> = ring
+                .completion()
+                .map(|cqe| (cqe.user_data() as usize, cqe.result()))
+                .collect();
+            for (slot, result) in completions {
+                in_flight -= 1;
+                if failure.is_some() {
+                    continue;
+                }
+
+                let block = slot_block[slot];
+                match result {
+                    result if result < 0 => {
+                        failure = Some(io::Error::from_raw_os_error(-result));
+                    }
+                    0 => {
+                        failure = Some(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "file ended before the range was read",
+                        ));
+                    }
+                    result => {
+                        filled[slot] += result as usize;
+                        if filled[slot] < block_len(block) {
+                            queue_read(
+                                &mut ring,
+                                fd,
+                                &mut buffers[slot][filled[slot]..block_len(block)],
+                                block_offset(block) + filled[slot] as u64,
+                                slot,
+                            );
+                            in_flight += 1;
+                        }
+                    }
+                }
+            }
+
+            while failure.is_none() && next_consume < blocks {
+                let slot = (next_consume % depth as u64) as usize;
+                if slot_block[slot] != next_consume || filled[slot] < block_len(next_consume) {
+                    break;
+                }
+                consume(&buffers[slot][..block_len(next_consume)]);
+                filled[slot] = 0;
+                next_consume += 1;
+
+                if next_read < blocks {
+                    slot_block[slot] = next_read;
+                    queue_read(
+                        &mut ring,
+                        fd,
+                        &mut buffers[slot][..block_len(next_read)],
+                        block_offset(next_read),
+                        slot,
+                    );
+                    in_flight += 1;
+                    next_read += 1;
+                }
+            }
+        }
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Queues a read of `buffer.len()` bytes at `position` into `buffer`.
+    fn queue_read(
+        ring: &mut IoUring,
+        fd: types::Fd,
+        buffer: &mut [u8],
+        position: u64,
+        slot: usize,
+    ) {
+        let entry = opcode::Read::new(fd, buffer.as_mut_ptr(), buffer.len() as u32)
+            .offset(position)
+            .build()
+            .user_data(slot as u64);
+
+        // SAFETY: `read_in_order` keeps the buffer alive until the request
+        // completed, and never queues two requests for the same slot.
+        unsafe { ring.submission().push(&entry) }
+            .expect("the submission queue has room for a request per slot");
+    }
+}
+
+#[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+mod platform {
+    use std::fs::File;
+    use std::io;
+
+    pub fn probe() -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn read_in_order(
+        _file: &File,
+        _offset: u64,
+        _len: u64,
+        _consume: impl FnMut(&[u8]),
+    ) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    fn unsupported() -> io::Error {
+        let reason = if cfg!(target_os = "linux") {
+            "prefixload was built without the io-uring feature"
+        } else {
+            "io_uring is only available on Linux"
+        };
+        io::Error::new(io::ErrorKind::Unsupported, reason)
+    }
+}
+
+#[cfg(all(test, target_os = "linux", feature = "io-uring"))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn reads_ranges_like_the_standard_library() {
+        // Kernels without io_uring, or seccomp profiles blocking it.
+        if probe().is_err() {
+            return;
+        }
+
+        let content: Vec<u8> = (0..(9 * 1024 * 1024 + 7))
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&content).unwrap();
+
+        let range = 1024 * 1024 + 3..content.len() - 5;
+        let start = range.start as u64;
+        let len = range.len() as u64;
+        assert_eq!(
+            read_range(file.as_file(), start, len).unwrap(),
+            &content[range.clone()]
+        );
+        assert_eq!(
+            md5_range(file.as_file(), start, len).unwrap(),
+            md5::compute(&content[range])
+        );
+
+        let err = read_range(file.as_file(), start, content.len() as u64).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
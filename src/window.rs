@@ -0,0 +1,203 @@
+use chrono::{NaiveTime, Timelike};
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// A daily time window in local time, written as `HH:MM-HH:MM`
+/// (seconds are optional: `HH:MM:SS`).
+///
+/// The window may wrap around midnight (`22:00-06:00`). The start is
+/// inclusive, the end exclusive; equal bounds mean the whole day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    /// Returns `true` if `time` falls inside the window.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start == self.end {
+            true
+        } else if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Returns how long to wait from `time` until the window opens
+    /// (zero when it is already open).
+    pub fn until_open(&self, time: NaiveTime) -> Duration {
+        if self.contains(time) {
+            return Duration::ZERO;
+        }
+
+        let now = time.num_seconds_from_midnight();
+        let start = self.start.num_seconds_from_midnight();
+        let seconds = (start + SECONDS_PER_DAY - now) % SECONDS_PER_DAY;
+
+        Duration::from_secs(u64::from(seconds))
+    }
+}
+
+/// Formats a time as `HH:MM`, or `HH:MM:SS` when it has seconds.
+fn format_time(time: NaiveTime) -> String {
+    if time.second() == 0 {
+        time.format("%H:%M").to_string()
+    } else {
+        time.format("%H:%M:%S").to_string()
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", format_time(self.start), format_time(self.end))
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |part: &str| {
+            let part = part.trim();
+            NaiveTime::parse_from_str(part, "%H:%M:%S")
+                .or_else(|_| NaiveTime::parse_from_str(part, "%H:%M"))
+                .map_err(|_| {
+                    format!(
+                        "invalid time window '{}': '{}' is not a HH:MM time",
+                        s, part
+                    )
+                })
+        };
+
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("invalid time window '{}': expected HH:MM-HH:MM", s))?;
+
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+impl Serialize for TimeWindow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeWindow {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for TimeWindow {
+    fn schema_name() -> Cow<'static, str> {
+        "TimeWindow".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A daily time window in local time, written as HH:MM-HH:MM",
+            "type": "string",
+            "pattern": "^\\s*[0-9]{1,2}:[0-9]{2}(:[0-9]{2})?\\s*-\\s*[0-9]{1,2}:[0-9]{2}(:[0-9]{2})?\\s*$"
+        })
+    }
+}
+
+/// What `run` does when it is started outside the upload window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutsideWindow {
+    /// Exit immediately with a note.
+    #[default]
+    Refuse,
+    /// Wait until the window opens, then upload.
+    Wait,
+}
+
+impl FromStr for OutsideWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "refuse" => Ok(Self::Refuse),
+            "wait" => Ok(Self::Wait),
+            _ => Err(format!("invalid value '{}': use 'refuse' or 'wait'", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_and_displays_window() {
+        let window: TimeWindow = "22:00 - 6:30".parse().unwrap();
+
+        assert_eq!(window.start, time(22, 0));
+        assert_eq!(window.end, time(6, 30));
+        assert_eq!(window.to_string(), "22:00-06:30");
+
+        let precise: TimeWindow = "01:02:03-04:05".parse().unwrap();
+        assert_eq!(precise.to_string(), "01:02:03-04:05");
+
+        assert!("22:00".parse::<TimeWindow>().is_err());
+        assert!("25:00-06:00".parse::<TimeWindow>().is_err());
+    }
+
+    #[test]
+    fn contains_handles_windows_across_midnight() {
+        let night: TimeWindow = "22:00-06:00".parse().unwrap();
+        assert!(night.contains(time(23, 0)));
+        assert!(night.contains(time(0, 0)));
+        assert!(!night.contains(time(6, 0)));
+        assert!(!night.contains(time(12, 0)));
+
+        let day: TimeWindow = "09:00-17:00".parse().unwrap();
+        assert!(day.contains(time(9, 0)));
+        assert!(!day.contains(time(17, 0)));
+
+        let always: TimeWindow = "00:00-00:00".parse().unwrap();
+        assert!(always.contains(time(12, 0)));
+    }
+
+    #[test]
+    fn until_open_wraps_to_next_day() {
+        let night: TimeWindow = "22:00-06:00".parse().unwrap();
+
+        assert_eq!(night.until_open(time(23, 0)), Duration::ZERO);
+        assert_eq!(night.until_open(time(21, 30)), Duration::from_secs(30 * 60));
+
+        let morning: TimeWindow = "02:00-04:00".parse().unwrap();
+        assert_eq!(
+            morning.until_open(time(5, 0)),
+            Duration::from_secs(21 * 60 * 60)
+        );
+    }
+
+    #[test]
+    fn outside_window_parses_and_serializes_lowercase() {
+        assert_eq!("Wait".parse(), Ok(OutsideWindow::Wait));
+        assert!("pause".parse::<OutsideWindow>().is_err());
+        assert_eq!(
+            serde_yaml::to_string(&OutsideWindow::Refuse).unwrap(),
+            "refuse\n"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::error::{PrefixloadError, Result};
+use aws_smithy_types::base64;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Suffix appended to a file's remote key to name its sidecar object.
+pub const SIDECAR_SUFFIX: &str = ".xattrs.json";
+
+/// Version of the sidecar object format.
+const SIDECAR_VERSION: u32 = 1;
+
+/// Where the entries of a sidecar were read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtendedAttributeKind {
+    /// Extended attributes (Linux, macOS), e.g. `user.comment`.
+    Xattr,
+    /// NTFS alternate data streams (Windows), e.g. `Zone.Identifier`.
+    Ads,
+}
+
+/// Extended attributes or alternate data streams of a file, uploaded as a
+/// JSON sidecar object next to it for rules with `xattrs: true`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExtendedAttributes {
+    pub version: u32,
+    pub kind: ExtendedAttributeKind,
+    /// Base64-encoded value of every attribute or stream, by name.
+    pub entries: BTreeMap<String, String>,
+}
+
+impl ExtendedAttributes {
+    /// Reads the extended attributes (or alternate data streams on Windows)
+    /// of a file. Names that are not valid UTF-8 are skipped, and a file
+    /// system without extended attributes yields no entries.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let entries = platform::read_entries(path.as_ref())?
+            .into_iter()
+            .map(|(name, value)| (name, base64::encode(value)))
+            .collect();
+
+        Ok(Self {
+            version: SIDECAR_VERSION,
+            kind: platform::KIND,
+            entries,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Serializes the attributes to the JSON body of the sidecar object.
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).map_err(|e| {
+            PrefixloadError::Custom(format!("Failed to serialize extended attributes: {}", e))
+        })
+    }
+}
+
+/// Returns the object key of the sidecar of the file stored at `remote_key`.
+pub fn sidecar_key(remote_key: &str) -> String {
+    format!("{}{}", remote_key, SIDECAR_SUFFIX)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+mod platform {
+    use super::ExtendedAttributeKind;
+    use crate::error::Result;
+    use rustix::io::Errno;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    pub const KIND: ExtendedAttributeKind = ExtendedAttributeKind::Xattr;
+
+    /// Error of `getxattr` for an attribute that does not exist.
+    #[cfg(target_vendor = "apple")]
+    const NO_ATTRIBUTE: Errno = Errno::NOATTR;
+    #[cfg(not(target_vendor = "apple"))]
+    const NO_ATTRIBUTE: Errno = Errno::NODATA;
+
+    pub fn read_entries(path: &Path) -> Result<BTreeMap<String, Vec<u8>>> {
+        let names = match read_sized(|buf| rustix::fs::listxattr(path, buf)) {
+            Ok(names) => names,
+            Err(Errno::NOTSUP) => return Ok(BTreeMap::new()),
+            Err(e) => return Err(std::io::Error::from(e).into()),
+        };
+
+        let mut entries = BTreeMap::new();
+        for name in names.split(|&b| b == 0).filter(|name| !name.is_empty()) {
+            let Ok(name) = std::str::from_utf8(name) else {
+                continue;
+            };
+            match read_sized(|buf| rustix::fs::getxattr(path, name, buf)) {
+                Ok(value) => {
+                    entries.insert(name.to_string(), value);
+                }
+                // Removed since it was listed.
+                Err(NO_ATTRIBUTE) => {}
+                Err(e) => return Err(std::io::Error::from(e).into()),
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Calls `read` with an empty buffer to learn the size, then with a buffer
+    /// of that size, starting over if the value grew in between.
+    fn read_sized(
+        read: impl Fn(&mut [u8]) -> rustix::io::Result<usize>,
+    ) -> rustix::io::Result<Vec<u8>> {
+        loop {
+            let mut buf = vec![0u8; read(&mut [])?];
+            match read(&mut buf) {
+                Ok(len) => {
+                    buf.truncate(len);
+                    return Ok(buf);
+                }
+                Err(Errno::RANGE) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::ExtendedAttributeKind;
+    use crate::error::Result;
+    use std::collections::BTreeMap;
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{ERROR_HANDLE_EOF, GetLastError, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard,
+        WIN32_FIND_STREAM_DATA,
+    };
+
+    pub const KIND: ExtendedAttributeKind = ExtendedAttributeKind::Ads;
+
+    /// Name of the unnamed stream holding the file content.
+    const DEFAULT_STREAM: &str = "::$DATA";
+
+    pub fn read_entries(path: &Path) -> Result<BTreeMap<String, Vec<u8>>> {
+        let mut entries = BTreeMap::new();
+        for stream in stream_names(path)? {
+            if stream == DEFAULT_STREAM {
+                continue;
+            }
+            // Stream names are reported as ":<name>:$DATA".
+            let Some(name) = stream
+                .strip_prefix(':')
+                .and_then(|s| s.strip_suffix(":$DATA"))
+            else {
+                continue;
+            };
+
+            let mut stream_path = path.as_os_str().to_owned();
+            stream_path.push(":");
+            stream_path.push(name);
+            entries.insert(name.to_string(), std::fs::read(stream_path)?);
+        }
+
+        Ok(entries)
+    }
+
+    fn stream_names(path: &Path) -> Result<Vec<String>> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut data = WIN32_FIND_STREAM_DATA::default();
+        let mut names = Vec::new();
+
+        // SAFETY: `wide` is NUL-terminated and `data` outlives the search handle.
+        unsafe {
+            let handle = FindFirstStreamW(
+                wide.as_ptr(),
+                FindStreamInfoStandard,
+                (&raw mut data).cast(),
+                0,
+            );
+            if handle == INVALID_HANDLE_VALUE {
+                return match GetLastError() {
+                    ERROR_HANDLE_EOF => Ok(names),
+                    code => Err(std::io::Error::from_raw_os_error(code as i32).into()),
+                };
+            }
+
+            loop {
+                let len = data
+                    .cStreamName
+                    .iter()
+                    .position(|&c| c == 0)
+                    .unwrap_or(data.cStreamName.len());
+                if let Ok(name) = OsString::from_wide(&data.cStreamName[..len]).into_string() {
+                    names.push(name);
+                }
+                if FindNextStreamW(handle, (&raw mut data).cast()) == 0 {
+                    break;
+                }
+            }
+            FindClose(handle);
+        }
+
+        Ok(names)
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    windows
+)))]
+mod platform {
+    use super::ExtendedAttributeKind;
+    use crate::error::Result;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    pub const KIND: ExtendedAttributeKind = ExtendedAttributeKind::Xattr;
+
+    pub fn read_entries(_path: &Path) -> Result<BTreeMap<String, Vec<u8>>> {
+        Ok(BTreeMap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_key_uses_suffix() {
+        assert_eq!(sidecar_key("db/dump.sql"), "db/dump.sql.xattrs.json");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn read_collects_user_xattrs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "data").unwrap();
+
+        // tmpfs and some container file systems reject user xattrs.
+        let flags = rustix::fs::XattrFlags::empty();
+        if rustix::fs::setxattr(&path, "user.comment", b"hello\0world", flags).is_err() {
+            return;
+        }
+
+        let attributes = ExtendedAttributes::read(&path).unwrap();
+        assert_eq!(attributes.kind, ExtendedAttributeKind::Xattr);
+        assert_eq!(
+            attributes.entries.get("user.comment").map(String::as_str),
+            Some(base64::encode(b"hello\0world").as_str())
+        );
+    }
+}